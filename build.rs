@@ -0,0 +1,14 @@
+use std::process::Command;
+
+/// Record the commit the binary is built from, if built from a git checkout
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/logs/HEAD");
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output();
+    if let Some(output) = output.ok().filter(|output| output.status.success()) {
+        let hash = String::from_utf8_lossy(&output.stdout);
+        println!("cargo:rustc-env=VCLUST_GIT_HASH={}", hash.trim());
+    }
+}
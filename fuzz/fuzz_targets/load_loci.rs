@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vclust::locus::parse_loci;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_loci(data);
+});
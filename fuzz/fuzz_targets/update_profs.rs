@@ -0,0 +1,70 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use vclust::profile::update_profs;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    pos: u16,
+    ops: Vec<(u8, u8)>,
+    region_start: u16,
+    region_len: u16,
+    min_alt_len: u8,
+}
+
+fuzz_target!(|input: Input| {
+    // Hard clips, pads, and reference skips are rejected before profiling
+    let ops = input
+        .ops
+        .iter()
+        .map(|(kind, len)| {
+            let len = *len as u32 + 1;
+            match kind % 6 {
+                0 => Cigar::Match(len),
+                1 => Cigar::Equal(len),
+                2 => Cigar::Diff(len),
+                3 => Cigar::Ins(len),
+                4 => Cigar::Del(len),
+                _ => Cigar::SoftClip(len),
+            }
+        })
+        .collect::<Vec<_>>();
+    if ops.is_empty() || input.region_len == 0 {
+        return;
+    }
+    let query_len = ops
+        .iter()
+        .map(|op| match op {
+            Cigar::Match(len)
+            | Cigar::Equal(len)
+            | Cigar::Diff(len)
+            | Cigar::Ins(len)
+            | Cigar::SoftClip(len) => *len as usize,
+            _ => 0,
+        })
+        .sum::<usize>();
+
+    let mut rec = Record::new();
+    let seq = vec![b'A'; query_len];
+    let qual = vec![30; query_len];
+    rec.set(b"read", Some(&CigarString(ops)), &seq, &qual);
+    rec.set_pos(input.pos as i64);
+
+    let start = input.region_start as i64;
+    let region = ("chr1", start, start + input.region_len as i64);
+    let mut covs = vec![0; input.region_len as usize];
+    let mut alts = vec![0; input.region_len as usize];
+    let mut clips = vec![0; input.region_len as usize];
+    // Counts always match the non-empty region, so profiling must succeed
+    let profiled = update_profs(
+        &rec,
+        &mut covs,
+        &mut alts,
+        &mut clips,
+        region,
+        input.min_alt_len as usize,
+    );
+    assert!(profiled.is_ok(), "{profiled:?} for {input:?}");
+});
@@ -0,0 +1,131 @@
+use crate::error::Result;
+use rust_htslib::bam::record::Cigar;
+use rust_htslib::bam::{self, Read};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Aligners whose output the models were trained on
+pub const SUPPORTED_ALIGNERS: &[&str] = &["pbmm2", "minimap2", "winnowmap"];
+
+/// Number of leading records whose CIGARs are checked
+const PROBE_RECORDS: usize = 100;
+
+/// Aligner that produced the alignments of a sample, from its `@PG` line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aligner {
+    pub program: String,
+    pub version: Option<String>,
+    pub command_line: Option<String>,
+}
+
+impl std::fmt::Display for Aligner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {version}", self.program),
+            None => write!(f, "{}", self.program),
+        }
+    }
+}
+
+/// Upstream processing of a BAM as recorded in its header and CIGARs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentCheck {
+    /// Whether the header has any `@PG` lines; many pipelines drop them
+    pub has_programs: bool,
+    /// Most recent supported aligner of the program chain, if any
+    pub aligner: Option<Aligner>,
+    /// Whether the leading records have `M` operations but no `=`/`X` ones
+    pub missing_eqx: bool,
+}
+
+impl AlignmentCheck {
+    /// Issues worth warning about, as messages naming the sample
+    pub fn warnings(&self, sample: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.has_programs && self.aligner.is_none() {
+            warnings.push(format!(
+                "Reads of {sample} were not aligned with a supported aligner ({})",
+                SUPPORTED_ALIGNERS.join(", ")
+            ));
+        }
+        if self.missing_eqx {
+            warnings.push(format!(
+                "CIGARs of {sample} use M instead of =/X operations, which hides mismatches; \
+                 realign with --eqx"
+            ));
+        }
+        warnings
+    }
+}
+
+/// Program that produced the alignments: the nearest supported aligner when
+/// following the `PP` links of the `@PG` lines back from the last program
+pub fn find_aligner(programs: &[HashMap<String, String>]) -> Option<Aligner> {
+    let by_id = programs
+        .iter()
+        .filter_map(|pg| Some((pg.get("ID")?.as_str(), pg)))
+        .collect::<HashMap<_, _>>();
+    let is_parent = |id: &str| {
+        programs
+            .iter()
+            .any(|pg| pg.get("PP").is_some_and(|pp| pp == id))
+    };
+    let mut program = programs
+        .iter()
+        .rev()
+        .find(|pg| pg.get("ID").is_none_or(|id| !is_parent(id)));
+    let mut visited = 0;
+    while let Some(pg) = program {
+        let name = pg.get("PN").or_else(|| pg.get("ID"))?;
+        if let Some(supported) = SUPPORTED_ALIGNERS
+            .iter()
+            .find(|aligner| name.to_lowercase().starts_with(*aligner))
+        {
+            return Some(Aligner {
+                program: supported.to_string(),
+                version: pg.get("VN").cloned(),
+                command_line: pg.get("CL").cloned(),
+            });
+        }
+        // Guard against cycles in malformed headers
+        visited += 1;
+        if visited > programs.len() {
+            return None;
+        }
+        program = pg.get("PP").and_then(|pp| by_id.get(pp.as_str()).copied());
+    }
+    None
+}
+
+/// Check the program chain and CIGARs of a BAM; PAF files are not checked
+pub fn check_alignments(path: &Path) -> Result<Option<AlignmentCheck>> {
+    if path.extension().is_some_and(|ext| ext == "paf") {
+        return Ok(None);
+    }
+    let mut reader = bam::Reader::from_path(path)?;
+    let header = bam::Header::from_template(reader.header()).to_hashmap();
+    let programs = header
+        .get("PG")
+        .into_iter()
+        .flatten()
+        .map(|pg| pg.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+        .collect::<Vec<_>>();
+    let aligner = find_aligner(&programs);
+
+    let (mut has_match, mut has_eqx) = (false, false);
+    for rec in reader.records().take(PROBE_RECORDS) {
+        for op in rec?.cigar().iter() {
+            match op {
+                Cigar::Match(_) => has_match = true,
+                Cigar::Equal(_) | Cigar::Diff(_) => has_eqx = true,
+                _ => {}
+            }
+        }
+    }
+    Ok(Some(AlignmentCheck {
+        has_programs: !programs.is_empty(),
+        aligner,
+        missing_eqx: has_match && !has_eqx,
+    }))
+}
@@ -0,0 +1,167 @@
+use crate::error::{Result, VclustError};
+use crate::profile::Region;
+use rust_htslib::bam::record::{Aux, Cigar, CigarString};
+use rust_htslib::bam::{self, IndexedReader, Record};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Provider of the alignments overlapping a region of the reference
+pub trait AlignmentSource: Send {
+    /// Position the source at the alignments overlapping the region
+    fn fetch(&mut self, region: Region) -> Result<()>;
+
+    /// Next alignment of the fetched region or `None` once all were returned
+    fn next_record(&mut self) -> Option<Result<Record>>;
+}
+
+impl AlignmentSource for IndexedReader {
+    fn fetch(&mut self, region: Region) -> Result<()> {
+        IndexedReader::fetch(self, region)?;
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> Option<Result<Record>> {
+        let mut rec = Record::new();
+        match bam::Read::read(self, &mut rec)? {
+            Ok(()) => Some(Ok(rec)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+/// Open a BAM/CRAM or, for files ending in `.paf`, a PAF file
+pub fn open_source(path: &Path) -> Result<Box<dyn AlignmentSource>> {
+    if path.extension().is_some_and(|ext| ext == "paf") {
+        Ok(Box::new(PafSource::load(path)?))
+    } else {
+        Ok(Box::new(IndexedReader::from_path(path)?))
+    }
+}
+
+/// Alignments from a PAF file with `cg` CIGAR tags, held in memory
+///
+/// PAF files of assembly alignments are small enough to be loaded by every
+/// worker; read-level PAF files should be converted to BAM instead.
+pub struct PafSource {
+    /// Alignments of each contig sorted by start
+    records: HashMap<String, Vec<Record>>,
+    /// Longest reference span of an alignment on each contig
+    max_spans: HashMap<String, i64>,
+    fetched: Vec<Record>,
+}
+
+impl PafSource {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mut records: HashMap<String, Vec<Record>> = HashMap::new();
+        let mut max_spans: HashMap<String, i64> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (contig, rec) = parse_paf_line(&line)?;
+            let span = rec.cigar().end_pos() - rec.pos();
+            let max_span = max_spans.entry(contig.clone()).or_default();
+            *max_span = (*max_span).max(span);
+            records.entry(contig).or_default().push(rec);
+        }
+        for recs in records.values_mut() {
+            recs.sort_by_key(|rec| rec.pos());
+        }
+        Ok(PafSource {
+            records,
+            max_spans,
+            fetched: Vec::new(),
+        })
+    }
+}
+
+impl AlignmentSource for PafSource {
+    fn fetch(&mut self, region: Region) -> Result<()> {
+        let (contig, start, end) = region;
+        self.fetched.clear();
+        let Some(recs) = self.records.get(contig) else {
+            return Ok(());
+        };
+        let min_pos = start - self.max_spans[contig];
+        let first = recs.partition_point(|rec| rec.pos() < min_pos);
+        for rec in &recs[first..] {
+            if rec.pos() >= end {
+                break;
+            }
+            if rec.cigar().end_pos() > start {
+                self.fetched.push(rec.clone());
+            }
+        }
+        // Records are handed out from the back
+        self.fetched.reverse();
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> Option<Result<Record>> {
+        self.fetched.pop().map(Ok)
+    }
+}
+
+fn parse_paf_line(line: &str) -> Result<(String, Record)> {
+    let bad_line = || VclustError::Parse(format!("Bad PAF line {line}"));
+    let fields = line.split('\t').collect::<Vec<_>>();
+    if fields.len() < 12 {
+        return Err(bad_line());
+    }
+    let pos = fields[7].parse::<i64>().map_err(|_| bad_line())?;
+    let mapq = fields[11].parse::<u8>().map_err(|_| bad_line())?;
+    let tag = |name: &str| {
+        fields[12..]
+            .iter()
+            .find_map(|field| field.strip_prefix(name))
+    };
+    let cigar = tag("cg:Z:")
+        .ok_or_else(|| VclustError::Parse(format!("PAF line without cg tag {line}")))?;
+    let cigar = parse_cigar(cigar).ok_or_else(bad_line)?;
+
+    let mut rec = Record::new();
+    rec.set(fields[0].as_bytes(), Some(&cigar), &[], &[]);
+    rec.set_pos(pos);
+    rec.set_mapq(mapq);
+    rec.unset_flags();
+    if fields[4] == "-" {
+        rec.set_reverse();
+    }
+    if tag("tp:A:") == Some("S") {
+        rec.set_secondary();
+    }
+    if let Some(cs) = tag("cs:Z:") {
+        rec.push_aux(b"cs", Aux::String(cs))
+            .map_err(|_| bad_line())?;
+    }
+    Ok((fields[5].to_string(), rec))
+}
+
+fn parse_cigar(cigar: &str) -> Option<CigarString> {
+    let mut ops = Vec::new();
+    let mut len = 0u32;
+    for ch in cigar.chars() {
+        if let Some(digit) = ch.to_digit(10) {
+            len = len.checked_mul(10)?.checked_add(digit)?;
+            continue;
+        }
+        ops.push(match ch {
+            'M' => Cigar::Match(len),
+            'I' => Cigar::Ins(len),
+            'D' => Cigar::Del(len),
+            'N' => Cigar::RefSkip(len),
+            'S' => Cigar::SoftClip(len),
+            'H' => Cigar::HardClip(len),
+            'P' => Cigar::Pad(len),
+            '=' => Cigar::Equal(len),
+            'X' => Cigar::Diff(len),
+            _ => return None,
+        });
+        len = 0;
+    }
+    Some(CigarString(ops))
+}
@@ -0,0 +1,59 @@
+use crate::error::{Result, VclustError};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Target intervals of an amplicon panel, sorted by start per contig
+#[derive(Debug, Default)]
+pub struct Amplicons {
+    intervals: BTreeMap<String, Vec<(i64, i64)>>,
+}
+
+impl Amplicons {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mut intervals: BTreeMap<String, Vec<(i64, i64)>> = BTreeMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rec = line.split_whitespace().collect::<Vec<_>>();
+            if rec.len() < 3 {
+                return Err(VclustError::Parse(format!("Bad amplicon line {line}")));
+            }
+            let parse = |val: &str| {
+                val.parse::<i64>()
+                    .map_err(|_| VclustError::Parse(format!("Bad amplicon line {line}")))
+            };
+            let (start, end) = (parse(rec[1])?, parse(rec[2])?);
+            intervals
+                .entry(rec[0].to_string())
+                .or_default()
+                .push((start, end));
+        }
+        for contig_intervals in intervals.values_mut() {
+            contig_intervals.sort();
+        }
+        Ok(Amplicons { intervals })
+    }
+
+    /// Amplicons overlapping the interval [start, end)
+    pub fn overlapping(&self, chrom: &str, start: i64, end: i64) -> Vec<(i64, i64)> {
+        self.intervals.get(chrom).map_or(Vec::new(), |intervals| {
+            intervals
+                .iter()
+                .take_while(|(amp_start, _)| *amp_start < end)
+                .filter(|(_, amp_end)| *amp_end > start)
+                .copied()
+                .collect()
+        })
+    }
+
+    pub fn is_near_boundary(&self, chrom: &str, pos: i64, tolerance: i64) -> bool {
+        self.overlapping(chrom, pos - tolerance, pos + tolerance + 1)
+            .iter()
+            .any(|(start, end)| (pos - start).abs() <= tolerance || (pos - end).abs() <= tolerance)
+    }
+}
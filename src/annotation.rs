@@ -0,0 +1,98 @@
+use crate::error::{Result, VclustError};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Output columns describing the known elements overlapping each cluster
+pub const ANNOTATION_COLUMNS: &[&str] = &["known_elements", "known_fraction"];
+
+/// Known repeats or segmental duplications, sorted by start per contig
+#[derive(Debug, Default)]
+pub struct Annotations {
+    elements: BTreeMap<String, Vec<(i64, i64, String)>>,
+}
+
+impl Annotations {
+    /// Load a BED file; elements without a name in the fourth column are named
+    /// by their coordinates
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mut elements: BTreeMap<String, Vec<(i64, i64, String)>> = BTreeMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') || line.starts_with("track") {
+                continue;
+            }
+            let rec = line.split('\t').collect::<Vec<_>>();
+            if rec.len() < 3 {
+                return Err(VclustError::Parse(format!("Bad annotation line {line}")));
+            }
+            let parse = |val: &str| {
+                val.trim()
+                    .parse::<i64>()
+                    .map_err(|_| VclustError::Parse(format!("Bad annotation line {line}")))
+            };
+            let (start, end) = (parse(rec[1])?, parse(rec[2])?);
+            let name = match rec.get(3).map(|name| name.trim()) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => format!("{}:{start}-{end}", rec[0]),
+            };
+            elements
+                .entry(rec[0].to_string())
+                .or_default()
+                .push((start, end, name));
+        }
+        for contig_elements in elements.values_mut() {
+            contig_elements.sort();
+        }
+        Ok(Annotations { elements })
+    }
+
+    /// Names of the elements overlapping the interval [start, end) and the
+    /// fraction of the interval they cover
+    pub fn annotate(&self, chrom: &str, start: i64, end: i64) -> (Vec<String>, f64) {
+        let Some(elements) = self.elements.get(chrom) else {
+            return (Vec::new(), 0.0);
+        };
+        let overlapping = elements
+            .iter()
+            .take_while(|(elem_start, _, _)| *elem_start < end)
+            .filter(|(_, elem_end, _)| *elem_end > start)
+            .collect_vec();
+
+        // Elements are sorted by start, so the covered length is a single sweep
+        let mut covered = 0;
+        let mut covered_to = start;
+        for (elem_start, elem_end, _) in &overlapping {
+            let elem_start = (*elem_start).max(covered_to);
+            let elem_end = (*elem_end).min(end);
+            if elem_end > elem_start {
+                covered += elem_end - elem_start;
+                covered_to = elem_end;
+            }
+        }
+        let fraction = if end > start {
+            covered as f64 / (end - start) as f64
+        } else {
+            0.0
+        };
+        let names = overlapping
+            .into_iter()
+            .map(|(_, _, name)| name.clone())
+            .collect();
+        (names, fraction)
+    }
+
+    /// Values of the annotation columns for the interval [start, end)
+    pub fn columns_for(&self, chrom: &str, start: i64, end: i64) -> Vec<String> {
+        let (names, fraction) = self.annotate(chrom, start, end);
+        let names = if names.is_empty() {
+            ".".to_string()
+        } else {
+            names.join(",")
+        };
+        vec![names, format!("{fraction:.2}")]
+    }
+}
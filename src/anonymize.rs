@@ -0,0 +1,51 @@
+use crate::pedigree::Segregation;
+use crate::provenance::hex;
+use crate::workflow::LocusResult;
+use sha2::{Digest, Sha256};
+
+/// Stable pseudonym of a sample or read identifier; the same identifier maps
+/// to the same pseudonym in every run, so anonymized outputs can still be joined
+pub fn pseudonym(id: &str) -> String {
+    let digest = hex(&Sha256::digest(id.as_bytes()));
+    format!("anon_{}", &digest[..16])
+}
+
+/// Replace the sample names carried by the result with their pseudonyms
+pub fn anonymize_result(result: &mut LocusResult) {
+    let extensions = result
+        .concordance
+        .iter_mut()
+        .flat_map(|concordance| {
+            concordance.sample = pseudonym(&concordance.sample);
+            concordance.replicates.iter_mut().flatten()
+        })
+        .chain(result.extension.as_mut());
+    for extension in extensions {
+        for evidence in &mut extension.support_evidence {
+            evidence.sample = pseudonym(&evidence.sample);
+        }
+        extension.skipped_reads = std::mem::take(&mut extension.skipped_reads)
+            .into_iter()
+            .map(|(sample, counts)| (pseudonym(&sample), counts))
+            .collect();
+        extension.hom_ref_alt_reads = std::mem::take(&mut extension.hom_ref_alt_reads)
+            .into_iter()
+            .map(|(sample, reads)| (pseudonym(&sample), reads))
+            .collect();
+    }
+}
+
+/// Replace the family and sample names of a segregation row with their
+/// pseudonyms
+pub fn anonymize_segregation(segregation: &mut Segregation) {
+    segregation.family = pseudonym(&segregation.family);
+    let lists = [
+        &mut segregation.carriers,
+        &mut segregation.non_carriers,
+        &mut segregation.unassessed,
+        &mut segregation.inconsistent,
+    ];
+    for sample in lists.into_iter().flatten() {
+        *sample = pseudonym(sample);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::{Result, VclustError};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, LineWriter, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/// Output file written under a temporary name next to its final path and
+/// renamed into place by `commit`; if it is dropped before that, for example
+/// because the run failed, the temporary file is removed
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: BufWriter<File>,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| VclustError::Other(format!("Bad output path {}", path.display())))?;
+        let tmp_name = format!(".{}.tmp{}", file_name.to_string_lossy(), std::process::id());
+        let tmp_path = path.with_file_name(tmp_name);
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(File::create(&tmp_path)?),
+            tmp_path,
+            committed: false,
+        })
+    }
+
+    /// Flush the contents to disk and move the file to its final path
+    pub fn commit(mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        fs::rename(&self.tmp_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.writer.seek(pos)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+/// Refuse to replace existing outputs unless overwriting was requested
+pub fn check_overwrite(paths: &[&Path], force: bool) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+    match paths.iter().find(|path| path.exists()) {
+        Some(path) => Err(VclustError::Other(format!(
+            "Output {} already exists; use --force to overwrite it",
+            path.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Socket the results are streamed to instead of being written to a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddress {
+    /// `tcp://HOST:PORT`
+    Tcp(String),
+    /// `unix://PATH`
+    Unix(PathBuf),
+}
+
+impl SocketAddress {
+    pub fn parse(path: &Path) -> Option<Self> {
+        let path = path.to_str()?;
+        if let Some(address) = path.strip_prefix("tcp://") {
+            Some(SocketAddress::Tcp(address.to_string()))
+        } else {
+            path.strip_prefix("unix://")
+                .map(|path| SocketAddress::Unix(PathBuf::from(path)))
+        }
+    }
+
+    fn connect(&self) -> Result<Box<dyn Write>> {
+        let connected: io::Result<Box<dyn Write>> = match self {
+            SocketAddress::Tcp(address) => TcpStream::connect(address).map(|s| Box::new(s) as _),
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => UnixStream::connect(path).map(|s| Box::new(s) as _),
+            #[cfg(not(unix))]
+            SocketAddress::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not available on this platform",
+            )),
+        };
+        connected.map_err(|e| VclustError::Other(format!("Cannot connect to {self:?}: {e}")))
+    }
+}
+
+/// Destination of the results; stdout is passed through as it cannot be
+/// replaced atomically, and sockets receive each line as soon as it is
+/// complete
+pub enum OutputTarget {
+    Stdout(io::StdoutLock<'static>),
+    File(AtomicFile),
+    Socket(LineWriter<Box<dyn Write>>),
+}
+
+impl OutputTarget {
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => match SocketAddress::parse(path) {
+                Some(address) => Ok(OutputTarget::Socket(LineWriter::new(address.connect()?))),
+                None => Ok(OutputTarget::File(AtomicFile::create(path)?)),
+            },
+            None => Ok(OutputTarget::Stdout(io::stdout().lock())),
+        }
+    }
+
+    pub fn is_socket(&self) -> bool {
+        matches!(self, OutputTarget::Socket(_))
+    }
+
+    pub fn commit(self) -> Result<()> {
+        match self {
+            OutputTarget::Stdout(mut stdout) => Ok(stdout.flush()?),
+            OutputTarget::File(file) => file.commit(),
+            OutputTarget::Socket(mut socket) => Ok(socket.flush()?),
+        }
+    }
+}
+
+impl Write for OutputTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputTarget::Stdout(stdout) => stdout.write(buf),
+            OutputTarget::File(file) => file.write(buf),
+            OutputTarget::Socket(socket) => socket.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputTarget::Stdout(stdout) => stdout.flush(),
+            OutputTarget::File(file) => file.flush(),
+            OutputTarget::Socket(socket) => socket.flush(),
+        }
+    }
+}
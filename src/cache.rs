@@ -0,0 +1,159 @@
+use crate::concordance::Concordance;
+use crate::context::{ContextBuilder, WorkflowContext};
+use crate::error::Result;
+use crate::extend::Extension;
+use crate::locus::Locus;
+use crate::workflow::{run_workflow, LocusResult};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Per-locus results stored on disk and keyed by the locus, the sample set,
+/// and the parameters of the run
+#[derive(Debug)]
+pub struct ResultCache {
+    dir: PathBuf,
+    /// Hash of all inputs shared by every locus of the run
+    run_key: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedResult {
+    extension: Option<Extension>,
+    concordance: Vec<Concordance>,
+    #[serde(default)]
+    scores: Vec<String>,
+}
+
+impl ResultCache {
+    pub fn new(dir: &Path, builder: &ContextBuilder) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut hasher = Fnv1a::default();
+        hasher.write_str(&format!("{:?}", builder.params));
+        hasher.write_str(&format!("{:?}", builder.models));
+        hasher.write_str(&format!("{:?}", builder.amplicons));
+        hasher.write_str(&format!("{:?}", builder.annotations));
+        hasher.write_str(
+            builder
+                .kmer_index
+                .as_ref()
+                .map_or("", |index| &index.sha256),
+        );
+        hasher.write_str(&format!("{:?}", builder.prior_spans));
+        hasher.write_str(&format!("{:?}", builder.population));
+        hasher.write_str(&format!("{:?}", builder.score_columns()));
+        hash_file(&mut hasher, &builder.genome_path)?;
+        for entry in &builder.reads {
+            hasher.write_str(&entry.sample);
+            hasher.write_str(&format!("{:?}", entry.karyotype));
+            hash_file(&mut hasher, &entry.path)?;
+        }
+        Ok(ResultCache {
+            dir: dir.to_path_buf(),
+            run_key: hasher.finish(),
+        })
+    }
+
+    /// Return the cached result of the locus or compute and store it
+    pub fn get_or_run(&self, ctx: &mut WorkflowContext, locus: &Locus) -> Result<LocusResult> {
+        let path = self.entry_path(locus);
+        if let Some(cached) = self.get(&path) {
+            return Ok(LocusResult {
+                locus: locus.clone(),
+                extension: cached.extension,
+                concordance: cached.concordance,
+                scores: cached.scores,
+            });
+        }
+
+        let result = run_workflow(ctx, locus)?;
+        let cached = CachedResult {
+            extension: result.extension,
+            concordance: result.concordance,
+            scores: result.scores,
+        };
+        if let Err(e) = self.put(&path, &cached) {
+            log::warn!("Unable to cache result of {}: {e}", locus.name);
+        }
+        Ok(LocusResult {
+            locus: result.locus,
+            extension: cached.extension,
+            concordance: cached.concordance,
+            scores: cached.scores,
+        })
+    }
+
+    fn entry_path(&self, locus: &Locus) -> PathBuf {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&self.run_key.to_le_bytes());
+        hasher.write_str(&locus.chrom);
+        hasher.write(&locus.start.to_le_bytes());
+        hasher.write(&locus.end.to_le_bytes());
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    fn get(&self, path: &Path) -> Option<CachedResult> {
+        let file = File::open(path).ok()?;
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(cached) => Some(cached),
+            Err(e) => {
+                log::warn!("Ignoring corrupt cache entry {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    fn put(&self, path: &Path, cached: &CachedResult) -> Result<()> {
+        // Write to a temporary file first so that concurrent or interrupted
+        // runs never observe partial entries
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        serde_json::to_writer(BufWriter::new(File::create(&tmp_path)?), cached)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Identify a file by its path, size, and modification time; reads and
+/// references are too large to checksum per run, so a same-sized replacement
+/// with a preserved modification time is not told apart
+fn hash_file(hasher: &mut Fnv1a, path: &Path) -> Result<()> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    hasher.write_str(&path.to_string_lossy());
+    hasher.write(&metadata.len().to_le_bytes());
+    hasher.write(&modified.to_le_bytes());
+    Ok(())
+}
+
+/// 64-bit FNV-1a; unlike the std hasher, it is stable across Rust releases
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Length-prefixed so that consecutive strings cannot run together
+    fn write_str(&mut self, value: &str) {
+        self.write(&value.len().to_le_bytes());
+        self.write(value.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::Result;
+use crate::liftover::ChainMap;
+use crate::tracks::ClusterTrack;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Columns of the comparison of clusters between two references
+pub const COMPARISON_COLUMNS: &[&str] = &[
+    "reference",
+    "name",
+    "extended_region",
+    "ns",
+    "status",
+    "other_region",
+];
+
+/// Whether a cluster found against one reference is found against the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ClusterStatus {
+    /// An overlapping cluster was found against the other reference
+    Shared,
+    /// No cluster was found at the corresponding position of the other
+    /// reference, suggesting reference bias
+    Specific,
+    /// The cluster has no corresponding position in the other reference
+    Unlifted,
+}
+
+impl std::fmt::Display for ClusterStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = match self {
+            ClusterStatus::Shared => "shared",
+            ClusterStatus::Specific => "specific",
+            ClusterStatus::Unlifted => "unlifted",
+        };
+        write!(f, "{status}")
+    }
+}
+
+/// Cluster of one of the compared references and its counterpart, given in
+/// the coordinates of the other reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComparedCluster {
+    /// 0 for the first reference, 1 for the second
+    pub reference: usize,
+    pub cluster: ClusterTrack,
+    pub status: ClusterStatus,
+    pub other_region: Option<(String, i64, i64)>,
+}
+
+/// Match the clusters found against two references; the clusters of the first
+/// are lifted to the second with the chain, and each cluster is shared if it
+/// overlaps a cluster of the other reference
+pub fn compare_clusters(
+    first: &[ClusterTrack],
+    second: &[ClusterTrack],
+    chains: &ChainMap,
+) -> Vec<ComparedCluster> {
+    let mut by_chrom: BTreeMap<&str, Vec<&ClusterTrack>> = BTreeMap::new();
+    for cluster in second {
+        by_chrom.entry(&cluster.chrom).or_default().push(cluster);
+    }
+    let overlapping = |chrom: &str, start: i64, end: i64| {
+        by_chrom
+            .get(chrom)
+            .into_iter()
+            .flatten()
+            .find(|cluster| cluster.start < end && cluster.end > start)
+            .map(|cluster| (cluster.chrom.clone(), cluster.start, cluster.end))
+    };
+
+    let mut compared = Vec::with_capacity(first.len() + second.len());
+    let mut lifted: BTreeMap<String, Vec<(i64, i64, usize)>> = BTreeMap::new();
+    for cluster in first {
+        let (status, other_region) = match chains.lift(&cluster.chrom, cluster.start, cluster.end) {
+            None => (ClusterStatus::Unlifted, None),
+            Some((chrom, start, end)) => {
+                lifted
+                    .entry(chrom.clone())
+                    .or_default()
+                    .push((start, end, compared.len()));
+                match overlapping(&chrom, start, end) {
+                    Some(region) => (ClusterStatus::Shared, Some(region)),
+                    None => (ClusterStatus::Specific, Some((chrom, start, end))),
+                }
+            }
+        };
+        compared.push(ComparedCluster {
+            reference: 0,
+            cluster: cluster.clone(),
+            status,
+            other_region,
+        });
+    }
+
+    for cluster in second {
+        let counterpart = lifted
+            .get(&cluster.chrom)
+            .into_iter()
+            .flatten()
+            .find(|(start, end, _)| *start < cluster.end && *end > cluster.start);
+        let (status, other_region) = match counterpart {
+            Some((_, _, index)) => {
+                let first_cluster = &compared[*index].cluster;
+                let region = (
+                    first_cluster.chrom.clone(),
+                    first_cluster.start,
+                    first_cluster.end,
+                );
+                (ClusterStatus::Shared, Some(region))
+            }
+            None => (ClusterStatus::Specific, None),
+        };
+        compared.push(ComparedCluster {
+            reference: 1,
+            cluster: cluster.clone(),
+            status,
+            other_region,
+        });
+    }
+    compared
+}
+
+/// Write the compared clusters as a TSV, naming the references by their labels
+pub fn write_comparison(
+    compared: &[ComparedCluster],
+    labels: [&str; 2],
+    writer: &mut impl Write,
+) -> Result<()> {
+    writeln!(writer, "#{}", COMPARISON_COLUMNS.join("\t"))?;
+    for rec in compared {
+        let cluster = &rec.cluster;
+        let other_region = match &rec.other_region {
+            Some((chrom, start, end)) => format!("{chrom}:{start}-{end}"),
+            None => "NA".to_string(),
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}:{}-{}\t{}\t{}\t{other_region}",
+            labels[rec.reference],
+            cluster.name,
+            cluster.chrom,
+            cluster.start,
+            cluster.end,
+            cluster.ns,
+            rec.status
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use clap::{Arg, Command, ValueEnum};
+use itertools::Itertools;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Completion script for `shell` covering the subcommands and long options of
+/// `cmd`; option values without a fixed set of choices complete as file names
+pub fn completion_script(cmd: &Command, bin_name: &str, shell: Shell) -> String {
+    let mut cmd = cmd.clone();
+    cmd.build();
+    match shell {
+        Shell::Bash => bash_script(&cmd, bin_name),
+        Shell::Zsh => zsh_script(&cmd, bin_name),
+        Shell::Fish => fish_script(&cmd, bin_name),
+    }
+}
+
+fn options(cmd: &Command) -> Vec<&Arg> {
+    cmd.get_arguments()
+        .filter(|arg| arg.get_long().is_some() && !arg.is_hide_set())
+        .collect()
+}
+
+fn subcommands(cmd: &Command) -> Vec<&Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .collect()
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+fn choices(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn bash_script(cmd: &Command, bin_name: &str) -> String {
+    let func = format!("_{}", bin_name.replace('-', "_"));
+    let long_opts = |cmd: &Command| {
+        options(cmd)
+            .iter()
+            .map(|arg| format!("--{}", arg.get_long().unwrap()))
+            .join(" ")
+    };
+    let value_cases = |cmd: &Command| {
+        options(cmd)
+            .iter()
+            .filter(|arg| !choices(arg).is_empty())
+            .map(|arg| {
+                format!(
+                    "                --{}) opts=\"{}\" ;;\n",
+                    arg.get_long().unwrap(),
+                    choices(arg).join(" ")
+                )
+            })
+            .join("")
+    };
+
+    let mut script = String::new();
+    let sub_names = subcommands(cmd).iter().map(|sub| sub.get_name()).join("|");
+    writeln!(script, "{func}() {{").unwrap();
+    writeln!(script, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(script, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"").unwrap();
+    writeln!(script, "    local subcommand=\"\" opts=\"\"").unwrap();
+    writeln!(
+        script,
+        "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do"
+    )
+    .unwrap();
+    writeln!(script, "        case \"$word\" in").unwrap();
+    writeln!(
+        script,
+        "            {sub_names}) subcommand=\"$word\"; break ;;"
+    )
+    .unwrap();
+    writeln!(script, "        esac").unwrap();
+    writeln!(script, "    done").unwrap();
+    writeln!(script, "    case \"$subcommand\" in").unwrap();
+    let sub_list = subcommands(cmd).iter().map(|sub| sub.get_name()).join(" ");
+    let mut branches = subcommands(cmd)
+        .into_iter()
+        .map(|sub| {
+            (
+                sub.get_name().to_string(),
+                long_opts(sub),
+                value_cases(sub),
+                "",
+            )
+        })
+        .collect_vec();
+    branches.push(("*".to_string(), long_opts(cmd), value_cases(cmd), &sub_list));
+    for (name, opts, values, subs) in branches {
+        writeln!(script, "        {name})").unwrap();
+        writeln!(script, "            case \"$prev\" in").unwrap();
+        write!(script, "{values}").unwrap();
+        writeln!(script, "                *)").unwrap();
+        writeln!(script, "                    if [[ \"$cur\" == -* ]]; then").unwrap();
+        writeln!(script, "                        opts=\"{opts}\"").unwrap();
+        if !subs.is_empty() {
+            writeln!(
+                script,
+                "                    elif [[ $COMP_CWORD -eq 1 ]]; then"
+            )
+            .unwrap();
+            writeln!(script, "                        opts=\"{subs}\"").unwrap();
+        }
+        writeln!(script, "                    fi").unwrap();
+        writeln!(script, "                    ;;").unwrap();
+        writeln!(script, "            esac").unwrap();
+        writeln!(script, "            ;;").unwrap();
+    }
+    writeln!(script, "    esac").unwrap();
+    writeln!(
+        script,
+        "    COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))"
+    )
+    .unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script, "complete -o default -F {func} {bin_name}").unwrap();
+    script
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_specs(cmd: &Command) -> String {
+    options(cmd)
+        .iter()
+        .map(|arg| {
+            let long = arg.get_long().unwrap();
+            let help = zsh_escape(&help(arg));
+            if !arg.get_action().takes_values() {
+                return format!("'--{long}[{help}]'");
+            }
+            let value_name = arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| long.to_uppercase());
+            let action = match choices(arg) {
+                choices if choices.is_empty() => "_files".to_string(),
+                choices => format!("({})", choices.join(" ")),
+            };
+            format!("'--{long}[{help}]:{value_name}:{action}'")
+        })
+        .chain(["'*:file:_files'".to_string()])
+        .join(" \\\n                ")
+}
+
+fn zsh_script(cmd: &Command, bin_name: &str) -> String {
+    let func = format!("_{}", bin_name.replace('-', "_"));
+    let mut script = String::new();
+    writeln!(script, "#compdef {bin_name}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "{func}() {{").unwrap();
+    writeln!(script, "    local -a subcommands").unwrap();
+    writeln!(script, "    subcommands=(").unwrap();
+    for sub in subcommands(cmd) {
+        let about = sub
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        writeln!(
+            script,
+            "        '{}:{}'",
+            sub.get_name(),
+            zsh_escape(&about)
+        )
+        .unwrap();
+    }
+    writeln!(script, "    )").unwrap();
+    writeln!(
+        script,
+        "    if (( CURRENT == 2 )) && [[ $words[2] != -* ]]; then"
+    )
+    .unwrap();
+    writeln!(script, "        _describe 'command' subcommands").unwrap();
+    writeln!(script, "        return").unwrap();
+    writeln!(script, "    fi").unwrap();
+    writeln!(script, "    case $words[2] in").unwrap();
+    for sub in subcommands(cmd) {
+        writeln!(script, "        {})", sub.get_name()).unwrap();
+        writeln!(script, "            _arguments {}", zsh_specs(sub)).unwrap();
+        writeln!(script, "            ;;").unwrap();
+    }
+    writeln!(script, "        *)").unwrap();
+    writeln!(script, "            _arguments {}", zsh_specs(cmd)).unwrap();
+    writeln!(script, "            ;;").unwrap();
+    writeln!(script, "    esac").unwrap();
+    writeln!(script, "}}").unwrap();
+    writeln!(script).unwrap();
+    writeln!(script, "{func} \"$@\"").unwrap();
+    script
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish_script(cmd: &Command, bin_name: &str) -> String {
+    let mut script = String::new();
+    let option_lines = |script: &mut String, cmd: &Command, condition: &str| {
+        for arg in options(cmd) {
+            write!(
+                script,
+                "complete -c {bin_name} -n '{condition}' -l {} -d '{}'",
+                arg.get_long().unwrap(),
+                fish_escape(&help(arg))
+            )
+            .unwrap();
+            match choices(arg) {
+                choices if !choices.is_empty() => {
+                    write!(script, " -x -a '{}'", choices.join(" ")).unwrap()
+                }
+                _ if arg.get_action().takes_values() => write!(script, " -r -F").unwrap(),
+                _ => {}
+            }
+            writeln!(script).unwrap();
+        }
+    };
+
+    for sub in subcommands(cmd) {
+        let about = sub
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        writeln!(
+            script,
+            "complete -c {bin_name} -n '__fish_use_subcommand' -f -a {} -d '{}'",
+            sub.get_name(),
+            fish_escape(&about)
+        )
+        .unwrap();
+    }
+    option_lines(&mut script, cmd, "__fish_use_subcommand");
+    for sub in subcommands(cmd) {
+        let condition = format!("__fish_seen_subcommand_from {}", sub.get_name());
+        option_lines(&mut script, sub, &condition);
+    }
+    script
+}
+
+fn roff_escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    // Lines starting with a control character would be read as requests
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{text}"),
+        false => text,
+    }
+}
+
+fn man_options(page: &mut String, cmd: &Command) {
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let value_names = arg
+            .get_value_names()
+            .map(|names| names.iter().map(|name| format!("\\fI{name}\\fR")).join(" "))
+            .unwrap_or_default();
+        let names = match (arg.get_short(), arg.get_long()) {
+            (Some(short), Some(long)) => {
+                format!("\\fB\\-{short}\\fR, \\fB\\-\\-{}\\fR", roff_escape(long))
+            }
+            (None, Some(long)) => format!("\\fB\\-\\-{}\\fR", roff_escape(long)),
+            (Some(short), None) => format!("\\fB\\-{short}\\fR"),
+            (None, None) => String::new(),
+        };
+        let term = match (names.is_empty(), arg.get_action().takes_values()) {
+            (true, _) => value_names,
+            (false, true) => format!("{names} {value_names}"),
+            (false, false) => names,
+        };
+        writeln!(page, ".TP\n{term}\n{}", roff_escape(&help(arg))).unwrap();
+        let choices = choices(arg);
+        if !choices.is_empty() {
+            writeln!(page, ".br\nOne of: {}", roff_escape(&choices.join(", "))).unwrap();
+        }
+        if let Some(var) = arg.get_env() {
+            let var = var.to_string_lossy();
+            writeln!(page, ".br\nEnvironment: \\fB{}\\fR", roff_escape(&var)).unwrap();
+        }
+    }
+}
+
+/// Manual page in roff format listing the options of `cmd` and of each of its
+/// subcommands
+pub fn man_page(cmd: &Command, bin_name: &str) -> String {
+    let mut cmd = cmd.clone();
+    cmd.build();
+    let version = cmd.get_version().unwrap_or_default();
+    let title = format!("{bin_name} {version}");
+    let about = cmd
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default();
+
+    let mut page = String::new();
+    writeln!(
+        page,
+        ".TH {} 1 \"\" \"{}\"",
+        bin_name.to_uppercase(),
+        title.trim_end()
+    )
+    .unwrap();
+    writeln!(page, ".SH NAME\n{bin_name} \\- {}", roff_escape(&about)).unwrap();
+    writeln!(page, ".SH SYNOPSIS").unwrap();
+    writeln!(page, "\\fB{bin_name}\\fR [\\fIOPTIONS\\fR]").unwrap();
+    writeln!(
+        page,
+        ".br\n\\fB{bin_name}\\fR \\fICOMMAND\\fR [\\fIARGS\\fR]"
+    )
+    .unwrap();
+    writeln!(page, ".SH OPTIONS").unwrap();
+    man_options(&mut page, &cmd);
+    writeln!(page, ".SH COMMANDS").unwrap();
+    for sub in subcommands(&cmd) {
+        let about = sub
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        writeln!(page, ".SS {}\n{}", sub.get_name(), roff_escape(&about)).unwrap();
+        man_options(&mut page, sub);
+    }
+    page
+}
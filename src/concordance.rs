@@ -0,0 +1,106 @@
+use crate::context::WorkflowContext;
+use crate::extend::{get_sample_extension, Extension};
+use crate::locus::Locus;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub const CONCORDANCE_COLUMNS: &[&str] = &[
+    "name",
+    "sample",
+    "n_replicates",
+    "lf_offsets",
+    "rf_offsets",
+    "support",
+    "max_boundary_diff",
+    "support_concordant",
+];
+
+/// Extensions of a single locus computed separately for each replicate of a sample
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Concordance {
+    pub sample: String,
+    pub replicates: Vec<Option<Extension>>,
+}
+
+impl Concordance {
+    /// Largest disagreement between replicates across both boundaries
+    pub fn max_boundary_diff(&self) -> Option<i64> {
+        let exts = self
+            .replicates
+            .iter()
+            .map(Option::as_ref)
+            .collect::<Option<Vec<_>>>()?;
+        let lf = exts
+            .iter()
+            .map(|ext| ext.lf_offset)
+            .minmax()
+            .into_option()?;
+        let rf = exts
+            .iter()
+            .map(|ext| ext.rf_offset)
+            .minmax()
+            .into_option()?;
+        Some((lf.1 - lf.0).max(rf.1 - rf.0))
+    }
+
+    pub fn support_concordant(&self) -> Option<bool> {
+        let exts = self
+            .replicates
+            .iter()
+            .map(Option::as_ref)
+            .collect::<Option<Vec<_>>>()?;
+        Some(exts.iter().map(|ext| ext.ns > 0).all_equal())
+    }
+
+    pub fn to_row(&self, locus: &Locus) -> String {
+        let field = |get: fn(&Extension) -> i64| {
+            self.replicates
+                .iter()
+                .map(|ext| {
+                    ext.as_ref()
+                        .map_or("NA".to_string(), |ext| get(ext).to_string())
+                })
+                .join(",")
+        };
+        let na = |val: Option<String>| val.unwrap_or("NA".to_string());
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            locus.name,
+            self.sample,
+            self.replicates.len(),
+            field(|ext| ext.lf_offset),
+            field(|ext| ext.rf_offset),
+            field(|ext| ext.ns),
+            na(self.max_boundary_diff().map(|diff| diff.to_string())),
+            na(self.support_concordant().map(|flag| flag.to_string())),
+        )
+    }
+}
+
+pub fn concordance_header() -> String {
+    format!("#{}", CONCORDANCE_COLUMNS.join("\t"))
+}
+
+/// Extend the locus independently in every replicate of samples with several BAMs
+pub fn get_concordance(ctx: &mut WorkflowContext, locus: &Locus) -> Vec<Concordance> {
+    let groups = ctx
+        .samples
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| (sample.clone(), index))
+        .into_group_map();
+
+    let mut concordance = Vec::new();
+    for sample in ctx.samples.iter().unique().cloned().collect_vec() {
+        let indexes = &groups[&sample];
+        if indexes.len() < 2 {
+            continue;
+        }
+        let replicates = indexes
+            .iter()
+            .map(|index| get_sample_extension(ctx, locus, &[*index]).ok())
+            .collect();
+        concordance.push(Concordance { sample, replicates });
+    }
+    concordance
+}
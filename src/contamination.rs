@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Background alt fraction above which a sample is reported as likely
+/// contaminated
+pub const CONTAMINATION_WARNING_FRACTION: f64 = 0.02;
+
+/// Loci with fewer reads than this in a sample do not count towards its
+/// estimate
+pub const MIN_ESTIMATE_READS: usize = 10;
+
+/// Alt support of a sample at loci where no sample supports a cluster; reads
+/// of another individual show up there as low-fraction alt support
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContaminationEstimate {
+    pub hom_ref_loci: usize,
+    pub reads: usize,
+    pub alt_reads: usize,
+    /// Fraction of the reads carrying an alt operation
+    pub alt_fraction: f64,
+}
+
+impl ContaminationEstimate {
+    /// Add the reads of one locus
+    pub fn add(&mut self, alt_reads: usize, reads: usize) {
+        if reads < MIN_ESTIMATE_READS {
+            return;
+        }
+        self.hom_ref_loci += 1;
+        self.merge_counts(alt_reads, reads);
+    }
+
+    pub fn merge(&mut self, other: &ContaminationEstimate) {
+        self.hom_ref_loci += other.hom_ref_loci;
+        self.merge_counts(other.alt_reads, other.reads);
+    }
+
+    fn merge_counts(&mut self, alt_reads: usize, reads: usize) {
+        self.alt_reads += alt_reads;
+        self.reads += reads;
+        self.alt_fraction = match self.reads {
+            0 => 0.0,
+            reads => self.alt_reads as f64 / reads as f64,
+        };
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        self.alt_fraction > CONTAMINATION_WARNING_FRACTION
+    }
+}
+
+/// Warnings naming the samples whose background alt fraction suggests
+/// contamination
+pub fn contamination_warnings(estimates: &BTreeMap<String, ContaminationEstimate>) -> Vec<String> {
+    estimates
+        .iter()
+        .filter(|(_, estimate)| estimate.is_suspicious())
+        .map(|(sample, estimate)| {
+            format!(
+                "{:.1}% of the reads of {sample} carry alt operations at {} loci without \
+                 support, which suggests contamination; its support may inflate ns",
+                100.0 * estimate.alt_fraction,
+                estimate.hom_ref_loci
+            )
+        })
+        .collect()
+}
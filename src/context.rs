@@ -0,0 +1,404 @@
+use crate::alignment::{open_source, AlignmentSource};
+use crate::amplicon::Amplicons;
+use crate::annotation::Annotations;
+use crate::error::Result;
+use crate::expression::Expression;
+use crate::manifest::{Karyotype, ReadsEntry};
+use crate::models::{LogModels, Models};
+use crate::population::PopulationCatalog;
+use crate::prior::PriorSpans;
+use crate::profile::OntReadClass;
+use crate::scorer::LocusScorer;
+use crate::selection::ModelRule;
+use crate::span::SpanReferenceMode;
+use crate::uniqueness::KmerIndex;
+use crate::workflow::enabled_columns;
+use rust_htslib::bam::Record;
+use rust_htslib::faidx;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Tunable parameters of the profiling and extension steps
+#[derive(Debug, Clone, Serialize)]
+pub struct Params {
+    /// Size of the search window added to each side of a locus
+    pub radius: i64,
+    /// Scale the search window with locus length instead of using a fixed radius
+    pub radius_scale: Option<f64>,
+    /// Bounds of the search window when it is scaled with locus length
+    pub min_radius: i64,
+    pub max_radius: i64,
+    pub min_mapq: u8,
+    /// Profile supplementary alignments, which carry the split parts of
+    /// assembly contigs
+    pub keep_supplementary: bool,
+    pub ont_read_class: OntReadClass,
+    /// Loci with more reads than this in any sample are skipped
+    pub max_reads: usize,
+    /// Reads of all samples are subsampled in proportion to each sample's read
+    /// count when their total exceeds this
+    pub max_pooled_reads: Option<usize>,
+    /// Minimal length of a mismatch/indel run for a read to count as alt
+    pub min_alt_len: usize,
+    /// Minimal number of alt reads for a sample to count as supporting
+    pub min_alt_reads: usize,
+    /// Minimal depth for a sample to count as supporting
+    pub min_sample_depth: f64,
+    pub min_depth: f64,
+    pub max_depth: f64,
+    /// Window lengths used for successive rounds of flank extension
+    pub window_ladder: Vec<i64>,
+    /// Posterior of the reference model at which extension stops
+    pub min_prob_ref: f64,
+    /// Only reads spanning the locus count towards sample support
+    pub require_spanning: bool,
+    /// Positions with depth below this fraction of the mean are reported as gaps
+    pub gap_depth_fraction: f64,
+    /// Compute per-replicate boundaries for samples with several BAMs
+    pub replicate_concordance: bool,
+    /// Record the strongest alt evidence of each supporting sample
+    pub support_evidence: bool,
+    /// Record whether each sample supports the cluster
+    pub sample_support: bool,
+    /// Add the haplotype balance columns to the output
+    pub haplotype_balance: bool,
+    /// Largest minority haplotype fraction of support flagged as imbalance
+    pub imbalance_fraction: f64,
+    /// Call deletions from split reads at loci too shallow to profile
+    pub long_deletions: bool,
+    /// Add the priority score column to the output
+    pub priority_score: bool,
+    /// User-defined priority replacing the default composite score
+    pub priority_expression: Option<Expression>,
+    /// Model variants for loci matching each rule, tried in order
+    pub model_rules: Vec<ModelRule>,
+    /// Report where neither model explains the windows of a locus
+    pub flag_model_uncertain: bool,
+    /// Score the reference uniqueness of each search window and dampen the alt
+    /// signal of non-unique windows
+    pub uniqueness: bool,
+    /// Subsample reads instead of enforcing the read cap on every contig
+    pub high_copy: bool,
+    /// Contigs (e.g. chrM) to subsample instead of enforcing the read cap
+    pub high_copy_contigs: Vec<String>,
+    pub seed: u64,
+    /// Loci with less pooled alt signal than this within the locus are reported
+    /// as unextended without running the window ladder
+    pub prescreen_alt_mass: Option<f64>,
+    /// Soft clips within this distance of an amplicon boundary are ignored
+    pub amplicon_tolerance: i64,
+    /// Alt events within this distance of either end of a read alignment count
+    /// towards the read position bias
+    pub read_end_len: i64,
+    /// Clusters whose alt events all lie at read ends are reported as unextended
+    pub filter_read_end_alts: bool,
+    /// Move indels to their leftmost equivalent position in the reference
+    /// before profiling
+    pub left_align_indels: bool,
+    /// Reference of the extended span to report with each cluster, if any
+    pub span_reference: Option<SpanReferenceMode>,
+    /// Reads with supplementary alignments on other contigs or further away
+    /// than this are excluded as chimeras, if set
+    pub chimera_distance: Option<i64>,
+    /// Fail loci whose window log-likelihoods or posteriors are not finite
+    /// instead of extending them
+    pub audit_numerics: bool,
+    /// Search regions at least this long scan their two flanks on separate
+    /// threads
+    pub parallel_scan_len: Option<i64>,
+    /// Release the buffers of each worker after every locus instead of reusing
+    /// them for the next one
+    pub low_memory: bool,
+    /// Profile each read with this probability, for subsampled runs
+    pub read_fraction: Option<f64>,
+    /// Stop profiling the reads of a sample at a locus once its alt fraction
+    /// is known at this confidence and its support is settled
+    pub early_stop_confidence: Option<f64>,
+    /// Keep the discretized profile of each locus, for training exports
+    pub export_states: bool,
+    /// Extend each supported locus again on this many random halves of its
+    /// reads and report the spread of its boundaries
+    pub stability_check: Option<usize>,
+    /// Pooled depth outside the targets, if estimated for targeted panels
+    pub off_target_depth: Option<f64>,
+    /// Report physical and base-level depth over each extended region
+    pub base_coverage: bool,
+}
+
+impl Params {
+    /// Adjust thresholds for haplotype assemblies aligned to the reference, where
+    /// each haplotype contributes a single alignment to every locus
+    pub fn for_assemblies(self) -> Self {
+        Params {
+            min_mapq: 5,
+            keep_supplementary: true,
+            min_alt_reads: 1,
+            min_sample_depth: 1.0,
+            min_depth: 1.0,
+            // Contigs spanning structural variants are split across distant loci
+            chimera_distance: None,
+            ..self
+        }
+    }
+
+    /// Adjust thresholds for paired-end short reads, whose aligners report lower
+    /// mapping qualities and which have many more reads per locus and a larger
+    /// share of their bases near read ends than long reads
+    pub fn for_short_reads(self) -> Self {
+        Params {
+            min_mapq: 20,
+            max_reads: 2000,
+            read_end_len: 20,
+            ..self
+        }
+    }
+
+    /// Adjust thresholds for PacBio subreads, which carry many more small
+    /// indel errors than HiFi reads and contribute several passes of each
+    /// molecule to a locus
+    pub fn for_subreads(self) -> Self {
+        Params {
+            min_alt_len: 10,
+            min_alt_reads: 10,
+            max_reads: 2000,
+            max_depth: 1500.0,
+            ..self
+        }
+    }
+
+    /// Keep the memory of each worker to what the current locus needs: buffers
+    /// are released after every locus and both flanks are scanned on the
+    /// worker thread; results are unchanged
+    pub fn for_low_memory(self) -> Self {
+        Params {
+            low_memory: true,
+            parallel_scan_len: None,
+            ..self
+        }
+    }
+
+    /// Size of the search window added to each side of a locus of given length
+    pub fn radius_for(&self, locus_len: i64) -> i64 {
+        match self.radius_scale {
+            Some(scale) => {
+                let radius = (scale * locus_len as f64).round() as i64;
+                radius.clamp(self.min_radius, self.max_radius)
+            }
+            None => self.radius,
+        }
+    }
+
+    /// Windows of the ladder that cannot be slid over the flanks of the
+    /// smallest search window, and are skipped for the loci searched with it
+    pub fn dropped_windows(&self) -> Vec<i64> {
+        let min_radius = match self.radius_scale {
+            Some(_) => self.min_radius,
+            None => self.radius,
+        };
+        self.window_ladder
+            .iter()
+            .copied()
+            .filter(|len| *len >= min_radius)
+            .collect()
+    }
+
+    pub fn is_high_copy(&self, chrom: &str) -> bool {
+        self.high_copy || self.high_copy_contigs.iter().any(|contig| contig == chrom)
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            radius: 500,
+            radius_scale: None,
+            min_radius: 300,
+            max_radius: 5000,
+            min_mapq: 50,
+            keep_supplementary: false,
+            ont_read_class: OntReadClass::All,
+            max_reads: 200,
+            max_pooled_reads: None,
+            min_alt_len: 5,
+            min_alt_reads: 3,
+            min_sample_depth: 5.0,
+            min_depth: 5.0,
+            max_depth: 150.0,
+            window_ladder: vec![150, 50, 25, 10],
+            min_prob_ref: 0.5,
+            require_spanning: false,
+            gap_depth_fraction: 0.25,
+            replicate_concordance: false,
+            support_evidence: false,
+            sample_support: false,
+            haplotype_balance: false,
+            imbalance_fraction: 0.2,
+            long_deletions: false,
+            priority_score: false,
+            priority_expression: None,
+            model_rules: Vec::new(),
+            flag_model_uncertain: false,
+            uniqueness: false,
+            high_copy: false,
+            high_copy_contigs: Vec::new(),
+            seed: 42,
+            amplicon_tolerance: 10,
+            read_end_len: 100,
+            filter_read_end_alts: false,
+            left_align_indels: false,
+            span_reference: None,
+            chimera_distance: None,
+            audit_numerics: false,
+            parallel_scan_len: Some(4000),
+            low_memory: false,
+            read_fraction: None,
+            early_stop_confidence: None,
+            export_states: false,
+            stability_check: None,
+            prescreen_alt_mass: None,
+            off_target_depth: None,
+            base_coverage: false,
+        }
+    }
+}
+
+/// Buffers reused across loci to avoid per-locus allocations
+#[derive(Debug, Default)]
+pub struct Scratch {
+    pub covs: Vec<u32>,
+    /// Per-position counts of reads deleting the position
+    pub dels: Vec<u32>,
+    pub alts: Vec<u32>,
+    pub clips: Vec<u32>,
+    pub alt_events: Vec<u32>,
+    pub read_end_events: Vec<u32>,
+    /// Reads of all samples at the current locus, kept only for scorers
+    pub kept_reads: Vec<Record>,
+    /// Time spent fetching reads since the worker started, in seconds
+    pub read_secs: f64,
+}
+
+impl Scratch {
+    /// Free the buffers, which otherwise keep the capacity of the largest
+    /// locus seen so far
+    pub fn release(&mut self) {
+        *self = Scratch {
+            read_secs: self.read_secs,
+            ..Default::default()
+        };
+    }
+}
+
+/// Everything a worker needs to process loci
+pub struct WorkflowContext {
+    pub params: Arc<Params>,
+    pub models: Arc<Models>,
+    pub log_models: LogModels,
+    /// Models of each of the model rules of the parameters
+    pub rule_models: Vec<(Arc<Models>, LogModels)>,
+    pub bams: Vec<Box<dyn AlignmentSource>>,
+    /// Sample name of each BAM
+    pub samples: Vec<String>,
+    pub karyotypes: Vec<Option<Karyotype>>,
+    /// Targeted sequencing design, if any
+    pub amplicons: Option<Arc<Amplicons>>,
+    /// Known elements reported for each cluster, if any
+    pub annotations: Option<Arc<Annotations>>,
+    /// Repeated reference k-mers for the uniqueness score, if prebuilt
+    pub kmer_index: Option<Arc<KmerIndex>>,
+    /// Extended spans of a previous run narrowing the search, if any
+    pub prior_spans: Option<Arc<PriorSpans>>,
+    /// Cohort frequencies reported for each locus, if any
+    pub population: Option<Arc<PopulationCatalog>>,
+    pub reference: faidx::Reader,
+    pub scorers: Vec<Arc<dyn LocusScorer>>,
+    pub scratch: Scratch,
+}
+
+/// Inputs shared by all workers; readers are not thread-safe, so each worker
+/// builds its own context from a clone of this
+#[derive(Clone)]
+pub struct ContextBuilder {
+    pub params: Arc<Params>,
+    pub models: Arc<Models>,
+    pub amplicons: Option<Arc<Amplicons>>,
+    pub annotations: Option<Arc<Annotations>>,
+    pub kmer_index: Option<Arc<KmerIndex>>,
+    pub prior_spans: Option<Arc<PriorSpans>>,
+    pub population: Option<Arc<PopulationCatalog>>,
+    pub genome_path: PathBuf,
+    pub reads: Vec<ReadsEntry>,
+    pub scorers: Vec<Arc<dyn LocusScorer>>,
+}
+
+impl ContextBuilder {
+    /// Inputs with the default parameters and models, no optional catalogs
+    /// and no registered scorers
+    pub fn new(genome_path: PathBuf, reads: Vec<ReadsEntry>) -> Self {
+        ContextBuilder {
+            params: Arc::new(Params::default()),
+            models: Arc::new(Models::default()),
+            amplicons: None,
+            annotations: None,
+            kmer_index: None,
+            prior_spans: None,
+            population: None,
+            genome_path,
+            reads,
+            scorers: Vec::new(),
+        }
+    }
+
+    /// Output columns of the enabled built-in columns, in the order of the
+    /// `SCORE_COLUMNS`, followed by those of the registered scorers
+    pub fn score_columns(&self) -> Vec<String> {
+        enabled_columns(
+            &self.params,
+            self.annotations.is_some(),
+            self.population.is_some(),
+        )
+        .flat_map(|column| column.names().iter().map(|name| name.to_string()))
+        .chain(self.scorers.iter().flat_map(|scorer| scorer.columns()))
+        .collect()
+    }
+
+    pub fn build(&self) -> Result<WorkflowContext> {
+        self.models.validate()?;
+        let mut rule_models = Vec::new();
+        for rule in &self.params.model_rules {
+            let models = rule.apply(&self.models);
+            models.validate()?;
+            let log_models = LogModels::from(&models);
+            rule_models.push((Arc::new(models), log_models));
+        }
+        let reference = faidx::Reader::from_path(&self.genome_path)?;
+        let mut bams = Vec::new();
+        for entry in &self.reads {
+            bams.push(open_source(&entry.path)?);
+        }
+        let samples = self
+            .reads
+            .iter()
+            .map(|entry| entry.sample.clone())
+            .collect();
+        let karyotypes = self.reads.iter().map(|entry| entry.karyotype).collect();
+        Ok(WorkflowContext {
+            params: self.params.clone(),
+            models: self.models.clone(),
+            log_models: LogModels::from(&*self.models),
+            rule_models,
+            bams,
+            samples,
+            karyotypes,
+            amplicons: self.amplicons.clone(),
+            annotations: self.annotations.clone(),
+            kmer_index: self.kmer_index.clone(),
+            prior_spans: self.prior_spans.clone(),
+            population: self.population.clone(),
+            reference,
+            scorers: self.scorers.clone(),
+            scratch: Scratch::default(),
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use crate::extend::Extension;
+use crate::profile::{get_ref_len, profiled_ops, CigarOp};
+use rust_htslib::bam::Record;
+use serde::{Deserialize, Serialize};
+
+/// Columns added to the output by `--base-coverage`
+pub const COVERAGE_COLUMNS: &[&str] = &["physical_depth", "base_depth"];
+
+/// Mean depth per sample over a span, counting reads through deletions as
+/// covering (physical) or only their aligned bases (base-level)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BaseCoverage {
+    pub physical_depth: f64,
+    pub base_depth: f64,
+}
+
+/// Coverage of [start, end) by the profiled reads of `n_samples` samples;
+/// deletions count towards physical depth only, and spliced-out intervals
+/// towards neither
+pub fn get_base_coverage(
+    reads: &[Record],
+    (start, end): (i64, i64),
+    n_samples: usize,
+) -> BaseCoverage {
+    let (mut physical, mut sequenced) = (0, 0);
+    for rec in reads {
+        let (mut ref_pos, ops) = profiled_ops(rec, None);
+        for op in &ops {
+            if ref_pos >= end {
+                break;
+            }
+            let op_len = get_ref_len(op);
+            let overlap = ((ref_pos + op_len).min(end) - ref_pos.max(start)).max(0);
+            match op {
+                CigarOp::Match(_) | CigarOp::Equal(_) | CigarOp::Diff(_) => {
+                    physical += overlap;
+                    sequenced += overlap;
+                }
+                CigarOp::Del(_) => physical += overlap,
+                _ => {}
+            }
+            ref_pos += op_len;
+        }
+    }
+    let positions = ((end - start).max(1) * n_samples.max(1) as i64) as f64;
+    BaseCoverage {
+        physical_depth: physical as f64 / positions,
+        base_depth: sequenced as f64 / positions,
+    }
+}
+
+/// Mean coverage over `n_samples` samples from the coverage of each BAM,
+/// weighted by one over the number of replicates of its sample
+pub fn pool_base_coverage(
+    coverages: impl Iterator<Item = (BaseCoverage, f64)>,
+    n_samples: usize,
+) -> BaseCoverage {
+    let (physical, sequenced) =
+        coverages.fold((0.0, 0.0), |(physical, sequenced), (cov, weight)| {
+            (
+                physical + cov.physical_depth * weight,
+                sequenced + cov.base_depth * weight,
+            )
+        });
+    let n_samples = n_samples.max(1) as f64;
+    BaseCoverage {
+        physical_depth: physical / n_samples,
+        base_depth: sequenced / n_samples,
+    }
+}
+
+/// Values of the coverage columns for a locus, over its extended region
+pub fn coverage_columns(ext: Option<&Extension>) -> Vec<String> {
+    match ext.and_then(|ext| ext.base_coverage) {
+        Some(coverage) => vec![
+            format!("{:.1}", coverage.physical_depth),
+            format!("{:.1}", coverage.base_depth),
+        ],
+        None => vec!["NA".to_string(); COVERAGE_COLUMNS.len()],
+    }
+}
@@ -0,0 +1,181 @@
+use crate::alignment::AlignmentSource;
+use crate::context::Params;
+use crate::error::Result;
+use crate::locus::Locus;
+use crate::profile::{passes_filters, Region};
+use itertools::Itertools;
+use rust_htslib::bam::ext::BamRecordExtensions;
+use rust_htslib::bam::record::Aux;
+use rust_htslib::bam::Record;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Largest distance of the breakpoints of a split read from the consensus
+/// breakpoints for the read to support the deletion
+pub const BREAKPOINT_TOLERANCE: i64 = 100;
+/// Largest mean depth within the deletion relative to the mean depth of its
+/// flanks in the search window
+pub const MAX_DEPTH_RATIO: f64 = 0.75;
+/// Distance beyond the search window searched for split reads, since the
+/// breakpoints of a long deletion can lie outside the window
+pub const SPLIT_READ_FLANK: i64 = 20_000;
+
+/// Deletion too long to be spanned by reads, called from the reads whose
+/// alignments are split across it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LongDeletion {
+    pub start: i64,
+    pub end: i64,
+    /// Reads of all samples split across the deletion
+    pub split_reads: usize,
+}
+
+/// Deletion supported by split reads in some of the BAMs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionCall {
+    pub deletion: LongDeletion,
+    /// Indexes of the BAMs with enough split reads
+    pub supporting: Vec<usize>,
+}
+
+/// Call a deletion overlapping the locus from reads with a supplementary
+/// alignment on the far side of it, if the depth drops within the deletion
+pub fn find_long_deletion(
+    bams: &mut [Box<dyn AlignmentSource>],
+    bam_indexes: &[usize],
+    locus: &Locus,
+    region: Region,
+    covs: &[f64],
+    params: &Params,
+) -> Result<Option<DeletionCall>> {
+    let flanked = (
+        region.0,
+        (region.1 - SPLIT_READ_FLANK).max(0),
+        region.2 + SPLIT_READ_FLANK,
+    );
+    // A read is fetched twice if both of its alignments are in the region
+    let mut candidates: HashMap<(usize, Vec<u8>), (i64, i64)> = HashMap::new();
+    for &index in bam_indexes {
+        let bam = &mut bams[index];
+        bam.fetch(flanked)?;
+        while let Some(rec) = bam.next_record() {
+            let rec = rec?;
+            if !passes_filters(&rec, params) {
+                continue;
+            }
+            let Some((start, end)) = split_deletion(&rec, region.0) else {
+                continue;
+            };
+            let overlaps_locus = start < locus.end && end > locus.start;
+            if overlaps_locus && end - start >= params.min_alt_len as i64 {
+                candidates.insert((index, rec.qname().to_vec()), (start, end));
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let median = |values: Vec<i64>| values[values.len() / 2];
+    let start = median(
+        candidates
+            .values()
+            .map(|(start, _)| *start)
+            .sorted()
+            .collect(),
+    );
+    let end = median(candidates.values().map(|(_, end)| *end).sorted().collect());
+    let mut split_reads: HashMap<usize, usize> = HashMap::new();
+    for ((index, _), (read_start, read_end)) in &candidates {
+        if (read_start - start).abs() <= BREAKPOINT_TOLERANCE
+            && (read_end - end).abs() <= BREAKPOINT_TOLERANCE
+        {
+            *split_reads.entry(*index).or_default() += 1;
+        }
+    }
+    let supporting = split_reads
+        .iter()
+        .filter(|(_, reads)| **reads >= params.min_alt_reads)
+        .map(|(index, _)| *index)
+        .sorted()
+        .collect_vec();
+    if supporting.is_empty() || !has_depth_drop(covs, region.1, start, end) {
+        return Ok(None);
+    }
+    Ok(Some(DeletionCall {
+        deletion: LongDeletion {
+            start,
+            end,
+            split_reads: split_reads.values().sum(),
+        },
+        supporting,
+    }))
+}
+
+/// Whether the mean depth within [start, end) is well below that of the rest
+/// of the profile
+fn has_depth_drop(covs: &[f64], region_start: i64, start: i64, end: i64) -> bool {
+    let (mut inside, mut flanks) = (Vec::new(), Vec::new());
+    for (pos, cov) in covs.iter().enumerate() {
+        let pos = region_start + pos as i64;
+        match pos >= start && pos < end {
+            true => inside.push(*cov),
+            false => flanks.push(*cov),
+        }
+    }
+    let mean = |covs: &[f64]| covs.iter().sum::<f64>() / covs.len() as f64;
+    match (inside.is_empty(), flanks.is_empty()) {
+        (true, _) => false,
+        // The deletion covers the whole search window
+        (false, true) => true,
+        (false, false) => mean(&inside) <= MAX_DEPTH_RATIO * mean(&flanks),
+    }
+}
+
+/// Reference interval between an alignment and a supplementary alignment of
+/// the same read on the same contig and strand listed in its `SA` tag
+fn split_deletion(rec: &Record, contig: &str) -> Option<(i64, i64)> {
+    let Ok(Aux::String(sa)) = rec.aux(b"SA") else {
+        return None;
+    };
+    let strand = if rec.is_reverse() { "-" } else { "+" };
+    let (rec_start, rec_end) = (rec.pos(), rec.reference_end());
+    sa.split(';')
+        .filter(|entry| !entry.is_empty())
+        .find_map(|entry| {
+            let fields = entry.split(',').collect_vec();
+            let [sa_contig, sa_pos, sa_strand, sa_cigar, ..] = fields[..] else {
+                return None;
+            };
+            if sa_contig != contig || sa_strand != strand {
+                return None;
+            }
+            let sa_start = sa_pos.parse::<i64>().ok()? - 1;
+            let sa_end = sa_start + reference_len(sa_cigar)?;
+            if sa_start >= rec_end {
+                Some((rec_end, sa_start))
+            } else if sa_end <= rec_start {
+                Some((sa_end, rec_start))
+            } else {
+                None
+            }
+        })
+}
+
+/// Number of reference bases consumed by a CIGAR string
+fn reference_len(cigar: &str) -> Option<i64> {
+    let mut len = 0;
+    let mut op_len = String::new();
+    for c in cigar.chars() {
+        if c.is_ascii_digit() {
+            op_len.push(c);
+            continue;
+        }
+        let n = op_len.parse::<i64>().ok()?;
+        op_len.clear();
+        if matches!(c, 'M' | 'D' | 'N' | '=' | 'X') {
+            len += n;
+        }
+    }
+    Some(len)
+}
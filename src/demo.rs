@@ -0,0 +1,167 @@
+use crate::error::Result;
+use crate::schema::COLUMNS;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_htslib::bam::header::HeaderRecord;
+use rust_htslib::bam::record::{Cigar, CigarString};
+use rust_htslib::bam::{self, Header, Record};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const DEMO_CONTIG: &str = "chr1";
+pub const DEMO_CONTIG_LEN: usize = 12_000;
+const READ_LEN: i64 = 3000;
+const READS_PER_SAMPLE: usize = 30;
+
+/// Meaning of each standard output column, shown next to the demo results
+const COLUMN_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("lf_offset", "bases the cluster extends left of the region"),
+    ("rf_offset", "bases the cluster extends right of the region"),
+    (
+        "extended_region",
+        "region extended to the cluster boundaries",
+    ),
+    ("ns", "number of samples supporting the cluster"),
+    ("spanning_reads", "reads spanning the whole region"),
+    (
+        "partial_reads",
+        "reads overlapping the region without spanning it",
+    ),
+    ("chimeric_reads", "reads excluded as likely chimeras"),
+    ("coverage_gaps", "intervals of the cluster with low depth"),
+    (
+        "ladder_offsets",
+        "window length:lf_offset:rf_offset after each round",
+    ),
+    ("read_end_bias", "fraction of the alt events near read ends"),
+];
+
+/// Input files of the demo dataset
+pub struct DemoInputs {
+    pub genome_path: PathBuf,
+    pub reads_path: PathBuf,
+    pub regions_path: PathBuf,
+}
+
+/// Write a small reference, two samples of aligned reads, and two regions to
+/// a directory; the first sample carries a variation cluster at 6000-6100 on
+/// half of its reads and the second sample is reference-like
+pub fn write_demo(dir: &Path) -> Result<DemoInputs> {
+    fs::create_dir_all(dir)?;
+    let mut rng = StdRng::seed_from_u64(7);
+    let genome = (0..DEMO_CONTIG_LEN)
+        .map(|_| b"ACGT"[rng.gen_range(0..4)])
+        .collect_vec();
+    let genome_path = dir.join("demo.fa");
+    let mut file = File::create(&genome_path)?;
+    writeln!(file, ">{DEMO_CONTIG}")?;
+    for line in genome.chunks(60) {
+        file.write_all(line)?;
+        writeln!(file)?;
+    }
+
+    let reads_path = dir.join("reads.txt");
+    let mut reads = File::create(&reads_path)?;
+    for sample in ["carrier", "reference"] {
+        let mut records = Vec::new();
+        for index in 0..READS_PER_SAMPLE {
+            let pos = rng.gen_range(2000..10000 - READ_LEN);
+            let offset = 6000 - pos;
+            let carries = sample == "carrier" && index % 2 == 0;
+            let ops = if carries && (200..READ_LEN - 300).contains(&offset) {
+                let offset = offset as u32;
+                vec![
+                    Cigar::Equal(offset),
+                    Cigar::Del(30),
+                    Cigar::Equal(20),
+                    Cigar::Ins(40),
+                    Cigar::Diff(10),
+                    Cigar::Equal(READ_LEN as u32 - offset - 60),
+                ]
+            } else {
+                vec![Cigar::Equal(READ_LEN as u32)]
+            };
+            records.push(demo_record(&format!("{sample}/{index}"), pos, ops));
+        }
+        let bam_path = dir.join(format!("{sample}.bam"));
+        write_demo_bam(&bam_path, sample, records)?;
+        writeln!(reads, "{}\t{sample}", bam_path.display())?;
+    }
+
+    let regions_path = dir.join("regions.bed");
+    fs::write(
+        &regions_path,
+        format!("{DEMO_CONTIG}\t6040\t6060\tcluster\n{DEMO_CONTIG}\t4000\t4020\treference\n"),
+    )?;
+    Ok(DemoInputs {
+        genome_path,
+        reads_path,
+        regions_path,
+    })
+}
+
+/// Mapped record with a dummy sequence matching the query length of its CIGAR
+fn demo_record(name: &str, pos: i64, ops: Vec<Cigar>) -> Record {
+    let query_len = ops
+        .iter()
+        .map(|op| match op {
+            Cigar::Equal(len) | Cigar::Diff(len) | Cigar::Ins(len) => *len as usize,
+            _ => 0,
+        })
+        .sum::<usize>();
+    let mut rec = Record::new();
+    rec.set(
+        name.as_bytes(),
+        Some(&CigarString(ops)),
+        &vec![b'A'; query_len],
+        &vec![30; query_len],
+    );
+    rec.set_tid(0);
+    rec.set_pos(pos);
+    rec.set_mapq(60);
+    rec.unset_flags();
+    rec
+}
+
+fn write_demo_bam(path: &Path, sample: &str, mut records: Vec<Record>) -> Result<()> {
+    let mut header = Header::new();
+    let mut sq = HeaderRecord::new(b"SQ");
+    sq.push_tag(b"SN", DEMO_CONTIG);
+    sq.push_tag(b"LN", DEMO_CONTIG_LEN);
+    header.push_record(&sq);
+    let mut rg = HeaderRecord::new(b"RG");
+    rg.push_tag(b"ID", sample);
+    rg.push_tag(b"SM", sample);
+    header.push_record(&rg);
+
+    records.sort_by_key(|rec| rec.pos());
+    {
+        let mut writer = bam::Writer::from_path(path, &header, bam::Format::Bam)?;
+        for rec in &records {
+            writer.write(rec)?;
+        }
+    }
+    bam::index::build(path, None, bam::index::Type::Bai, 1)?;
+    Ok(())
+}
+
+/// Results listed region by region, one column per line with its meaning
+pub fn annotate_results(results: &str) -> String {
+    let mut annotated = String::new();
+    for line in results.lines().filter(|line| !line.starts_with('#')) {
+        let fields = line.split('\t').collect_vec();
+        annotated.push_str(&format!("{} ({})\n", fields[0], fields[1]));
+        for (column, value) in COLUMNS.iter().zip(&fields).skip(2) {
+            let description = COLUMN_DESCRIPTIONS
+                .iter()
+                .find(|(name, _)| name == column)
+                .map_or("", |(_, description)| description);
+            let row = format!("  {column:<16}{value:<40}{description}");
+            annotated.push_str(row.trim_end());
+            annotated.push('\n');
+        }
+    }
+    annotated
+}
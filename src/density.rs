@@ -0,0 +1,154 @@
+use crate::error::Result;
+use crate::workflow::LocusResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Column added to the output by `--max-cluster-density`, naming the dense
+/// megabase of a cluster
+pub const DENSITY_COLUMN: &str = "dense_region";
+/// Length of the bins over which clusters are counted
+pub const DENSITY_BIN_LEN: i64 = 1_000_000;
+
+/// Guards created so far, to name the spill file of each uniquely
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Megabase holding more supported clusters than allowed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DenseRegion {
+    pub region: String,
+    pub clusters: usize,
+    /// Whether the clusters were left out of the results
+    pub suppressed: bool,
+}
+
+/// Results held back until the cluster density of every megabase is known;
+/// clusters are counted in fixed bins along each contig by the start of their
+/// extended region as results arrive, and the results themselves are spilled
+/// to a temporary file as JSON lines once more than `chunk_records` are held
+pub struct DensityGuard {
+    max_clusters: usize,
+    suppress: bool,
+    counts: BTreeMap<(String, i64), usize>,
+    chunk_records: usize,
+    buffer: Vec<LocusResult>,
+    spill_path: PathBuf,
+    spill: Option<BufWriter<File>>,
+}
+
+impl DensityGuard {
+    /// Guard allowing at most `max_clusters` supported clusters per megabase,
+    /// flagging the clusters of denser megabases or suppressing them
+    pub fn new(max_clusters: usize, suppress: bool, chunk_records: usize, tmp_dir: &Path) -> Self {
+        let guard = GUARDS.fetch_add(1, Ordering::Relaxed);
+        DensityGuard {
+            max_clusters,
+            suppress,
+            counts: BTreeMap::new(),
+            chunk_records: chunk_records.max(1),
+            buffer: Vec::new(),
+            spill_path: tmp_dir.join(format!(
+                "vclust-density-{}-{guard}.jsonl",
+                std::process::id()
+            )),
+            spill: None,
+        }
+    }
+
+    pub fn push(&mut self, result: LocusResult) -> Result<()> {
+        if let Some(bin) = cluster_bin(&result) {
+            *self.counts.entry(bin).or_default() += 1;
+        }
+        self.buffer.push(result);
+        if self.buffer.len() >= self.chunk_records {
+            self.spill_buffer()?;
+        }
+        Ok(())
+    }
+
+    /// Whether results have been spilled to disk so far
+    pub fn has_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    fn spill_buffer(&mut self) -> Result<()> {
+        let writer = match &mut self.spill {
+            Some(writer) => writer,
+            None => self
+                .spill
+                .insert(BufWriter::new(File::create(&self.spill_path)?)),
+        };
+        for result in self.buffer.drain(..) {
+            serde_json::to_writer(&mut *writer, &result)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Pass the results to `write` in the order they were pushed, flagged with
+    /// the `DENSITY_COLUMN` or left out when suppressed, and return the dense
+    /// megabases in coordinate order
+    pub fn finish(
+        mut self,
+        mut write: impl FnMut(LocusResult) -> Result<()>,
+    ) -> Result<Vec<DenseRegion>> {
+        let counts = std::mem::take(&mut self.counts)
+            .into_iter()
+            .filter(|(_, clusters)| *clusters > self.max_clusters)
+            .collect::<BTreeMap<_, _>>();
+        let mut guard = |mut result: LocusResult| {
+            let dense = cluster_bin(&result).filter(|bin| counts.contains_key(bin));
+            match (dense, self.suppress) {
+                (Some(_), true) => return Ok(()),
+                (Some((chrom, bin)), false) => result.scores.push(bin_region(&chrom, bin)),
+                (None, false) => result.scores.push(".".to_string()),
+                (None, true) => {}
+            }
+            write(result)
+        };
+
+        if let Some(mut writer) = self.spill.take() {
+            writer.flush()?;
+            drop(writer);
+            for line in BufReader::new(File::open(&self.spill_path)?).lines() {
+                guard(serde_json::from_str(&line?)?)?;
+            }
+        }
+        for result in std::mem::take(&mut self.buffer) {
+            guard(result)?;
+        }
+
+        Ok(counts
+            .iter()
+            .map(|((chrom, bin), clusters)| DenseRegion {
+                region: bin_region(chrom, *bin),
+                clusters: *clusters,
+                suppressed: self.suppress,
+            })
+            .collect())
+    }
+}
+
+impl Drop for DensityGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.spill_path);
+    }
+}
+
+/// Contig and bin of a supported cluster
+fn cluster_bin(result: &LocusResult) -> Option<(String, i64)> {
+    let ext = result.extension.as_ref().filter(|ext| ext.ns > 0)?;
+    let start = (result.locus.start - ext.lf_offset).max(0);
+    Some((result.locus.chrom.clone(), start / DENSITY_BIN_LEN))
+}
+
+fn bin_region(chrom: &str, bin: i64) -> String {
+    format!(
+        "{chrom}:{}-{}",
+        bin * DENSITY_BIN_LEN,
+        (bin + 1) * DENSITY_BIN_LEN
+    )
+}
@@ -0,0 +1,80 @@
+/// Half-width of the confidence interval of the alt fraction of a sample below
+/// which further reads are not expected to change its support
+pub const EARLY_STOP_HALF_WIDTH: f64 = 0.15;
+
+/// Reads needed before stopping, so that a handful of reads never decide
+pub const MIN_EARLY_STOP_READS: usize = 20;
+
+/// Alt fraction and support of the reads of a sample spanning the whole
+/// profiled region, tracked while reading to stop once both are settled
+#[derive(Debug, Clone)]
+pub struct EarlyStop {
+    /// Standard normal quantile of the confidence level
+    z: f64,
+    /// Alt reads needed for the sample to support a cluster
+    min_alt_reads: usize,
+    reads: usize,
+    alt_reads: usize,
+}
+
+impl EarlyStop {
+    pub fn new(confidence: f64, min_alt_reads: usize) -> Self {
+        EarlyStop {
+            z: normal_quantile(0.5 + confidence / 2.0),
+            min_alt_reads,
+            reads: 0,
+            alt_reads: 0,
+        }
+    }
+
+    /// Spanning reads seen so far
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    pub fn add(&mut self, alt: bool) {
+        self.reads += 1;
+        self.alt_reads += alt as usize;
+    }
+
+    /// Whether the support of the sample is saturated, as it already has the
+    /// alt reads to support a cluster or has none at all, and the
+    /// Agresti-Coull interval of the alt fraction is within the allowed
+    /// half-width; a sample with a few alt reads may still gain support
+    pub fn is_decided(&self) -> bool {
+        if self.reads < MIN_EARLY_STOP_READS {
+            return false;
+        }
+        if self.alt_reads > 0 && self.alt_reads < self.min_alt_reads {
+            return false;
+        }
+        let z2 = self.z * self.z;
+        let n = self.reads as f64 + z2;
+        let p = (self.alt_reads as f64 + z2 / 2.0) / n;
+        self.z * (p * (1.0 - p) / n).sqrt() <= EARLY_STOP_HALF_WIDTH
+    }
+}
+
+/// Quantile of the standard normal distribution, by bisection of its CDF
+fn normal_quantile(p: f64) -> f64 {
+    let (mut low, mut high) = (-10.0, 10.0);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        match 0.5 * (1.0 + erf(mid / std::f64::consts::SQRT_2)) < p {
+            true => low = mid,
+            false => high = mid,
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Error function, with the approximation 7.1.26 of Abramowitz and Stegun
+/// (absolute error below 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - poly * (-x * x).exp();
+    value.copysign(x)
+}
@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VclustError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("htslib error: {0}")]
+    Htslib(#[from] rust_htslib::errors::Error),
+    #[error("Parse error: {0}")]
+    Parse(String),
+    #[error("Depth error: {0}")]
+    Depth(String),
+    #[error("Region error: {0}")]
+    Region(String),
+    #[error("Model error: {0}")]
+    Model(String),
+    #[error("Numerical error: {0}")]
+    Numerics(String),
+    #[error("Scorer error: {0}")]
+    Scorer(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl VclustError {
+    /// Short stable label used to group errors in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VclustError::Io(_) | VclustError::Htslib(_) => "io",
+            VclustError::Parse(_) => "parse",
+            VclustError::Depth(_) => "depth",
+            VclustError::Region(_) => "region",
+            VclustError::Model(_) => "model",
+            VclustError::Numerics(_) => "numerics",
+            VclustError::Scorer(_) => "scorer",
+            VclustError::Other(_) => "other",
+        }
+    }
+}
+
+/// Malformed input is a parse error; only failures to read or write are I/O
+impl From<serde_json::Error> for VclustError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.is_io() {
+            true => VclustError::Io(e.into()),
+            false => VclustError::Parse(e.to_string()),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, VclustError>;
@@ -0,0 +1,92 @@
+use crate::context::WorkflowContext;
+use crate::error::Result;
+use crate::locus::Locus;
+use crate::schema::format_row;
+use crate::workflow::run_workflow;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::time::Instant;
+
+/// Number of loci profiled to build the estimate
+const SAMPLE_SIZE: usize = 100;
+/// Minimal amount of work per thread (in seconds) for an extra thread to pay off
+const MIN_SECS_PER_THREAD: f64 = 60.0;
+
+pub struct Estimate {
+    pub loci_total: usize,
+    pub loci_sampled: usize,
+    pub secs_per_locus: f64,
+    pub bytes_per_locus: f64,
+    pub peak_rss_kb: Option<u64>,
+    pub threads: usize,
+}
+
+impl Estimate {
+    pub fn total_cpu_secs(&self) -> f64 {
+        self.secs_per_locus * self.loci_total as f64
+    }
+
+    pub fn recommended_threads(&self) -> usize {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let useful = (self.total_cpu_secs() / MIN_SECS_PER_THREAD).ceil() as usize;
+        useful.clamp(1, cores)
+    }
+
+    pub fn report(&self) -> String {
+        let wall = self.total_cpu_secs() / self.threads as f64;
+        let rss = self
+            .peak_rss_kb
+            .map_or("NA".to_string(), |kb| format!("{:.1}", kb as f64 / 1024.0));
+        format!(
+            "loci_total\t{}\nloci_sampled\t{}\nsecs_per_locus\t{:.4}\n\
+             est_wall_secs\t{:.1}\nest_output_bytes\t{:.0}\npeak_rss_mb\t{rss}\n\
+             recommended_threads\t{}",
+            self.loci_total,
+            self.loci_sampled,
+            self.secs_per_locus,
+            wall,
+            self.bytes_per_locus * self.loci_total as f64,
+            self.recommended_threads()
+        )
+    }
+}
+
+pub fn estimate_run(
+    ctx: &mut WorkflowContext,
+    loci: &[Locus],
+    seed: u64,
+    threads: usize,
+) -> Result<Estimate> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let sample = loci
+        .choose_multiple(&mut rng, SAMPLE_SIZE.min(loci.len()))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut n_bytes = 0;
+    for locus in &sample {
+        // Failed loci cost time but produce no output
+        if let Ok(result) = run_workflow(ctx, locus) {
+            n_bytes += format_row(&result).len() + 1;
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let n_sampled = sample.len().max(1) as f64;
+
+    Ok(Estimate {
+        loci_total: loci.len(),
+        loci_sampled: sample.len(),
+        secs_per_locus: elapsed / n_sampled,
+        bytes_per_locus: n_bytes as f64 / n_sampled,
+        peak_rss_kb: get_peak_rss_kb(),
+        threads,
+    })
+}
+
+/// Read the peak resident set size from procfs (Linux only)
+fn get_peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
@@ -0,0 +1,30 @@
+use crate::locus::Locus;
+use crate::profile::AltKind;
+use serde::{Deserialize, Serialize};
+
+pub const EVIDENCE_COLUMNS: &[&str] =
+    &["name", "sample", "chrom", "start", "end", "event", "reads"];
+
+/// Alt operation shared by the most reads of a supporting sample within the
+/// extended region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportEvidence {
+    pub sample: String,
+    pub start: i64,
+    pub end: i64,
+    pub kind: AltKind,
+    pub reads: usize,
+}
+
+impl SupportEvidence {
+    pub fn to_row(&self, locus: &Locus) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            locus.name, self.sample, locus.chrom, self.start, self.end, self.kind, self.reads
+        )
+    }
+}
+
+pub fn evidence_header() -> String {
+    format!("#{}", EVIDENCE_COLUMNS.join("\t"))
+}
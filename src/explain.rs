@@ -0,0 +1,552 @@
+use crate::context::WorkflowContext;
+use crate::error::{Result, VclustError};
+use crate::extend::{assess_window, get_extension_offsets, Extension};
+use crate::locus::Locus;
+use crate::models::LogModels;
+use crate::onnx::OnnxClassifier;
+use crate::training::ProfileStates;
+use itertools::Itertools;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+/// States shown on either side of each final boundary
+pub const BOUNDARY_CONTEXT: i64 = 10;
+/// Width of the columns of a comparison
+const COLUMN_WIDTH: usize = 24;
+
+/// Window assessed by the flank search, as a reference interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssessedWindow {
+    pub start: i64,
+    pub end: i64,
+    pub prob_ref: f64,
+}
+
+/// Where the search of one flank stopped in a step of the window ladder
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlankStop {
+    /// Boundary after the step
+    pub boundary: i64,
+    /// Window whose reference posterior reached the threshold
+    pub accepted: AssessedWindow,
+    /// Window next to it that fell short, if the step moved the boundary
+    pub rejected: Option<AssessedWindow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LadderStep {
+    pub window_len: i64,
+    pub lf_offset: i64,
+    pub rf_offset: i64,
+    pub left: FlankStop,
+    pub right: FlankStop,
+}
+
+/// Thresholds the decision at a locus depends on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    pub radius: i64,
+    pub min_prob_ref: f64,
+    pub prior_ref: f64,
+    pub window_ladder: Vec<i64>,
+    /// Smallest pooled alt fraction of each state after the first
+    pub lower_bounds: Vec<f64>,
+    pub min_depth: f64,
+    pub max_depth: f64,
+    pub min_alt_len: usize,
+    pub min_alt_reads: usize,
+    /// Categorical models, or the checksum of the ONNX classifier
+    pub model: String,
+}
+
+/// Thresholds and model a locus is explained under
+#[derive(Debug, Clone)]
+pub struct ExplainSetting {
+    pub min_prob_ref: f64,
+    pub prior_ref: f64,
+    pub window_ladder: Vec<i64>,
+    pub classifier: Option<Arc<OnnxClassifier>>,
+}
+
+/// Explanations of a locus under two settings, shown side by side
+#[derive(Debug)]
+pub struct Comparison {
+    pub first: Explanation,
+    pub second: Explanation,
+}
+
+/// Extension of a locus with the steps that led to its boundaries
+#[derive(Debug)]
+pub struct Explanation {
+    pub locus: Locus,
+    pub thresholds: Thresholds,
+    pub outcome: Result<(Extension, Vec<LadderStep>)>,
+}
+
+/// Locus given as `chrom:start-end` with 0-based start, named after it
+pub fn parse_locus(region: &str) -> Result<Locus> {
+    let bad_region =
+        || VclustError::Parse(format!("Bad region {region}; expected chrom:start-end"));
+    let (chrom, span) = region.rsplit_once(':').ok_or_else(bad_region)?;
+    let (start, end) = span.split_once('-').ok_or_else(bad_region)?;
+    let parse = |value: &str| {
+        value
+            .replace(',', "")
+            .parse::<i64>()
+            .map_err(|_| bad_region())
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if chrom.is_empty() || start < 0 || end <= start {
+        return Err(bad_region());
+    }
+    Ok(Locus {
+        chrom: chrom.to_string(),
+        start,
+        end,
+        name: region.to_string(),
+        group: None,
+    })
+}
+
+/// Extend a locus, keeping what is needed to recount the decision
+pub fn explain_locus(ctx: &mut WorkflowContext, locus: &Locus) -> Explanation {
+    let base_params = ctx.params.clone();
+    let mut params = (*base_params).clone();
+    params.export_states = true;
+    params.sample_support = true;
+    ctx.params = Arc::new(params);
+    let outcome = get_extension_offsets(ctx, locus);
+    ctx.params = base_params;
+
+    let params = &ctx.params;
+    let thresholds = Thresholds {
+        radius: params.radius_for(locus.end - locus.start),
+        min_prob_ref: params.min_prob_ref,
+        prior_ref: ctx.models.reference.prior,
+        window_ladder: params.window_ladder.clone(),
+        lower_bounds: ctx.models.alphabet.lower_bounds.clone(),
+        min_depth: params.min_depth,
+        max_depth: params.max_depth,
+        min_alt_len: params.min_alt_len,
+        min_alt_reads: params.min_alt_reads,
+        model: match &ctx.models.classifier {
+            Some(classifier) => format!("ONNX {}", &classifier.sha256[..12]),
+            None => "categorical".to_string(),
+        },
+    };
+    let outcome = outcome.map(|ext| {
+        let steps = match &ext.profile_states {
+            Some(states) => trace_ladder(&ext, states, locus, &ctx.log_models),
+            None => Vec::new(),
+        };
+        (ext, steps)
+    });
+    Explanation {
+        locus: locus.clone(),
+        thresholds,
+        outcome,
+    }
+}
+
+/// Explain a locus under a setting, restoring the context afterwards
+pub fn explain_with(
+    ctx: &mut WorkflowContext,
+    locus: &Locus,
+    setting: &ExplainSetting,
+) -> Explanation {
+    let base_params = ctx.params.clone();
+    let base_models = ctx.models.clone();
+    let mut params = (*base_params).clone();
+    params.min_prob_ref = setting.min_prob_ref;
+    params.window_ladder = match &setting.classifier {
+        // The classifier assesses windows of a single length
+        Some(classifier) => vec![classifier.window_len as i64],
+        None => setting.window_ladder.clone(),
+    };
+    ctx.params = Arc::new(params);
+    let mut models = (*base_models).clone();
+    models.reference.prior = setting.prior_ref;
+    models.cluster.prior = 1.0 - setting.prior_ref;
+    models.classifier = setting.classifier.clone();
+    ctx.log_models = LogModels::from(&models);
+    ctx.models = Arc::new(models);
+
+    let explanation = explain_locus(ctx, locus);
+
+    ctx.params = base_params;
+    ctx.log_models = LogModels::from(&*base_models);
+    ctx.models = base_models;
+    explanation
+}
+
+/// Explain a locus under two settings
+pub fn compare_settings(
+    ctx: &mut WorkflowContext,
+    locus: &Locus,
+    first: &ExplainSetting,
+    second: &ExplainSetting,
+) -> Comparison {
+    Comparison {
+        first: explain_with(ctx, locus, first),
+        second: explain_with(ctx, locus, second),
+    }
+}
+
+/// Windows at which each step of the ladder stopped, recovered from the
+/// offsets after each step and the states of the search region
+fn trace_ladder(
+    ext: &Extension,
+    profile: &ProfileStates,
+    locus: &Locus,
+    models: &LogModels,
+) -> Vec<LadderStep> {
+    let states = &profile.states;
+    let radius = locus.start - profile.start;
+    let locus_span = (radius, radius + locus.end - locus.start);
+    let window = |start: i64, end: i64, reversed: bool| {
+        let mut vals = states[start as usize..end as usize].to_vec();
+        if reversed {
+            vals.reverse();
+        }
+        AssessedWindow {
+            start: profile.start + start,
+            end: profile.start + end,
+            prob_ref: assess_window(&vals, models),
+        }
+    };
+
+    let mut span = locus_span;
+    let mut steps = Vec::new();
+    for &(window_len, lf_offset, rf_offset) in &ext.ladder_offsets {
+        let (lf, rf) = (locus_span.0 - lf_offset, locus_span.1 + rf_offset);
+        // Offsets reset after the search, as for alts confined to read ends,
+        // no longer tell where the search stopped
+        if lf < window_len || rf + window_len > states.len() as i64 {
+            break;
+        }
+        // Left windows are assessed from the boundary outwards
+        let left = FlankStop {
+            boundary: profile.start + lf,
+            accepted: window(lf - window_len, lf, true),
+            rejected: (lf < span.0).then(|| window(lf - window_len + 1, lf + 1, true)),
+        };
+        let right = FlankStop {
+            boundary: profile.start + rf,
+            accepted: window(rf, rf + window_len, false),
+            rejected: (rf > span.1).then(|| window(rf - 1, rf - 1 + window_len, false)),
+        };
+        steps.push(LadderStep {
+            window_len,
+            lf_offset,
+            rf_offset,
+            left,
+            right,
+        });
+        span = (lf, rf);
+    }
+    steps
+}
+
+impl Explanation {
+    /// States of the search region around a position, with the position
+    /// marked by a bar
+    fn boundary_states(&self, profile: &ProfileStates, pos: i64) -> String {
+        let offset = pos - profile.start;
+        let len = profile.states.len() as i64;
+        let format = |start: i64, end: i64| {
+            profile.states[start.clamp(0, len) as usize..end.clamp(0, len) as usize]
+                .iter()
+                .join("")
+        };
+        format!(
+            "{}|{}",
+            format(offset - BOUNDARY_CONTEXT, offset),
+            format(offset, offset + BOUNDARY_CONTEXT)
+        )
+    }
+
+    fn write_outcome(
+        &self,
+        f: &mut Formatter,
+        ext: &Extension,
+        steps: &[LadderStep],
+    ) -> fmt::Result {
+        let locus = &self.locus;
+        let thresholds = &self.thresholds;
+        let support = ext
+            .sample_support
+            .iter()
+            .map(|(sample, supports)| match supports {
+                true => format!("{sample} supports"),
+                false => format!("{sample} does not support"),
+            })
+            .join(", ");
+        writeln!(
+            f,
+            "2. Profile: {} reads span the locus and {} overlap it partially; {} were \
+             excluded as chimeras",
+            ext.spanning_reads, ext.partial_reads, ext.chimeric_reads
+        )?;
+        if !support.is_empty() {
+            writeln!(f, "   Samples with enough depth: {support}")?;
+        }
+
+        if let Some(deletion) = &ext.long_deletion {
+            writeln!(
+                f,
+                "3. Too few reads span the locus, and split reads call a deletion of \
+                 {}:{}-{}; its ends give the boundaries without the window ladder",
+                locus.chrom, deletion.start, deletion.end
+            )?;
+        } else {
+            let bounds = thresholds
+                .lower_bounds
+                .iter()
+                .enumerate()
+                .map(|(index, bound)| format!("{} from {bound:.2}", index + 1))
+                .join(", ");
+            writeln!(
+                f,
+                "3. Discretization: the pooled alt fraction of each position is binned \
+                 into state 0 below the first bound and states {bounds}"
+            )?;
+            writeln!(
+                f,
+                "4. Window ladder {}: each flank grows until a window has a reference \
+                 posterior of at least {} (reference prior {})",
+                thresholds.window_ladder.iter().join(", "),
+                thresholds.min_prob_ref,
+                thresholds.prior_ref
+            )?;
+            if steps.is_empty() {
+                writeln!(
+                    f,
+                    "   The ladder was not run; the locus is reported as it is"
+                )?;
+            }
+            for (index, step) in steps.iter().enumerate() {
+                writeln!(
+                    f,
+                    "   Step {} ({} bp windows): offsets {} and {}",
+                    index + 1,
+                    step.window_len,
+                    step.lf_offset,
+                    step.rf_offset
+                )?;
+                for (side, stop) in [("left", &step.left), ("right", &step.right)] {
+                    let rejected = match &stop.rejected {
+                        Some(window) => format!(
+                            ", after {}-{} at {:.4}",
+                            window.start, window.end, window.prob_ref
+                        ),
+                        None => String::new(),
+                    };
+                    writeln!(
+                        f,
+                        "     {side} boundary {}: window {}-{} has posterior {:.4}{rejected}",
+                        stop.boundary,
+                        stop.accepted.start,
+                        stop.accepted.end,
+                        stop.accepted.prob_ref
+                    )?;
+                }
+            }
+            if let Some(profile) = &ext.profile_states {
+                let (start, end) = (locus.start - ext.lf_offset, locus.end + ext.rf_offset);
+                writeln!(
+                    f,
+                    "5. States at the boundaries: {start} {}, {end} {}",
+                    self.boundary_states(profile, start),
+                    self.boundary_states(profile, end)
+                )?;
+            }
+        }
+
+        let (start, end) = (locus.start - ext.lf_offset, locus.end + ext.rf_offset);
+        writeln!(
+            f,
+            "Outcome: extended to {}:{start}-{end}; {} samples support the cluster with at \
+             least {} reads carrying a mismatch run or deletion of {} bp or more within it",
+            locus.chrom, ext.ns, thresholds.min_alt_reads, thresholds.min_alt_len
+        )
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let locus = &self.locus;
+        let radius = self.thresholds.radius;
+        writeln!(
+            f,
+            "Locus {}:{}-{} ({})",
+            locus.chrom, locus.start, locus.end, locus.name
+        )?;
+        writeln!(
+            f,
+            "1. Search region {}:{}-{}: {radius} bp on either side of the locus",
+            locus.chrom,
+            locus.start - radius,
+            locus.end + radius
+        )?;
+        match &self.outcome {
+            Ok((ext, steps)) => self.write_outcome(f, ext, steps),
+            Err(e) => {
+                let reason = match e {
+                    VclustError::Depth(_) => format!(
+                        "; the pooled mean depth must lie between {} and {}, and no sample \
+                         may have more reads than the read cap",
+                        self.thresholds.min_depth, self.thresholds.max_depth
+                    ),
+                    VclustError::Model(_) => format!(
+                        "; no window within {radius} bp looked reference-like, so the \
+                         cluster may be larger than the search region"
+                    ),
+                    _ => String::new(),
+                };
+                writeln!(f, "Outcome: not extended ({e}){reason}")
+            }
+        }
+    }
+}
+
+impl Explanation {
+    fn steps(&self) -> &[LadderStep] {
+        match &self.outcome {
+            Ok((_, steps)) => steps,
+            Err(_) => &[],
+        }
+    }
+
+    /// Final region, or the kind of error that prevented the extension
+    fn summary(&self) -> String {
+        let locus = &self.locus;
+        match &self.outcome {
+            Ok((ext, _)) => format!(
+                "{}:{}-{}",
+                locus.chrom,
+                locus.start - ext.lf_offset,
+                locus.end + ext.rf_offset
+            ),
+            Err(e) => {
+                let message = e.to_string();
+                let kind = message.split(':').next().unwrap_or_default().to_string();
+                format!("not extended ({kind})")
+            }
+        }
+    }
+}
+
+impl Comparison {
+    /// Step and flank at which the boundaries of the two settings first
+    /// differ, with the boundaries under either setting
+    pub fn first_divergence(&self) -> Option<(usize, &'static str, i64, i64)> {
+        let steps = self.first.steps().iter().zip(self.second.steps());
+        for (index, (first, second)) in steps.enumerate() {
+            if first.left.boundary != second.left.boundary {
+                return Some((index + 1, "left", first.left.boundary, second.left.boundary));
+            }
+            if first.right.boundary != second.right.boundary {
+                return Some((
+                    index + 1,
+                    "right",
+                    first.right.boundary,
+                    second.right.boundary,
+                ));
+            }
+        }
+        None
+    }
+}
+
+fn format_stop(stop: Option<&FlankStop>) -> String {
+    match stop {
+        Some(stop) => format!("{} ({:.4})", stop.boundary, stop.accepted.prob_ref),
+        None => "-".to_string(),
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (first, second) = (&self.first, &self.second);
+        let locus = &first.locus;
+        writeln!(
+            f,
+            "Locus {}:{}-{} ({}) under two settings; * marks where they differ",
+            locus.chrom, locus.start, locus.end, locus.name
+        )?;
+        let mut row = |label: String, a: String, b: String, marked: bool| {
+            let mark = if marked && a != b { " *" } else { "" };
+            let line = format!("{label:<COLUMN_WIDTH$}{a:<COLUMN_WIDTH$}{b:<COLUMN_WIDTH$}{mark}");
+            writeln!(f, "{}", line.trim_end())
+        };
+        row(String::new(), "A".to_string(), "B".to_string(), false)?;
+        let (a, b) = (&first.thresholds, &second.thresholds);
+        row(
+            "min_prob_ref".to_string(),
+            a.min_prob_ref.to_string(),
+            b.min_prob_ref.to_string(),
+            true,
+        )?;
+        row(
+            "prior_ref".to_string(),
+            a.prior_ref.to_string(),
+            b.prior_ref.to_string(),
+            true,
+        )?;
+        row(
+            "window_ladder".to_string(),
+            a.window_ladder.iter().join(":"),
+            b.window_ladder.iter().join(":"),
+            true,
+        )?;
+        row("model".to_string(), a.model.clone(), b.model.clone(), true)?;
+
+        let n_steps = first.steps().len().max(second.steps().len());
+        for index in 0..n_steps {
+            let (a, b) = (first.steps().get(index), second.steps().get(index));
+            let window = |step: Option<&LadderStep>| match step {
+                Some(step) => format!("{} bp", step.window_len),
+                None => "-".to_string(),
+            };
+            row(
+                format!("step {} window", index + 1),
+                window(a),
+                window(b),
+                false,
+            )?;
+            row(
+                format!("step {} left", index + 1),
+                format_stop(a.map(|step| &step.left)),
+                format_stop(b.map(|step| &step.left)),
+                true,
+            )?;
+            row(
+                format!("step {} right", index + 1),
+                format_stop(a.map(|step| &step.right)),
+                format_stop(b.map(|step| &step.right)),
+                true,
+            )?;
+        }
+        row(
+            "outcome".to_string(),
+            first.summary(),
+            second.summary(),
+            true,
+        )?;
+
+        match self.first_divergence() {
+            Some((step, side, a, b)) => writeln!(
+                f,
+                "Boundaries first diverge at step {step} on the {side} flank: {a} under A, \
+                 {b} under B"
+            ),
+            None if first.summary() != second.summary() => {
+                writeln!(
+                    f,
+                    "Boundaries agree at every shared step but the outcomes differ"
+                )
+            }
+            None => writeln!(f, "Boundaries agree at every step"),
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use crate::error::{Result, VclustError};
+use serde::{Serialize, Serializer};
+use std::str::FromStr;
+
+/// Arithmetic expression over named values, such as a user-defined priority
+///
+/// Expressions combine numbers and names with `+ - * / ^`, parentheses, the
+/// comparisons `< <= > >= == !=` (1 if true, 0 otherwise), and the functions
+/// `min`, `max`, `abs`, `log`, and `sqrt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Min,
+    Max,
+    Abs,
+    Log,
+    Sqrt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Expression {
+    /// Names referenced by the expression, in order of first appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.root.collect_variables(&mut names);
+        names
+    }
+
+    /// Check that every name referenced by the expression is one of `known`
+    pub fn check_variables(&self, known: &[String]) -> Result<()> {
+        match self
+            .variables()
+            .into_iter()
+            .find(|name| !known.iter().any(|k| k == name))
+        {
+            Some(name) => Err(VclustError::Parse(format!(
+                "Unknown name {name} in expression {}; available names are {}",
+                self.source,
+                known.join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Value of the expression, or None if a referenced value is missing or
+    /// the result is not finite
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        self.root.eval(lookup).filter(|value| value.is_finite())
+    }
+}
+
+impl Node {
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Variable(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name)
+                }
+            }
+            Node::Negate(node) => node.collect_variables(names),
+            Node::Binary(_, lhs, rhs) => {
+                lhs.collect_variables(names);
+                rhs.collect_variables(names);
+            }
+            Node::Call(_, args) => args.iter().for_each(|arg| arg.collect_variables(names)),
+        }
+    }
+
+    fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        let flag = |cond: bool| if cond { 1.0 } else { 0.0 };
+        match self {
+            Node::Number(value) => Some(*value),
+            Node::Variable(name) => lookup(name),
+            Node::Negate(node) => Some(-node.eval(lookup)?),
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(lookup)?, rhs.eval(lookup)?);
+                Some(match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                    BinaryOp::Pow => lhs.powf(rhs),
+                    BinaryOp::Lt => flag(lhs < rhs),
+                    BinaryOp::Le => flag(lhs <= rhs),
+                    BinaryOp::Gt => flag(lhs > rhs),
+                    BinaryOp::Ge => flag(lhs >= rhs),
+                    BinaryOp::Eq => flag(lhs == rhs),
+                    BinaryOp::Ne => flag(lhs != rhs),
+                })
+            }
+            Node::Call(func, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(lookup))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(match func {
+                    Function::Min => args.into_iter().fold(f64::INFINITY, f64::min),
+                    Function::Max => args.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                    Function::Abs => args[0].abs(),
+                    Function::Log => args[0].ln(),
+                    Function::Sqrt => args[0].sqrt(),
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = VclustError;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let error = |msg: String| VclustError::Parse(format!("{msg} in expression {source}"));
+        let tokens = tokenize(source).map_err(error)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.comparison().map_err(error)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("Unexpected {token:?}")));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+}
+
+impl Serialize for Expression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn tokenize(source: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let start = pos;
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            // Exponent of a number in scientific notation
+            if pos < chars.len() && matches!(chars[pos], 'e' | 'E') {
+                pos += 1;
+                if pos < chars.len() && matches!(chars[pos], '+' | '-') {
+                    pos += 1;
+                }
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            let text = chars[start..pos].iter().collect::<String>();
+            let value = text
+                .parse::<f64>()
+                .map_err(|_| format!("Bad number {text}"))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Name(chars[start..pos].iter().collect()));
+        } else {
+            let next = chars.get(pos + 1).copied();
+            let (token, len) = match (c, next) {
+                ('<', Some('=')) => (Token::Op("<="), 2),
+                ('>', Some('=')) => (Token::Op(">="), 2),
+                ('=', Some('=')) => (Token::Op("=="), 2),
+                ('!', Some('=')) => (Token::Op("!="), 2),
+                ('<', _) => (Token::Op("<"), 1),
+                ('>', _) => (Token::Op(">"), 1),
+                ('+', _) => (Token::Op("+"), 1),
+                ('-', _) => (Token::Op("-"), 1),
+                ('*', _) => (Token::Op("*"), 1),
+                ('/', _) => (Token::Op("/"), 1),
+                ('^', _) => (Token::Op("^"), 1),
+                ('(', _) => (Token::LParen, 1),
+                (')', _) => (Token::RParen, 1),
+                (',', _) => (Token::Comma, 1),
+                _ => return Err(format!("Unexpected character {c}")),
+            };
+            tokens.push(token);
+            pos += len;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser; each method parses one precedence level, from
+/// comparisons (lowest) to powers and operands (highest)
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+type ParseResult = std::result::Result<Node, String>;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it is one of the given operators
+    fn next_op(&mut self, ops: &[(&str, BinaryOp)]) -> Option<BinaryOp> {
+        let Some(Token::Op(op)) = self.peek() else {
+            return None;
+        };
+        let (_, binary_op) = ops.iter().find(|(name, _)| name == op)?;
+        self.pos += 1;
+        Some(*binary_op)
+    }
+
+    fn comparison(&mut self) -> ParseResult {
+        let ops = [
+            ("<", BinaryOp::Lt),
+            ("<=", BinaryOp::Le),
+            (">", BinaryOp::Gt),
+            (">=", BinaryOp::Ge),
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+        ];
+        let lhs = self.sum()?;
+        match self.next_op(&ops) {
+            Some(op) => Ok(Node::Binary(op, Box::new(lhs), Box::new(self.sum()?))),
+            None => Ok(lhs),
+        }
+    }
+
+    fn sum(&mut self) -> ParseResult {
+        let ops = [("+", BinaryOp::Add), ("-", BinaryOp::Sub)];
+        let mut node = self.product()?;
+        while let Some(op) = self.next_op(&ops) {
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> ParseResult {
+        let ops = [("*", BinaryOp::Mul), ("/", BinaryOp::Div)];
+        let mut node = self.unary()?;
+        while let Some(op) = self.next_op(&ops) {
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> ParseResult {
+        if self.peek() == Some(&Token::Op("-")) {
+            self.pos += 1;
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// Powers are right-associative and bind tighter than negation of their
+    /// base, so `-2^2` is -4
+    fn power(&mut self) -> ParseResult {
+        let base = self.operand()?;
+        match self.next_op(&[("^", BinaryOp::Pow)]) {
+            Some(op) => Ok(Node::Binary(op, Box::new(base), Box::new(self.unary()?))),
+            None => Ok(base),
+        }
+    }
+
+    fn operand(&mut self) -> ParseResult {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::LParen) => {
+                let node = self.comparison()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(node),
+                    _ => Err("Missing )".to_string()),
+                }
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                self.call(&name)
+            }
+            Some(Token::Name(name)) => Ok(Node::Variable(name)),
+            Some(token) => Err(format!("Unexpected {token:?}")),
+            None => Err("Unexpected end".to_string()),
+        }
+    }
+
+    /// Arguments of a function call, after its opening parenthesis
+    fn call(&mut self, name: &str) -> ParseResult {
+        let (func, n_args) = match name {
+            "min" => (Function::Min, None),
+            "max" => (Function::Max, None),
+            "abs" => (Function::Abs, Some(1)),
+            "log" => (Function::Log, Some(1)),
+            "sqrt" => (Function::Sqrt, Some(1)),
+            _ => return Err(format!("Unknown function {name}")),
+        };
+        let mut args = vec![self.comparison()?];
+        loop {
+            match self.next() {
+                Some(Token::Comma) => args.push(self.comparison()?),
+                Some(Token::RParen) => break,
+                _ => return Err(format!("Missing ) after the arguments of {name}")),
+            }
+        }
+        if n_args.is_some_and(|n_args| args.len() != n_args) {
+            return Err(format!("{name} takes a single argument"));
+        }
+        Ok(Node::Call(func, args))
+    }
+}
@@ -1,143 +1,989 @@
+use crate::alignment::AlignmentSource;
+use crate::context::{Params, WorkflowContext};
+use crate::coverage::{get_base_coverage, pool_base_coverage, BaseCoverage};
+use crate::deletion::{find_long_deletion, LongDeletion};
+use crate::error::{Result, VclustError};
+use crate::evidence::SupportEvidence;
 use crate::locus::Locus;
-use crate::models::{MODEL_REF, MODEL_VC, PRIOR_REF, PRIOR_VC, RADIUS};
-use crate::profile::{get_profile, Prof};
+use crate::models::{Alphabet, LogModel, LogModels};
+use crate::normalize::{RefWindow, LEFT_ALIGN_FLANK};
+use crate::profile::{
+    count_reads, get_profile, Prof, ProfileOptions, ReadStats, Region, SampleSupport, SkipCounts,
+};
+use crate::selection::{select_rule, LocusContext, DEFAULT_MODEL};
+use crate::span::SpanReference;
+use crate::training::ProfileStates;
+use crate::underflow::{format_underflow_runs, get_underflow_runs};
+use crate::uniqueness::{window_uniqueness, MIN_UNIQUENESS};
 use itertools::Itertools;
 use logaddexp::LogAddExp;
-use rust_htslib::bam::IndexedReader;
+use rust_htslib::faidx;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-pub fn get_extension_offsets(
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Extension {
+    pub lf_offset: i64,
+    pub rf_offset: i64,
+    /// Number of samples supporting the cluster
+    pub ns: i64,
+    pub spanning_reads: usize,
+    pub partial_reads: usize,
+    /// Reads excluded for supplementary alignments at distant loci
+    #[serde(default)]
+    pub chimeric_reads: usize,
+    /// Intervals of the extended span with depth below the gap threshold
+    pub coverage_gaps: Vec<(i64, i64)>,
+    /// MAPQ and clip length histograms of the reads of all samples
+    #[serde(default)]
+    pub read_stats: ReadStats,
+    /// Offsets (window length, lf_offset, rf_offset) after each round of the
+    /// window ladder, from the most conservative to the final boundaries
+    #[serde(default)]
+    pub ladder_offsets: Vec<(i64, i64, i64)>,
+    /// Fraction of the alt events of the extended region near read ends, if
+    /// there are any events
+    #[serde(default)]
+    pub read_end_bias: Option<f64>,
+    /// Values of the columns added by custom scorers
+    #[serde(default)]
+    pub scores: Vec<String>,
+    /// Strongest alt evidence of each supporting sample, if requested
+    #[serde(default)]
+    pub support_evidence: Vec<SupportEvidence>,
+    /// Records excluded by the read filters in each sample
+    #[serde(default)]
+    pub skipped_reads: BTreeMap<String, SkipCounts>,
+    /// Mean pooled alt fraction over the extended span
+    #[serde(default)]
+    pub alt_fraction: f64,
+    /// Whether each sample with enough depth supports the cluster, if requested
+    #[serde(default)]
+    pub sample_support: Vec<(String, bool)>,
+    /// Supporting reads on the majority and minority haplotypes of each
+    /// supporting sample, summed over the samples, if requested
+    #[serde(default)]
+    pub haplotype_reads: Option<(usize, usize)>,
+    /// Deletion called from split reads where too few reads span the locus
+    #[serde(default)]
+    pub long_deletion: Option<LongDeletion>,
+    /// Name of the models selected by the model rules, if there are any
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Fraction of the reference k-mers of the search window found nowhere
+    /// else, if requested
+    #[serde(default)]
+    pub uniqueness: Option<f64>,
+    /// Reference of the extended span, if requested
+    #[serde(default)]
+    pub span_reference: Option<SpanReference>,
+    /// Alt-carrying and total reads of the locus in each sample with enough
+    /// depth, if the locus was not extended and no sample supports it
+    #[serde(default)]
+    pub hom_ref_alt_reads: BTreeMap<String, (usize, usize)>,
+    /// Discretized profile of the search region, if requested for training
+    /// exports
+    #[serde(default)]
+    pub profile_states: Option<ProfileStates>,
+    /// Intervals where neither model explains the windows of the first ladder
+    /// step, if requested
+    #[serde(default)]
+    pub model_underflow: Vec<(i64, i64)>,
+    /// Mean pooled depth over the locus itself
+    #[serde(default)]
+    pub locus_depth: f64,
+    /// Physical and base-level depth over the extended region, if requested
+    #[serde(default)]
+    pub base_coverage: Option<BaseCoverage>,
+}
+
+pub fn get_extension_offsets(ctx: &mut WorkflowContext, locus: &Locus) -> Result<Extension> {
+    let indexes = (0..ctx.bams.len()).collect_vec();
+    get_sample_extension(ctx, locus, &indexes)
+}
+
+/// Extend the locus using only the BAMs with the given indexes
+pub fn get_sample_extension(
+    ctx: &mut WorkflowContext,
     locus: &Locus,
-    bams: &mut Vec<IndexedReader>,
-) -> Option<(i64, i64, i64)> {
-    let region = extend_region(locus).ok()?;
-
-    let mut ns = 0;
-    // let alt_minimum = 0.35;
-    let alt_depth = 5.0;
-    // Add as you go
-    let mut sum_alts: Option<Vec<f64>> = None;
-    let mut sum_depth: f64 = 0.0;
-    let mut count: usize = 0;
-
-    for bam in bams {
-        let (prof, any_alt) = get_profile(bam, region).ok()?;
-
-        if let Some(ref mut alts) = sum_alts {
-            for (sum, alt) in alts.iter_mut().zip(prof.alts.iter()) {
-                *sum += alt;
-                //any_alt |= *alt >= alt_minimum;
-            }
-        } else {
-            //any_alt |= prof.alts.iter().any(|&alt| alt >= alt_minimum);
-            sum_alts = Some(prof.alts.clone());
+    bam_indexes: &[usize],
+) -> Result<Extension> {
+    let radius = ctx.params.radius_for(locus.end - locus.start);
+    // Most catalog loci carry no variation; they are screened on the locus
+    // itself and only profiled there
+    if let Some(min_alt_mass) = ctx.params.prescreen_alt_mass {
+        if screen_alt_mass(ctx, locus, bam_indexes)? < min_alt_mass {
+            return extend_within(ctx, locus, bam_indexes, 0, Some(radius));
+        }
+    }
+    // Windows of the ladder must fit in the narrowed search
+    let prior_radius = ctx
+        .prior_spans
+        .as_ref()
+        .and_then(|prior| prior.radius_for(locus, radius))
+        .filter(|prior_radius| {
+            ctx.params
+                .window_ladder
+                .iter()
+                .all(|len| len < prior_radius)
+        });
+    match prior_radius {
+        Some(prior_radius) => match extend_within(ctx, locus, bam_indexes, prior_radius, None) {
+            // The cluster grew past its previous span
+            Err(VclustError::Model(_)) => extend_within(ctx, locus, bam_indexes, radius, None),
+            extension => extension,
+        },
+        None => extend_within(ctx, locus, bam_indexes, radius, None),
+    }
+}
+
+/// Pooled alt signal summed over the locus, profiled without its flanks
+fn screen_alt_mass(ctx: &mut WorkflowContext, locus: &Locus, bam_indexes: &[usize]) -> Result<f64> {
+    let WorkflowContext {
+        params,
+        bams,
+        karyotypes,
+        amplicons,
+        scratch,
+        samples,
+        reference,
+        ..
+    } = ctx;
+    let contig_len = reference.fetch_seq_len(&locus.chrom) as i64;
+    let region = extend_region(locus, 0, contig_len)?;
+    let ploidies = bam_indexes
+        .iter()
+        .map(|index| {
+            karyotypes[*index].map_or(2, |k| k.ploidy(&locus.chrom, (locus.start, locus.end)))
+        })
+        .collect_vec();
+    let mut replicates: BTreeMap<&str, usize> = BTreeMap::new();
+    for (&index, &ploidy) in bam_indexes.iter().zip(&ploidies) {
+        if ploidy > 0 {
+            *replicates.entry(&samples[index]).or_default() += 1;
+        }
+    }
+
+    let mut alt_mass = CompensatedSum::default();
+    for (&index, &ploidy) in bam_indexes.iter().zip(&ploidies) {
+        if ploidy == 0 {
+            continue;
+        }
+        let options = ProfileOptions {
+            locus_span: (locus.start, locus.end),
+            amplicons: amplicons.as_deref(),
+            keep_reads: false,
+            read_quota: None,
+            reference: None,
+        };
+        let (mut prof, _) = get_profile(bams[index].as_mut(), region, params, scratch, options)?;
+        if ploidy != 2 {
+            prof.rescale_ploidy(ploidy);
+        }
+        let weight = 1.0 / replicates[samples[index].as_str()] as f64;
+        prof.alts.iter().for_each(|alt| alt_mass.add(alt * weight));
+    }
+    Ok(alt_mass.total() / replicates.len().max(1) as f64)
+}
+
+/// Extend the locus within the given radius of it; a locus that failed the
+/// prescreen is profiled with a radius of 0 and reported unextended, with the
+/// ladder of its full `screened_radius`
+fn extend_within(
+    ctx: &mut WorkflowContext,
+    locus: &Locus,
+    bam_indexes: &[usize],
+    radius: i64,
+    screened_radius: Option<i64>,
+) -> Result<Extension> {
+    let WorkflowContext {
+        params,
+        models,
+        log_models,
+        rule_models,
+        bams,
+        karyotypes,
+        amplicons,
+        scorers,
+        scratch,
+        samples,
+        reference,
+        kmer_index,
+        ..
+    } = ctx;
+    // faidx reports missing contigs with a length of -1
+    let contig_len = reference.fetch_seq_len(&locus.chrom) as i64;
+    let region = extend_region(locus, radius, contig_len)?;
+
+    // Alt reads of each sample with enough depth to support the cluster
+    let mut supports = Vec::new();
+    let mut sum_alts = Vec::new();
+    let mut sum_covs = Vec::new();
+    let mut sum_base_covs = Vec::new();
+    let mut sum_clips = Vec::new();
+    let mut sum_events = Vec::new();
+    let mut sum_read_end_events = Vec::new();
+    let mut sum_depth = CompensatedSum::default();
+    // Bounds of each BAM's reads within the kept reads and its weight
+    let mut kept_bams = Vec::new();
+    let (mut spanning_reads, mut partial_reads) = (0, 0);
+    let mut chimeric_reads = 0;
+    let mut read_stats = ReadStats::default();
+    let mut skipped_reads: BTreeMap<String, SkipCounts> = BTreeMap::new();
+    let mut sample_reads: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    scratch.kept_reads.clear();
+
+    let ploidies = bam_indexes
+        .iter()
+        .map(|index| {
+            karyotypes[*index].map_or(2, |k| k.ploidy(&locus.chrom, (locus.start, locus.end)))
+        })
+        .collect_vec();
+    let read_quotas = match params.max_pooled_reads {
+        Some(cap) => get_read_quotas(bams, bam_indexes, &ploidies, region, params, cap)?,
+        None => vec![None; bam_indexes.len()],
+    };
+    let ref_window = match params.left_align_indels {
+        true => Some(fetch_ref_window(reference, region)?),
+        false => None,
+    };
+
+    // Replicates are averaged within their sample before samples are pooled
+    let mut replicates: BTreeMap<&str, usize> = BTreeMap::new();
+    for (&index, &ploidy) in bam_indexes.iter().zip(&ploidies) {
+        if ploidy > 0 {
+            *replicates.entry(&samples[index]).or_default() += 1;
         }
-        sum_depth += prof.depth;
-        count += 1;
-        if any_alt & (prof.depth >= alt_depth) {
-            ns += 1;
+    }
+    let count = replicates.len();
+
+    for ((&index, &ploidy), read_quota) in bam_indexes.iter().zip(&ploidies).zip(read_quotas) {
+        if ploidy == 0 {
+            continue;
+        }
+        let weight = 1.0 / replicates[samples[index].as_str()] as f64;
+        let kept_start = scratch.kept_reads.len();
+        let bam = &mut bams[index];
+        let options = ProfileOptions {
+            locus_span: (locus.start, locus.end),
+            amplicons: amplicons.as_deref(),
+            keep_reads: !scorers.is_empty() || params.base_coverage,
+            read_quota,
+            reference: ref_window
+                .as_ref()
+                .map(|(start, seq)| RefWindow { start: *start, seq }),
+        };
+        let (mut prof, support) = get_profile(bam.as_mut(), region, params, scratch, options)?;
+        if ploidy != 2 {
+            prof.rescale_ploidy(ploidy);
+        }
+
+        for (sums, vals) in [
+            (&mut sum_alts, &prof.alts),
+            (&mut sum_covs, &prof.covs),
+            (&mut sum_base_covs, &prof.base_covs),
+            (&mut sum_clips, &prof.clips),
+        ] {
+            sums.resize(vals.len(), CompensatedSum::default());
+            sums.iter_mut()
+                .zip(vals)
+                .for_each(|(sum, val)| sum.add(*val * weight));
+        }
+        for (sums, events) in [
+            (&mut sum_events, &prof.alt_events),
+            (&mut sum_read_end_events, &prof.read_end_events),
+        ] {
+            sums.resize(events.len(), 0);
+            sums.iter_mut()
+                .zip(events)
+                .for_each(|(sum, count)| *sum += count);
+        }
+        sum_depth.add(prof.depth * weight);
+        kept_bams.push((kept_start..scratch.kept_reads.len(), weight));
+        spanning_reads += prof.spanning_reads;
+        partial_reads += prof.partial_reads;
+        chimeric_reads += prof.chimeric_reads;
+        read_stats.merge(&prof.read_stats);
+        skipped_reads
+            .entry(samples[index].clone())
+            .or_default()
+            .merge(&prof.skipped_reads);
+        if prof.depth >= params.min_sample_depth {
+            // Reads of the locus itself, for the background alt rate
+            let (alt_reads, reads) = sample_reads.entry(samples[index].clone()).or_default();
+            *alt_reads += support.count_reads(locus.start, locus.end);
+            *reads += prof.spanning_reads + prof.partial_reads;
+            supports.push((index, support));
         }
     }
 
-    let prof = if let Some(sum_alts) = sum_alts {
-        let alts = sum_alts.into_iter().map(|sum| sum / count as f64).collect();
-        let depth = sum_depth / count as f64;
-        Prof { alts, depth }
+    let mut prof = if count > 0 {
+        let mean =
+            |sums: Vec<CompensatedSum>| sums.iter().map(|sum| sum.total() / count as f64).collect();
+        let depth = sum_depth.total() / count as f64;
+        Prof {
+            alts: mean(sum_alts),
+            clips: mean(sum_clips),
+            covs: mean(sum_covs),
+            base_covs: mean(sum_base_covs),
+            depth,
+            spanning_reads,
+            partial_reads,
+            chimeric_reads,
+            read_stats,
+            skipped_reads: skipped_reads.values().fold(
+                SkipCounts::default(),
+                |mut total, counts| {
+                    total.merge(counts);
+                    total
+                },
+            ),
+            alt_events: sum_events,
+            read_end_events: sum_read_end_events,
+        }
     } else {
-        return None;
+        return Err(VclustError::Other("No samples to profile".to_string()));
     };
 
-    if prof.depth < 5.0 || prof.depth > 150.0 {
-        return None;
+    // Subsampled high-copy regions have bounded depth by construction
+    let max_depth = if params.is_high_copy(&locus.chrom) {
+        f64::INFINITY
+    } else {
+        params.max_depth
+    };
+    let low_depth = prof.depth < params.min_depth;
+    // No read spans a deletion longer than the reads, so its locus looks shallow
+    let long_deletion = match low_depth && params.long_deletions {
+        true => find_long_deletion(bams, bam_indexes, locus, region, &prof.covs, params)?,
+        false => None,
+    };
+    if (low_depth && long_deletion.is_none()) || prof.depth > max_depth {
+        return Err(VclustError::Depth(format!(
+            "Mean depth {:.1} outside of allowed range",
+            prof.depth
+        )));
     }
 
-    let alts = discretize(&prof.alts);
+    let uniqueness = match params.uniqueness {
+        true => Some(window_uniqueness(reference, kmer_index.as_deref(), region)?),
+        false => None,
+    };
+    // Alt signal in recent segmental duplications mostly comes from reads of
+    // the other copies
+    if let Some(score) = uniqueness.filter(|score| *score < MIN_UNIQUENESS) {
+        prof.alts.iter_mut().for_each(|alt| *alt *= score);
+    }
 
-    let span = (RADIUS, RADIUS + locus.end - locus.start);
-    let span = extend_to_ref_flanks(&alts, span, 150)?;
-    let span = extend_to_ref_flanks(&alts, span, 50)?;
-    let span = extend_to_ref_flanks(&alts, span, 25)?;
-    let span = extend_to_ref_flanks(&alts, span, 10)?;
+    let mut scores = Vec::new();
+    for scorer in scorers.iter() {
+        let columns = scorer.columns();
+        let values = scorer.score(locus, &prof, &scratch.kept_reads);
+        // Misaligned values would shift every later column of the row
+        if values.len() != columns.len() {
+            return Err(VclustError::Scorer(format!(
+                "Scorer of columns {} returned {} values",
+                columns.join(","),
+                values.len()
+            )));
+        }
+        scores.extend(values);
+    }
 
-    let lf_offset = RADIUS - span.0;
-    let rf_offset = span.1 - (RADIUS + locus.end - locus.start);
+    // Heterogeneous genomes can use different models in different contexts
+    let (models, log_models, model) = match params.model_rules.is_empty() {
+        true => (&**models, &*log_models, None),
+        false => {
+            // Fetched interval is closed
+            let seq = reference.fetch_seq(region.0, region.1 as usize, region.2 as usize - 1)?;
+            let context = LocusContext::new(prof.depth, seq);
+            match select_rule(&params.model_rules, &context) {
+                Some(index) => {
+                    let (models, log_models) = &rule_models[index];
+                    let name = params.model_rules[index].name.clone();
+                    (&**models, log_models, Some(name))
+                }
+                None => (&**models, &*log_models, Some(DEFAULT_MODEL.to_string())),
+            }
+        }
+    };
 
-    Some((lf_offset, rf_offset, ns))
-}
+    let locus_span = (radius, radius + locus.end - locus.start);
+    let mut span = locus_span;
+    let ladder = params
+        .window_ladder
+        .iter()
+        .copied()
+        // Windows longer than the search extent cannot be slid over the flanks
+        .filter(|len| *len < radius)
+        .filter(|len| *len as usize <= models.max_window_len())
+        .collect_vec();
+    let min_gap_depth = params.gap_depth_fraction * prof.depth;
+    let assessed = supports.iter().map(|(index, _)| *index).collect_vec();
+    let locus_depth = get_mean_depth(&prof, locus_span);
+    let base_coverage = |start: i64, end: i64| {
+        params.base_coverage.then(|| {
+            let coverages = kept_bams.iter().map(|(reads, weight)| {
+                let coverage =
+                    get_base_coverage(&scratch.kept_reads[reads.clone()], (start, end), 1);
+                (coverage, *weight)
+            });
+            pool_base_coverage(coverages, count)
+        })
+    };
 
-fn extend_region(locus: &Locus) -> Result<(&str, i64, i64), String> {
-    if locus.start < RADIUS {
-        Err("Locus too close to chromosome start".to_string())
-    } else {
-        Ok((&locus.chrom[..], locus.start - RADIUS, locus.end + RADIUS))
-    }
-}
-
-fn discretize(vals: &[f64]) -> Vec<u8> {
-    vals.iter()
-        .map(|val| {
-            if *val <= 0.10 {
-                0
-            } else if *val < 0.25 {
-                1
-            } else if *val < 0.75 {
-                2
-            } else if *val < 1.50 {
-                3
-            } else if *val < 5.00 {
-                4
-            } else {
-                5
+    if let Some(call) = long_deletion {
+        let len = prof.covs.len() as i64;
+        let deletion = call.deletion;
+        let span = (
+            (deletion.start - region.1).clamp(0, len),
+            (deletion.end - region.1).clamp(0, len),
+        );
+        let sample_support = match params.sample_support {
+            // Samples too shallow to profile can still carry split reads
+            true => {
+                let assessed = bam_indexes
+                    .iter()
+                    .zip(&ploidies)
+                    .filter(|(_, ploidy)| **ploidy > 0)
+                    .map(|(index, _)| *index)
+                    .collect_vec();
+                get_sample_support(&assessed, &call.supporting, samples)
             }
+            false => Vec::new(),
+        };
+        let base_coverage =
+            base_coverage(locus.start.min(deletion.start), locus.end.max(deletion.end));
+        return Ok(Extension {
+            lf_offset: (locus.start - deletion.start).max(0),
+            rf_offset: (deletion.end - locus.end).max(0),
+            ns: count_samples(&call.supporting, samples),
+            spanning_reads,
+            partial_reads,
+            chimeric_reads,
+            read_stats: prof.read_stats.clone(),
+            coverage_gaps: get_coverage_gaps(&prof.base_covs, span, region.1, min_gap_depth),
+            ladder_offsets: Vec::new(),
+            read_end_bias: None,
+            scores,
+            support_evidence: Vec::new(),
+            skipped_reads,
+            alt_fraction: get_alt_fraction(&prof, span),
+            sample_support,
+            haplotype_reads: None,
+            long_deletion: Some(deletion),
+            model,
+            uniqueness,
+            span_reference: None,
+            hom_ref_alt_reads: BTreeMap::new(),
+            profile_states: None,
+            model_underflow: Vec::new(),
+            locus_depth,
+            base_coverage,
+        });
+    }
+
+    if let Some(screened_radius) = screened_radius {
+        let ladder = params
+            .window_ladder
+            .iter()
+            .copied()
+            .filter(|len| *len < screened_radius)
+            .filter(|len| *len as usize <= models.max_window_len());
+        return Ok(Extension {
+            lf_offset: 0,
+            rf_offset: 0,
+            ns: 0,
+            spanning_reads,
+            partial_reads,
+            chimeric_reads,
+            read_stats: prof.read_stats.clone(),
+            coverage_gaps: get_coverage_gaps(&prof.base_covs, span, region.1, min_gap_depth),
+            ladder_offsets: ladder.map(|len| (len, 0, 0)).collect(),
+            read_end_bias: get_read_end_bias(&prof, span),
+            scores,
+            support_evidence: Vec::new(),
+            skipped_reads,
+            alt_fraction: get_alt_fraction(&prof, span),
+            sample_support: match params.sample_support {
+                true => get_sample_support(&assessed, &[], samples),
+                false => Vec::new(),
+            },
+            haplotype_reads: params.haplotype_balance.then_some((0, 0)),
+            long_deletion: None,
+            model,
+            uniqueness,
+            span_reference: None,
+            hom_ref_alt_reads: sample_reads,
+            profile_states: params.export_states.then(|| ProfileStates {
+                start: region.1,
+                states: discretize(&prof, &models.alphabet),
+            }),
+            model_underflow: Vec::new(),
+            locus_depth,
+            base_coverage: base_coverage(locus.start, locus.end),
+        });
+    }
+
+    let alts = discretize(&prof, &models.alphabet);
+    let audit = params.audit_numerics.then_some(region.1);
+    let parallel = params
+        .parallel_scan_len
+        .is_some_and(|min_len| alts.len() as i64 >= min_len);
+    let model_underflow = match (params.flag_model_uncertain, ladder.first()) {
+        (true, Some(window_len)) if log_models.classifier.is_none() => {
+            get_underflow_runs(&alts, *window_len as usize, log_models)
+                .into_iter()
+                .map(|(start, end)| (region.1 + start, region.1 + end))
+                .collect_vec()
+        }
+        _ => Vec::new(),
+    };
+    if !model_underflow.is_empty() {
+        log::warn!(
+            "Neither model explains the profile of {} over {}; its boundaries are model-uncertain",
+            locus.name,
+            format_underflow_runs(&model_underflow)
+        );
+    }
+    let mut ladder_offsets = Vec::new();
+    for window_len in ladder {
+        let extended = extend_to_ref_flanks(
+            &alts,
+            span,
+            window_len,
+            log_models,
+            params.min_prob_ref,
+            audit,
+            parallel,
+        )?;
+        span = extended
+            .ok_or_else(|| VclustError::Model("Cluster extends past search window".to_string()))?;
+        ladder_offsets.push((window_len, locus_span.0 - span.0, span.1 - locus_span.1));
+    }
+
+    // Variation elsewhere in the search window does not support the cluster
+    let mut supports = supports
+        .into_iter()
+        .filter(|(_, support)| {
+            support.count_reads(region.1 + span.0, region.1 + span.1) >= params.min_alt_reads
         })
+        .collect_vec();
+    let read_end_bias = get_read_end_bias(&prof, span);
+    // Alt events confined to read ends point to adapter or chimera artifacts
+    if params.filter_read_end_alts && read_end_bias == Some(1.0) {
+        span = locus_span;
+        supports.clear();
+        ladder_offsets
+            .iter_mut()
+            .for_each(|(_, lf, rf)| (*lf, *rf) = (0, 0));
+    }
+    let supporting = supports.iter().map(|(index, _)| *index).collect_vec();
+    let ns = count_samples(&supporting, samples);
+    let support_evidence = match params.support_evidence {
+        true => get_support_evidence(&supports, samples, span, region.1),
+        false => Vec::new(),
+    };
+    let haplotype_reads = params
+        .haplotype_balance
+        .then(|| get_haplotype_reads(&supports, span, region.1));
+    let sample_support = match params.sample_support {
+        true => get_sample_support(&assessed, &supporting, samples),
+        false => Vec::new(),
+    };
+
+    let lf_offset = locus_span.0 - span.0;
+    let rf_offset = span.1 - locus_span.1;
+
+    let alt_fraction = get_alt_fraction(&prof, span);
+    let hom_ref_alt_reads = match ns == 0 && lf_offset == 0 && rf_offset == 0 {
+        true => sample_reads,
+        false => BTreeMap::new(),
+    };
+    let profile_states = params.export_states.then_some(ProfileStates {
+        start: region.1,
+        states: alts,
+    });
+    Ok(Extension {
+        lf_offset,
+        rf_offset,
+        ns,
+        spanning_reads,
+        partial_reads,
+        chimeric_reads,
+        read_stats: prof.read_stats,
+        coverage_gaps: get_coverage_gaps(&prof.base_covs, span, region.1, min_gap_depth),
+        ladder_offsets,
+        read_end_bias,
+        scores,
+        support_evidence,
+        skipped_reads,
+        alt_fraction,
+        sample_support,
+        haplotype_reads,
+        long_deletion: None,
+        model,
+        uniqueness,
+        span_reference: None,
+        hom_ref_alt_reads,
+        profile_states,
+        model_underflow,
+        locus_depth,
+        base_coverage: base_coverage(locus.start - lf_offset, locus.end + rf_offset),
+    })
+}
+
+/// Supporting reads within a span of the profile on the majority and minority
+/// haplotypes of each supporting sample, summed over the samples; haplotype
+/// labels are only meaningful within a sample
+fn get_haplotype_reads(
+    supports: &[(usize, SampleSupport)],
+    span: (i64, i64),
+    region_start: i64,
+) -> (usize, usize) {
+    let (start, end) = (region_start + span.0, region_start + span.1);
+    supports
+        .iter()
+        .map(|(_, support)| support.haplotype_reads(start, end))
+        .fold((0, 0), |(major, minor), [hp1, hp2]| {
+            (major + hp1.max(hp2), minor + hp1.min(hp2))
+        })
+}
+
+/// Number of distinct samples among BAMs given by index
+fn count_samples(indexes: &[usize], samples: &[String]) -> i64 {
+    indexes
+        .iter()
+        .map(|index| &samples[*index])
+        .unique()
+        .count() as i64
+}
+
+/// Whether each sample with enough depth, given by BAM index, supports the
+/// cluster; a sample with several BAMs supports it if any of them does
+fn get_sample_support(
+    assessed: &[usize],
+    supporting: &[usize],
+    samples: &[String],
+) -> Vec<(String, bool)> {
+    let mut support: BTreeMap<&str, bool> = BTreeMap::new();
+    for index in assessed {
+        *support.entry(&samples[*index]).or_default() |= supporting.contains(index);
+    }
+    support
+        .into_iter()
+        .map(|(sample, supports)| (sample.to_string(), supports))
+        .collect()
+}
+
+/// Alt operation shared by the most reads of each supporting sample within a
+/// span of the profile
+fn get_support_evidence(
+    supports: &[(usize, SampleSupport)],
+    samples: &[String],
+    span: (i64, i64),
+    region_start: i64,
+) -> Vec<SupportEvidence> {
+    let (start, end) = (region_start + span.0, region_start + span.1);
+    supports
+        .iter()
+        .filter_map(|(index, support)| {
+            let (alt, reads) = support.strongest_alt(start, end)?;
+            Some(SupportEvidence {
+                sample: samples[*index].clone(),
+                start: alt.start,
+                end: alt.end,
+                kind: alt.kind,
+                reads,
+            })
+        })
+        .collect()
+}
+
+/// Mean of the pooled alt fractions within a span of the profile
+fn get_alt_fraction(prof: &Prof, span: (i64, i64)) -> f64 {
+    let alts = &prof.alts[span.0 as usize..span.1 as usize];
+    alts.iter().sum::<f64>() / alts.len().max(1) as f64
+}
+
+fn get_mean_depth(prof: &Prof, span: (i64, i64)) -> f64 {
+    let covs = &prof.covs[span.0 as usize..span.1 as usize];
+    covs.iter().sum::<f64>() / covs.len().max(1) as f64
+}
+
+/// Fraction of the alt events within a span of the profile that are near read ends
+fn get_read_end_bias(prof: &Prof, span: (i64, i64)) -> Option<f64> {
+    let range = span.0 as usize..span.1 as usize;
+    let events = prof.alt_events[range.clone()].iter().sum::<u32>();
+    let read_end_events = prof.read_end_events[range].iter().sum::<u32>();
+    (events > 0).then(|| read_end_events as f64 / events as f64)
+}
+
+/// Split the pooled read cap among samples in proportion to their read counts
+fn get_read_quotas(
+    bams: &mut [Box<dyn AlignmentSource>],
+    bam_indexes: &[usize],
+    ploidies: &[u32],
+    region: (&str, i64, i64),
+    params: &Params,
+    cap: usize,
+) -> Result<Vec<Option<usize>>> {
+    let mut counts = Vec::new();
+    for (&index, &ploidy) in bam_indexes.iter().zip(ploidies) {
+        let count = if ploidy == 0 {
+            0
+        } else {
+            count_reads(bams[index].as_mut(), region, params)?
+        };
+        counts.push(count);
+    }
+    let total = counts.iter().sum::<usize>();
+    if total <= cap {
+        return Ok(vec![None; counts.len()]);
+    }
+    Ok(counts
+        .into_iter()
+        .map(|count| Some((count * cap / total).max(1)))
+        .collect())
+}
+
+/// Coverage gaps within a span of the profile in reference coordinates, from
+/// the depth of the sequenced bases so that deletions show up as gaps
+fn get_coverage_gaps(
+    covs: &[f64],
+    span: (i64, i64),
+    region_start: i64,
+    min_depth: f64,
+) -> Vec<(i64, i64)> {
+    find_coverage_gaps(&covs[span.0 as usize..span.1 as usize], min_depth)
+        .into_iter()
+        .map(|(start, end)| (region_start + span.0 + start, region_start + span.0 + end))
+        .collect()
+}
+
+/// Find maximal runs of positions with depth below a given minimum
+fn find_coverage_gaps(covs: &[f64], min_depth: f64) -> Vec<(i64, i64)> {
+    let mut gaps = Vec::new();
+    let mut gap_start = None;
+    for (pos, cov) in covs.iter().enumerate() {
+        match (gap_start, *cov < min_depth) {
+            (None, true) => gap_start = Some(pos as i64),
+            (Some(start), false) => {
+                gaps.push((start, pos as i64));
+                gap_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = gap_start {
+        gaps.push((start, covs.len() as i64));
+    }
+    gaps
+}
+
+/// Search region of a locus, which must lie within the contig; a negative
+/// contig length stands for a contig missing from the reference
+pub fn extend_region(locus: &Locus, radius: i64, contig_len: i64) -> Result<Region<'_>> {
+    if contig_len < 0 {
+        return Err(VclustError::Region(format!(
+            "Contig {} is not in the reference",
+            locus.chrom
+        )));
+    }
+    if locus.start < 0 || locus.end <= locus.start || radius < 0 {
+        return Err(VclustError::Region(format!(
+            "Bad locus coordinates {}:{}-{}",
+            locus.chrom, locus.start, locus.end
+        )));
+    }
+    if locus.start < radius {
+        return Err(VclustError::Region(
+            "Locus too close to chromosome start".to_string(),
+        ));
+    }
+    match locus.end.checked_add(radius) {
+        Some(end) if end <= contig_len => Ok((&locus.chrom[..], locus.start - radius, end)),
+        _ => Err(VclustError::Region(
+            "Locus too close to chromosome end".to_string(),
+        )),
+    }
+}
+
+/// Reference around a search window, far enough out that indels of reads near
+/// its ends can be left-aligned
+fn fetch_ref_window(reference: &faidx::Reader, region: Region) -> Result<(i64, Vec<u8>)> {
+    let contig_len = reference.fetch_seq_len(region.0) as i64;
+    let start = (region.1 - LEFT_ALIGN_FLANK).max(0);
+    let end = (region.2 + LEFT_ALIGN_FLANK).min(contig_len);
+    // Fetched intervals are closed
+    let seq = reference.fetch_seq(region.0, start as usize, end as usize - 1)?;
+    Ok((start, seq.to_vec()))
+}
+
+/// Running sum with Neumaier compensation, so that pooled profiles do not
+/// depend on the order of the samples through rounding errors
+#[derive(Debug, Clone, Copy, Default)]
+struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    fn add(&mut self, val: f64) {
+        let total = self.sum + val;
+        if self.sum.abs() >= val.abs() {
+            self.compensation += (self.sum - total) + val;
+        } else {
+            self.compensation += (val - total) + self.sum;
+        }
+        self.sum = total;
+    }
+
+    fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+fn discretize(prof: &Prof, alphabet: &Alphabet) -> Vec<u8> {
+    prof.alts
+        .iter()
+        .zip(&prof.clips)
+        .map(|(alt, clip)| alphabet.state(*alt, *clip))
         .collect()
 }
 
-fn extend_to_ref_flanks(alts: &[u8], span: (i64, i64), window_len: i64) -> Option<(i64, i64)> {
-    let mut lf_pos = span.0 - window_len;
+/// Slide windows outwards from the span until both flanks look reference-like;
+/// when `audit` holds the start of the search region, windows that cannot be
+/// assessed reliably fail the locus; the extended span, or `None` if either
+/// flank reaches the end of the profile first
+pub fn extend_to_ref_flanks(
+    alts: &[u8],
+    span: (i64, i64),
+    window_len: i64,
+    models: &LogModels,
+    min_prob_ref: f64,
+    audit: Option<i64>,
+    parallel: bool,
+) -> Result<Option<(i64, i64)>> {
+    let scan_left = || scan_left_flank(alts, span.0, window_len, models, min_prob_ref, audit);
+    let scan_right = || scan_right_flank(alts, span.1, window_len, models, min_prob_ref, audit);
+    // The flanks are independent, so long searches scan them concurrently
+    let (lf_pos, rf_pos) = if parallel {
+        std::thread::scope(|scope| {
+            let left = scope.spawn(scan_left);
+            let rf_pos = scan_right();
+            (left.join().unwrap(), rf_pos)
+        })
+    } else {
+        (scan_left(), scan_right())
+    };
+
+    // The scan runs past the start when no window is reference-like, and a
+    // reference-like window at the very start is still a flank
+    let lf_pos = lf_pos?;
+    if lf_pos < 0 {
+        return Ok(None);
+    }
+    let rf_pos = rf_pos?;
+    if alts.len() as i64 - window_len < rf_pos {
+        return Ok(None);
+    }
+
+    Ok(Some((lf_pos + window_len, rf_pos)))
+}
+
+fn scan_left_flank(
+    alts: &[u8],
+    start: i64,
+    window_len: i64,
+    models: &LogModels,
+    min_prob_ref: f64,
+    audit: Option<i64>,
+) -> Result<i64> {
+    let mut lf_pos = start - window_len;
     while lf_pos >= 0 {
         let window = &alts[lf_pos as usize..(lf_pos + window_len) as usize];
         let window = window.iter().rev().copied().collect_vec();
-        let prob_ref = assess_window(&window[..]);
-        if prob_ref >= 0.5 {
+        let prob_ref = assess_window(&window[..], models);
+        if let Some(region_start) = audit {
+            audit_window(&window, models, region_start + lf_pos)?;
+        }
+        if prob_ref >= min_prob_ref {
             break;
         }
         lf_pos -= 1;
     }
+    Ok(lf_pos)
+}
 
-    if lf_pos == 0 {
-        return None;
-    }
-
-    let mut rf_pos = span.1;
+fn scan_right_flank(
+    alts: &[u8],
+    end: i64,
+    window_len: i64,
+    models: &LogModels,
+    min_prob_ref: f64,
+    audit: Option<i64>,
+) -> Result<i64> {
+    let mut rf_pos = end;
     while rf_pos <= alts.len() as i64 - window_len {
         let window = &alts[rf_pos as usize..(rf_pos + window_len) as usize];
-        let prob_ref = assess_window(window);
-
-        if prob_ref >= 0.5 {
+        let prob_ref = assess_window(window, models);
+        if let Some(region_start) = audit {
+            audit_window(window, models, region_start + rf_pos)?;
+        }
+        if prob_ref >= min_prob_ref {
             break;
         }
         rf_pos += 1;
     }
-
-    if alts.len() as i64 - window_len < rf_pos {
-        return None;
-    }
-
-    Some((lf_pos + window_len, rf_pos))
+    Ok(rf_pos)
 }
 
-fn assess_window(vals: &[u8]) -> f64 {
-    let ll_norm = get_loglik(vals, &MODEL_REF) + PRIOR_REF.ln();
-    let ll_poly = get_loglik(vals, &MODEL_VC) + PRIOR_VC.ln();
+pub(crate) fn assess_window(vals: &[u8], models: &LogModels) -> f64 {
+    if let Some(classifier) = &models.classifier {
+        // Shapes are checked when the classifier is loaded
+        let prob_cluster = classifier
+            .cluster_probability(vals)
+            .expect("Classifier failed on a window of its length");
+        return 1.0 - prob_cluster;
+    }
+    let n_states = models.n_states;
+    let ll_norm = get_loglik(vals, &models.reference, n_states) + models.reference.log_prior;
+    let ll_poly = get_loglik(vals, &models.cluster, n_states) + models.cluster.log_prior;
     let ll_sum = ll_norm.ln_add_exp(ll_poly);
 
     (ll_norm - ll_sum).exp()
 }
 
-fn get_loglik(prof: &[u8], model: &[f64; 1500]) -> f64 {
+/// Check that the log-likelihoods and posterior of a window are finite,
+/// reporting the window and the log emissions of its states otherwise
+fn audit_window(vals: &[u8], models: &LogModels, start: i64) -> Result<()> {
+    let n_states = models.n_states;
+    let ll_norm = get_loglik(vals, &models.reference, n_states) + models.reference.log_prior;
+    let ll_poly = get_loglik(vals, &models.cluster, n_states) + models.cluster.log_prior;
+    let prob_ref = assess_window(vals, models);
+    if ll_norm.is_finite() && ll_poly.is_finite() && prob_ref.is_finite() {
+        return Ok(());
+    }
+    let emissions = |model: &LogModel| {
+        vals.iter()
+            .enumerate()
+            .map(|(pos, val)| model.log_probs[pos * n_states + *val as usize])
+            .join(",")
+    };
+    Err(VclustError::Numerics(format!(
+        "Window of {} bp at {start} has log-likelihoods {ll_norm} (reference) and {ll_poly} \
+         (cluster) and posterior {prob_ref}; states {}; reference log emissions {}; cluster log emissions {}",
+        vals.len(),
+        vals.iter().join(","),
+        emissions(&models.reference),
+        emissions(&models.cluster),
+    )))
+}
+
+fn get_loglik(prof: &[u8], model: &LogModel, n_states: usize) -> f64 {
     let mut ll = 0.0;
     for (pos, val) in prof.iter().enumerate() {
-        ll += model[pos * 6 + *val as usize].ln();
+        ll += model.log_probs[pos * n_states + *val as usize];
     }
     ll
 }
@@ -0,0 +1,253 @@
+use crate::error::{Result, VclustError};
+use crate::tracks::ClusterTrack;
+use itertools::Itertools;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Columns of the per-gene rollup
+pub const ROLLUP_COLUMNS: &[&str] = &[
+    "gene",
+    "chrom",
+    "start",
+    "end",
+    "exon_clusters",
+    "exon_bp",
+    "intron_clusters",
+    "intron_bp",
+    "utr_clusters",
+    "utr_bp",
+];
+
+/// Gene of an annotation, with 0-based half-open intervals merged per feature;
+/// exons exclude the UTRs and introns are the rest of the gene span
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gene {
+    pub name: String,
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub exons: Vec<(i64, i64)>,
+    pub introns: Vec<(i64, i64)>,
+    pub utrs: Vec<(i64, i64)>,
+}
+
+/// Clusters overlapping each part of a gene and the bases of the overlaps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GeneRollup {
+    pub exon_clusters: usize,
+    pub exon_bp: i64,
+    pub intron_clusters: usize,
+    pub intron_bp: i64,
+    pub utr_clusters: usize,
+    pub utr_bp: i64,
+}
+
+#[derive(Default)]
+struct GeneFeatures {
+    span: Option<(i64, i64)>,
+    exons: Vec<(i64, i64)>,
+    utrs: Vec<(i64, i64)>,
+}
+
+/// Load the genes of a GTF file, named by their `gene_name` attribute or else
+/// their `gene_id`; features other than genes, exons, and UTRs are ignored,
+/// and genes without exons are skipped
+pub fn load_genes(path: &Path) -> Result<Vec<Gene>> {
+    let file = File::open(path)?;
+    let mut features: BTreeMap<(String, String), GeneFeatures> = BTreeMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad_line = || VclustError::Parse(format!("Bad annotation line {line}"));
+        let rec = line.split('\t').collect_vec();
+        if rec.len() < 9 {
+            return Err(bad_line());
+        }
+        let kind = match rec[2] {
+            "gene" | "exon" | "UTR" | "five_prime_utr" | "three_prime_utr" => rec[2],
+            _ => continue,
+        };
+        let parse = |val: &str| val.trim().parse::<i64>().map_err(|_| bad_line());
+        // GTF intervals are 1-based and closed
+        let interval = (parse(rec[3])? - 1, parse(rec[4])?);
+        let name = attribute(rec[8], "gene_name")
+            .or_else(|| attribute(rec[8], "gene_id"))
+            .ok_or_else(bad_line)?;
+
+        // Genes of the pseudoautosomal regions are listed once per contig
+        let key = (rec[0].to_string(), name.to_string());
+        let gene = features.entry(key).or_default();
+        match kind {
+            "gene" => gene.span = Some(interval),
+            "exon" => gene.exons.push(interval),
+            _ => gene.utrs.push(interval),
+        }
+    }
+
+    let mut genes = Vec::new();
+    for ((chrom, name), gene) in features {
+        if gene.exons.is_empty() {
+            continue;
+        }
+        let exons = merge_intervals(gene.exons);
+        let utrs = merge_intervals(gene.utrs);
+        let (start, end) = gene.span.unwrap_or((exons[0].0, exons[exons.len() - 1].1));
+        genes.push(Gene {
+            name,
+            chrom,
+            start,
+            end,
+            introns: subtract_intervals(&[(start, end)], &exons),
+            exons: subtract_intervals(&exons, &utrs),
+            utrs,
+        });
+    }
+    genes.sort_by(|a, b| (&a.chrom, a.start, &a.name).cmp(&(&b.chrom, b.start, &b.name)));
+    Ok(genes)
+}
+
+/// Value of a GTF attribute such as `gene_name "BRCA1";`
+fn attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes.split(';').find_map(|field| {
+        let (name, value) = field.trim().split_once(' ')?;
+        (name == key).then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Sorted union of intervals
+fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Parts of sorted disjoint intervals not covered by other sorted disjoint
+/// intervals
+fn subtract_intervals(intervals: &[(i64, i64)], removed: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let mut remaining = Vec::new();
+    for &(start, end) in intervals {
+        let mut from = start;
+        for &(removed_start, removed_end) in removed {
+            if removed_end <= from || removed_start >= end {
+                continue;
+            }
+            if removed_start > from {
+                remaining.push((from, removed_start));
+            }
+            from = from.max(removed_end);
+        }
+        if from < end {
+            remaining.push((from, end));
+        }
+    }
+    remaining
+}
+
+/// Bases of the interval [start, end) covered by sorted disjoint intervals
+fn overlap_len(intervals: &[(i64, i64)], start: i64, end: i64) -> i64 {
+    intervals
+        .iter()
+        .map(|&(int_start, int_end)| (int_end.min(end) - int_start.max(start)).max(0))
+        .sum()
+}
+
+/// Tally the clusters overlapping the exons, introns, and UTRs of each gene;
+/// a cluster spanning several parts of a gene counts towards each of them
+pub fn rollup_genes(genes: &[Gene], clusters: &[ClusterTrack]) -> Vec<GeneRollup> {
+    let mut by_chrom: BTreeMap<&str, Vec<&ClusterTrack>> = BTreeMap::new();
+    for cluster in clusters {
+        by_chrom.entry(&cluster.chrom).or_default().push(cluster);
+    }
+    let mut longest: BTreeMap<&str, i64> = BTreeMap::new();
+    for chrom_clusters in by_chrom.values_mut() {
+        chrom_clusters.sort_by_key(|cluster| (cluster.start, cluster.end));
+        let max_len = chrom_clusters
+            .iter()
+            .map(|cluster| cluster.end - cluster.start)
+            .max()
+            .unwrap_or(0);
+        longest.insert(&chrom_clusters[0].chrom, max_len);
+    }
+
+    genes
+        .iter()
+        .map(|gene| {
+            let mut rollup = GeneRollup::default();
+            let Some(chrom_clusters) = by_chrom.get(gene.chrom.as_str()) else {
+                return rollup;
+            };
+            // No cluster starting before this one can reach the gene
+            let first_start = gene.start - longest[gene.chrom.as_str()];
+            let first = chrom_clusters.partition_point(|cluster| cluster.start < first_start);
+            for cluster in chrom_clusters[first..]
+                .iter()
+                .take_while(|cluster| cluster.start < gene.end)
+                .filter(|cluster| cluster.end > gene.start)
+            {
+                let overlaps = [
+                    (&gene.exons, &mut rollup.exon_clusters, &mut rollup.exon_bp),
+                    (
+                        &gene.introns,
+                        &mut rollup.intron_clusters,
+                        &mut rollup.intron_bp,
+                    ),
+                    (&gene.utrs, &mut rollup.utr_clusters, &mut rollup.utr_bp),
+                ];
+                for (intervals, n_clusters, n_bp) in overlaps {
+                    let bp = overlap_len(intervals, cluster.start, cluster.end);
+                    if bp > 0 {
+                        *n_clusters += 1;
+                        *n_bp += bp;
+                    }
+                }
+            }
+            rollup
+        })
+        .collect()
+}
+
+/// Gene names of a panel file, one per line
+pub fn load_panel(path: &Path) -> Result<BTreeSet<String>> {
+    let file = File::open(path)?;
+    let mut panel = BTreeSet::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let name = line.trim();
+        if !name.is_empty() && !name.starts_with('#') {
+            panel.insert(name.to_string());
+        }
+    }
+    Ok(panel)
+}
+
+/// Write the rollup as a TSV with one line per gene
+pub fn write_rollup(genes: &[Gene], rollups: &[GeneRollup], writer: &mut impl Write) -> Result<()> {
+    writeln!(writer, "#{}", ROLLUP_COLUMNS.join("\t"))?;
+    for (gene, rollup) in genes.iter().zip(rollups) {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            gene.name,
+            gene.chrom,
+            gene.start,
+            gene.end,
+            rollup.exon_clusters,
+            rollup.exon_bp,
+            rollup.intron_clusters,
+            rollup.intron_bp,
+            rollup.utr_clusters,
+            rollup.utr_bp
+        )?;
+    }
+    Ok(())
+}
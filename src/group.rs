@@ -0,0 +1,123 @@
+use crate::error::Result;
+use crate::locus::Locus;
+use crate::workflow::LocusResult;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// Columns of the joint record of each locus group
+pub const GROUP_COLUMNS: &[&str] = &[
+    "group",
+    "n_loci",
+    "n_extended",
+    "n_supported",
+    "max_ns",
+    "joint_region",
+    "members",
+];
+
+/// Outcome of a locus of a group
+#[derive(Debug, Clone)]
+struct Member {
+    locus: Locus,
+    /// Extended region and support, if the locus was extended
+    extension: Option<((i64, i64), i64)>,
+}
+
+/// Joint records of the locus groups, filled in as results arrive
+#[derive(Debug, Default)]
+pub struct GroupReport {
+    groups: BTreeMap<String, Vec<Member>>,
+    /// Group and position within it of each input locus that has a group
+    members: HashMap<usize, (String, usize)>,
+}
+
+impl GroupReport {
+    /// Report over the loci with a declared group, given in input order
+    pub fn new(loci: &[Locus]) -> Self {
+        let mut report = GroupReport::default();
+        for (index, locus) in loci.iter().enumerate() {
+            let Some(group) = &locus.group else {
+                continue;
+            };
+            let members = report.groups.entry(group.clone()).or_default();
+            report.members.insert(index, (group.clone(), members.len()));
+            members.push(Member {
+                locus: locus.clone(),
+                extension: None,
+            });
+        }
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Record the result of the input locus with the given index
+    pub fn add(&mut self, index: usize, result: &LocusResult) {
+        let Some((group, position)) = self.members.get(&index) else {
+            return;
+        };
+        let member = &mut self.groups.get_mut(group).expect("Group of a member")[*position];
+        member.extension = result.extension.as_ref().map(|ext| {
+            let locus = &result.locus;
+            (
+                (locus.start - ext.lf_offset, locus.end + ext.rf_offset),
+                ext.ns,
+            )
+        });
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        writeln!(writer, "#{}", GROUP_COLUMNS.join("\t"))?;
+        for (group, members) in &self.groups {
+            writeln!(writer, "{}", format_group(group, members))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_group(group: &str, members: &[Member]) -> String {
+    let extended = members
+        .iter()
+        .filter_map(|member| Some((&member.locus, member.extension?)))
+        .collect_vec();
+    let n_supported = extended.iter().filter(|(_, (_, ns))| *ns > 0).count();
+    let max_ns = extended
+        .iter()
+        .map(|(_, (_, ns))| *ns)
+        .max()
+        .map_or("NA".to_string(), |ns| ns.to_string());
+    // Groups spanning contigs, such as distant duplicate copies, have no
+    // single joint region
+    let joint_region = match extended
+        .iter()
+        .map(|(locus, _)| &locus.chrom)
+        .all_equal_value()
+    {
+        Ok(chrom) => {
+            let start = extended.iter().map(|(_, ((start, _), _))| start).min();
+            let end = extended.iter().map(|(_, ((_, end), _))| end).max();
+            format!("{chrom}:{}-{}", start.unwrap(), end.unwrap())
+        }
+        Err(_) => ".".to_string(),
+    };
+    let outcomes = members
+        .iter()
+        .map(|member| {
+            let locus = &member.locus;
+            match member.extension {
+                Some(((start, end), ns)) => {
+                    format!("{}={}:{start}-{end}:{ns}", locus.name, locus.chrom)
+                }
+                None => format!("{}=NA", locus.name),
+            }
+        })
+        .join(",");
+    format!(
+        "{group}\t{}\t{}\t{n_supported}\t{max_ns}\t{joint_region}\t{outcomes}",
+        members.len(),
+        extended.len()
+    )
+}
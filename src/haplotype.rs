@@ -0,0 +1,29 @@
+use crate::extend::Extension;
+
+pub const HAPLOTYPE_COLUMNS: &[&str] = &["haplotype_balance", "allelic_imbalance"];
+
+/// Phased supporting reads needed to assess the balance between haplotypes
+pub const MIN_PHASED_READS: usize = 10;
+
+/// Fraction of the phased supporting reads on the minority haplotype of each
+/// supporting sample, from 0 (support confined to one haplotype) to 0.5
+pub fn haplotype_balance(ext: &Extension) -> Option<f64> {
+    let (major, minor) = ext.haplotype_reads?;
+    (major + minor > 0).then(|| minor as f64 / (major + minor) as f64)
+}
+
+/// Values of the haplotype columns; support on both haplotypes but with a
+/// minority fraction below `max_fraction` is flagged as allelic imbalance,
+/// while support confined to one haplotype is expected for heterozygous
+/// clusters and is not
+pub fn haplotype_columns(ext: Option<&Extension>, max_fraction: f64) -> Vec<String> {
+    let Some((ext, balance)) = ext.and_then(|ext| Some((ext, haplotype_balance(ext)?))) else {
+        return vec!["NA".to_string(); HAPLOTYPE_COLUMNS.len()];
+    };
+    let (major, minor) = ext.haplotype_reads.unwrap_or_default();
+    let imbalance = match major + minor >= MIN_PHASED_READS {
+        true => (minor > 0 && balance < max_fraction).to_string(),
+        false => "NA".to_string(),
+    };
+    vec![format!("{balance:.2}"), imbalance]
+}
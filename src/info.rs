@@ -0,0 +1,74 @@
+use crate::models::Models;
+use crate::output::OutputFormat;
+use crate::provenance::model_checksum;
+use crate::schema::SCHEMA_VERSION;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Alignment formats accepted in the reads file
+pub const INPUT_FORMATS: &[&str] = &["bam", "cram", "paf"];
+
+/// Version and capabilities of this build, for checking deployments
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit the binary was built from, if built from a git checkout
+    pub git_hash: Option<String>,
+    pub schema_version: u32,
+    /// SHA-256 of the built-in model probabilities and priors
+    pub model_sha256: String,
+    pub features: Features,
+    pub input_formats: Vec<String>,
+    pub output_formats: Vec<String>,
+}
+
+/// Optional capabilities and whether this build has them
+#[derive(Debug, Serialize)]
+pub struct Features {
+    /// Reading alignments over HTTP(S), S3, or GCS
+    pub remote_io: bool,
+    /// Reading CRAM files, with the reference taken from their headers or
+    /// `REF_PATH`
+    pub cram: bool,
+    /// Writing results as Parquet
+    pub parquet: bool,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("VCLUST_GIT_HASH").map(str::to_string),
+            schema_version: SCHEMA_VERSION,
+            model_sha256: model_checksum(&Models::default(), &[]),
+            // htslib is built without libcurl and its cloud plugins
+            features: Features {
+                remote_io: false,
+                cram: true,
+                parquet: false,
+            },
+            input_formats: INPUT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            output_formats: OutputFormat::value_variants()
+                .iter()
+                .filter_map(|format| format.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let enabled = |enabled: bool| if enabled { "yes" } else { "no" };
+        [
+            format!("version\t{}", self.version),
+            format!("git_hash\t{}", self.git_hash.as_deref().unwrap_or("NA")),
+            format!("schema_version\t{}", self.schema_version),
+            format!("model_sha256\t{}", self.model_sha256),
+            format!("remote_io\t{}", enabled(self.features.remote_io)),
+            format!("cram\t{}", enabled(self.features.cram)),
+            format!("parquet\t{}", enabled(self.features.parquet)),
+            format!("input_formats\t{}", self.input_formats.join(",")),
+            format!("output_formats\t{}", self.output_formats.join(",")),
+        ]
+        .join("\n")
+    }
+}
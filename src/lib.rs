@@ -0,0 +1,59 @@
+pub mod aligner;
+pub mod alignment;
+pub mod amplicon;
+pub mod annotation;
+pub mod anonymize;
+pub mod atomic;
+pub mod cache;
+pub mod compare;
+pub mod completions;
+pub mod concordance;
+pub mod contamination;
+pub mod context;
+pub mod coverage;
+pub mod deletion;
+pub mod demo;
+pub mod density;
+pub mod early_stop;
+pub mod error;
+pub mod estimate;
+pub mod evidence;
+pub mod explain;
+pub mod expression;
+pub mod extend;
+pub mod genes;
+pub mod group;
+pub mod haplotype;
+pub mod info;
+pub mod liftover;
+pub mod locus;
+pub mod manifest;
+pub mod memory;
+pub mod models;
+pub mod normalize;
+pub mod offtarget;
+pub mod onnx;
+pub mod output;
+pub mod pedigree;
+pub mod population;
+pub mod preflight;
+pub mod prior;
+pub mod priority;
+pub mod profile;
+pub mod provenance;
+pub mod schema;
+pub mod scorer;
+pub mod selection;
+pub mod sort;
+pub mod span;
+pub mod stability;
+pub mod subreads;
+pub mod summary;
+pub mod sweep;
+pub mod throughput;
+pub mod tracks;
+pub mod training;
+pub mod underflow;
+pub mod uniqueness;
+pub mod validate;
+pub mod workflow;
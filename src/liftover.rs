@@ -0,0 +1,258 @@
+use crate::error::{Result, VclustError};
+use crate::schema::{SCHEMA_VERSION, VERSION_PREFIX};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Alignment of a target contig to the other assembly as gapless blocks of
+/// (target start, query start, length), sorted by target start
+#[derive(Debug)]
+struct Chain {
+    score: f64,
+    q_name: String,
+    q_size: i64,
+    q_reverse: bool,
+    blocks: Vec<(i64, i64, i64)>,
+}
+
+impl Chain {
+    /// Position on the forward strand of the query of a target position
+    fn lift_pos(&self, pos: i64) -> Option<i64> {
+        let index = self
+            .blocks
+            .partition_point(|(t_start, _, _)| *t_start <= pos);
+        let (t_start, q_start, len) = self.blocks[index.checked_sub(1)?];
+        if pos >= t_start + len {
+            return None;
+        }
+        let q_pos = q_start + pos - t_start;
+        Some(if self.q_reverse {
+            self.q_size - 1 - q_pos
+        } else {
+            q_pos
+        })
+    }
+}
+
+/// UCSC chain file indexed by target contig, with chains sorted by decreasing score
+#[derive(Debug, Default)]
+pub struct ChainMap {
+    chains: BTreeMap<String, Vec<Chain>>,
+}
+
+impl ChainMap {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        parse_chains(BufReader::new(file))
+    }
+
+    /// Lift the interval [start, end); both ends must lie in aligned blocks of
+    /// the same chain
+    pub fn lift(&self, chrom: &str, start: i64, end: i64) -> Option<(String, i64, i64)> {
+        if end <= start {
+            return None;
+        }
+        self.chains.get(chrom)?.iter().find_map(|chain| {
+            let first = chain.lift_pos(start)?;
+            let last = chain.lift_pos(end - 1)?;
+            Some((chain.q_name.clone(), first.min(last), first.max(last) + 1))
+        })
+    }
+}
+
+pub fn parse_chains(reader: impl BufRead) -> Result<ChainMap> {
+    let mut chains: BTreeMap<String, Vec<Chain>> = BTreeMap::new();
+    let mut current: Option<(String, Chain, i64, i64)> = None;
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split_whitespace().collect_vec();
+        let bad_line = || VclustError::Parse(format!("Bad chain line {line}"));
+        let parse = |val: &str| val.parse::<i64>().map_err(|_| bad_line());
+
+        if fields[0] == "chain" {
+            if fields.len() < 12 {
+                return Err(bad_line());
+            }
+            if let Some((t_name, chain, _, _)) = current.take() {
+                chains.entry(t_name).or_default().push(chain);
+            }
+            let chain = Chain {
+                score: fields[1].parse().map_err(|_| bad_line())?,
+                q_name: fields[7].to_string(),
+                q_size: parse(fields[8])?,
+                q_reverse: fields[9] == "-",
+                blocks: Vec::new(),
+            };
+            let (t_start, q_start) = (parse(fields[5])?, parse(fields[10])?);
+            current = Some((fields[2].to_string(), chain, t_start, q_start));
+            continue;
+        }
+
+        let (_, chain, t_pos, q_pos) = current.as_mut().ok_or_else(bad_line)?;
+        let len = parse(fields[0])?;
+        chain.blocks.push((*t_pos, *q_pos, len));
+        *t_pos += len;
+        *q_pos += len;
+        if fields.len() >= 3 {
+            *t_pos += parse(fields[1])?;
+            *q_pos += parse(fields[2])?;
+        }
+    }
+    if let Some((t_name, chain, _, _)) = current {
+        chains.entry(t_name).or_default().push(chain);
+    }
+    for contig_chains in chains.values_mut() {
+        contig_chains.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+    Ok(ChainMap { chains })
+}
+
+/// Counts of lifted and unmapped records
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LiftoverCounts {
+    pub lifted: usize,
+    pub unmapped: usize,
+}
+
+/// Convert the coordinates of a results file in the current schema; records
+/// that fail to lift are written to `unmapped`, each preceded by the reason
+pub fn liftover_results(
+    path: &Path,
+    chains: &ChainMap,
+    writer: &mut impl Write,
+    unmapped: &mut impl Write,
+) -> Result<LiftoverCounts> {
+    let file = File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+
+    let version_line = lines.next().transpose()?.unwrap_or_default();
+    let version = version_line
+        .strip_prefix(VERSION_PREFIX)
+        .and_then(|version| version.parse::<u32>().ok());
+    if version != Some(SCHEMA_VERSION) {
+        return Err(VclustError::Parse(format!(
+            "Results must be in schema version {SCHEMA_VERSION}; upgrade them with vclust migrate"
+        )));
+    }
+    let header = lines
+        .next()
+        .transpose()?
+        .ok_or(VclustError::Parse("Missing column header".to_string()))?;
+    let columns = header.trim_start_matches('#').split('\t').collect_vec();
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|c| *c == name)
+            .ok_or_else(|| VclustError::Parse(format!("Missing column {name}")))
+    };
+    let region_col = column("region")?;
+    let extended_col = column("extended_region")?;
+    let (lf_col, rf_col) = (column("lf_offset")?, column("rf_offset")?);
+    let gaps_col = column("coverage_gaps")?;
+
+    writeln!(writer, "{version_line}\n{header}")?;
+    writeln!(unmapped, "{version_line}\n{header}")?;
+    let mut counts = LiftoverCounts::default();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split('\t').map(str::to_string).collect_vec();
+        match lift_record(&mut fields, chains, region_col, extended_col, gaps_col) {
+            Ok(Some(offsets)) => {
+                (fields[lf_col], fields[rf_col]) = (offsets.0.to_string(), offsets.1.to_string());
+                writeln!(writer, "{}", fields.join("\t"))?;
+                counts.lifted += 1;
+            }
+            Ok(None) => {
+                writeln!(writer, "{}", fields.join("\t"))?;
+                counts.lifted += 1;
+            }
+            Err(reason) => {
+                writeln!(unmapped, "#{reason}\n{line}")?;
+                counts.unmapped += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Lift the coordinate columns of a record in place, returning the new offsets
+/// of extended records
+fn lift_record(
+    fields: &mut [String],
+    chains: &ChainMap,
+    region_col: usize,
+    extended_col: usize,
+    gaps_col: usize,
+) -> std::result::Result<Option<(i64, i64)>, String> {
+    let get = |index: usize| fields.get(index).map(String::as_str);
+    let (chrom, start, end) = get(region_col)
+        .and_then(parse_region)
+        .ok_or("Malformed region")?;
+    let lifted = chains
+        .lift(&chrom, start, end)
+        .ok_or("Region not in chain")?;
+    let region = format_region(&lifted);
+
+    let extended = match get(extended_col) {
+        Some("NA") => None,
+        other => {
+            let (chrom, start, end) = other
+                .and_then(parse_region)
+                .ok_or("Malformed extended_region")?;
+            let extended = chains
+                .lift(&chrom, start, end)
+                .ok_or("Extended region not in chain")?;
+            if extended.0 != lifted.0 || extended.1 > lifted.1 || extended.2 < lifted.2 {
+                return Err("Extended region does not contain the region".to_string());
+            }
+            Some(extended)
+        }
+    };
+
+    let gaps = match get(gaps_col) {
+        Some(gaps @ ("." | "NA")) => gaps.to_string(),
+        Some(gaps) => {
+            let lifted_gaps = gaps
+                .split(',')
+                .filter_map(|gap| {
+                    let (start, end) = gap.split_once('-')?;
+                    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                    let (_, start, end) = chains.lift(&chrom, start, end)?;
+                    Some(format!("{start}-{end}"))
+                })
+                .join(",");
+            if lifted_gaps.is_empty() {
+                ".".to_string()
+            } else {
+                lifted_gaps
+            }
+        }
+        None => return Err("Missing coverage_gaps".to_string()),
+    };
+
+    fields[region_col] = region;
+    fields[gaps_col] = gaps;
+    Ok(extended.map(|extended| {
+        fields[extended_col] = format_region(&extended);
+        (lifted.1 - extended.1, extended.2 - lifted.2)
+    }))
+}
+
+fn parse_region(region: &str) -> Option<(String, i64, i64)> {
+    let (chrom, span) = region.rsplit_once(':')?;
+    let (start, end) = span.split_once('-')?;
+    Some((chrom.to_string(), start.parse().ok()?, end.parse().ok()?))
+}
+
+fn format_region((chrom, start, end): &(String, i64, i64)) -> String {
+    format!("{chrom}:{start}-{end}")
+}
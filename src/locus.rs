@@ -1,42 +1,314 @@
+use crate::error::{Result, VclustError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashSet,
     fs::File,
     io::{BufRead, BufReader},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Locus {
     pub chrom: String,
     pub start: i64,
     pub end: i64,
     pub name: String,
+    /// Compound locus the locus belongs to, declared in the regions file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
 }
 
-pub fn load_loci(path: PathBuf) -> Result<Vec<Locus>, String> {
-    let file = File::open(path).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
+/// Key of the BED column (`group=NAME` after the name) or GFF3 attribute
+/// naming the group of a locus; interval lists cannot declare groups
+pub const GROUP_KEY: &str = "group";
+
+/// Layout of a regions file, detected from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionFormat {
+    Bed,
+    /// Picard interval list with a SAM-style header and 1-based closed intervals
+    IntervalList,
+    Gff,
+}
+
+impl RegionFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.to_string_lossy().to_lowercase();
+        if name.ends_with(".interval_list") {
+            RegionFormat::IntervalList
+        } else if name.ends_with(".gff") || name.ends_with(".gff3") {
+            RegionFormat::Gff
+        } else {
+            RegionFormat::Bed
+        }
+    }
+}
+
+/// Load regions from a BED, interval list, or GFF3 file; for GFF3, only
+/// features of the given type are kept if one is specified
+/// Output column naming the catalog of each record when several are given
+pub const CATALOG_COLUMN: &str = "catalog";
+
+/// Regions file given as `LABEL=PATH`, or as a path labelled by its file stem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    pub label: String,
+    pub path: PathBuf,
+    /// Whether the label was given rather than taken from the path
+    pub labelled: bool,
+}
+
+impl Catalog {
+    pub fn parse(value: &str) -> Self {
+        match value.split_once('=') {
+            Some((label, path))
+                if !label.is_empty() && !path.is_empty() && !Path::new(value).exists() =>
+            {
+                Catalog {
+                    label: label.to_string(),
+                    path: PathBuf::from(path),
+                    labelled: true,
+                }
+            }
+            _ => {
+                let path = PathBuf::from(value);
+                let label = path
+                    .file_stem()
+                    .map_or(value.to_string(), |stem| stem.to_string_lossy().to_string());
+                Catalog {
+                    label,
+                    path,
+                    labelled: false,
+                }
+            }
+        }
+    }
+}
+
+/// Records are tagged with their catalog when several catalogs or any labels
+/// are given, which requires distinct labels
+pub fn tag_catalogs(catalogs: &[Catalog]) -> Result<bool> {
+    let mut labels = HashSet::new();
+    if let Some(catalog) = catalogs
+        .iter()
+        .find(|catalog| !labels.insert(catalog.label.as_str()))
+    {
+        return Err(VclustError::Other(format!(
+            "Catalog label {} is used more than once; label catalogs as LABEL=PATH",
+            catalog.label
+        )));
+    }
+    Ok(catalogs.len() > 1 || catalogs.iter().any(|catalog| catalog.labelled))
+}
+
+pub fn load_loci(path: PathBuf, feature_type: Option<&str>) -> Result<Vec<Locus>> {
+    let format = RegionFormat::from_path(&path);
+    let reader = BufReader::new(File::open(path)?);
+    match format {
+        RegionFormat::Bed => parse_loci(reader),
+        RegionFormat::IntervalList => parse_interval_list(reader),
+        RegionFormat::Gff => parse_gff(reader, feature_type),
+    }
+}
+
+pub fn parse_loci(reader: impl BufRead) -> Result<Vec<Locus>> {
     let mut loci = Vec::new();
     for line in reader.lines() {
-        let line = line.map_err(|e| e.to_string())?;
+        let line = line?;
         let rec: Vec<&str> = line.split_whitespace().collect();
         if rec.len() < 4 {
-            return Err(format!("Bad input line {line}"));
+            return Err(VclustError::Parse(format!("Bad input line {line}")));
         }
         let (chrom, start, end, name) = (rec[0].to_string(), rec[1], rec[2], rec[3]);
         let start = start
             .parse::<i64>()
-            .map_err(|_| format!("Bad input line {line}"))?;
+            .map_err(|_| VclustError::Parse(format!("Bad input line {line}")))?;
         let end = end
             .parse::<i64>()
-            .map_err(|_| format!("Bad input line {line}"))?;
+            .map_err(|_| VclustError::Parse(format!("Bad input line {line}")))?;
         let name = name.to_string();
+        let group = rec[4..]
+            .iter()
+            .find_map(|field| field.strip_prefix(GROUP_KEY)?.strip_prefix('='))
+            .map(str::to_string);
         loci.push(Locus {
             chrom,
             start,
             end,
             name,
+            group,
+        });
+    }
+
+    Ok(loci)
+}
+
+pub fn parse_interval_list(reader: impl BufRead) -> Result<Vec<Locus>> {
+    let mut loci = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with('@') || line.trim().is_empty() {
+            continue;
+        }
+        let rec: Vec<&str> = line.split('\t').collect();
+        if rec.len() < 5 {
+            return Err(VclustError::Parse(format!("Bad interval line {line}")));
+        }
+        let (start, end) = parse_closed_interval(rec[1], rec[2], &line)?;
+        loci.push(Locus {
+            chrom: rec[0].to_string(),
+            start,
+            end,
+            name: rec[4].to_string(),
+            group: None,
         });
     }
 
     Ok(loci)
 }
+
+pub fn parse_gff(reader: impl BufRead, feature_type: Option<&str>) -> Result<Vec<Locus>> {
+    let mut loci = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        // Embedded sequences follow the features
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let rec: Vec<&str> = line.split('\t').collect();
+        if rec.len() < 9 {
+            return Err(VclustError::Parse(format!("Bad GFF line {line}")));
+        }
+        if feature_type.is_some_and(|feature_type| feature_type != rec[2]) {
+            continue;
+        }
+        let (chrom, start, end) = (rec[0].to_string(), rec[3], rec[4]);
+        let (start, end) = parse_closed_interval(start, end, &line)?;
+        let attribute = |key: &str| {
+            rec[8]
+                .split(';')
+                .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))
+                .map(str::to_string)
+        };
+        let name = attribute("ID")
+            .or_else(|| attribute("Name"))
+            .unwrap_or_else(|| format!("{chrom}:{start}-{end}"));
+        loci.push(Locus {
+            chrom,
+            start,
+            end,
+            name,
+            group: attribute(GROUP_KEY),
+        });
+    }
+
+    Ok(loci)
+}
+
+/// Convert a 1-based closed interval to 0-based half-open coordinates
+fn parse_closed_interval(start: &str, end: &str, line: &str) -> Result<(i64, i64)> {
+    let parse = |val: &str| {
+        val.parse::<i64>()
+            .map_err(|_| VclustError::Parse(format!("Bad input line {line}")))
+    };
+    Ok((parse(start)? - 1, parse(end)?))
+}
+
+pub fn sample_loci(loci: Vec<Locus>, fraction: f64, seed: u64) -> Vec<Locus> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    loci.into_iter()
+        .filter(|_| rng.gen::<f64>() < fraction)
+        .collect()
+}
+
+pub const DEDUP_COLUMNS: &[&str] = &["name", "region", "kept_name", "kept_region", "reason"];
+
+/// Catalog entry dropped in favor of an identical or enclosing entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollapsedLocus {
+    pub name: String,
+    pub region: String,
+    pub kept_name: String,
+    pub kept_region: String,
+    /// `duplicate` for identical coordinates, `contained` otherwise
+    pub reason: &'static str,
+}
+
+impl CollapsedLocus {
+    pub fn to_row(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.name, self.region, self.kept_name, self.kept_region, self.reason
+        )
+    }
+}
+
+pub fn dedup_header() -> String {
+    format!("#{}", DEDUP_COLUMNS.join("\t"))
+}
+
+/// Drop loci with the same coordinates as an earlier locus, and with
+/// `collapse_contained` those lying within another locus, keeping the input
+/// order of the remaining loci
+pub fn dedup_loci(loci: Vec<Locus>, collapse_contained: bool) -> (Vec<Locus>, Vec<CollapsedLocus>) {
+    let region = |locus: &Locus| format!("{}:{}-{}", locus.chrom, locus.start, locus.end);
+
+    // Enclosing loci sort before the loci they contain
+    let mut order = (0..loci.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| {
+        let (a, b) = (&loci[*a], &loci[*b]);
+        (&a.chrom, a.start, -a.end).cmp(&(&b.chrom, b.start, -b.end))
+    });
+
+    let mut keep = vec![true; loci.len()];
+    let mut collapsed = Vec::new();
+    // Last locus kept, which identical loci follow, and the kept locus reaching
+    // furthest on its contig, which contains any later locus ending before it
+    let mut last: Option<usize> = None;
+    let mut container: Option<usize> = None;
+    for index in order {
+        let locus = &loci[index];
+        let same = last.map(|kept| &loci[kept]).filter(|kept| {
+            (&kept.chrom, kept.start, kept.end) == (&locus.chrom, locus.start, locus.end)
+        });
+        let within = container
+            .map(|kept| &loci[kept])
+            .filter(|kept| kept.chrom == locus.chrom && locus.end <= kept.end);
+        // Identical loci sort next to each other, in input order
+        let kept = same.or(within.filter(|_| collapse_contained));
+        match kept {
+            Some(kept) => {
+                keep[index] = false;
+                collapsed.push(CollapsedLocus {
+                    name: locus.name.clone(),
+                    region: region(locus),
+                    kept_name: kept.name.clone(),
+                    kept_region: region(kept),
+                    reason: if same.is_some() {
+                        "duplicate"
+                    } else {
+                        "contained"
+                    },
+                });
+            }
+            None => {
+                last = Some(index);
+                if within.is_none() {
+                    container = Some(index);
+                }
+            }
+        }
+    }
+
+    let kept = loci
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(locus, keep)| keep.then_some(locus))
+        .collect();
+    (kept, collapsed)
+}
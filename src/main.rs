@@ -1,19 +1,83 @@
 use chrono::Datelike;
-use clap::Parser;
-use crossbeam_channel::{unbounded, Receiver, Sender};
-use locus::{load_loci, Locus};
-use rust_htslib::bam::IndexedReader;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use clap::builder::Str;
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use rust_htslib::faidx;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
-use workflow::run_workflow;
+use std::time::{Duration, Instant};
+use vclust::aligner::check_alignments;
+use vclust::amplicon::Amplicons;
+use vclust::annotation::Annotations;
+use vclust::anonymize::{anonymize_result, anonymize_segregation, pseudonym};
+use vclust::atomic::{check_overwrite, AtomicFile, OutputTarget, SocketAddress};
+use vclust::cache::ResultCache;
+use vclust::compare::{compare_clusters, write_comparison, ClusterStatus};
+use vclust::completions::{completion_script, man_page, Shell};
+use vclust::concordance::concordance_header;
+use vclust::contamination::contamination_warnings;
+use vclust::context::{ContextBuilder, Params};
+use vclust::demo::{annotate_results, write_demo};
+use vclust::density::{DensityGuard, DENSITY_COLUMN};
+use vclust::error::{Result, VclustError};
+use vclust::estimate::estimate_run;
+use vclust::evidence::evidence_header;
+use vclust::explain::{compare_settings, explain_with, parse_locus, ExplainSetting};
+use vclust::expression::Expression;
+use vclust::genes::{load_genes, load_panel, rollup_genes, write_rollup};
+use vclust::group::GroupReport;
+use vclust::info::BuildInfo;
+use vclust::liftover::{liftover_results, ChainMap};
+use vclust::locus::{
+    dedup_header, dedup_loci, load_loci, sample_loci, tag_catalogs, Catalog, Locus, CATALOG_COLUMN,
+};
+use vclust::manifest::{load_manifest, Karyotype};
+use vclust::memory::{parse_size, MemoryWatchdog};
+use vclust::models::{ModelType, Models};
+use vclust::offtarget::estimate_off_target_depth;
+use vclust::onnx::OnnxClassifier;
+use vclust::output::{make_sink, ColumnSelection, OutputFormat};
+use vclust::pedigree::{load_pedigree, segregation_header};
+use vclust::population::PopulationCatalog;
+use vclust::preflight::{check_loci, preflight_header};
+use vclust::prior::PriorSpans;
+use vclust::priority::{PRIORITY_COLUMN, PRIORITY_VARIABLES};
+use vclust::profile::{malformed_record_warnings, skipped_header, OntReadClass, CHIMERA_DISTANCE};
+use vclust::provenance::{FileRecord, HashingWriter, RunManifest};
+use vclust::schema;
+use vclust::selection::load_model_rules;
+use vclust::sort::{ResultSorter, SORT_CHUNK_RECORDS};
+use vclust::span::SpanReferenceMode;
+use vclust::subreads::is_subread_bam;
+use vclust::summary::{aggregate_summaries, RunSummary};
+use vclust::sweep::{make_grid, parse_window_ladder, run_sweep, sweep_header};
+use vclust::throughput::{thread_recommendation, WorkerTimes};
+use vclust::tracks::{hub_paths, load_clusters, write_track_hub};
+use vclust::training::{training_paths, TrainingWriter, TruthRegions};
+use vclust::uniqueness::KmerIndex;
+use vclust::validate::{load_fai, validate_results};
+use vclust::workflow::{run_workflow, LocusResult};
 
-mod extend;
-mod locus;
-mod models;
-mod profile;
-mod workflow;
+/// Exit code reported when the run is stopped by SIGINT/SIGTERM
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+/// Exit code of runs stopped by --rss-limit, distinct from OOM kills (137)
+const RSS_LIMIT_EXIT_CODE: i32 = 3;
+const DEFAULT_RSS_INTERVAL_SECS: f64 = 10.0;
+
+/// Prefix of the environment variables that set options missing from the
+/// command line, e.g. VCLUST_GENOME for --genome
+const ENV_PREFIX: &str = "VCLUST_";
+
+/// Loci in flight per worker thread under --low-memory, whether queued,
+/// profiled, or held back until earlier results are written
+const LOW_MEMORY_QUEUE_LEN: usize = 2;
+
+/// Buffer size of the results written to stdout or a file
+const OUTPUT_BUFFER_SIZE: usize = 8 * 1024;
 
 #[derive(Parser)]
 #[command(name="HIFI-VCLUST",
@@ -25,7 +89,392 @@ Research Use Only and not for use in diagnostic procedures.", chrono::Utc::now()
           help_template = "{name} {version}\n{author}{about-section}\n{usage-heading}\n    {usage}\n\n{all-args}{after-help}",
           )]
 #[command(arg_required_else_help(true))]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct CliParams {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub run: Option<RunParams>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Upgrade a results file from an earlier vclust release")]
+    Migrate {
+        #[clap(help = "Results file to upgrade")]
+        #[clap(value_name = "RESULTS")]
+        #[arg(value_parser = check_file_exists)]
+        input_path: PathBuf,
+    },
+    #[command(about = "Convert the coordinates of a results file to another assembly")]
+    Liftover {
+        #[clap(help = "Results file to convert")]
+        #[clap(value_name = "RESULTS")]
+        #[arg(value_parser = check_file_exists)]
+        input_path: PathBuf,
+
+        #[clap(long = "chain")]
+        #[clap(help = "UCSC chain file from the current to the target assembly")]
+        #[clap(value_name = "CHAIN")]
+        #[arg(value_parser = check_file_exists)]
+        chain_path: PathBuf,
+
+        #[clap(long = "unmapped")]
+        #[clap(help = "Output file for records that could not be converted")]
+        #[clap(value_name = "UNMAPPED")]
+        #[clap(default_value = "unmapped.txt")]
+        unmapped_path: PathBuf,
+
+        #[clap(long = "force")]
+        #[clap(help = "Overwrite existing output files")]
+        force: bool,
+    },
+    #[command(about = "Report the clusters found against one of two references only")]
+    CompareReferences {
+        #[clap(help = "Results of the reads aligned to the first reference")]
+        #[clap(value_name = "FIRST")]
+        #[arg(value_parser = check_file_exists)]
+        first_path: PathBuf,
+
+        #[clap(help = "Results of the reads aligned to the second reference")]
+        #[clap(value_name = "SECOND")]
+        #[arg(value_parser = check_file_exists)]
+        second_path: PathBuf,
+
+        #[clap(long = "chain")]
+        #[clap(help = "UCSC chain file from the first to the second reference")]
+        #[clap(value_name = "CHAIN")]
+        #[arg(value_parser = check_file_exists)]
+        chain_path: PathBuf,
+
+        #[clap(long = "labels")]
+        #[clap(help = "Names of the two references in the report")]
+        #[clap(value_name = "FIRST,SECOND")]
+        #[clap(value_delimiter = ',')]
+        #[clap(default_values_t = ["first".to_string(), "second".to_string()])]
+        labels: Vec<String>,
+    },
+    #[command(about = "Report boundary stability and support across a grid of parameters")]
+    Sweep(SweepParams),
+    #[command(about = "Describe step by step how the boundaries of one region were decided")]
+    Explain(ExplainParams),
+    #[command(about = "Merge the summaries of sharded runs and check them for consistency")]
+    AggregateSummaries {
+        #[clap(help = "Summary files written with --summary")]
+        #[clap(value_name = "SUMMARIES")]
+        #[clap(required = true)]
+        #[arg(value_parser = check_file_exists)]
+        input_paths: Vec<PathBuf>,
+    },
+    #[command(about = "Package results as a track hub for UCSC and IGV")]
+    Tracks(TracksParams),
+    #[command(
+        about = "Aggregate the results of a cohort, one file per sample, into a population catalog"
+    )]
+    PopulationCatalog {
+        #[clap(help = "Results files of the samples of the cohort")]
+        #[clap(value_name = "RESULTS")]
+        #[clap(required = true)]
+        #[arg(value_parser = check_file_exists)]
+        input_paths: Vec<PathBuf>,
+    },
+    #[command(
+        about = "Summarize the clusters overlapping the exons, introns, and UTRs of each gene"
+    )]
+    GeneRollup {
+        #[clap(help = "Results file to summarize")]
+        #[clap(value_name = "RESULTS")]
+        #[arg(value_parser = check_file_exists)]
+        input_path: PathBuf,
+
+        #[clap(long = "genes")]
+        #[clap(help = "GTF gene annotation")]
+        #[clap(value_name = "GTF")]
+        #[arg(value_parser = check_file_exists)]
+        genes_path: PathBuf,
+
+        #[clap(long = "panel")]
+        #[clap(help = "Restrict the report to the genes listed in a file, one per line")]
+        #[clap(value_name = "PANEL")]
+        #[arg(value_parser = check_file_exists)]
+        panel_path: Option<PathBuf>,
+    },
+    #[command(about = "Check a results file before loading it into a database")]
+    ValidateOutput {
+        #[clap(help = "Results file to check")]
+        #[clap(value_name = "RESULTS")]
+        #[arg(value_parser = check_file_exists)]
+        input_path: PathBuf,
+
+        #[clap(long = "fai")]
+        #[clap(help = "FAI index of the reference, to check coordinates and contig order")]
+        #[clap(value_name = "FAI")]
+        #[arg(value_parser = check_file_exists)]
+        fai_path: Option<PathBuf>,
+
+        #[clap(long = "allow-unsorted")]
+        #[clap(help = "Accept records in any order, as written for an unsorted catalog")]
+        allow_unsorted: bool,
+    },
+    #[command(about = "Index the repeated k-mers of a reference for --kmer-index")]
+    KmerIndex {
+        #[clap(long = "genome")]
+        #[clap(help = "Path to reference genome FASTA")]
+        #[clap(value_name = "FASTA")]
+        #[arg(value_parser = check_file_exists)]
+        genome_path: PathBuf,
+
+        #[clap(long = "output")]
+        #[clap(help = "Index file to write")]
+        #[clap(value_name = "INDEX")]
+        output_path: PathBuf,
+
+        #[clap(long = "force")]
+        #[clap(help = "Overwrite existing output files")]
+        force: bool,
+    },
+    #[command(about = "Run the workflow on a small bundled dataset to check the installation")]
+    Demo {
+        #[clap(long = "out-dir")]
+        #[clap(help = "Directory to write the demo inputs and results to")]
+        #[clap(value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    #[command(about = "Print the version and capabilities of this build")]
+    Info {
+        #[clap(long = "json")]
+        #[clap(help = "Print as JSON")]
+        json: bool,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        #[clap(help = "Shell to complete for")]
+        #[clap(value_name = "SHELL")]
+        shell: Shell,
+    },
+    #[command(about = "Print the manual page in roff format")]
+    Man,
+}
+
+#[derive(Args)]
+pub struct TracksParams {
+    #[clap(help = "Results file to package")]
+    #[clap(value_name = "RESULTS")]
+    #[arg(value_parser = check_file_exists)]
+    pub input_path: PathBuf,
+
+    #[clap(long = "genome")]
+    #[clap(help = "Path to reference genome FASTA")]
+    #[clap(value_name = "FASTA")]
+    #[arg(value_parser = check_file_exists)]
+    pub genome_path: PathBuf,
+
+    #[clap(long = "genome-name")]
+    #[clap(help = "UCSC name of the reference genome (e.g. hg38)")]
+    #[clap(value_name = "NAME")]
+    pub genome_name: String,
+
+    #[clap(long = "email")]
+    #[clap(help = "Contact address listed in the hub")]
+    #[clap(value_name = "EMAIL")]
+    pub email: String,
+
+    #[clap(long = "out-dir")]
+    #[clap(help = "Directory to write the hub to")]
+    #[clap(value_name = "DIR")]
+    pub out_dir: PathBuf,
+
+    #[clap(long = "force")]
+    #[clap(help = "Overwrite existing output files")]
+    pub force: bool,
+}
+
+/// Options of the extension search shared by runs and parameter sweeps
+#[derive(Args)]
+pub struct SearchParams {
+    #[clap(long = "radius")]
+    #[clap(help = "Search this many bps on either side of each region [default: 500]")]
+    #[clap(value_name = "BPS")]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub radius: Option<i64>,
+
+    #[clap(long = "radius-scale")]
+    #[clap(help = "Search this many region lengths around each region instead of a fixed radius")]
+    #[clap(value_name = "SCALE")]
+    #[arg(value_parser = check_positive)]
+    pub radius_scale: Option<f64>,
+
+    #[clap(long = "min-radius")]
+    #[clap(help = "Smallest search radius with --radius-scale")]
+    #[clap(value_name = "BPS")]
+    #[clap(default_value_t = 300)]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub min_radius: i64,
+
+    #[clap(long = "max-radius")]
+    #[clap(help = "Largest search radius with --radius-scale")]
+    #[clap(value_name = "BPS")]
+    #[clap(default_value_t = 5000)]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_radius: i64,
+
+    #[clap(long = "min-mapq")]
+    #[clap(
+        help = "Smallest mapping quality of profiled reads [default: 50, or that of --assembly or --short-reads]"
+    )]
+    #[clap(value_name = "MAPQ")]
+    pub min_mapq: Option<u8>,
+}
+
+impl SearchParams {
+    /// Set the search options that were given on the parameters
+    fn apply(&self, params: &mut Params) -> Result<()> {
+        if self.min_radius > self.max_radius {
+            return Err(VclustError::Other(
+                "--min-radius must not exceed --max-radius".to_string(),
+            ));
+        }
+        if let Some(radius) = self.radius {
+            params.radius = radius;
+        }
+        if let Some(min_mapq) = self.min_mapq {
+            params.min_mapq = min_mapq;
+        }
+        params.radius_scale = self.radius_scale;
+        params.min_radius = self.min_radius;
+        params.max_radius = self.max_radius;
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct SweepParams {
+    #[clap(long = "genome")]
+    #[clap(help = "Path to reference genome FASTA")]
+    #[clap(value_name = "FASTA")]
+    #[arg(value_parser = check_file_exists)]
+    pub genome_path: PathBuf,
+
+    #[clap(long = "reads")]
+    #[clap(help = "File listing BAMs with aligned HiFi reads and optional sample names")]
+    #[clap(value_name = "READS")]
+    #[arg(value_parser = check_file_exists)]
+    pub reads_paths: PathBuf,
+
+    #[clap(long = "regions")]
+    #[clap(help = "BED, interval_list, or GFF3 file with the regions to sweep over")]
+    #[clap(value_name = "REGIONS")]
+    #[arg(value_parser = check_file_exists)]
+    pub repeats_path: PathBuf,
+
+    #[clap(long = "feature-type")]
+    #[clap(help = "Use only GFF3 features of this type (e.g. exon) as regions")]
+    #[clap(value_name = "TYPE")]
+    pub feature_type: Option<String>,
+
+    #[clap(long = "min-prob-ref")]
+    #[clap(help = "Comma-separated reference posteriors at which extension stops")]
+    #[clap(value_name = "PROBS")]
+    #[clap(value_delimiter = ',')]
+    #[clap(default_value = "0.5")]
+    #[arg(value_parser = check_fraction)]
+    pub min_prob_refs: Vec<f64>,
+
+    #[clap(long = "prior-ref")]
+    #[clap(help = "Comma-separated prior probabilities of the reference model")]
+    #[clap(value_name = "PRIORS")]
+    #[clap(value_delimiter = ',')]
+    #[clap(default_value = "0.5779905")]
+    #[arg(value_parser = check_prior)]
+    pub prior_refs: Vec<f64>,
+
+    #[clap(long = "window-ladders")]
+    #[clap(help = "Comma-separated window ladders written as colon-separated lengths")]
+    #[clap(value_name = "LADDERS")]
+    #[clap(value_delimiter = ',')]
+    #[clap(default_value = "150:50:25:10")]
+    #[arg(value_parser = check_window_ladder)]
+    pub window_ladders: Vec<Vec<i64>>,
+
+    #[command(flatten)]
+    pub search: SearchParams,
+}
+
+#[derive(Args)]
+pub struct ExplainParams {
+    #[clap(long = "genome")]
+    #[clap(help = "Path to reference genome FASTA")]
+    #[clap(value_name = "FASTA")]
+    #[arg(value_parser = check_file_exists)]
+    pub genome_path: PathBuf,
+
+    #[clap(long = "reads")]
+    #[clap(help = "File listing BAMs with aligned HiFi reads and optional sample names")]
+    #[clap(value_name = "READS")]
+    #[arg(value_parser = check_file_exists)]
+    pub reads_paths: PathBuf,
+
+    #[clap(help = "Region to explain, as chrom:start-end with a 0-based start")]
+    #[clap(value_name = "REGION")]
+    #[arg(value_parser = check_region)]
+    pub locus: Locus,
+
+    #[clap(long = "min-prob-ref")]
+    #[clap(help = "Reference posterior at which extension stops")]
+    #[clap(value_name = "PROB")]
+    #[clap(default_value = "0.5")]
+    #[arg(value_parser = check_fraction)]
+    pub min_prob_ref: f64,
+
+    #[clap(long = "prior-ref")]
+    #[clap(help = "Prior probability of the reference model")]
+    #[clap(value_name = "PRIOR")]
+    #[clap(default_value = "0.5779905")]
+    #[arg(value_parser = check_prior)]
+    pub prior_ref: f64,
+
+    #[clap(long = "window-ladder")]
+    #[clap(help = "Colon-separated window lengths, from longest to shortest")]
+    #[clap(value_name = "LADDER")]
+    #[clap(default_value = "150:50:25:10")]
+    #[arg(value_parser = check_window_ladder)]
+    pub window_ladder: ::std::vec::Vec<i64>,
+
+    #[clap(long = "model")]
+    #[clap(help = "ONNX classifier of window states to use instead of the categorical models")]
+    #[clap(value_name = "MODEL")]
+    #[arg(value_parser = check_file_exists)]
+    pub model_path: Option<PathBuf>,
+
+    #[clap(long = "compare-min-prob-ref")]
+    #[clap(help = "Reference posterior of a second setting to compare against")]
+    #[clap(value_name = "PROB")]
+    #[arg(value_parser = check_fraction)]
+    pub compare_min_prob_ref: Option<f64>,
+
+    #[clap(long = "compare-prior-ref")]
+    #[clap(help = "Reference prior of a second setting to compare against")]
+    #[clap(value_name = "PRIOR")]
+    #[arg(value_parser = check_prior)]
+    pub compare_prior_ref: Option<f64>,
+
+    #[clap(long = "compare-window-ladder")]
+    #[clap(help = "Window ladder of a second setting to compare against")]
+    #[clap(value_name = "LADDER")]
+    #[arg(value_parser = check_window_ladder)]
+    pub compare_window_ladder: Option<::std::vec::Vec<i64>>,
+
+    #[clap(long = "compare-model")]
+    #[clap(
+        help = "ONNX classifier of a second setting to compare against, or \"categorical\" for the categorical models"
+    )]
+    #[clap(value_name = "MODEL")]
+    pub compare_model: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RunParams {
     #[clap(required = true)]
     #[clap(long = "genome")]
     #[clap(help = "Path to reference genome FASTA")]
@@ -35,7 +484,7 @@ pub struct CliParams {
 
     #[clap(required = true)]
     #[clap(long = "reads")]
-    #[clap(help = "BAM file with aligned HiFi reads")]
+    #[clap(help = "File listing BAMs with aligned HiFi reads and optional sample names")]
     #[clap(value_name = "READS")]
     #[clap(num_args = 1)]
     #[arg(value_parser = check_file_exists)]
@@ -43,118 +492,1626 @@ pub struct CliParams {
 
     #[clap(required = true)]
     #[clap(long = "regions")]
-    #[clap(help = "BED file with region coordinates")]
+    #[clap(
+        help = "BED, interval_list, or GFF3 file with region coordinates, given as LABEL=PATH and repeated to tag the results of several catalogs"
+    )]
     #[clap(value_name = "REGIONS")]
-    #[arg(value_parser = check_file_exists)]
-    pub repeats_path: PathBuf,
+    #[arg(value_parser = check_catalog)]
+    pub catalogs: Vec<Catalog>,
+
+    #[clap(long = "feature-type")]
+    #[clap(help = "Use only GFF3 features of this type (e.g. exon) as regions")]
+    #[clap(value_name = "TYPE")]
+    pub feature_type: Option<String>,
 
     #[clap(long = "threads")]
     #[clap(help = "Number of threads to use")]
     #[clap(value_name = "THREADS")]
     #[clap(default_value_t = 1)]
     pub threads: usize,
+
+    #[clap(long = "summary")]
+    #[clap(help = "Write a JSON run summary to this file")]
+    #[clap(value_name = "SUMMARY")]
+    pub summary_path: Option<PathBuf>,
+
+    #[clap(long = "rss-interval")]
+    #[clap(help = "Record the RSS of the process in the summary every SECS seconds")]
+    #[clap(value_name = "SECS")]
+    #[arg(value_parser = check_positive)]
+    pub rss_interval: Option<f64>,
+
+    #[clap(long = "rss-limit")]
+    #[clap(help = "Stop with partial results once the RSS exceeds this size (e.g. 16G)")]
+    #[clap(value_name = "SIZE")]
+    #[arg(value_parser = check_size)]
+    pub rss_limit: Option<u64>,
+
+    #[clap(long = "sample-fraction")]
+    #[clap(help = "Process only a random fraction of the input regions")]
+    #[clap(value_name = "FRACTION")]
+    #[arg(value_parser = check_fraction)]
+    pub sample_fraction: Option<f64>,
+
+    #[clap(long = "seed")]
+    #[clap(help = "Seed for random region and read sampling")]
+    #[clap(value_name = "SEED")]
+    #[clap(default_value_t = 42)]
+    pub seed: u64,
+
+    #[clap(long = "estimate")]
+    #[clap(
+        help = "Profile a random sample of regions and report predicted runtime and output size"
+    )]
+    pub estimate: bool,
+
+    #[clap(long = "require-spanning")]
+    #[clap(help = "Count only reads spanning the whole region towards sample support")]
+    pub require_spanning: bool,
+
+    #[clap(long = "gap-depth-fraction")]
+    #[clap(help = "Report positions with depth below this fraction of the mean as coverage gaps")]
+    #[clap(value_name = "FRACTION")]
+    #[clap(default_value_t = 0.25)]
+    #[arg(value_parser = check_fraction)]
+    pub gap_depth_fraction: f64,
+
+    #[clap(long = "concordance")]
+    #[clap(help = "Write boundary concordance between replicates of each sample to this file")]
+    #[clap(value_name = "CONCORDANCE")]
+    pub concordance_path: Option<PathBuf>,
+
+    #[clap(long = "support-evidence")]
+    #[clap(help = "Write the strongest alt evidence of each supporting sample to this file")]
+    #[clap(value_name = "EVIDENCE")]
+    pub support_evidence_path: Option<PathBuf>,
+
+    #[clap(long = "pedigree")]
+    #[clap(help = "PED file of the families of the samples, for segregation analysis")]
+    #[clap(value_name = "PED")]
+    #[arg(value_parser = check_file_exists, requires = "segregation_path")]
+    pub pedigree_path: Option<PathBuf>,
+
+    #[clap(long = "segregation")]
+    #[clap(
+        help = "Write the carriers of each cluster in each family of the pedigree to this file"
+    )]
+    #[clap(value_name = "SEGREGATION")]
+    #[arg(requires = "pedigree_path")]
+    pub segregation_path: Option<PathBuf>,
+
+    #[clap(long = "group-report")]
+    #[clap(
+        help = "Write a joint record for each group of regions declared in the catalogs with group=NAME to this file"
+    )]
+    #[clap(value_name = "GROUPS")]
+    pub group_report_path: Option<PathBuf>,
+
+    #[clap(long = "max-cluster-density")]
+    #[clap(
+        help = "Flag the clusters of any megabase with more than N supported clusters, such as misassembled contigs, in a dense_region column and list those megabases in the run summary"
+    )]
+    #[clap(value_name = "N")]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_cluster_density: Option<u32>,
+
+    #[clap(long = "suppress-dense-regions")]
+    #[clap(help = "Leave the clusters of megabases over --max-cluster-density out of the results")]
+    #[clap(requires = "max_cluster_density")]
+    pub suppress_dense_regions: bool,
+
+    #[clap(long = "skipped-reads")]
+    #[clap(
+        help = "Write the records excluded by the read filters in each sample and region to this file"
+    )]
+    #[clap(value_name = "SKIPPED")]
+    pub skipped_reads_path: Option<PathBuf>,
+
+    #[clap(long = "priority")]
+    #[clap(help = "Add a column scoring each cluster for review, from 0 to 100")]
+    pub priority_score: bool,
+
+    #[clap(long = "priority-expr")]
+    #[clap(help = "Compute the priority column with this expression over the output columns")]
+    #[clap(value_name = "EXPRESSION")]
+    #[arg(value_parser = check_expression)]
+    pub priority_expression: Option<Expression>,
+
+    #[clap(long = "haplotype-balance")]
+    #[clap(help = "Add columns for the balance of supporting reads between haplotypes (HP tags)")]
+    pub haplotype_balance: bool,
+
+    #[clap(long = "imbalance-fraction")]
+    #[clap(
+        help = "Flag allelic imbalance when the minority haplotype has less than this fraction of the phased support"
+    )]
+    #[clap(value_name = "FRACTION")]
+    #[clap(default_value_t = 0.2)]
+    #[arg(value_parser = check_fraction)]
+    pub imbalance_fraction: f64,
+
+    #[clap(long = "long-deletions")]
+    #[clap(
+        help = "Call deletions too long to be spanned by reads from split reads instead of failing for low depth"
+    )]
+    pub long_deletions: bool,
+
+    #[clap(long = "anonymize")]
+    #[clap(help = "Replace sample and family names in the secondary outputs with hashes")]
+    pub anonymize: bool,
+
+    #[clap(long = "karyotype")]
+    #[clap(help = "Sex chromosome karyotype of samples without one in the reads file")]
+    #[clap(value_name = "KARYOTYPE")]
+    pub karyotype: Option<Karyotype>,
+
+    #[clap(long = "high-copy")]
+    #[clap(
+        help = "Subsample reads in all regions exceeding the read cap instead of skipping them"
+    )]
+    pub high_copy: bool,
+
+    #[clap(long = "high-copy-contigs")]
+    #[clap(help = "Comma-separated contigs (e.g. chrM) handled in high-copy mode")]
+    #[clap(value_name = "CONTIGS")]
+    #[clap(value_delimiter = ',')]
+    pub high_copy_contigs: Vec<String>,
+
+    #[clap(long = "amplicon-bed")]
+    #[clap(help = "BED file with amplicons of a targeted panel")]
+    #[clap(value_name = "AMPLICONS")]
+    #[arg(value_parser = check_file_exists)]
+    pub amplicons_path: Option<PathBuf>,
+
+    #[clap(long = "prior-results")]
+    #[clap(help = "Results of a previous run narrowing the search around its extended regions")]
+    #[clap(value_name = "RESULTS")]
+    #[arg(value_parser = check_file_exists)]
+    pub prior_results_path: Option<PathBuf>,
+
+    // Same options as SearchParams; flattening them would leave the optional
+    // run parameters unset, as clap cannot detect nested flattened arguments
+    #[clap(long = "radius")]
+    #[clap(help = "Search this many bps on either side of each region [default: 500]")]
+    #[clap(value_name = "BPS")]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub radius: Option<i64>,
+
+    #[clap(long = "radius-scale")]
+    #[clap(help = "Search this many region lengths around each region instead of a fixed radius")]
+    #[clap(value_name = "SCALE")]
+    #[arg(value_parser = check_positive)]
+    pub radius_scale: Option<f64>,
+
+    #[clap(long = "min-radius")]
+    #[clap(help = "Smallest search radius with --radius-scale")]
+    #[clap(value_name = "BPS")]
+    #[clap(default_value_t = 300)]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub min_radius: i64,
+
+    #[clap(long = "max-radius")]
+    #[clap(help = "Largest search radius with --radius-scale")]
+    #[clap(value_name = "BPS")]
+    #[clap(default_value_t = 5000)]
+    #[arg(value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_radius: i64,
+
+    #[clap(long = "min-mapq")]
+    #[clap(
+        help = "Smallest mapping quality of profiled reads [default: 50, or that of --assembly or --short-reads]"
+    )]
+    #[clap(value_name = "MAPQ")]
+    pub min_mapq: Option<u8>,
+
+    #[clap(long = "prescreen")]
+    #[clap(help = "Skip extension of regions with less pooled alt signal than this")]
+    #[clap(value_name = "ALT_MASS")]
+    #[arg(value_parser = check_positive)]
+    pub prescreen_alt_mass: Option<f64>,
+
+    #[clap(long = "cache")]
+    #[clap(
+        help = "Reuse per-region results stored in this directory by earlier runs; the reference and BAMs are recognized by path, size, and modification time, not content"
+    )]
+    #[clap(value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    #[clap(long = "run-manifest")]
+    #[clap(help = "Write a JSON manifest with checksums of all inputs and outputs to this file")]
+    #[clap(value_name = "MANIFEST")]
+    pub run_manifest_path: Option<PathBuf>,
+
+    #[clap(long = "keep-duplicates")]
+    #[clap(help = "Keep regions identical to other regions")]
+    pub keep_duplicates: bool,
+
+    #[clap(long = "collapse-contained")]
+    #[clap(help = "Also collapse regions lying entirely within another region into it")]
+    #[clap(conflicts_with = "keep_duplicates")]
+    pub collapse_contained: bool,
+
+    #[clap(long = "dedup-report")]
+    #[clap(help = "Write the regions collapsed into other regions to this file")]
+    #[clap(value_name = "REPORT")]
+    pub dedup_report_path: Option<PathBuf>,
+
+    #[clap(long = "output")]
+    #[clap(
+        help = "Write the results to this file instead of stdout, or stream them as JSONL to tcp://HOST:PORT or unix://PATH"
+    )]
+    #[clap(value_name = "RESULTS")]
+    pub output_path: Option<PathBuf>,
+
+    #[clap(long = "force")]
+    #[clap(help = "Overwrite existing output files")]
+    pub force: bool,
+
+    #[clap(long = "output-format")]
+    #[clap(help = "Format of the results")]
+    #[clap(value_name = "FORMAT")]
+    #[clap(default_value = "tsv")]
+    pub output_format: OutputFormat,
+
+    #[clap(long = "columns")]
+    #[clap(
+        help = "Comma-separated columns of TSV or BED output, including chrom, start and end of the extended region"
+    )]
+    #[clap(value_name = "COLUMNS")]
+    #[clap(value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+
+    #[clap(long = "sort-output")]
+    #[clap(help = "Write the results in coordinate order instead of the order of the regions")]
+    pub sort_output: bool,
+
+    #[clap(long = "preflight")]
+    #[clap(help = "Check regions against the reference and write suspicious ones to this file")]
+    #[clap(value_name = "REPORT")]
+    pub preflight_path: Option<PathBuf>,
+
+    #[clap(long = "assembly")]
+    #[clap(help = "Treat BAMs as haplotype assemblies aligned to the reference instead of reads")]
+    pub assembly: bool,
+
+    #[clap(long = "short-reads")]
+    #[clap(help = "Adjust the read filters for paired-end short reads")]
+    #[clap(conflicts_with = "assembly")]
+    pub short_reads: bool,
+
+    #[clap(long = "allow-subreads")]
+    #[clap(help = "Accept PacBio subread BAMs and adjust the read filters for them")]
+    #[clap(conflicts_with_all = ["assembly", "short_reads"])]
+    pub allow_subreads: bool,
+
+    #[clap(long = "low-memory")]
+    #[clap(
+        help = "Reduce memory use at some cost in speed by releasing buffers after each locus and capping queues"
+    )]
+    pub low_memory: bool,
+
+    #[clap(long = "max-pooled-reads")]
+    #[clap(help = "Subsample reads proportionally across samples when their total exceeds this")]
+    #[clap(value_name = "READS")]
+    pub max_pooled_reads: Option<usize>,
+
+    #[clap(long = "filter-read-end-alts")]
+    #[clap(help = "Report clusters whose alt events all lie near read ends as unextended")]
+    pub filter_read_end_alts: bool,
+
+    #[clap(long = "span-reference")]
+    #[clap(help = "Report the reference of each extended span in JSONL output")]
+    #[clap(value_name = "MODE")]
+    pub span_reference: Option<SpanReferenceMode>,
+
+    #[clap(long = "left-align-indels")]
+    #[clap(help = "Left-align indels against the reference before profiling")]
+    pub left_align_indels: bool,
+
+    #[clap(long = "ont-read-class")]
+    #[clap(help = "Oxford Nanopore reads to profile, by their dx tag")]
+    #[clap(value_name = "CLASS")]
+    #[clap(default_value = "all")]
+    pub ont_read_class: OntReadClass,
+
+    #[clap(long = "exclude-chimeras")]
+    #[clap(help = "Exclude reads with supplementary alignments at distant loci as chimeras")]
+    pub exclude_chimeras: bool,
+
+    #[clap(long = "clip-state")]
+    #[clap(
+        help = "Discretize positions where this fraction of reads is soft clipped to a separate state"
+    )]
+    #[clap(value_name = "FRACTION")]
+    #[arg(value_parser = check_fraction)]
+    pub clip_state: Option<f64>,
+
+    #[clap(long = "model-pseudocount")]
+    #[clap(help = "Laplace smoothing added to each model emission probability")]
+    #[clap(value_name = "PSEUDOCOUNT")]
+    #[arg(value_parser = check_positive)]
+    pub model_pseudocount: Option<f64>,
+
+    #[clap(long = "model-type")]
+    #[clap(help = "Model assessing whether windows are reference-like")]
+    #[clap(value_name = "TYPE")]
+    #[clap(default_value = "categorical")]
+    pub model_type: ModelType,
+
+    #[clap(long = "model")]
+    #[clap(help = "ONNX classifier of window states, for --model-type onnx")]
+    #[clap(value_name = "MODEL")]
+    #[arg(value_parser = check_file_exists)]
+    pub model_path: Option<PathBuf>,
+
+    #[clap(long = "model-rules")]
+    #[clap(
+        help = "Select adjusted models per locus by its depth, GC, and homopolymer content, from this file"
+    )]
+    #[clap(value_name = "RULES")]
+    #[arg(value_parser = check_file_exists)]
+    pub model_rules_path: Option<PathBuf>,
+
+    #[clap(long = "flag-model-uncertain")]
+    #[clap(
+        help = "Add a column listing where neither model explains the windows of a locus, making its boundaries uncertain"
+    )]
+    pub flag_model_uncertain: bool,
+
+    #[clap(long = "uniqueness")]
+    #[clap(
+        help = "Add a column scoring the reference uniqueness of each search window and dampen the alt signal of non-unique windows"
+    )]
+    pub uniqueness: bool,
+
+    #[clap(long = "kmer-index")]
+    #[clap(help = "Score uniqueness with this genome-wide index built by kmer-index")]
+    #[clap(value_name = "INDEX")]
+    #[arg(value_parser = check_file_exists)]
+    pub kmer_index_path: Option<PathBuf>,
+
+    #[clap(long = "stability-check")]
+    #[clap(
+        help = "Extend each supported locus again on N random halves of its reads and add columns with the spread of its boundaries"
+    )]
+    #[clap(value_name = "N")]
+    #[arg(value_parser = clap::value_parser!(u16).range(2..))]
+    pub stability_check: Option<u16>,
+
+    #[clap(long = "off-target-sample")]
+    #[clap(
+        help = "Estimate the off-target depth of targeted panels from N positions sampled outside the regions and amplicons, and add columns comparing each region against it"
+    )]
+    #[clap(value_name = "N")]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    pub off_target_sample: Option<u32>,
+
+    #[clap(long = "base-coverage")]
+    #[clap(
+        help = "Add columns with the physical depth of each extended region, counting reads through deletions, and the base-level depth of the bases actually sequenced"
+    )]
+    pub base_coverage: bool,
+
+    #[clap(long = "export-training")]
+    #[clap(
+        help = "Write the discretized windows of each search region with labels from --truth-regions to this directory as NumPy arrays"
+    )]
+    #[clap(value_name = "DIR")]
+    #[clap(requires = "truth_regions_path")]
+    pub export_training_path: Option<PathBuf>,
+
+    #[clap(long = "truth-regions")]
+    #[clap(help = "BED file with the true cluster regions labelling the exported windows")]
+    #[clap(value_name = "BED")]
+    #[clap(requires = "export_training_path")]
+    #[arg(value_parser = check_file_exists)]
+    pub truth_regions_path: Option<PathBuf>,
+
+    #[clap(long = "early-stop")]
+    #[clap(
+        help = "Stop profiling the reads of a sample at a locus once its alt fraction is known at this confidence and its support is settled"
+    )]
+    #[clap(value_name = "CONFIDENCE")]
+    #[arg(value_parser = check_confidence)]
+    pub early_stop_confidence: Option<f64>,
+
+    #[clap(long = "audit-numerics")]
+    #[clap(help = "Fail loci whose window log-likelihoods or posteriors are not finite")]
+    pub audit_numerics: bool,
+
+    #[clap(long = "annotations")]
+    #[clap(
+        help = "BED file of known repeats or segmental duplications to report for each cluster"
+    )]
+    #[clap(value_name = "ANNOTATIONS")]
+    #[arg(value_parser = check_file_exists)]
+    pub annotations_path: Option<PathBuf>,
+
+    #[clap(long = "population-catalog")]
+    #[clap(
+        help = "Population catalog written by vclust population-catalog, to report the cohort frequency and typical extension of each region"
+    )]
+    #[clap(value_name = "CATALOG")]
+    #[arg(value_parser = check_file_exists)]
+    pub population_catalog_path: Option<PathBuf>,
 }
 
-type InputType = Option<Locus>;
-type OutputType = Option<String>;
+/// Loci and their results are tagged with the input index so that results are
+/// written in input order whatever the number of threads
+type InputType = Option<(usize, Locus)>;
+type OutputType = Option<(usize, Result<LocusResult>)>;
 
 // Return some kind of Result/Status or something.
 fn task_thread(
-    reads_paths: Vec<PathBuf>,
+    builder: ContextBuilder,
+    cache: Option<Arc<ResultCache>>,
     task_receiver: Receiver<InputType>,
     result_sender: Sender<OutputType>,
-) -> Result<(), String> {
-    let mut bams = Vec::new();
-    for path in reads_paths {
-        let bam = IndexedReader::from_path(&path).map_err(|e| e.to_string())?;
-        bams.push(bam);
-    }
+    interrupted: Arc<AtomicBool>,
+) -> Result<WorkerTimes> {
+    let mut ctx = builder.build()?;
+    let mut times = WorkerTimes::default();
     loop {
-        match task_receiver.recv() {
+        // Stop picking up new loci once a shutdown was requested
+        if interrupted.load(Ordering::Relaxed) {
+            break;
+        }
+        let wait_start = Instant::now();
+        let task = task_receiver.recv();
+        times.wait_secs += wait_start.elapsed().as_secs_f64();
+        match task {
             Ok(None) | Err(_) => break,
-            Ok(Some(locus)) => match run_workflow(&mut bams, &locus) {
-                Err(message) => {
-                    log::warn!("{message}");
-                }
-                Ok(result) => {
-                    result_sender.send(Some(result)).unwrap();
+            Ok(Some((index, locus))) => {
+                let locus_start = Instant::now();
+                let read_secs = ctx.scratch.read_secs;
+                let result = match &cache {
+                    Some(cache) => cache.get_or_run(&mut ctx, &locus),
+                    None => run_workflow(&mut ctx, &locus),
+                };
+                if ctx.params.low_memory {
+                    ctx.scratch.release();
                 }
-            },
+                let locus_read_secs = ctx.scratch.read_secs - read_secs;
+                times.read_secs += locus_read_secs;
+                times.compute_secs += locus_start.elapsed().as_secs_f64() - locus_read_secs;
+                let send_start = Instant::now();
+                result_sender.send(Some((index, result))).unwrap();
+                times.send_secs += send_start.elapsed().as_secs_f64();
+            }
         }
     }
 
     result_sender.send(None).unwrap();
 
-    Ok(())
+    Ok(times)
 }
 
-fn read_bam_paths(file_path: PathBuf) -> std::io::Result<Vec<PathBuf>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+/// Channel holding at most `capacity` messages, if given
+fn channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    match capacity {
+        Some(capacity) => bounded(capacity),
+        None => unbounded(),
+    }
+}
 
-    let mut paths = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        if !line.trim().is_empty() {
-            paths.push(PathBuf::from(line));
+/// Let every long option of the command be set through an environment
+/// variable named after it
+fn with_env_vars(cmd: clap::Command) -> clap::Command {
+    cmd.mut_args(|arg| {
+        let settable =
+            arg.get_action().takes_values() || matches!(arg.get_action(), ArgAction::SetTrue);
+        match arg.get_long() {
+            Some(long) if settable => {
+                let name = format!("{ENV_PREFIX}{}", long.to_uppercase().replace('-', "_"));
+                arg.env(Str::from(name))
+            }
+            _ => arg,
         }
+    })
+}
+
+/// Command line interface reading the environment for the options of the
+/// invoked subcommand, or of a run if there is none; run options conflict with
+/// subcommands, so they must not be filled in when a subcommand is used
+fn cli_command() -> clap::Command {
+    let cmd = CliParams::command();
+    let subcommand = std::env::args_os()
+        .nth(1)
+        .and_then(|arg| arg.into_string().ok())
+        .filter(|arg| cmd.find_subcommand(arg).is_some());
+    match subcommand {
+        Some(name) => cmd.mut_subcommand(name, with_env_vars),
+        None => with_env_vars(cmd),
     }
+}
 
-    Ok(paths)
+/// Command line interface with the environment variables of every option, as
+/// listed in the manual page
+fn documented_command() -> clap::Command {
+    let mut cmd = with_env_vars(CliParams::command());
+    let subcommands = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in subcommands {
+        cmd = cmd.mut_subcommand(name, with_env_vars);
+    }
+    cmd.version(env!("CARGO_PKG_VERSION"))
 }
 
-fn main() -> Result<(), String> {
-    let args = CliParams::parse();
+fn main() -> Result<()> {
+    env_logger::init();
+    let matches = cli_command().get_matches();
+    let cli = CliParams::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match cli.command {
+        Some(Command::Migrate { input_path }) => {
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            schema::migrate(&input_path, &mut writer)?;
+            writer.flush()?;
+            Ok(())
+        }
+        Some(Command::Liftover {
+            input_path,
+            chain_path,
+            unmapped_path,
+            force,
+        }) => {
+            check_overwrite(&[&unmapped_path], force)?;
+            let chains = ChainMap::load(&chain_path)?;
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            let mut unmapped = AtomicFile::create(&unmapped_path)?;
+            let counts = liftover_results(&input_path, &chains, &mut writer, &mut unmapped)?;
+            writer.flush()?;
+            unmapped.commit()?;
+            log::info!(
+                "Lifted {} records; {} unmapped records written to {}",
+                counts.lifted,
+                counts.unmapped,
+                unmapped_path.display()
+            );
+            Ok(())
+        }
+        Some(Command::Sweep(args)) => sweep(args),
+        Some(Command::Explain(args)) => explain(args),
+        Some(Command::Tracks(args)) => tracks(args),
+        Some(Command::CompareReferences {
+            first_path,
+            second_path,
+            chain_path,
+            labels,
+        }) => {
+            if labels.len() != 2 {
+                return Err(VclustError::Other(
+                    "--labels takes exactly two names".to_string(),
+                ));
+            }
+            let chains = ChainMap::load(&chain_path)?;
+            let first = load_clusters(&first_path)?;
+            let second = load_clusters(&second_path)?;
+            let compared = compare_clusters(&first, &second, &chains);
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            write_comparison(&compared, [&labels[0], &labels[1]], &mut writer)?;
+            writer.flush()?;
+            for (reference, label) in labels.iter().enumerate() {
+                let count = |status| {
+                    compared
+                        .iter()
+                        .filter(|rec| rec.reference == reference && rec.status == status)
+                        .count()
+                };
+                log::info!(
+                    "{label}: {} shared, {} specific, {} unlifted clusters",
+                    count(ClusterStatus::Shared),
+                    count(ClusterStatus::Specific),
+                    count(ClusterStatus::Unlifted)
+                );
+            }
+            Ok(())
+        }
+        Some(Command::GeneRollup {
+            input_path,
+            genes_path,
+            panel_path,
+        }) => gene_rollup(&input_path, &genes_path, panel_path.as_deref()),
+        Some(Command::ValidateOutput {
+            input_path,
+            fai_path,
+            allow_unsorted,
+        }) => {
+            let contigs = match &fai_path {
+                Some(path) => Some(load_fai(path)?),
+                None => None,
+            };
+            let issues = validate_results(&input_path, contigs.as_deref(), !allow_unsorted)?;
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            for issue in &issues {
+                writeln!(writer, "{issue}")?;
+            }
+            writer.flush()?;
+            match issues.len() {
+                0 => {
+                    log::info!("{} is valid", input_path.display());
+                    Ok(())
+                }
+                n_issues => Err(VclustError::Parse(format!(
+                    "Found {n_issues} problems in {}",
+                    input_path.display()
+                ))),
+            }
+        }
+        Some(Command::KmerIndex {
+            genome_path,
+            output_path,
+            force,
+        }) => {
+            check_overwrite(&[&output_path], force)?;
+            let reference = faidx::Reader::from_path(&genome_path)?;
+            let index = KmerIndex::build(&reference)?;
+            let mut file = AtomicFile::create(&output_path)?;
+            index.save(&mut file)?;
+            file.commit()?;
+            log::info!("Wrote {index:?} to {}", output_path.display());
+            Ok(())
+        }
+        Some(Command::Demo { out_dir }) => demo(out_dir),
+        Some(Command::Info { json }) => {
+            let info = BuildInfo::current();
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&info)?),
+                false => println!("{}", info.to_text()),
+            }
+            Ok(())
+        }
+        Some(Command::Completions { shell }) => {
+            let script = completion_script(&CliParams::command(), "vclust", shell);
+            print!("{script}");
+            Ok(())
+        }
+        Some(Command::Man) => {
+            print!("{}", man_page(&documented_command(), "vclust"));
+            Ok(())
+        }
+        Some(Command::PopulationCatalog { input_paths }) => {
+            let catalog = PopulationCatalog::from_results(&input_paths)?;
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            catalog.write(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        }
+        Some(Command::AggregateSummaries { input_paths }) => {
+            let aggregate = aggregate_summaries(&input_paths)?;
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            serde_json::to_writer_pretty(&mut writer, &aggregate)?;
+            writeln!(writer)?;
+            writer.flush()?;
+            if !aggregate.is_consistent() {
+                log::warn!("Summaries are inconsistent; see the interrupted, incomplete, and duplicated entries");
+            }
+            Ok(())
+        }
+        None => run(cli
+            .run
+            .ok_or(VclustError::Other("Missing run parameters".to_string()))?),
+    }
+}
+
+fn tracks(args: TracksParams) -> Result<()> {
+    let hub_paths = hub_paths(&args.out_dir);
+    check_overwrite(&hub_paths.each_ref().map(PathBuf::as_path), args.force)?;
+    let clusters = load_clusters(&args.input_path)?;
+    let reference = faidx::Reader::from_path(&args.genome_path)?;
+    let mut chrom_sizes = Vec::new();
+    for index in 0..reference.n_seqs() {
+        let name = reference.seq_name(index as i32)?;
+        let len = reference.fetch_seq_len(&name);
+        chrom_sizes.push((name, len));
+    }
+    write_track_hub(
+        &clusters,
+        &args.out_dir,
+        &args.genome_name,
+        &chrom_sizes,
+        &args.email,
+    )?;
+    log::info!(
+        "Wrote {} clusters to {}",
+        clusters.len(),
+        args.out_dir.display()
+    );
+    Ok(())
+}
+
+fn gene_rollup(input_path: &Path, genes_path: &Path, panel_path: Option<&Path>) -> Result<()> {
+    let clusters = load_clusters(input_path)?;
+    let mut genes = load_genes(genes_path)?;
+    if let Some(panel_path) = panel_path {
+        let panel = load_panel(panel_path)?;
+        genes.retain(|gene| panel.contains(&gene.name));
+        let found = genes.iter().map(|gene| &gene.name).collect::<BTreeSet<_>>();
+        for name in panel.iter().filter(|name| !found.contains(name)) {
+            log::warn!("Panel gene {name} is missing from {}", genes_path.display());
+        }
+    }
+    let rollups = rollup_genes(&genes, &clusters);
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    write_rollup(&genes, &rollups, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn demo(out_dir: Option<PathBuf>) -> Result<()> {
+    let dir = out_dir.unwrap_or_else(|| {
+        std::env::temp_dir().join(format!("vclust-demo-{}", std::process::id()))
+    });
+    let inputs = write_demo(&dir)?;
+    let results_path = dir.join("results.txt");
+    let argv = [
+        "vclust".as_ref(),
+        "--genome".as_ref(),
+        inputs.genome_path.as_os_str(),
+        "--reads".as_ref(),
+        inputs.reads_path.as_os_str(),
+        "--regions".as_ref(),
+        inputs.regions_path.as_os_str(),
+        "--output".as_ref(),
+        results_path.as_os_str(),
+        "--force".as_ref(),
+    ];
+    let cli = CliParams::try_parse_from::<_, &std::ffi::OsStr>(argv)
+        .map_err(|e| VclustError::Other(e.to_string()))?;
+    run(cli
+        .run
+        .ok_or(VclustError::Other("Missing run parameters".to_string()))?)?;
+
+    let results = std::fs::read_to_string(&results_path)?;
+    print!("{}", annotate_results(&results));
+    println!("Demo inputs and results are in {}", dir.display());
+    Ok(())
+}
+
+fn sweep(args: SweepParams) -> Result<()> {
+    let mut params = Params::default();
+    args.search.apply(&mut params)?;
+    let builder = ContextBuilder {
+        params: Arc::new(params),
+        ..ContextBuilder::new(args.genome_path, load_manifest(&args.reads_paths)?)
+    };
+    let loci = load_loci(args.repeats_path, args.feature_type.as_deref())?;
+    let grid = make_grid(&args.min_prob_refs, &args.prior_refs, &args.window_ladders);
+
+    let mut ctx = builder.build()?;
+    let rows = run_sweep(&mut ctx, &loci, &grid)?;
+
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    writeln!(writer, "{}", sweep_header())?;
+    for row in rows {
+        writeln!(writer, "{}", row.to_row())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn explain(args: ExplainParams) -> Result<()> {
+    let classifier = match &args.model_path {
+        Some(path) => Some(Arc::new(OnnxClassifier::load(path)?)),
+        None => None,
+    };
+    let setting = ExplainSetting {
+        min_prob_ref: args.min_prob_ref,
+        prior_ref: args.prior_ref,
+        window_ladder: args.window_ladder,
+        classifier,
+    };
+    let compared = args.compare_min_prob_ref.is_some()
+        || args.compare_prior_ref.is_some()
+        || args.compare_window_ladder.is_some()
+        || args.compare_model.is_some();
+    let other = ExplainSetting {
+        min_prob_ref: args.compare_min_prob_ref.unwrap_or(setting.min_prob_ref),
+        prior_ref: args.compare_prior_ref.unwrap_or(setting.prior_ref),
+        window_ladder: args
+            .compare_window_ladder
+            .unwrap_or_else(|| setting.window_ladder.clone()),
+        classifier: match args.compare_model.as_deref() {
+            Some("categorical") => None,
+            Some(path) => Some(Arc::new(OnnxClassifier::load(Path::new(path))?)),
+            None => setting.classifier.clone(),
+        },
+    };
+
+    let builder = ContextBuilder::new(args.genome_path, load_manifest(&args.reads_paths)?);
+    let mut ctx = builder.build()?;
+    if compared {
+        print!(
+            "{}",
+            compare_settings(&mut ctx, &args.locus, &setting, &other)
+        );
+    } else {
+        print!("{}", explain_with(&mut ctx, &args.locus, &setting));
+    }
+    Ok(())
+}
+
+impl RunParams {
+    fn search(&self) -> SearchParams {
+        SearchParams {
+            radius: self.radius,
+            radius_scale: self.radius_scale,
+            min_radius: self.min_radius,
+            max_radius: self.max_radius,
+            min_mapq: self.min_mapq,
+        }
+    }
+}
+
+fn run(args: RunParams) -> Result<()> {
+    let start_time = Instant::now();
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        handler_flag.store(true, Ordering::Relaxed);
+    })
+    .map_err(|e| VclustError::Other(e.to_string()))?;
+
+    let output_paths = [
+        &args.output_path,
+        &args.summary_path,
+        &args.concordance_path,
+        &args.support_evidence_path,
+        &args.skipped_reads_path,
+        &args.segregation_path,
+        &args.group_report_path,
+        &args.preflight_path,
+        &args.run_manifest_path,
+        &args.dedup_report_path,
+    ];
+    let training_paths = args.export_training_path.as_deref().map(training_paths);
+    let output_paths = output_paths
+        .iter()
+        .filter_map(|path| path.as_deref())
+        .chain(training_paths.iter().flatten().map(PathBuf::as_path))
+        .collect::<Vec<_>>();
+    check_overwrite(&output_paths, args.force)?;
+
+    let mut reads = load_manifest(&args.reads_paths)?;
+    for entry in reads.iter_mut() {
+        entry.karyotype = entry.karyotype.or(args.karyotype);
+    }
+    // Aligner of each sample, recorded with the results
+    let mut aligners = Vec::new();
+    for entry in &reads {
+        if let Some(check) = check_alignments(&entry.path)? {
+            for warning in check.warnings(&entry.sample) {
+                log::warn!("{warning}");
+            }
+            if let Some(aligner) = check.aligner {
+                let sample = match args.anonymize {
+                    true => pseudonym(&entry.sample),
+                    false => entry.sample.clone(),
+                };
+                aligners.push((sample, aligner));
+            }
+        }
+        if !is_subread_bam(&entry.path)? {
+            continue;
+        }
+        if !args.allow_subreads {
+            return Err(VclustError::Other(format!(
+                "{} looks like a PacBio subread BAM; vclust expects HiFi reads, which can be \
+                 generated from subreads with ccs (use --allow-subreads to analyze subreads anyway)",
+                entry.path.display()
+            )));
+        }
+        log::warn!(
+            "Analyzing subreads of {}; results are less accurate than with HiFi reads",
+            entry.sample
+        );
+    }
+    let pedigree = match &args.pedigree_path {
+        Some(path) => {
+            let pedigree = load_pedigree(path)?;
+            let unsequenced = pedigree
+                .samples()
+                .filter(|sample| !reads.iter().any(|entry| entry.sample == *sample))
+                .count();
+            if unsequenced > 0 {
+                log::info!("{unsequenced} pedigree members have no reads and are unassessed");
+            }
+            Some(pedigree)
+        }
+        None => None,
+    };
+    // Catalogs are deduplicated and sampled separately, and each locus keeps
+    // the label of its catalog
+    let tag_catalog = tag_catalogs(&args.catalogs)?;
+    let mut loci = Vec::new();
+    let mut catalog_labels = Vec::new();
+    let mut collapsed = Vec::new();
+    let mut n_input = 0;
+    for catalog in &args.catalogs {
+        let mut catalog_loci = load_loci(catalog.path.clone(), args.feature_type.as_deref())?;
+        if !args.keep_duplicates {
+            let (kept, catalog_collapsed) = dedup_loci(catalog_loci, args.collapse_contained);
+            catalog_loci = kept;
+            collapsed.extend(catalog_collapsed);
+        }
+        n_input += catalog_loci.len();
+        if let Some(fraction) = args.sample_fraction {
+            catalog_loci = sample_loci(catalog_loci, fraction, args.seed);
+        }
+        catalog_labels.extend(std::iter::repeat_n(
+            catalog.label.clone(),
+            catalog_loci.len(),
+        ));
+        loci.extend(catalog_loci);
+    }
+    if !collapsed.is_empty() {
+        log::info!(
+            "Collapsed {} duplicate or contained regions",
+            collapsed.len()
+        );
+    }
+    if let (false, Some(path)) = (args.keep_duplicates, &args.dedup_report_path) {
+        let mut writer = AtomicFile::create(path)?;
+        writeln!(writer, "{}", dedup_header())?;
+        for entry in &collapsed {
+            writeln!(writer, "{}", entry.to_row())?;
+        }
+        writer.commit()?;
+    }
+    if args.sample_fraction.is_some() {
+        log::info!("Sampled {} of {} regions", loci.len(), n_input);
+    }
+    let mut group_report = match &args.group_report_path {
+        Some(_) => {
+            let report = GroupReport::new(&loci);
+            if report.is_empty() {
+                log::warn!("No region of the catalogs declares a group");
+            }
+            Some(report)
+        }
+        None => None,
+    };
+
+    if let Some(path) = &args.preflight_path {
+        let reference = faidx::Reader::from_path(&args.genome_path)?;
+        let issues = check_loci(&reference, &loci)?;
+        let mut writer = AtomicFile::create(path)?;
+        writeln!(writer, "{}", preflight_header())?;
+        for issue in &issues {
+            writeln!(writer, "{}", issue.to_row())?;
+        }
+        writer.commit()?;
+        if !issues.is_empty() {
+            log::warn!(
+                "Preflight flagged {} issues; see {}",
+                issues.len(),
+                path.display()
+            );
+        }
+    }
+
+    let mut params = Params {
+        require_spanning: args.require_spanning,
+        gap_depth_fraction: args.gap_depth_fraction,
+        replicate_concordance: args.concordance_path.is_some(),
+        support_evidence: args.support_evidence_path.is_some(),
+        sample_support: args.pedigree_path.is_some(),
+        haplotype_balance: args.haplotype_balance,
+        imbalance_fraction: args.imbalance_fraction,
+        long_deletions: args.long_deletions,
+        priority_score: args.priority_score || args.priority_expression.is_some(),
+        priority_expression: args.priority_expression.clone(),
+        high_copy: args.high_copy,
+        high_copy_contigs: args.high_copy_contigs.clone(),
+        seed: args.seed,
+        prescreen_alt_mass: args.prescreen_alt_mass,
+        max_pooled_reads: args.max_pooled_reads,
+        filter_read_end_alts: args.filter_read_end_alts,
+        left_align_indels: args.left_align_indels,
+        span_reference: args.span_reference,
+        ont_read_class: args.ont_read_class,
+        uniqueness: args.uniqueness || args.kmer_index_path.is_some(),
+        flag_model_uncertain: args.flag_model_uncertain,
+        model_rules: match &args.model_rules_path {
+            Some(path) => load_model_rules(path)?,
+            None => Vec::new(),
+        },
+        audit_numerics: args.audit_numerics,
+        stability_check: args.stability_check.map(usize::from),
+        early_stop_confidence: args.early_stop_confidence,
+        export_states: args.export_training_path.is_some(),
+        base_coverage: args.base_coverage,
+        chimera_distance: args.exclude_chimeras.then_some(CHIMERA_DISTANCE),
+        ..Default::default()
+    };
+    if args.assembly {
+        params = params.for_assemblies();
+    }
+    if args.short_reads {
+        params = params.for_short_reads();
+    }
+    if args.allow_subreads {
+        params = params.for_subreads();
+    }
+    if args.low_memory {
+        params = params.for_low_memory();
+    }
+    // Explicit search options override those of the presets
+    args.search().apply(&mut params)?;
+    let classifier = match (args.model_type, &args.model_path) {
+        (ModelType::Onnx, _) if args.model_rules_path.is_some() => return Err(VclustError::Other(
+            "--model-rules selects categorical models and cannot be used with --model-type onnx"
+                .to_string(),
+        )),
+        (ModelType::Onnx, Some(path)) => {
+            let classifier = OnnxClassifier::load(path)?;
+            // The classifier assesses windows of a single length
+            params.window_ladder = vec![classifier.window_len as i64];
+            Some(Arc::new(classifier))
+        }
+        (ModelType::Onnx, None) => {
+            return Err(VclustError::Other(
+                "--model-type onnx requires --model".to_string(),
+            ))
+        }
+        (ModelType::Categorical, Some(_)) => {
+            return Err(VclustError::Other(
+                "--model only applies to --model-type onnx".to_string(),
+            ))
+        }
+        (ModelType::Categorical, None) => None,
+    };
+    let dropped_windows = params.dropped_windows();
+    if !dropped_windows.is_empty() {
+        log::warn!(
+            "Windows of {} bps are longer than the smallest search radius and are skipped for the loci searched with it",
+            dropped_windows
+                .iter()
+                .map(|len| len.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    let params = Arc::new(params);
+    let amplicons = match &args.amplicons_path {
+        Some(path) => Some(Arc::new(Amplicons::load(path)?)),
+        None => None,
+    };
+    let annotations = match &args.annotations_path {
+        Some(path) => Some(Arc::new(Annotations::load(path)?)),
+        None => None,
+    };
+    let kmer_index = match &args.kmer_index_path {
+        Some(path) => Some(Arc::new(KmerIndex::load(path)?)),
+        None => None,
+    };
+    let prior_spans = match &args.prior_results_path {
+        Some(path) => Some(Arc::new(PriorSpans::load(path)?)),
+        None => None,
+    };
+    let population = match &args.population_catalog_path {
+        Some(path) => Some(Arc::new(PopulationCatalog::load(path)?)),
+        None => None,
+    };
+    let mut models = Models::default();
+    if let Some(min_fraction) = args.clip_state {
+        models = models.with_clip_state(min_fraction);
+    }
+    if let Some(pseudocount) = args.model_pseudocount {
+        models = models.with_pseudocount(pseudocount);
+    }
+    models.classifier = classifier;
+    let mut builder = ContextBuilder {
+        params,
+        models: Arc::new(models),
+        amplicons,
+        annotations,
+        kmer_index,
+        prior_spans,
+        population,
+        ..ContextBuilder::new(args.genome_path.clone(), reads)
+    };
+    if let Some(n_positions) = args.off_target_sample {
+        let mut ctx = builder.build()?;
+        let off_target =
+            estimate_off_target_depth(&mut ctx, &loci, n_positions as usize, args.seed)?;
+        log::info!(
+            "Off-target depth {:.2} from {} positions outside the regions",
+            off_target.depth,
+            off_target.positions
+        );
+        builder.params = Arc::new(Params {
+            off_target_depth: Some(off_target.depth),
+            ..(*builder.params).clone()
+        });
+    }
+    if let Some(expression) = &args.priority_expression {
+        let known = PRIORITY_VARIABLES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(
+                builder
+                    .score_columns()
+                    .into_iter()
+                    .filter(|column| column != PRIORITY_COLUMN),
+            )
+            .collect::<Vec<_>>();
+        expression.check_variables(&known)?;
+    }
+    let mut score_columns = builder.score_columns();
+    if tag_catalog {
+        score_columns.push(CATALOG_COLUMN.to_string());
+    }
+    if args.max_cluster_density.is_some() && !args.suppress_dense_regions {
+        score_columns.push(DENSITY_COLUMN.to_string());
+    }
+    let streams_to_socket = args
+        .output_path
+        .as_deref()
+        .is_some_and(|path| SocketAddress::parse(path).is_some());
+    if args.max_cluster_density.is_some() && streams_to_socket {
+        return Err(VclustError::Other(
+            "--max-cluster-density holds the results until the end of the run and cannot stream them to a socket".to_string(),
+        ));
+    }
+    let columns = match &args.columns {
+        Some(_) if !matches!(args.output_format, OutputFormat::Tsv | OutputFormat::Bed) => {
+            return Err(VclustError::Other(
+                "--columns only applies to TSV and BED output".to_string(),
+            ))
+        }
+        Some(names) => Some(ColumnSelection::new(names.clone(), &score_columns)?),
+        None => None,
+    };
+
+    let cache = match &args.cache_dir {
+        Some(dir) => Some(Arc::new(ResultCache::new(dir, &builder)?)),
+        None => None,
+    };
+
+    let mut manifest = match &args.run_manifest_path {
+        Some(_) => {
+            let mut input_paths = vec![("reads", args.reads_paths.as_path())];
+            for catalog in &args.catalogs {
+                input_paths.push(("regions", catalog.path.as_path()));
+            }
+            if let Some(path) = &args.amplicons_path {
+                input_paths.push(("amplicons", path.as_path()));
+            }
+            if let Some(path) = &args.annotations_path {
+                input_paths.push(("annotations", path.as_path()));
+            }
+            if let Some(path) = &args.population_catalog_path {
+                input_paths.push(("population_catalog", path.as_path()));
+            }
+            if let Some(path) = &args.model_rules_path {
+                input_paths.push(("model_rules", path.as_path()));
+            }
+            if let Some(path) = &args.model_path {
+                input_paths.push(("model", path.as_path()));
+            }
+            if let Some(path) = &args.kmer_index_path {
+                input_paths.push(("kmer_index", path.as_path()));
+            }
+            if let Some(path) = &args.prior_results_path {
+                input_paths.push(("prior_results", path.as_path()));
+            }
+            if let Some(path) = &args.pedigree_path {
+                input_paths.push(("pedigree", path.as_path()));
+            }
+            if let Some(path) = &args.truth_regions_path {
+                input_paths.push(("truth_regions", path.as_path()));
+            }
+            Some(RunManifest::new(&builder, &input_paths, started_at)?)
+        }
+        None => None,
+    };
+
+    if args.estimate {
+        let mut ctx = builder.build()?;
+        let estimate = estimate_run(&mut ctx, &loci, args.seed, args.threads)?;
+        println!("{}", estimate.report());
+        return Ok(());
+    }
 
-    let paths = read_bam_paths(args.reads_paths).map_err(|e| e.to_string())?;
     // Create channels for communication between threads
-    let (task_sender, task_receiver): (Sender<InputType>, Receiver<InputType>) = unbounded();
-    let (result_sender, result_receiver): (Sender<OutputType>, Receiver<OutputType>) = unbounded();
+    let queue_len = args
+        .low_memory
+        .then_some(LOW_MEMORY_QUEUE_LEN * args.threads);
+    let (task_sender, task_receiver): (Sender<InputType>, Receiver<InputType>) = channel(queue_len);
+    let (result_sender, result_receiver): (Sender<OutputType>, Receiver<OutputType>) =
+        channel(queue_len);
 
-    let task_handles: Vec<JoinHandle<Result<(), String>>> = (0..args.threads)
+    let task_handles: Vec<JoinHandle<Result<WorkerTimes>>> = (0..args.threads)
         .map(|_| {
-            let m_reads = paths.clone();
+            let m_builder = builder.clone();
+            let m_cache = cache.clone();
             let m_receiver = task_receiver.clone();
             let m_result_sender = result_sender.clone();
+            let m_interrupted = interrupted.clone();
 
-            thread::spawn(move || task_thread(m_reads, m_receiver, m_result_sender))
+            thread::spawn(move || {
+                task_thread(
+                    m_builder,
+                    m_cache,
+                    m_receiver,
+                    m_result_sender,
+                    m_interrupted,
+                )
+            })
         })
         .collect();
+    // Workers hold the only receivers, so the feeder stops once they all exit
+    drop(task_receiver);
+
+    let loci_done = Arc::new(AtomicUsize::new(0));
+    let watchdog = (args.rss_interval.is_some() || args.rss_limit.is_some()).then(|| {
+        let interval = args.rss_interval.unwrap_or(DEFAULT_RSS_INTERVAL_SECS);
+        MemoryWatchdog::start(
+            Duration::from_secs_f64(interval),
+            args.rss_limit,
+            loci_done.clone(),
+            interrupted.clone(),
+        )
+    });
 
     // Push each of the loci to the channel
-    let loci = load_loci(args.repeats_path)?;
-    for locus in loci {
-        task_sender.send(Some(locus)).unwrap();
-    }
+    let mut summary = RunSummary {
+        loci_total: loci.len(),
+        loci: loci
+            .iter()
+            .map(|locus| format!("{}:{}-{}", locus.chrom, locus.start, locus.end))
+            .collect(),
+        aligners: aligners.iter().cloned().collect(),
+        ..Default::default()
+    };
+    // Loci are fed from their own thread as a bounded queue fills up before
+    // the results are collected
+    let n_threads = args.threads;
+    // With bounded queues, the feeder also stays at most a queue length ahead
+    // of the results written, so that results held back behind a slow locus
+    // do not pile up either
+    let (written_sender, written_receiver): (Sender<()>, Receiver<()>) = unbounded();
+    let feeder = thread::spawn(move || {
+        for (index, locus) in loci.into_iter().enumerate() {
+            let ahead = queue_len.is_some_and(|queue_len| index >= queue_len);
+            if ahead && written_receiver.recv().is_err() {
+                return;
+            }
+            if task_sender.send(Some((index, locus))).is_err() {
+                return;
+            }
+        }
 
-    // Signal worker threads to exit
-    for _ in 0..args.threads {
-        task_sender.send(None).unwrap();
-    }
+        // Signal worker threads to exit
+        for _ in 0..n_threads {
+            let _ = task_sender.send(None);
+        }
+    });
 
     // Collect results
+    let target = OutputTarget::open(args.output_path.as_deref())?;
+    // Streamed records are passed on without waiting for a full buffer
+    let (output_format, capacity) = match target.is_socket() {
+        true => (OutputFormat::Jsonl, 0),
+        false => (args.output_format, OUTPUT_BUFFER_SIZE),
+    };
+    let mut writer = BufWriter::with_capacity(capacity, HashingWriter::new(target));
+    let mut sink = make_sink(
+        output_format,
+        &mut writer,
+        &args.genome_path,
+        score_columns,
+        aligners,
+        columns,
+    )?;
+    sink.write_header()?;
+    let mut concordance_writer = match &args.concordance_path {
+        Some(path) => {
+            let mut writer = AtomicFile::create(path)?;
+            writeln!(writer, "{}", concordance_header())?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut evidence_writer = match &args.support_evidence_path {
+        Some(path) => {
+            let mut writer = AtomicFile::create(path)?;
+            writeln!(writer, "{}", evidence_header())?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut skipped_writer = match &args.skipped_reads_path {
+        Some(path) => {
+            let mut writer = AtomicFile::create(path)?;
+            writeln!(writer, "{}", skipped_header())?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut training_writer = match (&args.export_training_path, &args.truth_regions_path) {
+        (Some(dir), Some(truth_path)) => Some(TrainingWriter::create(
+            dir,
+            TruthRegions::load(truth_path)?,
+        )?),
+        _ => None,
+    };
+    let mut segregation_writer = match &args.segregation_path {
+        Some(path) => {
+            let mut writer = AtomicFile::create(path)?;
+            writeln!(writer, "{}", segregation_header())?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut sorter = match args.sort_output {
+        true => {
+            let reference = faidx::Reader::from_path(&args.genome_path)?;
+            let contigs = (0..reference.n_seqs())
+                .map(|index| reference.seq_name(index as i32))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Some(ResultSorter::new(
+                &contigs,
+                SORT_CHUNK_RECORDS,
+                &std::env::temp_dir(),
+            ))
+        }
+        false => None,
+    };
+    // Results wait for the densities on disk rather than in memory, right
+    // away under --low-memory
+    let density_chunk_records = match args.low_memory {
+        true => 1,
+        false => SORT_CHUNK_RECORDS,
+    };
+    let mut density_guard = args.max_cluster_density.map(|max_clusters| {
+        DensityGuard::new(
+            max_clusters as usize,
+            args.suppress_dense_regions,
+            density_chunk_records,
+            &std::env::temp_dir(),
+        )
+    });
+    let mut write_result = |index: usize, result: Result<LocusResult>| -> Result<()> {
+        match result {
+            Ok(mut result) => {
+                if tag_catalog {
+                    result.scores.push(catalog_labels[index].clone());
+                }
+                if let Some(group_report) = &mut group_report {
+                    group_report.add(index, &result);
+                }
+                if let (Some(segregation_writer), Some(pedigree), Some(extension)) =
+                    (&mut segregation_writer, &pedigree, &result.extension)
+                {
+                    for mut segregation in pedigree.segregation(extension) {
+                        if args.anonymize {
+                            anonymize_segregation(&mut segregation);
+                        }
+                        writeln!(segregation_writer, "{}", segregation.to_row(&result.locus))?;
+                    }
+                }
+                if args.anonymize {
+                    anonymize_result(&mut result);
+                }
+                if let (Some(training_writer), Some(profile_states)) = (
+                    &mut training_writer,
+                    result
+                        .extension
+                        .as_mut()
+                        .and_then(|ext| ext.profile_states.take()),
+                ) {
+                    training_writer.add(&result.locus, &profile_states)?;
+                }
+                if let Some(concordance_writer) = &mut concordance_writer {
+                    for concordance in &result.concordance {
+                        writeln!(concordance_writer, "{}", concordance.to_row(&result.locus))?;
+                    }
+                }
+                if let (Some(evidence_writer), Some(extension)) =
+                    (&mut evidence_writer, &result.extension)
+                {
+                    for evidence in &extension.support_evidence {
+                        writeln!(evidence_writer, "{}", evidence.to_row(&result.locus))?;
+                    }
+                }
+                if let Some(extension) = &result.extension {
+                    for (sample, (alt_reads, reads)) in &extension.hom_ref_alt_reads {
+                        summary
+                            .contamination
+                            .entry(sample.clone())
+                            .or_default()
+                            .add(*alt_reads, *reads);
+                    }
+                    for (sample, counts) in &extension.skipped_reads {
+                        if let Some(skipped_writer) = &mut skipped_writer {
+                            writeln!(skipped_writer, "{}", counts.to_row(&result.locus, sample))?;
+                        }
+                        summary
+                            .skipped_reads
+                            .entry(sample.clone())
+                            .or_default()
+                            .merge(counts);
+                    }
+                }
+                // Dense megabases are only known once every result is in
+                match (&mut density_guard, &mut sorter) {
+                    (Some(density_guard), _) => density_guard.push(result)?,
+                    (None, Some(sorter)) => sorter.push(result)?,
+                    (None, None) => sink.write_record(&result)?,
+                }
+                summary.loci_processed += 1;
+            }
+            Err(e) => {
+                log::warn!("{e}");
+                summary.loci_failed += 1;
+                *summary.failures.entry(e.kind().to_string()).or_default() += 1;
+            }
+        }
+        Ok(())
+    };
+    // Results arriving ahead of an earlier locus wait for it
+    let mut pending = BTreeMap::new();
+    let mut next_index = 0;
     let mut n_done = 0;
     while n_done < args.threads {
         match result_receiver.recv() {
             Ok(None) | Err(_) => {
                 n_done += 1;
             }
-            Ok(Some(result)) => {
-                println!("{result}");
+            Ok(Some((index, result))) => {
+                loci_done.fetch_add(1, Ordering::Relaxed);
+                pending.insert(index, result);
+                while let Some(result) = pending.remove(&next_index) {
+                    write_result(next_index, result)?;
+                    next_index += 1;
+                    if queue_len.is_some() {
+                        let _ = written_sender.send(());
+                    }
+                }
             }
         }
     }
+    // A feeder waiting for results after an interruption stops
+    drop(written_sender);
+    // Loci skipped after an interruption leave gaps before the remaining results
+    for (index, result) in pending {
+        write_result(index, result)?;
+    }
+    if let Some(density_guard) = density_guard {
+        let dense_regions = density_guard.finish(|result| match &mut sorter {
+            Some(sorter) => sorter.push(result),
+            None => sink.write_record(&result),
+        })?;
+        for dense in &dense_regions {
+            log::warn!(
+                "{} holds {} supported clusters; review it for misassembly",
+                dense.region,
+                dense.clusters
+            );
+        }
+        summary.dense_regions = dense_regions;
+    }
+    if let Some(sorter) = sorter {
+        sorter.finish(|result| sink.write_record(&result))?;
+    }
+    sink.finalize()?;
+    drop(sink);
+    let writer = writer.into_inner().map_err(|e| e.into_error())?;
+    let results_path = args.output_path.as_deref().unwrap_or(Path::new("-"));
+    let results_record = writer.record("results", results_path);
+    writer.into_inner().commit()?;
+    if let Some(concordance_writer) = concordance_writer {
+        concordance_writer.commit()?;
+    }
+    if let Some(evidence_writer) = evidence_writer {
+        evidence_writer.commit()?;
+    }
+    if let Some(skipped_writer) = skipped_writer {
+        skipped_writer.commit()?;
+    }
+    if let Some(segregation_writer) = segregation_writer {
+        segregation_writer.commit()?;
+    }
+    if let (Some(group_report), Some(path)) = (&group_report, &args.group_report_path) {
+        let mut writer = AtomicFile::create(path)?;
+        group_report.write(&mut writer)?;
+        writer.commit()?;
+    }
+    if let Some(training_writer) = training_writer {
+        training_writer.finish()?;
+    }
 
     // Close up
+    feeder.join().unwrap();
+    let mut worker_times = WorkerTimes::default();
     for handle in task_handles {
-        let _ = handle.join().unwrap();
+        if let Ok(times) = handle.join().unwrap() {
+            worker_times.merge(&times);
+        }
+    }
+    log::info!("{}", worker_times.report());
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+    summary.thread_recommendation = thread_recommendation(&worker_times, args.threads, cores);
+    if let Some(recommendation) = &summary.thread_recommendation {
+        log::info!("{recommendation}");
+    }
+    summary.worker_times = Some(worker_times);
+
+    summary.interrupted = interrupted.load(Ordering::Relaxed);
+    for warning in contamination_warnings(&summary.contamination) {
+        log::warn!("{warning}");
+    }
+    for warning in malformed_record_warnings(&summary.skipped_reads) {
+        log::warn!("{warning}");
+    }
+    if let Some(watchdog) = watchdog {
+        let (samples, limit_exceeded) = watchdog.finish();
+        summary.peak_rss_bytes = samples.iter().map(|sample| sample.rss_bytes).max();
+        if let Some(peak) = summary.peak_rss_bytes {
+            log::info!("Peak sampled RSS: {} MiB", peak >> 20);
+        }
+        summary.rss_samples = samples;
+        summary.rss_limit_exceeded = limit_exceeded;
+    }
+    if let Some(path) = &args.summary_path {
+        summary.write(path)?;
+    }
+
+    if let (Some(manifest), Some(path)) = (&mut manifest, &args.run_manifest_path) {
+        manifest.duration_secs = start_time.elapsed().as_secs_f64();
+        manifest.outputs.push(results_record);
+        let written_paths = [
+            ("concordance", &args.concordance_path),
+            ("support_evidence", &args.support_evidence_path),
+            ("skipped_reads", &args.skipped_reads_path),
+            ("segregation", &args.segregation_path),
+            ("group_report", &args.group_report_path),
+            ("preflight", &args.preflight_path),
+            ("summary", &args.summary_path),
+        ];
+        for (role, path) in written_paths {
+            if let Some(path) = path {
+                manifest.outputs.push(FileRecord::from_path(role, path)?);
+            }
+        }
+        if let (false, Some(path)) = (args.keep_duplicates, &args.dedup_report_path) {
+            manifest
+                .outputs
+                .push(FileRecord::from_path("dedup_report", path)?);
+        }
+        for path in training_paths.iter().flatten() {
+            manifest
+                .outputs
+                .push(FileRecord::from_path("export_training", path)?);
+        }
+        manifest.write(path)?;
+    }
+    if summary.rss_limit_exceeded {
+        log::error!(
+            "Stopped at the RSS limit after {} of {} loci",
+            summary.loci_processed + summary.loci_failed,
+            summary.loci_total
+        );
+        std::process::exit(RSS_LIMIT_EXIT_CODE);
+    }
+    if summary.interrupted {
+        log::warn!(
+            "Interrupted after {} of {} loci",
+            summary.loci_processed + summary.loci_failed,
+            summary.loci_total
+        );
+        std::process::exit(INTERRUPTED_EXIT_CODE);
     }
 
-    // For now, we'll just have the task_handles hold the lines
     Ok(())
 }
 
-fn check_file_exists(path: &str) -> Result<PathBuf, String> {
+fn check_file_exists(path: &str) -> std::result::Result<PathBuf, String> {
     let path = Path::new(path);
     if path.exists() {
         Ok(path.to_path_buf())
@@ -162,3 +2119,69 @@ fn check_file_exists(path: &str) -> Result<PathBuf, String> {
         Err(format!("File does not exist: {}", path.display()))
     }
 }
+
+fn check_catalog(value: &str) -> std::result::Result<Catalog, String> {
+    let catalog = Catalog::parse(value);
+    check_file_exists(&catalog.path.to_string_lossy())?;
+    Ok(catalog)
+}
+
+fn check_fraction(value: &str) -> std::result::Result<f64, String> {
+    let fraction = value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid fraction: {value}"))?;
+    if fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("Fraction must be in (0, 1]: {value}"))
+    }
+}
+
+fn check_prior(value: &str) -> std::result::Result<f64, String> {
+    let prior = value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid prior: {value}"))?;
+    if prior > 0.0 && prior < 1.0 {
+        Ok(prior)
+    } else {
+        Err(format!("Prior must be in (0, 1): {value}"))
+    }
+}
+
+fn check_confidence(value: &str) -> std::result::Result<f64, String> {
+    let confidence = value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid confidence: {value}"))?;
+    if confidence > 0.0 && confidence < 1.0 {
+        Ok(confidence)
+    } else {
+        Err(format!("Confidence must be in (0, 1): {value}"))
+    }
+}
+
+fn check_positive(value: &str) -> std::result::Result<f64, String> {
+    let number = value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number: {value}"))?;
+    if number > 0.0 {
+        Ok(number)
+    } else {
+        Err(format!("Number must be positive: {value}"))
+    }
+}
+
+fn check_size(value: &str) -> std::result::Result<u64, String> {
+    parse_size(value).map_err(|e| e.to_string())
+}
+
+fn check_expression(value: &str) -> std::result::Result<Expression, String> {
+    value.parse::<Expression>().map_err(|e| e.to_string())
+}
+
+fn check_region(value: &str) -> std::result::Result<Locus, String> {
+    parse_locus(value).map_err(|e| e.to_string())
+}
+
+fn check_window_ladder(value: &str) -> std::result::Result<Vec<i64>, String> {
+    parse_window_ladder(value).map_err(|e| e.to_string())
+}
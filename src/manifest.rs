@@ -0,0 +1,93 @@
+use crate::error::{Result, VclustError};
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Karyotype {
+    #[value(name = "XX")]
+    Xx,
+    #[value(name = "XY")]
+    Xy,
+}
+
+/// Pseudoautosomal regions of GRCh38 as (contig, start, end), 0-based and
+/// end-exclusive; XY samples carry them on both sex chromosomes
+pub const PAR_REGIONS: &[(&str, i64, i64)] = &[
+    ("X", 10_000, 2_781_479),
+    ("X", 155_701_382, 156_030_895),
+    ("Y", 10_000, 2_781_479),
+    ("Y", 56_887_902, 57_217_415),
+];
+
+impl Karyotype {
+    /// Number of copies of the given span of a contig; spans overlapping a
+    /// pseudoautosomal region are diploid in XY samples
+    pub fn ploidy(&self, chrom: &str, (start, end): (i64, i64)) -> u32 {
+        let chrom = chrom.strip_prefix("chr").unwrap_or(chrom);
+        let pseudoautosomal = || {
+            PAR_REGIONS.iter().any(|(par, par_start, par_end)| {
+                *par == chrom && start < *par_end && *par_start < end
+            })
+        };
+        match (self, chrom) {
+            (Karyotype::Xx, "X") => 2,
+            (Karyotype::Xx, "Y") => 0,
+            (Karyotype::Xy, "X" | "Y") if pseudoautosomal() => 2,
+            (Karyotype::Xy, "X" | "Y") => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// One line of the reads file: a BAM path and the sample it belongs to
+#[derive(Debug, Clone)]
+pub struct ReadsEntry {
+    pub path: PathBuf,
+    /// Sample name; BAMs sharing a name are treated as technical replicates
+    pub sample: String,
+    pub karyotype: Option<Karyotype>,
+}
+
+/// Load a reads file with one BAM path per line and optional sample name and
+/// karyotype columns
+pub fn load_manifest(path: &Path) -> Result<Vec<ReadsEntry>> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let (path, sample, karyotype) = match fields[..] {
+            [] => continue,
+            [path] => (PathBuf::from(path), None, None),
+            [path, sample] => (PathBuf::from(path), Some(sample.to_string()), None),
+            [path, sample, karyotype] => {
+                let karyotype = Karyotype::from_str(karyotype, true)
+                    .map_err(|_| VclustError::Parse(format!("Bad karyotype in line {line}")))?;
+                (
+                    PathBuf::from(path),
+                    Some(sample.to_string()),
+                    Some(karyotype),
+                )
+            }
+            _ => return Err(VclustError::Parse(format!("Bad reads line {line}"))),
+        };
+        let sample = match sample {
+            Some(sample) => sample,
+            None => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .ok_or(VclustError::Parse(format!("Bad reads line {line}")))?,
+        };
+        entries.push(ReadsEntry {
+            path,
+            sample,
+            karyotype,
+        });
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,105 @@
+use crate::error::{Result, VclustError};
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Resident set size of the process at one point of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RssSample {
+    pub elapsed_secs: u64,
+    pub rss_bytes: u64,
+    /// Loci finished when the sample was taken, to tell leaks, with a growing
+    /// RSS at a steady pace, from large loci
+    pub loci_done: usize,
+}
+
+/// Parse a size such as `512M` or `16G`, with binary units; plain numbers are
+/// bytes
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let bad_size = || VclustError::Parse(format!("Invalid size: {value}"));
+    let (number, multiplier) = match value.char_indices().last().ok_or_else(bad_size)? {
+        (index, 'K' | 'k') => (&value[..index], 1u64 << 10),
+        (index, 'M' | 'm') => (&value[..index], 1 << 20),
+        (index, 'G' | 'g') => (&value[..index], 1 << 30),
+        (index, 'T' | 't') => (&value[..index], 1 << 40),
+        _ => (value, 1),
+    };
+    let number = number.parse::<f64>().map_err(|_| bad_size())?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err(bad_size());
+    }
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Current resident set size of the process, on Linux only
+pub fn current_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// Thread sampling the RSS of the process at a fixed interval; once the RSS
+/// exceeds the limit, if any, the shutdown flag is raised so that the run
+/// stops picking up loci and writes out its partial results
+pub struct MemoryWatchdog {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+    samples: Arc<Mutex<Vec<RssSample>>>,
+    limit_exceeded: Arc<AtomicBool>,
+}
+
+impl MemoryWatchdog {
+    pub fn start(
+        interval: Duration,
+        limit: Option<u64>,
+        loci_done: Arc<AtomicUsize>,
+        shutdown: Arc<AtomicBool>,
+    ) -> Self {
+        let (stop, stopped) = bounded::<()>(0);
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let limit_exceeded = Arc::new(AtomicBool::new(false));
+        let (m_samples, m_limit_exceeded) = (samples.clone(), limit_exceeded.clone());
+        let start_time = Instant::now();
+        let handle = thread::spawn(move || loop {
+            if let Some(rss_bytes) = current_rss() {
+                m_samples.lock().unwrap().push(RssSample {
+                    elapsed_secs: start_time.elapsed().as_secs(),
+                    rss_bytes,
+                    loci_done: loci_done.load(Ordering::Relaxed),
+                });
+                if limit.is_some_and(|limit| rss_bytes > limit)
+                    && !m_limit_exceeded.swap(true, Ordering::Relaxed)
+                {
+                    log::error!(
+                        "RSS of {} MiB exceeds --rss-limit; stopping after the loci in progress",
+                        rss_bytes >> 20
+                    );
+                    shutdown.store(true, Ordering::Relaxed);
+                }
+            }
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => break,
+            }
+        });
+        MemoryWatchdog {
+            stop,
+            handle,
+            samples,
+            limit_exceeded,
+        }
+    }
+
+    /// Stop sampling, returning the samples and whether the limit was exceeded
+    pub fn finish(self) -> (Vec<RssSample>, bool) {
+        drop(self.stop);
+        self.handle.join().unwrap();
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        (samples, self.limit_exceeded.load(Ordering::Relaxed))
+    }
+}
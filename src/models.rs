@@ -1,4 +1,220 @@
-pub const RADIUS: i64 = 500;
+use crate::error::{Result, VclustError};
+use crate::onnx::OnnxClassifier;
+use clap::ValueEnum;
+use std::sync::Arc;
+
+/// Model assessing whether windows are reference-like
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ModelType {
+    /// Categorical emission likelihoods of the states at each window position
+    #[default]
+    Categorical,
+    /// Classifier of the window states read from an ONNX file
+    Onnx,
+}
+
+/// Emission probabilities of each state at each window position, stored
+/// position-major
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub probs: Vec<f64>,
+    pub prior: f64,
+}
+
+/// Discretization of pooled alt fractions into the states emitted by the models
+#[derive(Debug, Clone)]
+pub struct Alphabet {
+    /// Smallest value of each state after the first, in increasing order
+    pub lower_bounds: Vec<f64>,
+    /// Positions where the clip fraction reaches this are assigned to an extra
+    /// last state instead of an alt state
+    pub clip_min_fraction: Option<f64>,
+}
+
+impl Alphabet {
+    pub fn n_states(&self) -> usize {
+        self.lower_bounds.len() + 1 + self.clip_min_fraction.is_some() as usize
+    }
+
+    /// State of a position with given alt and soft clip fractions
+    pub fn state(&self, alt: f64, clip: f64) -> u8 {
+        match self.clip_min_fraction {
+            Some(min_fraction) if clip >= min_fraction => self.lower_bounds.len() as u8 + 1,
+            _ => self.lower_bounds.partition_point(|bound| *bound <= alt) as u8,
+        }
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet {
+            // Alt fractions of exactly 0.10 are still reference-like
+            lower_bounds: vec![0.10f64.next_up(), 0.25, 0.75, 1.50, 5.00],
+            clip_min_fraction: None,
+        }
+    }
+}
+
+/// Emission models of reference-like and variation cluster windows
+#[derive(Debug, Clone)]
+pub struct Models {
+    pub reference: Model,
+    pub cluster: Model,
+    pub alphabet: Alphabet,
+    /// Classifier used instead of the emission likelihoods, if any
+    pub classifier: Option<Arc<OnnxClassifier>>,
+}
+
+impl Models {
+    /// Check that the emission tables of both models match the alphabet and
+    /// hold a nonzero distribution over the states at each window position
+    pub fn validate(&self) -> Result<()> {
+        let n_states = self.alphabet.n_states();
+        if n_states > u8::MAX as usize + 1 {
+            return Err(VclustError::Model(format!(
+                "Alphabet has {n_states} states; at most 256 are supported"
+            )));
+        }
+        for model in [&self.reference, &self.cluster] {
+            if model.probs.is_empty() || model.probs.len() % n_states != 0 {
+                return Err(VclustError::Model(format!(
+                    "Model with {} probabilities does not match an alphabet of {n_states} states",
+                    model.probs.len()
+                )));
+            }
+        }
+        if self.reference.probs.len() != self.cluster.probs.len() {
+            return Err(VclustError::Model(
+                "Reference and cluster models cover different window lengths".to_string(),
+            ));
+        }
+        for (name, model) in [("Reference", &self.reference), ("Cluster", &self.cluster)] {
+            let zeros = model.probs.iter().filter(|prob| **prob == 0.0).count();
+            if zeros > 0 {
+                return Err(VclustError::Model(format!(
+                    "{name} model has {zeros} zero probabilities; smooth it with --model-pseudocount"
+                )));
+            }
+            for (pos, row) in model.probs.chunks(n_states).enumerate() {
+                let total = row.iter().sum::<f64>();
+                let valid =
+                    row.iter().all(|prob| *prob > 0.0) && (total - 1.0).abs() <= NORM_TOLERANCE;
+                if !valid {
+                    return Err(VclustError::Model(format!(
+                        "{name} model emissions at window position {pos} are not a distribution (sum {total})"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply Laplace smoothing to the emissions of both models, adding
+    /// `pseudocount` to each probability before renormalizing each position
+    pub fn with_pseudocount(mut self, pseudocount: f64) -> Self {
+        let n_states = self.alphabet.n_states();
+        for model in [&mut self.reference, &mut self.cluster] {
+            for row in model.probs.chunks_mut(n_states) {
+                let total = row.iter().sum::<f64>() + pseudocount * n_states as f64;
+                for prob in row {
+                    *prob = (*prob + pseudocount) / total;
+                }
+            }
+        }
+        self
+    }
+
+    /// Add a state for clip-dominated positions to the alphabet
+    ///
+    /// Until models are trained with the clip state, its emissions start from
+    /// those of the highest alt state, where clipping walls used to fall, and
+    /// each position is renormalized.
+    pub fn with_clip_state(mut self, min_fraction: f64) -> Self {
+        if self.alphabet.clip_min_fraction.is_none() {
+            let n_states = self.alphabet.n_states();
+            for model in [&mut self.reference, &mut self.cluster] {
+                model.probs = model
+                    .probs
+                    .chunks(n_states)
+                    .flat_map(|row| {
+                        let extended = row.iter().copied().chain([row[n_states - 1]]);
+                        let total = extended.clone().sum::<f64>();
+                        extended.map(move |prob| prob / total)
+                    })
+                    .collect();
+            }
+        }
+        self.alphabet.clip_min_fraction = Some(min_fraction);
+        self
+    }
+
+    /// Longest window the models can assess; a classifier only assesses
+    /// windows of the length it was trained on
+    pub fn max_window_len(&self) -> usize {
+        match &self.classifier {
+            Some(classifier) => classifier.window_len,
+            None => self.reference.probs.len() / self.alphabet.n_states(),
+        }
+    }
+}
+
+/// Emissions and prior of a model in log space
+#[derive(Debug, Clone)]
+pub struct LogModel {
+    pub log_probs: Vec<f64>,
+    pub log_prior: f64,
+}
+
+impl From<&Model> for LogModel {
+    fn from(model: &Model) -> Self {
+        LogModel {
+            log_probs: model.probs.iter().map(|prob| prob.ln()).collect(),
+            log_prior: model.prior.ln(),
+        }
+    }
+}
+
+/// Log-space copy of the models, computed once per worker so that the windows
+/// of every ladder step look up their log terms instead of recomputing them
+#[derive(Debug, Clone)]
+pub struct LogModels {
+    pub reference: LogModel,
+    pub cluster: LogModel,
+    pub n_states: usize,
+    pub classifier: Option<Arc<OnnxClassifier>>,
+}
+
+impl From<&Models> for LogModels {
+    fn from(models: &Models) -> Self {
+        LogModels {
+            reference: LogModel::from(&models.reference),
+            cluster: LogModel::from(&models.cluster),
+            n_states: models.alphabet.n_states(),
+            classifier: models.classifier.clone(),
+        }
+    }
+}
+
+impl Default for Models {
+    fn default() -> Self {
+        Models {
+            reference: Model {
+                probs: MODEL_REF.to_vec(),
+                prior: PRIOR_REF,
+            },
+            cluster: Model {
+                probs: MODEL_VC.to_vec(),
+                prior: PRIOR_VC,
+            },
+            alphabet: Alphabet::default(),
+            classifier: None,
+        }
+    }
+}
+
+/// Largest deviation from one allowed for the emissions at a window position
+const NORM_TOLERANCE: f64 = 1e-4;
+
 pub const PRIOR_REF: f64 = 0.5779905;
 pub const PRIOR_VC: f64 = 0.4220095;
 
@@ -0,0 +1,177 @@
+use crate::profile::CigarOp;
+
+/// Reference bases beyond each end of a search window that indels of reads
+/// near its ends are left-aligned against
+pub const LEFT_ALIGN_FLANK: i64 = 1000;
+
+/// Rewrite alignment operations into the canonical form profiled by vclust,
+/// so that reads aligned by different aligners yield the same profiles:
+///
+/// - `M` operations become `=`, as they carry no mismatch information
+/// - hard clips and padding, which consume neither sequence, and empty
+///   operations are dropped
+/// - adjacent operations of the same kind are merged, and each run of
+///   insertions and deletions becomes a single deletion followed by a single
+///   insertion
+/// - insertions before the first or after the last aligned base are soft
+///   clipped, and deletions there are trimmed from the alignment
+///
+/// Returns the start of the normalized alignment, which moves past a leading
+/// deletion, and its operations.
+pub fn normalize_ops(pos: i64, ops: &[CigarOp]) -> (i64, Vec<CigarOp>) {
+    let mut normalized = Vec::with_capacity(ops.len());
+    let (mut del_len, mut ins_len) = (0, 0);
+    for op in ops {
+        match *op {
+            CigarOp::HardClip(_) | CigarOp::Pad(_) => {}
+            op if op.is_empty() => {}
+            CigarOp::Del(len) => del_len += len,
+            CigarOp::Ins(len) => ins_len += len,
+            op => {
+                push_indels(&mut normalized, &mut del_len, &mut ins_len);
+                let op = match op {
+                    CigarOp::Match(len) => CigarOp::Equal(len),
+                    op => op,
+                };
+                push_merged(&mut normalized, op);
+            }
+        }
+    }
+    push_indels(&mut normalized, &mut del_len, &mut ins_len);
+
+    let is_aligned = |op: &CigarOp| {
+        matches!(
+            op,
+            CigarOp::Equal(_) | CigarOp::Diff(_) | CigarOp::RefSkip(_)
+        )
+    };
+    let (Some(first), Some(last)) = (
+        normalized.iter().position(is_aligned),
+        normalized.iter().rposition(is_aligned),
+    ) else {
+        return (pos, normalized);
+    };
+    let (lead_clip, lead_del) = flank_lengths(&normalized[..first]);
+    let (trail_clip, _) = flank_lengths(&normalized[last + 1..]);
+    let mut trimmed = Vec::with_capacity(last + 3 - first);
+    if lead_clip > 0 {
+        trimmed.push(CigarOp::SoftClip(lead_clip));
+    }
+    trimmed.extend_from_slice(&normalized[first..=last]);
+    if trail_clip > 0 {
+        trimmed.push(CigarOp::SoftClip(trail_clip));
+    }
+    (pos + lead_del as i64, trimmed)
+}
+
+/// Append the pending run of deletions and insertions, if any
+fn push_indels(ops: &mut Vec<CigarOp>, del_len: &mut u32, ins_len: &mut u32) {
+    if *del_len > 0 {
+        ops.push(CigarOp::Del(*del_len));
+    }
+    if *ins_len > 0 {
+        ops.push(CigarOp::Ins(*ins_len));
+    }
+    (*del_len, *ins_len) = (0, 0);
+}
+
+/// Append an operation, extending the last one if it is of the same kind
+fn push_merged(ops: &mut Vec<CigarOp>, op: CigarOp) {
+    match (ops.last_mut(), op) {
+        (Some(CigarOp::Equal(len)), CigarOp::Equal(extra))
+        | (Some(CigarOp::Diff(len)), CigarOp::Diff(extra))
+        | (Some(CigarOp::SoftClip(len)), CigarOp::SoftClip(extra))
+        | (Some(CigarOp::RefSkip(len)), CigarOp::RefSkip(extra)) => *len += extra,
+        (_, op) => ops.push(op),
+    }
+}
+
+/// Sequence bases clipped and reference bases deleted outside of the aligned
+/// bases, which are left with soft clips, deletions, and insertions only
+fn flank_lengths(ops: &[CigarOp]) -> (u32, u32) {
+    ops.iter().fold((0, 0), |(clip, del), op| match op {
+        CigarOp::SoftClip(len) | CigarOp::Ins(len) => (clip + len, del),
+        CigarOp::Del(len) => (clip, del + len),
+        _ => (clip, del),
+    })
+}
+
+/// Reference bases of an interval, for left-aligning the indels of reads
+/// within it
+#[derive(Debug, Clone, Copy)]
+pub struct RefWindow<'a> {
+    pub start: i64,
+    pub seq: &'a [u8],
+}
+
+impl RefWindow<'_> {
+    fn base(&self, pos: i64) -> Option<u8> {
+        let index = usize::try_from(pos - self.start).ok()?;
+        self.seq.get(index).map(u8::to_ascii_uppercase)
+    }
+}
+
+/// Move each indel of normalized operations to its leftmost equivalent
+/// position, so that indels in repeats are placed the same way whatever the
+/// aligner; indels only move across `=` operations and within the reference
+/// window, and the result is normalized again
+pub fn left_align_indels(
+    pos: i64,
+    ops: &[CigarOp],
+    read_seq: &[u8],
+    reference: RefWindow,
+) -> (i64, Vec<CigarOp>) {
+    let read_base = |pos: i64| {
+        let index = usize::try_from(pos).ok()?;
+        read_seq.get(index).map(u8::to_ascii_uppercase)
+    };
+    let mut aligned: Vec<CigarOp> = Vec::with_capacity(ops.len() + 2);
+    let (mut ref_pos, mut query_pos) = (pos, 0);
+    for op in ops {
+        let mut shift = 0;
+        if let (CigarOp::Del(len) | CigarOp::Ins(len), Some(CigarOp::Equal(available))) =
+            (op, aligned.last())
+        {
+            let len = *len as i64;
+            while shift < *available as i64 {
+                let (ref_start, query_start) = (ref_pos - shift, query_pos - shift);
+                // The base before the indel matches the reference, so the indel
+                // can move left if it ends with that base
+                let (before, last) = match op {
+                    CigarOp::Del(_) => (
+                        reference.base(ref_start - 1),
+                        reference.base(ref_start + len - 1),
+                    ),
+                    _ => (read_base(query_start - 1), read_base(query_start + len - 1)),
+                };
+                if before.is_none() || before != last {
+                    break;
+                }
+                shift += 1;
+            }
+        }
+        if shift > 0 {
+            if let Some(CigarOp::Equal(len)) = aligned.last_mut() {
+                *len -= shift as u32;
+            }
+            ref_pos -= shift;
+            query_pos -= shift;
+        }
+        aligned.push(*op);
+        match op {
+            CigarOp::Del(len) | CigarOp::RefSkip(len) => ref_pos += *len as i64,
+            CigarOp::Ins(len) | CigarOp::SoftClip(len) => query_pos += *len as i64,
+            CigarOp::Equal(len) | CigarOp::Diff(len) | CigarOp::Match(len) => {
+                ref_pos += *len as i64;
+                query_pos += *len as i64;
+            }
+            CigarOp::HardClip(_) | CigarOp::Pad(_) => {}
+        }
+        if shift > 0 {
+            aligned.push(CigarOp::Equal(shift as u32));
+            ref_pos += shift;
+            query_pos += shift;
+        }
+    }
+    normalize_ops(pos, &aligned)
+}
@@ -0,0 +1,130 @@
+use crate::context::WorkflowContext;
+use crate::error::{Result, VclustError};
+use crate::extend::Extension;
+use crate::locus::Locus;
+use crate::profile::passes_filters;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+/// Columns added to the output by `--off-target-sample`
+pub const OFF_TARGET_COLUMNS: &[&str] =
+    &["on_target_depth", "off_target_depth", "capture_enrichment"];
+/// Positions drawn per requested position before giving up, as most of the
+/// genome may be covered by regions in dense catalogs
+const MAX_DRAWS_PER_POSITION: usize = 20;
+
+/// Pooled depth of positions sampled away from the regions and amplicons
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffTargetDepth {
+    pub positions: usize,
+    pub depth: f64,
+}
+
+/// Estimate the pooled depth outside the targets from positions sampled
+/// uniformly over the genome, excluding the search regions of the loci and
+/// the amplicons
+pub fn estimate_off_target_depth(
+    ctx: &mut WorkflowContext,
+    loci: &[Locus],
+    n_positions: usize,
+    seed: u64,
+) -> Result<OffTargetDepth> {
+    let mut contigs = Vec::new();
+    for index in 0..ctx.reference.n_seqs() as usize {
+        let name = ctx.reference.seq_name(index as i32)?;
+        let len = ctx.reference.fetch_seq_len(&name) as i64;
+        if len > 0 {
+            contigs.push((name, len));
+        }
+    }
+    let genome_len = contigs.iter().map(|(_, len)| len).sum::<i64>();
+    if genome_len == 0 {
+        return Err(VclustError::Other("Reference has no sequence".to_string()));
+    }
+
+    let mut targets: BTreeMap<&str, Vec<(i64, i64)>> = BTreeMap::new();
+    for locus in loci {
+        let radius = ctx.params.radius_for(locus.end - locus.start);
+        targets
+            .entry(&locus.chrom)
+            .or_default()
+            .push((locus.start - radius, locus.end + radius));
+    }
+    targets.values_mut().for_each(|spans| spans.sort_unstable());
+    let is_target = |chrom: &str, pos: i64| {
+        let in_region = targets.get(chrom).is_some_and(|spans| {
+            let after = spans.partition_point(|(start, _)| *start <= pos);
+            // Spans may nest, so any of those starting before the position may reach it
+            spans[..after].iter().any(|(_, end)| *end > pos)
+        });
+        let in_amplicon = ctx
+            .amplicons
+            .as_ref()
+            .is_some_and(|amplicons| !amplicons.overlapping(chrom, pos, pos + 1).is_empty());
+        in_region || in_amplicon
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut positions = Vec::new();
+    for _ in 0..n_positions * MAX_DRAWS_PER_POSITION {
+        if positions.len() == n_positions {
+            break;
+        }
+        let (chrom, pos) = locate(&contigs, rng.gen_range(0..genome_len));
+        if !is_target(chrom, pos) {
+            positions.push((chrom.to_string(), pos));
+        }
+    }
+    if positions.is_empty() {
+        return Err(VclustError::Other(
+            "No position outside the regions to estimate off-target depth".to_string(),
+        ));
+    }
+
+    let mut total_depth = 0;
+    for (chrom, pos) in &positions {
+        for bam in ctx.bams.iter_mut() {
+            bam.fetch((chrom.as_str(), *pos, *pos + 1))?;
+            while let Some(rec) = bam.next_record() {
+                let rec = rec?;
+                if passes_filters(&rec, &ctx.params) {
+                    total_depth += 1;
+                }
+            }
+        }
+    }
+    Ok(OffTargetDepth {
+        positions: positions.len(),
+        depth: total_depth as f64 / positions.len() as f64,
+    })
+}
+
+/// Contig and position of an offset into the concatenated contigs
+fn locate(contigs: &[(String, i64)], mut offset: i64) -> (&str, i64) {
+    for (name, len) in contigs {
+        if offset < *len {
+            return (name, offset);
+        }
+        offset -= len;
+    }
+    unreachable!("Offset lies within the genome")
+}
+
+/// Values of the off-target columns for an extended locus
+pub fn off_target_columns(ext: Option<&Extension>, off_target_depth: f64) -> Vec<String> {
+    match ext {
+        Some(ext) => {
+            let enrichment = match off_target_depth > 0.0 {
+                true => format!("{:.2}", ext.locus_depth / off_target_depth),
+                false => ".".to_string(),
+            };
+            vec![
+                format!("{:.1}", ext.locus_depth),
+                format!("{off_target_depth:.2}"),
+                enrichment,
+            ]
+        }
+        None => vec!["NA".to_string(); OFF_TARGET_COLUMNS.len()],
+    }
+}
@@ -0,0 +1,492 @@
+use crate::error::{Result, VclustError};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Operators the classifier can be built from, enough for small multilayer
+/// perceptrons and logistic regressions
+pub const SUPPORTED_OPS: &[&str] = &[
+    "Add", "Flatten", "Gemm", "Identity", "MatMul", "Relu", "Sigmoid", "Softmax", "Tanh",
+];
+
+/// Element type of 32-bit float tensors in ONNX
+const FLOAT: u64 = 1;
+
+/// Two-dimensional row-major tensor; vectors have a single row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<f32>,
+}
+
+impl Tensor {
+    fn new(rows: usize, cols: usize, data: Vec<f32>) -> Self {
+        debug_assert_eq!(rows * cols, data.len());
+        Tensor { rows, cols, data }
+    }
+
+    fn transposed(&self) -> Tensor {
+        let mut data = Vec::with_capacity(self.data.len());
+        for col in 0..self.cols {
+            data.extend((0..self.rows).map(|row| self.data[row * self.cols + col]));
+        }
+        Tensor::new(self.cols, self.rows, data)
+    }
+
+    fn matmul(&self, other: &Tensor) -> Result<Tensor> {
+        if self.cols != other.rows {
+            return Err(VclustError::Model(format!(
+                "Cannot multiply {}x{} and {}x{} tensors",
+                self.rows, self.cols, other.rows, other.cols
+            )));
+        }
+        let mut data = vec![0.0; self.rows * other.cols];
+        for row in 0..self.rows {
+            for k in 0..self.cols {
+                let value = self.data[row * self.cols + k];
+                for col in 0..other.cols {
+                    data[row * other.cols + col] += value * other.data[k * other.cols + col];
+                }
+            }
+        }
+        Ok(Tensor::new(self.rows, other.cols, data))
+    }
+
+    /// Elementwise sum, broadcasting single rows and single values
+    fn add(&self, other: &Tensor) -> Result<Tensor> {
+        let value = |tensor: &Tensor, row: usize, col: usize| {
+            let row = if tensor.rows == 1 { 0 } else { row };
+            let col = if tensor.cols == 1 { 0 } else { col };
+            tensor.data[row * tensor.cols + col]
+        };
+        let (rows, cols) = (self.rows.max(other.rows), self.cols.max(other.cols));
+        for tensor in [self, other] {
+            if (tensor.rows != rows && tensor.rows != 1)
+                || (tensor.cols != cols && tensor.cols != 1)
+            {
+                return Err(VclustError::Model(format!(
+                    "Cannot broadcast a {}x{} tensor to {rows}x{cols}",
+                    tensor.rows, tensor.cols
+                )));
+            }
+        }
+        let data = (0..rows * cols)
+            .map(|index| {
+                let (row, col) = (index / cols, index % cols);
+                value(self, row, col) + value(other, row, col)
+            })
+            .collect();
+        Ok(Tensor::new(rows, cols, data))
+    }
+
+    fn map(&self, f: impl Fn(f32) -> f32) -> Tensor {
+        Tensor::new(
+            self.rows,
+            self.cols,
+            self.data.iter().map(|v| f(*v)).collect(),
+        )
+    }
+
+    fn softmax(&self) -> Tensor {
+        let mut data = self.data.clone();
+        for row in data.chunks_mut(self.cols.max(1)) {
+            let max = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            row.iter_mut().for_each(|v| *v = (*v - max).exp());
+            let total = row.iter().sum::<f32>();
+            row.iter_mut().for_each(|v| *v /= total);
+        }
+        Tensor::new(self.rows, self.cols, data)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    op_type: String,
+    inputs: Vec<String>,
+    output: String,
+    float_attributes: HashMap<String, f32>,
+    int_attributes: HashMap<String, i64>,
+}
+
+/// Classifier of discretized windows read from an ONNX file: it takes the
+/// states of a window as a 1xN float tensor and outputs the probability that
+/// the window belongs to a variation cluster, as a 1x1 tensor or as the second
+/// column of a 1x2 tensor of class probabilities
+#[derive(Clone)]
+pub struct OnnxClassifier {
+    /// Window length the classifier was trained on
+    pub window_len: usize,
+    pub sha256: String,
+    input: String,
+    output: String,
+    nodes: Vec<Node>,
+    initializers: HashMap<String, Tensor>,
+}
+
+impl std::fmt::Debug for OnnxClassifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("OnnxClassifier")
+            .field("window_len", &self.window_len)
+            .field("sha256", &self.sha256)
+            .finish()
+    }
+}
+
+impl OnnxClassifier {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let classifier = Self::from_bytes(&bytes)
+            .map_err(|e| VclustError::Model(format!("Bad ONNX model {}: {e}", path.display())))?;
+        // Catch shape mismatches before any locus is processed
+        classifier
+            .cluster_probability(&vec![0; classifier.window_len])
+            .map_err(|e| VclustError::Model(format!("Bad ONNX model {}: {e}", path.display())))?;
+        Ok(classifier)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let graph = fields(bytes)?
+            .into_iter()
+            .find_map(|(number, value)| match (number, value) {
+                (7, Value::Bytes(graph)) => Some(graph),
+                _ => None,
+            })
+            .ok_or_else(|| VclustError::Model("No graph".to_string()))?;
+
+        let mut nodes = Vec::new();
+        let mut initializers = HashMap::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for (number, value) in fields(graph)? {
+            match (number, value) {
+                (1, Value::Bytes(node)) => nodes.push(parse_node(node)?),
+                (5, Value::Bytes(tensor)) => {
+                    let (name, tensor) = parse_tensor(tensor)?;
+                    initializers.insert(name, tensor);
+                }
+                (11, Value::Bytes(info)) => inputs.push(parse_value_info(info)?),
+                (12, Value::Bytes(info)) => outputs.push(parse_value_info(info)?),
+                _ => {}
+            }
+        }
+        if let Some(node) = nodes
+            .iter()
+            .find(|node| !SUPPORTED_OPS.contains(&node.op_type.as_str()))
+        {
+            return Err(VclustError::Model(format!(
+                "Unsupported operator {}; supported operators are {}",
+                node.op_type,
+                SUPPORTED_OPS.join(", ")
+            )));
+        }
+        // Older exporters also list the initializers as graph inputs
+        let (input, dims) = inputs
+            .into_iter()
+            .find(|(name, _)| !initializers.contains_key(name))
+            .ok_or_else(|| VclustError::Model("No graph input".to_string()))?;
+        let window_len = match dims[..] {
+            [_, Some(len)] | [Some(len)] if len > 0 => len as usize,
+            _ => {
+                return Err(VclustError::Model(format!(
+                    "Input {input} must have a fixed window length as its last dimension"
+                )))
+            }
+        };
+        let (output, _) = outputs
+            .into_iter()
+            .next()
+            .ok_or_else(|| VclustError::Model("No graph output".to_string()))?;
+
+        Ok(OnnxClassifier {
+            window_len,
+            sha256: format!("{:x}", Sha256::digest(bytes)),
+            input,
+            output,
+            nodes,
+            initializers,
+        })
+    }
+
+    /// Probability that a window of states belongs to a variation cluster
+    pub fn cluster_probability(&self, states: &[u8]) -> Result<f64> {
+        let input = Tensor::new(1, states.len(), states.iter().map(|s| *s as f32).collect());
+        let mut values: HashMap<&str, Tensor> = HashMap::new();
+        values.insert(&self.input, input);
+        for node in &self.nodes {
+            let arg = |index: usize| {
+                node.inputs
+                    .get(index)
+                    .filter(|name| !name.is_empty())
+                    .and_then(|name| {
+                        values
+                            .get(name.as_str())
+                            .or_else(|| self.initializers.get(name))
+                    })
+            };
+            let required = |index: usize| {
+                arg(index).ok_or_else(|| {
+                    VclustError::Model(format!("Missing input {index} of {}", node.op_type))
+                })
+            };
+            let output = match node.op_type.as_str() {
+                "Identity" | "Flatten" => required(0)?.clone(),
+                "Relu" => required(0)?.map(|v| v.max(0.0)),
+                "Sigmoid" => required(0)?.map(|v| 1.0 / (1.0 + (-v).exp())),
+                "Tanh" => required(0)?.map(f32::tanh),
+                "Softmax" => required(0)?.softmax(),
+                "Add" => required(0)?.add(required(1)?)?,
+                "MatMul" => required(0)?.matmul(required(1)?)?,
+                "Gemm" => {
+                    let int = |name: &str| node.int_attributes.get(name).copied().unwrap_or(0);
+                    let float = |name: &str| node.float_attributes.get(name).copied();
+                    let a = match int("transA") {
+                        0 => required(0)?.clone(),
+                        _ => required(0)?.transposed(),
+                    };
+                    let b = match int("transB") {
+                        0 => required(1)?.clone(),
+                        _ => required(1)?.transposed(),
+                    };
+                    let alpha = float("alpha").unwrap_or(1.0);
+                    let product = a.matmul(&b)?.map(|v| alpha * v);
+                    match arg(2) {
+                        Some(c) => {
+                            let beta = float("beta").unwrap_or(1.0);
+                            product.add(&c.map(|v| beta * v))?
+                        }
+                        None => product,
+                    }
+                }
+                op => unreachable!("Unsupported operator {op}"),
+            };
+            values.insert(&node.output, output);
+        }
+        let output = values
+            .get(self.output.as_str())
+            .ok_or_else(|| VclustError::Model(format!("Output {} is never set", self.output)))?;
+        match (output.rows, output.cols) {
+            (1, 1) => Ok(output.data[0] as f64),
+            (1, 2) => Ok(output.data[1] as f64),
+            (rows, cols) => Err(VclustError::Model(format!(
+                "Output must be 1x1 or 1x2, found {rows}x{cols}"
+            ))),
+        }
+    }
+}
+
+/// Value of a protobuf field, by wire type; no field read here is a 64-bit
+/// fixed value
+enum Value<'a> {
+    Varint(u64),
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn truncated() -> VclustError {
+    VclustError::Model("Truncated protobuf message".to_string())
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or_else(truncated)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(VclustError::Model("Varint too long".to_string()))
+}
+
+/// Field numbers and values of a protobuf message
+fn fields(bytes: &[u8]) -> Result<Vec<(u64, Value<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let key = read_varint(bytes, &mut pos)?;
+        let len = match key & 7 {
+            0 => {
+                fields.push((key >> 3, Value::Varint(read_varint(bytes, &mut pos)?)));
+                continue;
+            }
+            1 => 8,
+            2 => read_varint(bytes, &mut pos)? as usize,
+            5 => 4,
+            wire_type => {
+                return Err(VclustError::Model(format!(
+                    "Unsupported protobuf wire type {wire_type}"
+                )))
+            }
+        };
+        let slice = bytes.get(pos..pos + len).ok_or_else(truncated)?;
+        pos += len;
+        let value = match key & 7 {
+            1 => Value::Fixed64,
+            5 => Value::Fixed32(u32::from_le_bytes(slice.try_into().unwrap())),
+            _ => Value::Bytes(slice),
+        };
+        fields.push((key >> 3, value));
+    }
+    Ok(fields)
+}
+
+fn string(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| VclustError::Model("Invalid UTF-8 string".to_string()))
+}
+
+/// Integers of a repeated int64 field, packed or not
+fn push_ints(value: Value, ints: &mut Vec<i64>) -> Result<()> {
+    match value {
+        Value::Varint(int) => ints.push(int as i64),
+        Value::Bytes(packed) => {
+            let mut pos = 0;
+            while pos < packed.len() {
+                ints.push(read_varint(packed, &mut pos)? as i64);
+            }
+        }
+        _ => return Err(VclustError::Model("Bad integer field".to_string())),
+    }
+    Ok(())
+}
+
+fn parse_node(bytes: &[u8]) -> Result<Node> {
+    let mut node = Node {
+        op_type: String::new(),
+        inputs: Vec::new(),
+        output: String::new(),
+        float_attributes: HashMap::new(),
+        int_attributes: HashMap::new(),
+    };
+    let mut outputs = Vec::new();
+    for (number, value) in fields(bytes)? {
+        match (number, value) {
+            (1, Value::Bytes(input)) => node.inputs.push(string(input)?),
+            (2, Value::Bytes(output)) => outputs.push(string(output)?),
+            (4, Value::Bytes(op_type)) => node.op_type = string(op_type)?,
+            (5, Value::Bytes(attribute)) => {
+                let mut name = String::new();
+                let (mut float, mut int) = (None, None);
+                for (number, value) in fields(attribute)? {
+                    match (number, value) {
+                        (1, Value::Bytes(bytes)) => name = string(bytes)?,
+                        (2, Value::Fixed32(bits)) => float = Some(f32::from_bits(bits)),
+                        (3, Value::Varint(value)) => int = Some(value as i64),
+                        _ => {}
+                    }
+                }
+                if let Some(float) = float {
+                    node.float_attributes.insert(name.clone(), float);
+                }
+                if let Some(int) = int {
+                    node.int_attributes.insert(name, int);
+                }
+            }
+            _ => {}
+        }
+    }
+    node.output = match &outputs[..] {
+        [output] => output.clone(),
+        _ => {
+            return Err(VclustError::Model(format!(
+                "{} node must have a single output",
+                node.op_type
+            )))
+        }
+    };
+    Ok(node)
+}
+
+/// Name and values of an initializer, with up to two dimensions
+fn parse_tensor(bytes: &[u8]) -> Result<(String, Tensor)> {
+    let mut dims = Vec::new();
+    let mut data_type = FLOAT;
+    let mut name = String::new();
+    let mut data = Vec::new();
+    let mut raw_data = None;
+    for (number, value) in fields(bytes)? {
+        match (number, value) {
+            (1, value) => push_ints(value, &mut dims)?,
+            (2, Value::Varint(value)) => data_type = value,
+            (4, Value::Fixed32(bits)) => data.push(f32::from_bits(bits)),
+            (4, Value::Bytes(packed)) => data.extend(
+                packed
+                    .chunks_exact(4)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())),
+            ),
+            (8, Value::Bytes(bytes)) => name = string(bytes)?,
+            (9, Value::Bytes(bytes)) => raw_data = Some(bytes),
+            _ => {}
+        }
+    }
+    if data_type != FLOAT {
+        return Err(VclustError::Model(format!(
+            "Initializer {name} is not a float tensor"
+        )));
+    }
+    if let Some(raw_data) = raw_data {
+        data = raw_data
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+    }
+    let (rows, cols) = match dims[..] {
+        [] => (1, 1),
+        [cols] => (1, cols as usize),
+        [rows, cols] => (rows as usize, cols as usize),
+        _ => {
+            return Err(VclustError::Model(format!(
+                "Initializer {name} has more than two dimensions"
+            )))
+        }
+    };
+    if rows * cols != data.len() {
+        return Err(VclustError::Model(format!(
+            "Initializer {name} has {} values for {rows}x{cols}",
+            data.len()
+        )));
+    }
+    Ok((name, Tensor::new(rows, cols, data)))
+}
+
+/// Name and dimensions of a graph input or output, with `None` for symbolic
+/// dimensions
+fn parse_value_info(bytes: &[u8]) -> Result<(String, Vec<Option<i64>>)> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    for (number, value) in fields(bytes)? {
+        match (number, value) {
+            (1, Value::Bytes(bytes)) => name = string(bytes)?,
+            // TypeProto, then its tensor type and the shape of that
+            (2, Value::Bytes(type_proto)) => {
+                for tensor_type in nested(type_proto, 1)? {
+                    for shape in nested(tensor_type, 2)? {
+                        for dim in nested(shape, 1)? {
+                            let value = fields(dim)?.into_iter().find_map(|field| match field {
+                                (1, Value::Varint(value)) => Some(value as i64),
+                                _ => None,
+                            });
+                            dims.push(value);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((name, dims))
+}
+
+/// Embedded messages of a field of a message
+fn nested(bytes: &[u8], field: u64) -> Result<Vec<&[u8]>> {
+    Ok(fields(bytes)?
+        .into_iter()
+        .filter_map(|(number, value)| match value {
+            Value::Bytes(bytes) if number == field => Some(bytes),
+            _ => None,
+        })
+        .collect())
+}
@@ -0,0 +1,375 @@
+use crate::aligner::Aligner;
+use crate::error::{Result, VclustError};
+use crate::profile::{CLIP_BINS, MAPQ_BINS};
+use crate::schema::{format_row, header_with, COLUMNS};
+use crate::workflow::LocusResult;
+use clap::ValueEnum;
+use rust_htslib::faidx;
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::path::Path;
+
+/// Destination of per-locus results
+pub trait OutputSink {
+    fn write_header(&mut self) -> Result<()>;
+    fn write_record(&mut self, result: &LocusResult) -> Result<()>;
+    /// Called once after the last record
+    fn finalize(&mut self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Versioned tab-separated table listing every region
+    Tsv,
+    /// Extended regions in BED format
+    Bed,
+    /// Extended regions as VCF records with symbolic alleles
+    Vcf,
+    /// One JSON object per region
+    Jsonl,
+}
+
+/// Columns derived from the extended region that can be selected besides the
+/// TSV columns
+pub const DERIVED_COLUMNS: &[&str] = &["chrom", "start", "end"];
+
+/// Columns chosen with `--columns` for TSV or BED output, in the given order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSelection {
+    names: Vec<String>,
+    score_columns: Vec<String>,
+}
+
+impl ColumnSelection {
+    /// Check the names against the TSV columns, including the score columns of
+    /// the run, and the derived columns
+    pub fn new(names: Vec<String>, score_columns: &[String]) -> Result<Self> {
+        let available = DERIVED_COLUMNS
+            .iter()
+            .chain(COLUMNS)
+            .map(|c| c.to_string())
+            .chain(score_columns.iter().cloned())
+            .collect::<Vec<_>>();
+        if let Some(unknown) = names.iter().find(|name| !available.contains(name)) {
+            return Err(VclustError::Other(format!(
+                "Unknown output column {unknown}; available columns are {}",
+                available.join(",")
+            )));
+        }
+        Ok(ColumnSelection {
+            names,
+            score_columns: score_columns.to_vec(),
+        })
+    }
+
+    pub fn header(&self) -> String {
+        format!("#{}", self.names.join("\t"))
+    }
+
+    /// The selection without the derived columns, which BED records always
+    /// start with
+    fn without_derived(mut self) -> Self {
+        self.names
+            .retain(|name| !DERIVED_COLUMNS.contains(&name.as_str()));
+        self
+    }
+
+    /// Values of the selected columns, with `NA` for the derived columns of
+    /// regions that could not be extended
+    pub fn format_row(&self, result: &LocusResult) -> String {
+        let row = format_row(result);
+        let fields = row.split('\t').collect::<Vec<_>>();
+        let locus = &result.locus;
+        let extended = result
+            .extension
+            .as_ref()
+            .map(|ext| (locus.start - ext.lf_offset, locus.end + ext.rf_offset));
+        self.names
+            .iter()
+            .map(|name| match (name.as_str(), extended) {
+                ("chrom", _) => locus.chrom.clone(),
+                ("start", Some((start, _))) => start.to_string(),
+                ("end", Some((_, end))) => end.to_string(),
+                ("start" | "end", None) => "NA".to_string(),
+                (name, _) => {
+                    let index = COLUMNS
+                        .iter()
+                        .map(|c| c.to_string())
+                        .chain(self.score_columns.iter().cloned())
+                        .position(|column| column == name)
+                        .unwrap();
+                    fields.get(index).unwrap_or(&"NA").to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+}
+
+/// Sink writing results in the given format; the reference genome is only
+/// read for VCF output
+pub fn make_sink<'a>(
+    format: OutputFormat,
+    writer: &'a mut dyn Write,
+    genome_path: &Path,
+    score_columns: Vec<String>,
+    aligners: Vec<(String, Aligner)>,
+    columns: Option<ColumnSelection>,
+) -> Result<Box<dyn OutputSink + 'a>> {
+    Ok(match format {
+        OutputFormat::Tsv => Box::new(TsvSink {
+            writer,
+            score_columns,
+            columns,
+        }),
+        OutputFormat::Bed => Box::new(BedSink {
+            writer,
+            columns: columns.map(ColumnSelection::without_derived),
+        }),
+        OutputFormat::Vcf => Box::new(VcfSink {
+            writer,
+            reference: faidx::Reader::from_path(genome_path)?,
+            aligners,
+        }),
+        OutputFormat::Jsonl => Box::new(JsonlSink {
+            writer,
+            score_columns,
+        }),
+    })
+}
+
+/// With a column selection, the versioned header is replaced by the names of
+/// the selected columns, as the table no longer follows the schema
+pub struct TsvSink<'a> {
+    writer: &'a mut dyn Write,
+    score_columns: Vec<String>,
+    columns: Option<ColumnSelection>,
+}
+
+impl OutputSink for TsvSink<'_> {
+    fn write_header(&mut self) -> Result<()> {
+        match &self.columns {
+            Some(columns) => writeln!(self.writer, "{}", columns.header())?,
+            None => writeln!(self.writer, "{}", header_with(&self.score_columns))?,
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, result: &LocusResult) -> Result<()> {
+        match &self.columns {
+            Some(columns) => writeln!(self.writer, "{}", columns.format_row(result))?,
+            None => writeln!(self.writer, "{}", format_row(result))?,
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Regions that could not be extended are omitted; records start with the
+/// contig, start, and end of the extended region, and a column selection
+/// replaces the default name and ns columns after them
+pub struct BedSink<'a> {
+    writer: &'a mut dyn Write,
+    columns: Option<ColumnSelection>,
+}
+
+impl OutputSink for BedSink<'_> {
+    fn write_header(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_record(&mut self, result: &LocusResult) -> Result<()> {
+        let locus = &result.locus;
+        let Some(ext) = &result.extension else {
+            return Ok(());
+        };
+        let fields = match &self.columns {
+            Some(columns) if columns.names.is_empty() => String::new(),
+            Some(columns) => format!("\t{}", columns.format_row(result)),
+            None => format!("\t{}\t{}", locus.name, ext.ns),
+        };
+        writeln!(
+            self.writer,
+            "{}\t{}\t{}{fields}",
+            locus.chrom,
+            locus.start - ext.lf_offset,
+            locus.end + ext.rf_offset,
+        )?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Regions that could not be extended are omitted; the reference allele is
+/// the base at the start of the extended region, and the contigs of the
+/// reference index are listed in the header
+pub struct VcfSink<'a> {
+    writer: &'a mut dyn Write,
+    reference: faidx::Reader,
+    /// Sample and aligner of each BAM whose aligner is known, listed in the
+    /// header
+    aligners: Vec<(String, Aligner)>,
+}
+
+impl OutputSink for VcfSink<'_> {
+    fn write_header(&mut self) -> Result<()> {
+        let lines = [
+            "##fileformat=VCFv4.2",
+            "##ALT=<ID=VC,Description=\"Variation cluster\">",
+            "##INFO=<ID=END,Number=1,Type=Integer,Description=\"End of the extended region\">",
+            "##INFO=<ID=LF,Number=1,Type=Integer,Description=\"Extension of the region start\">",
+            "##INFO=<ID=RF,Number=1,Type=Integer,Description=\"Extension of the region end\">",
+            "##INFO=<ID=NS,Number=1,Type=Integer,Description=\"Number of supporting samples\">",
+            "##INFO=<ID=SR,Number=1,Type=Integer,Description=\"Reads spanning the region\">",
+            "##INFO=<ID=PR,Number=1,Type=Integer,Description=\"Reads partially overlapping the region\">",
+        ];
+        for line in lines {
+            writeln!(self.writer, "{line}")?;
+        }
+        for index in 0..self.reference.n_seqs() {
+            let name = self.reference.seq_name(index as i32)?;
+            let len = self.reference.fetch_seq_len(&name);
+            writeln!(self.writer, "##contig=<ID={name},length={len}>")?;
+        }
+        for (sample, aligner) in &self.aligners {
+            writeln!(
+                self.writer,
+                "##vclust_aligner=<Sample={sample},Program={},Version={}>",
+                aligner.program,
+                aligner.version.as_deref().unwrap_or(".")
+            )?;
+        }
+        writeln!(self.writer, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+        Ok(())
+    }
+
+    fn write_record(&mut self, result: &LocusResult) -> Result<()> {
+        let locus = &result.locus;
+        if let Some(ext) = &result.extension {
+            let start = locus.start - ext.lf_offset;
+            // Fetched interval is closed
+            let base = self
+                .reference
+                .fetch_seq(&locus.chrom, start as usize, start as usize)?
+                .first()
+                .map(u8::to_ascii_uppercase)
+                .filter(|base| b"ACGT".contains(base))
+                .unwrap_or(b'N') as char;
+            writeln!(
+                self.writer,
+                "{}\t{}\t{}\t{base}\t<VC>\t.\tPASS\tEND={};LF={};RF={};NS={};SR={};PR={}",
+                locus.chrom,
+                start + 1,
+                locus.name,
+                locus.end + ext.rf_offset,
+                ext.lf_offset,
+                ext.rf_offset,
+                ext.ns,
+                ext.spanning_reads,
+                ext.partial_reads
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Objects carry the same fields as the columns of the TSV output, with
+/// `null` in place of `NA`
+pub struct JsonlSink<'a> {
+    writer: &'a mut dyn Write,
+    score_columns: Vec<String>,
+}
+
+impl OutputSink for JsonlSink<'_> {
+    fn write_header(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_record(&mut self, result: &LocusResult) -> Result<()> {
+        let locus = &result.locus;
+        let mut record = Map::new();
+        record.insert(COLUMNS[0].to_string(), json!(locus.name));
+        record.insert(
+            COLUMNS[1].to_string(),
+            json!(format!("{}:{}-{}", locus.chrom, locus.start, locus.end)),
+        );
+        let values = match &result.extension {
+            Some(ext) => vec![
+                json!(ext.lf_offset),
+                json!(ext.rf_offset),
+                json!(format!(
+                    "{}:{}-{}",
+                    locus.chrom,
+                    locus.start - ext.lf_offset,
+                    locus.end + ext.rf_offset
+                )),
+                json!(ext.ns),
+                json!(ext.spanning_reads),
+                json!(ext.partial_reads),
+                json!(ext.chimeric_reads),
+                json!(ext.coverage_gaps),
+                json!(ext.ladder_offsets),
+                json!(ext.read_end_bias),
+            ],
+            None => vec![Value::Null; COLUMNS.len() - 2],
+        };
+        for (column, value) in COLUMNS[2..].iter().zip(values) {
+            record.insert(column.to_string(), value);
+        }
+        if let Some(ext) = &result.extension {
+            let stats = &ext.read_stats;
+            record.insert(
+                "mapq_histogram".to_string(),
+                histogram(&MAPQ_BINS, &stats.mapq_counts),
+            );
+            record.insert(
+                "clip_histogram".to_string(),
+                histogram(&CLIP_BINS, &stats.clip_counts),
+            );
+            if let Some(deletion) = &ext.long_deletion {
+                record.insert("long_deletion".to_string(), json!(deletion));
+            }
+            if let Some(span_reference) = &ext.span_reference {
+                record.insert("span_reference".to_string(), json!(span_reference));
+            }
+        }
+        for (column, value) in self.score_columns.iter().zip(&result.scores) {
+            let value = match value.as_str() {
+                "NA" => Value::Null,
+                value => json!(value),
+            };
+            record.insert(column.clone(), value);
+        }
+        serde_json::to_writer(&mut *self.writer, &record)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Histogram as an object mapping the lower bound of each bin to its count
+fn histogram<T: ToString>(bins: &[T], counts: &[usize]) -> Value {
+    let hist = bins
+        .iter()
+        .zip(counts)
+        .map(|(start, count)| (start.to_string(), json!(count)))
+        .collect::<Map<_, _>>();
+    Value::Object(hist)
+}
@@ -0,0 +1,168 @@
+use crate::error::{Result, VclustError};
+use crate::extend::Extension;
+use crate::locus::Locus;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub const SEGREGATION_COLUMNS: &[&str] = &[
+    "name",
+    "family",
+    "carriers",
+    "non_carriers",
+    "unassessed",
+    "inconsistent",
+];
+
+/// Individual of a pedigree with the sample names of its parents, if they
+/// are part of the pedigree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub sample: String,
+    pub father: Option<String>,
+    pub mother: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Family {
+    pub id: String,
+    /// Members in the order of the pedigree file
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pedigree {
+    pub families: Vec<Family>,
+}
+
+/// Members of a family carrying the cluster at a locus and children whose
+/// support cannot be inherited from either parent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segregation {
+    pub family: String,
+    pub carriers: Vec<String>,
+    pub non_carriers: Vec<String>,
+    /// Members without reads or with too little depth to be assessed
+    pub unassessed: Vec<String>,
+    /// Carriers whose parents were both assessed and carry no cluster
+    pub inconsistent: Vec<String>,
+}
+
+/// Load a PED file whose columns are family, sample, father, mother, and
+/// optionally sex and phenotype; parents not in the study are given as 0
+pub fn load_pedigree(path: &Path) -> Result<Pedigree> {
+    let file = File::open(path)?;
+    let mut families: Vec<Family> = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split_whitespace().collect_vec();
+        let [family, sample, father, mother, ..] = fields[..] else {
+            return Err(VclustError::Parse(format!("Bad pedigree line {line}")));
+        };
+        let parent = |name: &str| (name != "0").then(|| name.to_string());
+        let member = Member {
+            sample: sample.to_string(),
+            father: parent(father),
+            mother: parent(mother),
+        };
+        match families.iter_mut().find(|f| f.id == family) {
+            Some(family) => family.members.push(member),
+            None => families.push(Family {
+                id: family.to_string(),
+                members: vec![member],
+            }),
+        }
+    }
+
+    let samples = families
+        .iter()
+        .flat_map(|family| &family.members)
+        .map(|member| &member.sample)
+        .collect_vec();
+    if let Some(sample) = samples.iter().duplicates().next() {
+        return Err(VclustError::Parse(format!(
+            "Sample {sample} is listed more than once in the pedigree"
+        )));
+    }
+    Ok(Pedigree { families })
+}
+
+impl Pedigree {
+    pub fn samples(&self) -> impl Iterator<Item = &str> {
+        self.families
+            .iter()
+            .flat_map(|family| &family.members)
+            .map(|member| member.sample.as_str())
+    }
+
+    /// Segregation of the cluster in each family with at least one assessed
+    /// member, given the per-sample support of the extension
+    pub fn segregation(&self, ext: &Extension) -> Vec<Segregation> {
+        let support = ext
+            .sample_support
+            .iter()
+            .map(|(sample, supports)| (sample.as_str(), *supports))
+            .collect::<HashMap<_, _>>();
+        let status = |sample: &Option<String>| {
+            sample
+                .as_deref()
+                .and_then(|sample| support.get(sample).copied())
+        };
+
+        let mut rows = Vec::new();
+        for family in &self.families {
+            let mut row = Segregation {
+                family: family.id.clone(),
+                carriers: Vec::new(),
+                non_carriers: Vec::new(),
+                unassessed: Vec::new(),
+                inconsistent: Vec::new(),
+            };
+            for member in &family.members {
+                let sample = member.sample.clone();
+                match support.get(member.sample.as_str()) {
+                    Some(true) => {
+                        let parents = (status(&member.father), status(&member.mother));
+                        if parents == (Some(false), Some(false)) {
+                            row.inconsistent.push(sample.clone());
+                        }
+                        row.carriers.push(sample);
+                    }
+                    Some(false) => row.non_carriers.push(sample),
+                    None => row.unassessed.push(sample),
+                }
+            }
+            if row.unassessed.len() < family.members.len() {
+                rows.push(row);
+            }
+        }
+        rows
+    }
+}
+
+impl Segregation {
+    pub fn to_row(&self, locus: &Locus) -> String {
+        let list = |samples: &[String]| match samples.is_empty() {
+            true => ".".to_string(),
+            false => samples.join(","),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            locus.name,
+            self.family,
+            list(&self.carriers),
+            list(&self.non_carriers),
+            list(&self.unassessed),
+            list(&self.inconsistent)
+        )
+    }
+}
+
+pub fn segregation_header() -> String {
+    format!("#{}", SEGREGATION_COLUMNS.join("\t"))
+}
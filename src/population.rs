@@ -0,0 +1,245 @@
+use crate::error::{Result, VclustError};
+use crate::locus::Locus;
+use crate::schema::{SCHEMA_VERSION, VERSION_PREFIX};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Version of the population catalog layout; bump whenever `CATALOG_COLUMNS` changes
+pub const CATALOG_VERSION: u32 = 1;
+
+pub const CATALOG_VERSION_PREFIX: &str = "#vclust_population_catalog_version=";
+
+/// Columns of the population catalog
+pub const CATALOG_COLUMNS: &[&str] = &[
+    "name",
+    "region",
+    "n_samples",
+    "n_supporting",
+    "support_frequency",
+    "median_extension",
+    "extension_sizes",
+];
+
+/// Columns added to the output by `--population-catalog`
+pub const POPULATION_COLUMNS: &[&str] = &["population_frequency", "population_extension"];
+
+/// Support of a locus across the samples of a cohort
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocusFrequency {
+    /// Samples in which the locus was extended
+    pub n_samples: usize,
+    /// Samples supporting a cluster at the locus
+    pub n_supporting: usize,
+    /// Number of supporting samples by extension size, the sum of the offsets
+    pub extension_sizes: BTreeMap<i64, usize>,
+}
+
+impl LocusFrequency {
+    pub fn support_frequency(&self) -> f64 {
+        match self.n_samples {
+            0 => 0.0,
+            n_samples => self.n_supporting as f64 / n_samples as f64,
+        }
+    }
+
+    /// Lower median of the extension sizes of the supporting samples
+    pub fn median_extension(&self) -> Option<i64> {
+        let mut rank = self.n_supporting.checked_sub(1)? / 2;
+        for (size, count) in &self.extension_sizes {
+            if rank < *count {
+                return Some(*size);
+            }
+            rank -= count;
+        }
+        None
+    }
+}
+
+/// Frequencies of the loci of a cohort, keyed by contig, start, end, and name
+#[derive(Debug, Default)]
+pub struct PopulationCatalog {
+    pub loci: BTreeMap<(String, i64, i64, String), LocusFrequency>,
+}
+
+impl PopulationCatalog {
+    /// Aggregate the results of a cohort, one file per sample, in the current
+    /// schema; loci that were not extended in a sample are not counted for it
+    pub fn from_results(paths: &[impl AsRef<Path>]) -> Result<Self> {
+        let mut catalog = PopulationCatalog::default();
+        for path in paths {
+            catalog.add_results(path.as_ref())?;
+        }
+        Ok(catalog)
+    }
+
+    fn add_results(&mut self, path: &Path) -> Result<()> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(file).lines();
+
+        let version_line = lines.next().transpose()?.unwrap_or_default();
+        let version = version_line
+            .strip_prefix(VERSION_PREFIX)
+            .and_then(|version| version.parse::<u32>().ok());
+        if version != Some(SCHEMA_VERSION) {
+            return Err(VclustError::Parse(format!(
+                "Results must be in schema version {SCHEMA_VERSION}; upgrade them with vclust migrate"
+            )));
+        }
+        let header = lines
+            .next()
+            .transpose()?
+            .ok_or(VclustError::Parse("Missing column header".to_string()))?;
+        let columns = header.trim_start_matches('#').split('\t').collect_vec();
+        let column = |name: &str| {
+            columns
+                .iter()
+                .position(|c| *c == name)
+                .ok_or_else(|| VclustError::Parse(format!("Missing column {name}")))
+        };
+        let (name_col, region_col, ns_col) = (column("name")?, column("region")?, column("ns")?);
+        let (lf_col, rf_col) = (column("lf_offset")?, column("rf_offset")?);
+
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = line.split('\t').collect_vec();
+            let bad_line = || VclustError::Parse(format!("Bad results line {line}"));
+            let field = |index: usize| fields.get(index).copied().ok_or_else(bad_line);
+            let (lf, rf, ns) = (field(lf_col)?, field(rf_col)?, field(ns_col)?);
+            if lf == "NA" {
+                continue;
+            }
+            let (chrom, start, end) = parse_region(field(region_col)?).ok_or_else(bad_line)?;
+            let parse = |value: &str| value.parse::<i64>().map_err(|_| bad_line());
+            let key = (chrom, start, end, field(name_col)?.to_string());
+            let freq = self.loci.entry(key).or_default();
+            freq.n_samples += 1;
+            if parse(ns)? > 0 {
+                freq.n_supporting += 1;
+                *freq
+                    .extension_sizes
+                    .entry(parse(lf)? + parse(rf)?)
+                    .or_default() += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a catalog written by `vclust population-catalog`
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(file).lines();
+
+        let version_line = lines.next().transpose()?.unwrap_or_default();
+        let version = version_line
+            .strip_prefix(CATALOG_VERSION_PREFIX)
+            .and_then(|version| version.parse::<u32>().ok());
+        if version != Some(CATALOG_VERSION) {
+            return Err(VclustError::Parse(format!(
+                "Population catalog {} must be in version {CATALOG_VERSION}",
+                path.display()
+            )));
+        }
+        let header = lines
+            .next()
+            .transpose()?
+            .ok_or(VclustError::Parse("Missing column header".to_string()))?;
+        let columns = header.trim_start_matches('#').split('\t').collect_vec();
+        let column = |name: &str| {
+            columns
+                .iter()
+                .position(|c| *c == name)
+                .ok_or_else(|| VclustError::Parse(format!("Missing column {name}")))
+        };
+        let (name_col, region_col) = (column("name")?, column("region")?);
+        let (samples_col, supporting_col) = (column("n_samples")?, column("n_supporting")?);
+        let sizes_col = column("extension_sizes")?;
+
+        let mut catalog = PopulationCatalog::default();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = line.split('\t').collect_vec();
+            let bad_line = || VclustError::Parse(format!("Bad population catalog line {line}"));
+            let field = |index: usize| fields.get(index).copied().ok_or_else(bad_line);
+            let (chrom, start, end) = parse_region(field(region_col)?).ok_or_else(bad_line)?;
+            let count = |value: &str| value.parse::<usize>().map_err(|_| bad_line());
+            let extension_sizes = match field(sizes_col)? {
+                "." => BTreeMap::new(),
+                sizes => sizes
+                    .split(',')
+                    .map(|pair| {
+                        let (size, n) = pair.split_once(':').ok_or_else(bad_line)?;
+                        Ok((size.parse::<i64>().map_err(|_| bad_line())?, count(n)?))
+                    })
+                    .collect::<Result<_>>()?,
+            };
+            let freq = LocusFrequency {
+                n_samples: count(field(samples_col)?)?,
+                n_supporting: count(field(supporting_col)?)?,
+                extension_sizes,
+            };
+            let key = (chrom, start, end, field(name_col)?.to_string());
+            catalog.loci.insert(key, freq);
+        }
+        Ok(catalog)
+    }
+
+    /// Values of the population columns for a locus; loci missing from the
+    /// catalog were extended in none of its samples
+    pub fn columns_for(&self, locus: &Locus) -> Vec<String> {
+        let key = (
+            locus.chrom.clone(),
+            locus.start,
+            locus.end,
+            locus.name.clone(),
+        );
+        match self.loci.get(&key) {
+            Some(freq) => vec![
+                format!("{:.4}", freq.support_frequency()),
+                freq.median_extension()
+                    .map_or(".".to_string(), |size| size.to_string()),
+            ],
+            None => vec![".".to_string(); POPULATION_COLUMNS.len()],
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        writeln!(writer, "{CATALOG_VERSION_PREFIX}{CATALOG_VERSION}")?;
+        writeln!(writer, "#{}", CATALOG_COLUMNS.join("\t"))?;
+        for ((chrom, start, end, name), freq) in &self.loci {
+            let median = freq
+                .median_extension()
+                .map_or(".".to_string(), |size| size.to_string());
+            let sizes = if freq.extension_sizes.is_empty() {
+                ".".to_string()
+            } else {
+                freq.extension_sizes
+                    .iter()
+                    .map(|(size, count)| format!("{size}:{count}"))
+                    .join(",")
+            };
+            writeln!(
+                writer,
+                "{name}\t{chrom}:{start}-{end}\t{}\t{}\t{:.4}\t{median}\t{sizes}",
+                freq.n_samples,
+                freq.n_supporting,
+                freq.support_frequency()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_region(region: &str) -> Option<(String, i64, i64)> {
+    let (chrom, span) = region.rsplit_once(':')?;
+    let (start, end) = span.split_once('-')?;
+    Some((chrom.to_string(), start.parse().ok()?, end.parse().ok()?))
+}
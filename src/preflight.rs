@@ -0,0 +1,81 @@
+use crate::error::Result;
+use crate::locus::Locus;
+use rust_htslib::faidx;
+use std::collections::HashMap;
+
+/// Loci with a larger fraction of N bases are flagged
+const MAX_N_FRACTION: f64 = 0.5;
+
+pub const PREFLIGHT_COLUMNS: &[&str] = &["name", "region", "issue", "detail"];
+
+/// A catalog entry whose coordinates look inconsistent with the reference,
+/// typically after a liftover from a different assembly
+#[derive(Debug)]
+pub struct PreflightIssue {
+    pub name: String,
+    pub region: String,
+    pub issue: &'static str,
+    pub detail: String,
+}
+
+impl PreflightIssue {
+    pub fn to_row(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.name, self.region, self.issue, self.detail
+        )
+    }
+}
+
+pub fn preflight_header() -> String {
+    format!("#{}", PREFLIGHT_COLUMNS.join("\t"))
+}
+
+/// Cross-check each locus against the contigs and sequence of the reference
+pub fn check_loci(reference: &faidx::Reader, loci: &[Locus]) -> Result<Vec<PreflightIssue>> {
+    let mut contig_lens = HashMap::new();
+    for index in 0..reference.n_seqs() {
+        let name = reference.seq_name(index as i32)?;
+        let len = reference.fetch_seq_len(&name) as i64;
+        contig_lens.insert(name, len);
+    }
+
+    let mut issues = Vec::new();
+    for locus in loci {
+        let mut flag = |issue, detail: String| {
+            issues.push(PreflightIssue {
+                name: locus.name.clone(),
+                region: format!("{}:{}-{}", locus.chrom, locus.start, locus.end),
+                issue,
+                detail,
+            })
+        };
+
+        let Some(&contig_len) = contig_lens.get(&locus.chrom) else {
+            flag("missing_contig", "contig not in reference".to_string());
+            continue;
+        };
+        if locus.start < 0 || locus.start >= locus.end {
+            flag("empty_region", "start not before end".to_string());
+            continue;
+        }
+        if locus.end > contig_len {
+            flag("out_of_bounds", format!("contig length {contig_len}"));
+            continue;
+        }
+
+        // Fetched interval is closed
+        let seq =
+            reference.fetch_seq(&locus.chrom, locus.start as usize, locus.end as usize - 1)?;
+        let n_count = seq
+            .iter()
+            .filter(|base| matches!(base, b'N' | b'n'))
+            .count();
+        let n_fraction = n_count as f64 / seq.len().max(1) as f64;
+        if n_fraction > MAX_N_FRACTION {
+            flag("n_bases", format!("{:.2} of bases are N", n_fraction));
+        }
+    }
+
+    Ok(issues)
+}
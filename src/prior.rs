@@ -0,0 +1,40 @@
+use crate::error::Result;
+use crate::locus::Locus;
+use crate::tracks::load_clusters;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Bases searched beyond the previously found extended span of a locus
+pub const PRIOR_MARGIN: i64 = 200;
+
+/// Extended spans found by a previous run, used to narrow the search of the
+/// same loci in a later run
+#[derive(Debug, Default)]
+pub struct PriorSpans {
+    /// Extended span of each locus, keyed by contig and name
+    spans: BTreeMap<(String, String), (i64, i64)>,
+}
+
+impl PriorSpans {
+    /// Load the extended regions of a results file in the current schema
+    pub fn load(path: &Path) -> Result<Self> {
+        let spans = load_clusters(path)?
+            .into_iter()
+            .map(|cluster| ((cluster.chrom, cluster.name), (cluster.start, cluster.end)))
+            .collect();
+        Ok(PriorSpans { spans })
+    }
+
+    /// Search radius covering the previous extended span of the locus with a
+    /// margin, if it is narrower than the full radius; loci that were not
+    /// extended before, or whose span no longer contains them, are searched
+    /// with the full radius
+    pub fn radius_for(&self, locus: &Locus, radius: i64) -> Option<i64> {
+        let (start, end) = self.spans.get(&(locus.chrom.clone(), locus.name.clone()))?;
+        if *start > locus.start || *end < locus.end {
+            return None;
+        }
+        let prior_radius = (locus.start - start).max(end - locus.end) + PRIOR_MARGIN;
+        (prior_radius < radius).then_some(prior_radius)
+    }
+}
@@ -0,0 +1,150 @@
+use crate::expression::Expression;
+use crate::extend::Extension;
+use crate::locus::Locus;
+use crate::profile::MAPQ_BINS;
+
+pub const PRIORITY_COLUMN: &str = "priority";
+
+/// Names usable in priority expressions in addition to the numeric columns
+/// added by annotations and scorers
+pub const PRIORITY_VARIABLES: &[&str] = &[
+    "lf_offset",
+    "rf_offset",
+    "ns",
+    "spanning_reads",
+    "partial_reads",
+    "chimeric_reads",
+    "read_end_bias",
+    "breadth",
+    "alt",
+    "extension",
+    "mappability",
+    "depth",
+    "priority",
+];
+
+/// Alignments with at least this MAPQ count as uniquely mapped
+const HIGH_MAPQ: u8 = 50;
+/// Extension length in bps at which the extension component reaches one half
+const EXTENSION_SCALE: f64 = 100.0;
+
+/// Components of the priority score, each between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriorityComponents {
+    /// Fraction of the profiled samples supporting the cluster
+    pub breadth: f64,
+    /// Mean pooled alt fraction over the extended region, capped at 1
+    pub alt: f64,
+    /// Saturating function of the number of bps added by the extension
+    pub extension: f64,
+    /// Fraction of the alignments with a high MAPQ
+    pub mappability: f64,
+    /// Fraction of the extended region without coverage gaps
+    pub depth: f64,
+}
+
+/// Weights of the components, in the order of the fields; they sum to one
+const WEIGHTS: [f64; 5] = [0.3, 0.25, 0.15, 0.15, 0.15];
+
+impl PriorityComponents {
+    pub fn new(locus: &Locus, ext: &Extension, n_samples: usize) -> Self {
+        let stats = &ext.read_stats;
+        let n_reads = stats.mapq_counts.iter().sum::<usize>();
+        let high_mapq_reads = MAPQ_BINS
+            .iter()
+            .zip(&stats.mapq_counts)
+            .filter(|(start, _)| **start >= HIGH_MAPQ)
+            .map(|(_, count)| count)
+            .sum::<usize>();
+        let extension_len = (ext.lf_offset + ext.rf_offset) as f64;
+        let span_len = locus.end + ext.rf_offset - (locus.start - ext.lf_offset);
+        let gap_len = ext
+            .coverage_gaps
+            .iter()
+            .map(|(start, end)| end - start)
+            .sum::<i64>();
+        let fraction = |num: f64, denom: f64| match denom > 0.0 {
+            true => (num / denom).clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        PriorityComponents {
+            breadth: fraction(ext.ns as f64, n_samples as f64),
+            alt: ext.alt_fraction.clamp(0.0, 1.0),
+            extension: extension_len / (extension_len + EXTENSION_SCALE),
+            mappability: fraction(high_mapq_reads as f64, n_reads as f64),
+            depth: 1.0 - fraction(gap_len as f64, span_len as f64),
+        }
+    }
+
+    /// Weighted mean of the components scaled to 0-100; higher scores mark
+    /// stronger candidates
+    pub fn score(&self) -> f64 {
+        let values = [
+            self.breadth,
+            self.alt,
+            self.extension,
+            self.mappability,
+            self.depth,
+        ];
+        100.0
+            * values
+                .iter()
+                .zip(WEIGHTS)
+                .map(|(value, weight)| value * weight)
+                .sum::<f64>()
+    }
+}
+
+/// Priority of an extended region, computed by `expression` if given and as
+/// the default composite score otherwise; `columns` and `values` are the
+/// names and values of the columns added by annotations and scorers
+pub fn priority(
+    expression: Option<&Expression>,
+    locus: &Locus,
+    ext: &Extension,
+    n_samples: usize,
+    columns: &[String],
+    values: &[String],
+) -> Option<f64> {
+    let components = PriorityComponents::new(locus, ext, n_samples);
+    let Some(expression) = expression else {
+        return Some(components.score());
+    };
+    let lookup = |name: &str| match name {
+        "lf_offset" => Some(ext.lf_offset as f64),
+        "rf_offset" => Some(ext.rf_offset as f64),
+        "ns" => Some(ext.ns as f64),
+        "spanning_reads" => Some(ext.spanning_reads as f64),
+        "partial_reads" => Some(ext.partial_reads as f64),
+        "chimeric_reads" => Some(ext.chimeric_reads as f64),
+        "read_end_bias" => ext.read_end_bias,
+        "breadth" => Some(components.breadth),
+        "alt" => Some(components.alt),
+        "extension" => Some(components.extension),
+        "mappability" => Some(components.mappability),
+        "depth" => Some(components.depth),
+        "priority" => Some(components.score()),
+        // Values such as NA or names of elements are missing
+        _ => columns
+            .iter()
+            .position(|column| column == name)
+            .and_then(|index| values.get(index))
+            .and_then(|value| value.parse::<f64>().ok()),
+    };
+    expression.eval(&lookup)
+}
+
+/// Score with up to four decimals, or NA if it could not be computed
+pub fn format_priority(score: Option<f64>) -> String {
+    match score {
+        Some(score) => {
+            let text = format!("{score:.4}");
+            let text = text.trim_end_matches('0').trim_end_matches('.');
+            match text {
+                "-0" => "0".to_string(),
+                text => text.to_string(),
+            }
+        }
+        None => "NA".to_string(),
+    }
+}
@@ -1,61 +1,795 @@
+use crate::alignment::AlignmentSource;
+use crate::amplicon::Amplicons;
+use crate::context::{Params, Scratch};
+use crate::early_stop::EarlyStop;
+use crate::error::{Result, VclustError};
+use crate::locus::Locus;
+use crate::normalize::{left_align_indels, normalize_ops, RefWindow};
+use clap::ValueEnum;
 use itertools::Itertools;
-use rust_htslib::bam::{self, IndexedReader, Record};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_htslib::bam::ext::BamRecordExtensions;
+use rust_htslib::bam::record::Aux;
+use rust_htslib::bam::Record;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
 
 pub type CigarOp = rust_htslib::bam::record::Cigar;
 
 pub type Region<'a> = (&'a str, i64, i64);
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Cigar {
-    pub ref_pos: i64,
-    pub ops: Vec<CigarOp>,
-}
-
 #[derive(Debug)]
 pub struct Prof {
     pub alts: Vec<f64>,
+    /// Per-position soft clip counts normalized by depth, excluding clips
+    /// explained by amplicon boundaries
+    pub clips: Vec<f64>,
+    /// Per-position read depth, counting reads that delete the position
+    pub covs: Vec<f64>,
+    /// Per-position depth of the bases actually sequenced, excluding reads
+    /// that delete the position
+    pub base_covs: Vec<f64>,
     pub depth: f64,
+    /// Reads whose alignments span the whole locus
+    pub spanning_reads: usize,
+    /// Reads overlapping the locus without spanning it
+    pub partial_reads: usize,
+    /// Reads excluded for supplementary alignments at distant loci
+    pub chimeric_reads: usize,
+    pub read_stats: ReadStats,
+    pub skipped_reads: SkipCounts,
+    /// Per-position counts of mismatch runs and indels
+    pub alt_events: Vec<u32>,
+    /// Per-position counts of the alt events near read alignment ends
+    pub read_end_events: Vec<u32>,
 }
 
-pub fn get_profile(bam: &mut IndexedReader, region: Region) -> Result<(Prof, bool), String> {
-    let prof_len = (region.2 - region.1) as usize;
-    let mut covs = vec![0; prof_len];
-    let mut alts = vec![0; prof_len];
-    bam.fetch(region).map_err(|e| e.to_string())?;
-    let mut any_alt = 0;
-    for (index, rec) in bam::Read::records(bam).enumerate() {
-        let rec = rec.map_err(|e| e.to_string())?;
+/// Lower bounds of the MAPQ histogram bins
+pub const MAPQ_BINS: [u8; 7] = [0, 10, 20, 30, 40, 50, 60];
+/// Lower bounds of the bins of total soft clip length per read
+pub const CLIP_BINS: [u32; 4] = [0, 1, 100, 1000];
+/// Supplementary alignments further away than this mark a read as chimeric
+/// when chimeras are excluded
+pub const CHIMERA_DISTANCE: i64 = 100_000;
 
-        if rec.is_secondary() || rec.is_supplementary() || rec.mapq() < 50 {
-            continue;
+/// Histograms of MAPQ and soft clip length over all primary and supplementary
+/// alignments in the region, including those failing the MAPQ filter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadStats {
+    pub mapq_counts: [usize; MAPQ_BINS.len()],
+    pub clip_counts: [usize; CLIP_BINS.len()],
+}
+
+impl ReadStats {
+    pub fn add(&mut self, rec: &Record) {
+        let mapq_bin = MAPQ_BINS.partition_point(|start| *start <= rec.mapq()) - 1;
+        self.mapq_counts[mapq_bin] += 1;
+        let cigar = rec.cigar();
+        let clip_len = cigar.leading_softclips() + cigar.trailing_softclips();
+        let clip_bin = CLIP_BINS.partition_point(|start| *start as i64 <= clip_len) - 1;
+        self.clip_counts[clip_bin] += 1;
+    }
+
+    pub fn merge(&mut self, other: &ReadStats) {
+        let counts = self
+            .mapq_counts
+            .iter_mut()
+            .chain(self.clip_counts.iter_mut());
+        let other_counts = other.mapq_counts.iter().chain(other.clip_counts.iter());
+        for (count, other) in counts.zip(other_counts) {
+            *count += other;
         }
-        any_alt += update_profs(rec, &mut covs, &mut alts, region) as usize;
+    }
+}
+
+pub const SKIPPED_COLUMNS: &[&str] = &[
+    "name",
+    "sample",
+    "secondary",
+    "supplementary",
+    "mapq",
+    "read_class",
+    "chimeric",
+    "unmapped",
+    "empty_cigar",
+    "cigar_length",
+];
+
+pub fn skipped_header() -> String {
+    format!("#{}", SKIPPED_COLUMNS.join("\t"))
+}
 
-        // Absolute max depth
-        if index >= 200 {
-            return Err("High depth".to_string());
+/// Records of the region excluded from profiling, by reason; a record fetched
+/// for several loci is counted at each of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkipCounts {
+    pub secondary: usize,
+    pub supplementary: usize,
+    pub mapq: usize,
+    /// Reads not of the ONT read class being profiled
+    #[serde(default)]
+    pub read_class: usize,
+    /// Reads with supplementary alignments at distant loci
+    pub chimeric: usize,
+    /// Unmapped records placed at the position of their mate
+    #[serde(default)]
+    pub unmapped: usize,
+    /// Mapped records without alignment operations
+    #[serde(default)]
+    pub empty_cigar: usize,
+    /// Records whose CIGAR implies a different read length than their sequence
+    #[serde(default)]
+    pub cigar_length: usize,
+}
+
+impl SkipCounts {
+    pub fn merge(&mut self, other: &SkipCounts) {
+        self.secondary += other.secondary;
+        self.supplementary += other.supplementary;
+        self.mapq += other.mapq;
+        self.read_class += other.read_class;
+        self.chimeric += other.chimeric;
+        self.unmapped += other.unmapped;
+        self.empty_cigar += other.empty_cigar;
+        self.cigar_length += other.cigar_length;
+    }
+
+    /// Number of records skipped as malformed
+    pub fn malformed(&self) -> usize {
+        self.unmapped + self.empty_cigar + self.cigar_length
+    }
+
+    pub fn to_row(&self, locus: &Locus, sample: &str) -> String {
+        format!(
+            "{}\t{sample}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            locus.name,
+            self.secondary,
+            self.supplementary,
+            self.mapq,
+            self.read_class,
+            self.chimeric,
+            self.unmapped,
+            self.empty_cigar,
+            self.cigar_length
+        )
+    }
+
+    fn add(&mut self, reason: SkipReason) {
+        match reason {
+            SkipReason::Secondary => self.secondary += 1,
+            SkipReason::Supplementary => self.supplementary += 1,
+            SkipReason::Mapq => self.mapq += 1,
+            SkipReason::ReadClass => self.read_class += 1,
+            SkipReason::Malformed(Malformation::Unmapped) => self.unmapped += 1,
+            SkipReason::Malformed(Malformation::EmptyCigar) => self.empty_cigar += 1,
+            SkipReason::Malformed(Malformation::CigarLength) => self.cigar_length += 1,
         }
     }
+}
+
+/// Warnings naming the samples with records skipped as malformed, by kind
+pub fn malformed_record_warnings(skipped: &BTreeMap<String, SkipCounts>) -> Vec<String> {
+    skipped
+        .iter()
+        .filter(|(_, counts)| counts.malformed() > 0)
+        .map(|(sample, counts)| {
+            let kinds = [
+                (counts.unmapped, "unmapped but placed at a position"),
+                (counts.empty_cigar, "mapped without a CIGAR"),
+                (
+                    counts.cigar_length,
+                    "with a CIGAR inconsistent with the read length",
+                ),
+            ]
+            .into_iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, kind)| format!("{count} {kind}"))
+            .join(", ");
+            format!("Skipped malformed records of {sample}: {kinds}")
+        })
+        .collect()
+}
 
-    let depth = get_mean(&covs);
+/// Defect of a record that makes its alignment unusable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformation {
+    /// Unmapped, though placed at a position such as that of its mate
+    Unmapped,
+    /// Mapped without any alignment operations
+    EmptyCigar,
+    /// The CIGAR consumes a different number of read bases than the sequence
+    /// holds
+    CigarLength,
+}
 
-    let alts = alts
+/// Defect of a record, if any; records without a stored sequence are not
+/// checked against the CIGAR
+pub fn malformation(rec: &Record) -> Option<Malformation> {
+    if rec.is_unmapped() {
+        return Some(Malformation::Unmapped);
+    }
+    let cigar = rec.cigar();
+    if cigar.is_empty() {
+        return Some(Malformation::EmptyCigar);
+    }
+    let seq_len = rec.seq_len();
+    let query_len = cigar
         .iter()
-        .map(|v| *v as f64 / depth.max(1.0))
+        .map(|op| match op {
+            CigarOp::Match(len)
+            | CigarOp::Equal(len)
+            | CigarOp::Diff(len)
+            | CigarOp::Ins(len)
+            | CigarOp::SoftClip(len) => *len as usize,
+            _ => 0,
+        })
+        .sum::<usize>();
+    if seq_len > 0 && query_len != seq_len {
+        return Some(Malformation::CigarLength);
+    }
+    None
+}
+
+/// Filter excluding a record from profiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkipReason {
+    Secondary,
+    Supplementary,
+    Mapq,
+    ReadClass,
+    Malformed(Malformation),
+}
+
+/// Oxford Nanopore reads to profile, by the `dx` tag of the basecaller
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OntReadClass {
+    /// All reads, whatever their class
+    #[default]
+    All,
+    /// Duplex reads only (`dx:i:1`); reads without the tag are simplex
+    Duplex,
+}
+
+impl Prof {
+    /// Convert to a diploid-equivalent profile for a contig present in given
+    /// number of copies
+    pub fn rescale_ploidy(&mut self, ploidy: u32) {
+        let scale = ploidy as f64 / 2.0;
+        self.alts.iter_mut().for_each(|alt| *alt *= scale);
+        self.clips.iter_mut().for_each(|clip| *clip *= scale);
+        self.covs.iter_mut().for_each(|cov| *cov /= scale);
+        self.base_covs.iter_mut().for_each(|cov| *cov /= scale);
+        self.depth /= scale;
+    }
+}
+
+/// Type of an alt operation of a read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AltKind {
+    Mismatch,
+    Deletion,
+    Insertion,
+}
+
+impl std::fmt::Display for AltKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            AltKind::Mismatch => "mismatch",
+            AltKind::Deletion => "deletion",
+            AltKind::Insertion => "insertion",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Alt operation of at least the minimum length as a reference interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AltSpan {
+    pub start: i64,
+    pub end: i64,
+    pub kind: AltKind,
+}
+
+/// Alt operations of a read carrying any and its haplotype, if phased
+#[derive(Debug, Clone)]
+struct AltRead {
+    spans: Vec<AltSpan>,
+    haplotype: Option<i64>,
+}
+
+/// Alt operations of the reads of a sample that can support a cluster, kept so
+/// that support can be counted once the cluster boundaries are known
+#[derive(Debug, Clone, Default)]
+pub struct SampleSupport {
+    alt_reads: Vec<AltRead>,
+}
+
+impl SampleSupport {
+    /// Reads with an alt operation overlapping [start, end)
+    fn supporting_reads(&self, start: i64, end: i64) -> impl Iterator<Item = &AltRead> {
+        self.alt_reads.iter().filter(move |read| {
+            read.spans
+                .iter()
+                .any(|span| span.start < end && span.end > start)
+        })
+    }
+
+    /// Number of reads with an alt operation overlapping [start, end)
+    pub fn count_reads(&self, start: i64, end: i64) -> usize {
+        self.supporting_reads(start, end).count()
+    }
+
+    /// Number of reads with an alt operation overlapping [start, end) on the
+    /// first and second haplotypes, from their `HP` tags
+    pub fn haplotype_reads(&self, start: i64, end: i64) -> [usize; 2] {
+        let mut counts = [0; 2];
+        for read in self.supporting_reads(start, end) {
+            match read.haplotype {
+                Some(1) => counts[0] += 1,
+                Some(2) => counts[1] += 1,
+                _ => {}
+            }
+        }
+        counts
+    }
+
+    /// Alt operation overlapping [start, end) that is shared by the most reads,
+    /// with its read count; ties go to the leftmost operation
+    pub fn strongest_alt(&self, start: i64, end: i64) -> Option<(AltSpan, usize)> {
+        let mut counts: BTreeMap<AltSpan, usize> = BTreeMap::new();
+        for read in &self.alt_reads {
+            for span in read
+                .spans
+                .iter()
+                .filter(|span| span.start < end && span.end > start)
+            {
+                *counts.entry(*span).or_default() += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .max_by(|(a, a_reads), (b, b_reads)| a_reads.cmp(b_reads).then(b.cmp(a)))
+    }
+}
+
+/// Per-locus inputs of profiling beyond the run parameters
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileOptions<'a> {
+    pub locus_span: (i64, i64),
+    pub amplicons: Option<&'a Amplicons>,
+    /// Keep the profiled reads in the scratch space for scorers
+    pub keep_reads: bool,
+    /// Profile at most this many reads, sampled uniformly
+    pub read_quota: Option<usize>,
+    /// Reference around the region, to left-align indels with
+    pub reference: Option<RefWindow<'a>>,
+}
+
+pub fn get_profile(
+    bam: &mut dyn AlignmentSource,
+    region: Region,
+    params: &Params,
+    scratch: &mut Scratch,
+    options: ProfileOptions,
+) -> Result<(Prof, SampleSupport)> {
+    let ProfileOptions {
+        locus_span,
+        amplicons,
+        keep_reads,
+        read_quota,
+        reference,
+    } = options;
+    let prof_len = region_len(region)?;
+    let Scratch {
+        covs,
+        dels,
+        alts,
+        clips,
+        alt_events,
+        read_end_events,
+        kept_reads,
+        read_secs,
+    } = scratch;
+    for counts in [
+        &mut *covs,
+        &mut *dels,
+        &mut *alts,
+        &mut *clips,
+        &mut *alt_events,
+        &mut *read_end_events,
+    ] {
+        counts.clear();
+        counts.resize(prof_len, 0);
+    }
+    let mut read_stats = ReadStats::default();
+    let mut skipped_reads = SkipCounts::default();
+    let read_start = Instant::now();
+    let reads = fetch_reads(
+        bam,
+        region,
+        locus_span,
+        params,
+        read_quota,
+        &mut read_stats,
+        &mut skipped_reads,
+    )?;
+    *read_secs += read_start.elapsed().as_secs_f64();
+
+    let mut support = SampleSupport::default();
+    let (mut spanning_reads, mut partial_reads) = (0, 0);
+    let mut chimeric_reads = 0;
+    let reads = reads
+        .into_iter()
+        .filter(|rec| {
+            let chimeric = params
+                .chimera_distance
+                .is_some_and(|distance| is_chimeric(rec, region.0, distance));
+            chimeric_reads += chimeric as usize;
+            !chimeric
+        })
         .collect_vec();
+    skipped_reads.chimeric = chimeric_reads;
+    for rec in &reads {
+        let (rec_start, rec_end) = (rec.pos(), rec.reference_end());
+        let spanning = rec_start <= locus_span.0 && rec_end >= locus_span.1;
+        if spanning {
+            spanning_reads += 1;
+        } else if rec_start < locus_span.1 && rec_end > locus_span.0 {
+            partial_reads += 1;
+        }
+
+        let mut alt_spans = Vec::new();
+        let (pos, ops) = profiled_ops(rec, reference);
+        record_alignment(
+            (pos, &ops),
+            covs,
+            alts,
+            clips,
+            region,
+            params.min_alt_len,
+            &mut alt_spans,
+        );
+        record_alt_events(
+            (pos, &ops),
+            alt_events,
+            read_end_events,
+            region,
+            params.read_end_len,
+        );
+        record_deletions((pos, &ops), dels, region);
+        if !alt_spans.is_empty() && (spanning || !params.require_spanning) {
+            support.alt_reads.push(AltRead {
+                spans: alt_spans,
+                haplotype: get_haplotype(rec),
+            });
+        }
+    }
+    if keep_reads {
+        kept_reads.extend(reads);
+    }
+
+    // Clipping at amplicon ends reflects library design rather than variation
+    for (pos, (alt, clip)) in alts.iter_mut().zip(clips.iter_mut()).enumerate() {
+        if *clip == 0 {
+            continue;
+        }
+        let near_primer = amplicons.is_some_and(|amplicons| {
+            let pos = region.1 + pos as i64;
+            amplicons.is_near_boundary(region.0, pos, params.amplicon_tolerance)
+        });
+        if near_primer {
+            *clip = 0;
+        } else {
+            *alt += *clip;
+        }
+    }
+
+    let depth = get_mean(covs);
+
+    let alts = match amplicons {
+        Some(amplicons) => normalize_per_amplicon(alts, covs, region, amplicons, depth),
+        None => alts
+            .iter()
+            .map(|v| *v as f64 / depth.max(1.0))
+            .collect_vec(),
+    };
 
-    Ok((Prof { alts, depth }, any_alt >= 3))
+    let prof = Prof {
+        alts,
+        clips: clips
+            .iter()
+            .map(|clip| *clip as f64 / depth.max(1.0))
+            .collect(),
+        covs: covs.iter().map(|cov| *cov as f64).collect(),
+        base_covs: covs
+            .iter()
+            .zip(dels.iter())
+            .map(|(cov, del)| cov.saturating_sub(*del) as f64)
+            .collect(),
+        depth,
+        spanning_reads,
+        partial_reads,
+        chimeric_reads,
+        read_stats,
+        skipped_reads,
+        alt_events: alt_events.clone(),
+        read_end_events: read_end_events.clone(),
+    };
+    Ok((prof, support))
 }
 
-pub fn update_profs(rec: Record, covs: &mut [u32], alts: &mut [u32], region: Region) -> bool {
-    assert_eq!(covs.len() as i64, region.2 - region.1);
-    assert_eq!(covs.len(), alts.len());
+/// Haplotype assigned to the read by phasing tools in its `HP` tag
+fn get_haplotype(rec: &Record) -> Option<i64> {
+    int_tag(rec, b"HP")
+}
+
+/// Value of an integer tag of any width
+fn int_tag(rec: &Record, tag: &[u8]) -> Option<i64> {
+    match rec.aux(tag).ok()? {
+        Aux::I8(hp) => Some(hp as i64),
+        Aux::U8(hp) => Some(hp as i64),
+        Aux::I16(hp) => Some(hp as i64),
+        Aux::U16(hp) => Some(hp as i64),
+        Aux::I32(hp) => Some(hp as i64),
+        Aux::U32(hp) => Some(hp as i64),
+        _ => None,
+    }
+}
+
+/// Whether any supplementary alignment of the read listed in its `SA` tag lies
+/// on another contig or further than `max_distance` away
+fn is_chimeric(rec: &Record, contig: &str, max_distance: i64) -> bool {
+    let Ok(Aux::String(sa)) = rec.aux(b"SA") else {
+        return false;
+    };
+    sa.split(';')
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            let mut fields = entry.split(',');
+            let sa_contig = fields.next();
+            let sa_pos = fields.next().and_then(|pos| pos.parse::<i64>().ok());
+            match (sa_contig, sa_pos) {
+                (Some(sa_contig), Some(sa_pos)) => {
+                    sa_contig != contig || (sa_pos - 1 - rec.pos()).abs() > max_distance
+                }
+                _ => false,
+            }
+        })
+}
+
+/// Number of reads passing the alignment filters in the region
+pub fn count_reads(
+    bam: &mut dyn AlignmentSource,
+    region: Region,
+    params: &Params,
+) -> Result<usize> {
+    bam.fetch(region)?;
+    let mut count = 0;
+    while let Some(rec) = bam.next_record() {
+        if passes_filters(&rec?, params) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+pub(crate) fn passes_filters(rec: &Record, params: &Params) -> bool {
+    skip_reason(rec, params).is_none()
+}
 
-    let mut ref_pos = rec.pos();
+fn skip_reason(rec: &Record, params: &Params) -> Option<SkipReason> {
+    if let Some(malformation) = malformation(rec) {
+        Some(SkipReason::Malformed(malformation))
+    } else if rec.is_secondary() {
+        Some(SkipReason::Secondary)
+    } else if rec.is_supplementary() && !params.keep_supplementary {
+        Some(SkipReason::Supplementary)
+    } else if rec.mapq() < params.min_mapq {
+        Some(SkipReason::Mapq)
+    } else if params.ont_read_class == OntReadClass::Duplex && int_tag(rec, b"dx") != Some(1) {
+        Some(SkipReason::ReadClass)
+    } else {
+        None
+    }
+}
+
+/// Fetch the reads used for profiling; in high-copy regions a uniform subsample of
+/// reads is kept instead of rejecting the region for exceeding the read cap, a
+/// uniform subsample is also taken if the number of reads exceeds the quota, and
+/// with early stopping the reads are thinned once the sample is decided
+fn fetch_reads(
+    bam: &mut dyn AlignmentSource,
+    region: Region,
+    locus_span: (i64, i64),
+    params: &Params,
+    read_quota: Option<usize>,
+    read_stats: &mut ReadStats,
+    skipped_reads: &mut SkipCounts,
+) -> Result<Vec<Record>> {
+    let high_copy = params.is_high_copy(region.0);
+    let cap = if high_copy {
+        Some(read_quota.map_or(params.max_reads, |quota| quota.min(params.max_reads)))
+    } else {
+        read_quota
+    };
+    // Seeded by region rather than per worker, so subsampling does not depend
+    // on which thread processes the locus
+    let mut rng = StdRng::seed_from_u64(params.seed ^ region.1 as u64);
+    let mut subsample_rng = StdRng::seed_from_u64(params.seed.rotate_left(32) ^ region.1 as u64);
+    let mut thin_rng = StdRng::seed_from_u64(params.seed.rotate_left(16) ^ region.1 as u64);
+    let mut reads = Vec::new();
+    let mut n_seen = 0;
+    // Only reads spanning the whole region count towards stopping. Reads come
+    // in position order, so those starting within the region, which cover its
+    // right flank, follow every spanning read: once decided, further spanning
+    // reads are skipped and the other reads are thinned at the end alike
+    let mut early_stop = params
+        .early_stop_confidence
+        .map(|confidence| EarlyStop::new(confidence, params.min_alt_reads));
+    let mut skipped_spanning = 0;
+    let spans_region = |rec: &Record| rec.pos() <= region.1 && rec.reference_end() >= region.2;
+
+    bam.fetch(region)?;
+    let mut n_records = 0;
+    while let Some(rec) = bam.next_record() {
+        let rec = rec?;
+        let index = n_records;
+        n_records += 1;
+        let reason = skip_reason(&rec, params);
+        // Malformed records have no meaningful MAPQ or clips
+        if !rec.is_secondary() && !matches!(reason, Some(SkipReason::Malformed(_))) {
+            read_stats.add(&rec);
+        }
+
+        if let Some(reason) = reason {
+            skipped_reads.add(reason);
+            continue;
+        }
+
+        // Absolute max depth, up to the point where the sample is decided
+        let stopped = early_stop.as_ref().is_some_and(EarlyStop::is_decided);
+        if !high_copy && !stopped && index >= params.max_reads {
+            return Err(VclustError::Depth("High depth".to_string()));
+        }
+        if params
+            .read_fraction
+            .is_some_and(|fraction| subsample_rng.gen::<f64>() >= fraction)
+        {
+            continue;
+        }
+        if let Some(early_stop) = early_stop.as_mut().filter(|_| spans_region(&rec)) {
+            if early_stop.is_decided() {
+                skipped_spanning += 1;
+                continue;
+            }
+            early_stop.add(has_alt_within(&rec, locus_span, params.min_alt_len));
+        }
+
+        match cap {
+            // Reservoir sampling
+            Some(cap) if reads.len() >= cap => {
+                let slot = rng.gen_range(0..=n_seen);
+                if slot < cap {
+                    reads[slot] = rec;
+                }
+            }
+            _ => reads.push(rec),
+        }
+        n_seen += 1;
+    }
+
+    // Keep the reads that do not span the region at the rate spanning reads
+    // were kept, so that both flanks lose the same share of their depth
+    if let Some(early_stop) = early_stop.filter(|_| skipped_spanning > 0) {
+        let rate = early_stop.reads() as f64 / (early_stop.reads() + skipped_spanning) as f64;
+        reads.retain(|rec| spans_region(rec) || thin_rng.gen::<f64>() < rate);
+    }
+
+    Ok(reads)
+}
+
+/// Add a read to the coverage, alt, and soft clip counts of the region,
+/// returning whether it carries an alt operation of at least `min_alt_len`;
+/// the counts must be as long as the region
+pub fn update_profs(
+    rec: &Record,
+    covs: &mut [u32],
+    alts: &mut [u32],
+    clips: &mut [u32],
+    region: Region,
+    min_alt_len: usize,
+) -> Result<bool> {
+    let len = region_len(region)?;
+    if [covs.len(), alts.len(), clips.len()] != [len; 3] {
+        return Err(VclustError::Region(format!(
+            "Counts do not match the {len} bp region {}:{}-{}",
+            region.0, region.1, region.2
+        )));
+    }
+    let mut alt_spans = Vec::new();
+    let (pos, ops) = profiled_ops(rec, None);
+    record_alignment(
+        (pos, &ops),
+        covs,
+        alts,
+        clips,
+        region,
+        min_alt_len,
+        &mut alt_spans,
+    );
+    Ok(!alt_spans.is_empty())
+}
+
+/// Length of a region, which must start at a nonnegative position before its
+/// end
+pub fn region_len((chrom, start, end): Region) -> Result<usize> {
+    if start < 0 || end <= start {
+        return Err(VclustError::Region(format!(
+            "Bad region {chrom}:{start}-{end}"
+        )));
+    }
+    Ok((end - start) as usize)
+}
+
+/// Whether a read carries an alt operation of at least `min_alt_len` within
+/// [start, end)
+fn has_alt_within(rec: &Record, (start, end): (i64, i64), min_alt_len: usize) -> bool {
+    let (mut ref_pos, ops) = profiled_ops(rec, None);
+    for op in &ops {
+        let op_len = get_ref_len(op);
+        if ref_pos >= end {
+            break;
+        }
+        let overlaps = ref_pos + op_len > start;
+        match op {
+            CigarOp::Diff(len) | CigarOp::Del(len) if overlaps && *len as usize >= min_alt_len => {
+                return true
+            }
+            CigarOp::Ins(len) if ref_pos >= start && *len as usize >= min_alt_len => return true,
+            _ => {}
+        }
+        ref_pos += op_len;
+    }
+    false
+}
+
+/// Normalized alignment operations of a read and their reference start, with
+/// indels left-aligned if the reference is given
+pub(crate) fn profiled_ops(rec: &Record, reference: Option<RefWindow>) -> (i64, Vec<CigarOp>) {
+    let cigar = rec.cigar();
+    let cs_ops = get_cs_ops(rec, &cigar);
+    let (pos, ops) = normalize_ops(rec.pos(), cs_ops.as_deref().unwrap_or(&cigar));
+    match reference {
+        Some(reference) => left_align_indels(pos, &ops, &rec.seq().as_bytes(), reference),
+        None => (pos, ops),
+    }
+}
+
+/// Add the alignment operations of a read and their reference start to the
+/// counts of the region, collecting the reference intervals of its alt operations of at
+/// least `min_alt_len` in `alt_spans`
+fn record_alignment(
+    (pos, ops): (i64, &[CigarOp]),
+    covs: &mut [u32],
+    alts: &mut [u32],
+    clips: &mut [u32],
+    region: Region,
+    min_alt_len: usize,
+    alt_spans: &mut Vec<AltSpan>,
+) {
+    // Checked by the callers with region_len
+    debug_assert_eq!(covs.len() as i64, region.2 - region.1);
+    debug_assert_eq!(covs.len(), alts.len());
+    debug_assert_eq!(covs.len(), clips.len());
+
+    let mut ref_pos = pos;
     let region_start = region.1;
     let region_end = region.2;
-    let mut any_alt = false;
-    for op in rec.cigar().iter() {
+    for op in ops.iter() {
         let op_len = get_ref_len(op);
 
         // Skip operations entirely before the region
@@ -90,7 +824,17 @@ pub fn update_profs(rec: Record, covs: &mut [u32], alts: &mut [u32], region: Reg
                     *cov += 1;
                     *alt += 1;
                 }
-                any_alt |= clipped_len >= 5;
+                if clipped_len >= min_alt_len {
+                    let kind = match op {
+                        CigarOp::Del(_) => AltKind::Deletion,
+                        _ => AltKind::Mismatch,
+                    };
+                    alt_spans.push(AltSpan {
+                        start: clipped_start,
+                        end: clipped_end,
+                        kind,
+                    });
+                }
             }
             CigarOp::Ins(len) => {
                 // Insertions don't consume reference but we can still bump alt at insertion site
@@ -98,17 +842,24 @@ pub fn update_profs(rec: Record, covs: &mut [u32], alts: &mut [u32], region: Reg
                     let idx = (ref_pos - region_start) as usize;
                     alts[idx] += *len;
                 }
-                any_alt |= clipped_len >= 5;
+                // Insertions consume no reference, so their own length counts
+                if *len as usize >= min_alt_len {
+                    alt_spans.push(AltSpan {
+                        start: clipped_start,
+                        end: clipped_start + 1,
+                        kind: AltKind::Insertion,
+                    });
+                }
             }
             CigarOp::SoftClip(_) => {
                 if ref_pos >= region_start && ref_pos < region_end {
                     let idx = (ref_pos - region_start) as usize;
-                    alts[idx] += 1;
+                    clips[idx] += 1;
                 }
             }
-            CigarOp::HardClip(_) | CigarOp::Pad(_) | CigarOp::RefSkip(_) => {
-                panic!("Unexpected operation {:?}", op);
-            }
+            // Spliced gaps hold no read bases; clips and padding are dropped
+            // by normalization
+            CigarOp::RefSkip(_) | CigarOp::HardClip(_) | CigarOp::Pad(_) => {}
         }
 
         // Advance the reference position if this op consumes ref
@@ -117,15 +868,183 @@ pub fn update_profs(rec: Record, covs: &mut [u32], alts: &mut [u32], region: Reg
             _ => op_len,
         };
     }
-    //Return
-    any_alt
+}
+
+/// Count mismatch runs and indels of a read at their reference positions, and
+/// separately those within `read_end_len` aligned bases of either end of the read
+pub fn count_alt_events(
+    rec: &Record,
+    events: &mut [u32],
+    read_end_events: &mut [u32],
+    region: Region,
+    read_end_len: i64,
+) {
+    let (pos, ops) = profiled_ops(rec, None);
+    record_alt_events((pos, &ops), events, read_end_events, region, read_end_len);
+}
+
+fn record_alt_events(
+    (pos, ops): (i64, &[CigarOp]),
+    events: &mut [u32],
+    read_end_events: &mut [u32],
+    region: Region,
+    read_end_len: i64,
+) {
+    let mut ref_pos = pos;
+    let query_len = ops.iter().map(get_query_len).sum::<i64>();
+    let soft_clip_len = |op: Option<&CigarOp>| match op {
+        Some(CigarOp::SoftClip(len)) => *len as i64,
+        _ => 0,
+    };
+    let aligned_start = soft_clip_len(ops.first());
+    let aligned_end = query_len - soft_clip_len(ops.last().filter(|_| ops.len() > 1));
+    let mut query_pos = 0;
+    for op in ops.iter() {
+        if ref_pos >= region.2 {
+            break;
+        }
+        let is_event = matches!(op, CigarOp::Diff(_) | CigarOp::Del(_) | CigarOp::Ins(_));
+        if is_event && ref_pos >= region.1 {
+            let index = (ref_pos - region.1) as usize;
+            events[index] += 1;
+            let end_dist = (query_pos - aligned_start).min(aligned_end - query_pos);
+            if end_dist < read_end_len {
+                read_end_events[index] += 1;
+            }
+        }
+        ref_pos += get_ref_len(op);
+        query_pos += get_query_len(op);
+    }
+}
+
+/// Count the reads deleting each position of the region
+fn record_deletions((pos, ops): (i64, &[CigarOp]), dels: &mut [u32], region: Region) {
+    let mut ref_pos = pos;
+    for op in ops.iter() {
+        if ref_pos >= region.2 {
+            break;
+        }
+        let op_len = get_ref_len(op);
+        if let CigarOp::Del(_) = op {
+            let start = ref_pos.max(region.1);
+            let end = (ref_pos + op_len).min(region.2);
+            for del in dels
+                .iter_mut()
+                .take((end - region.1).max(0) as usize)
+                .skip((start - region.1).max(0) as usize)
+            {
+                *del += 1;
+            }
+        }
+        ref_pos += op_len;
+    }
+}
+
+/// Alignment operations derived from the minimap2 `cs` tag, if the read has one;
+/// it pinpoints mismatches even when the CIGAR only has `M` operations
+fn get_cs_ops(rec: &Record, cigar: &[CigarOp]) -> Option<Vec<CigarOp>> {
+    let Ok(Aux::String(cs)) = rec.aux(b"cs") else {
+        return None;
+    };
+    // Soft clips are not part of the cs tag
+    let mut ops = Vec::new();
+    if let Some(op @ CigarOp::SoftClip(_)) = cigar.first() {
+        ops.push(*op);
+    }
+    parse_cs(cs, &mut ops)?;
+    if let Some(op @ CigarOp::SoftClip(_)) = cigar.last().filter(|_| cigar.len() > 1) {
+        ops.push(*op);
+    }
+    Some(ops)
+}
+
+/// Append the operations of a short or long form cs string, merging runs of
+/// substitutions into single mismatch operations
+fn parse_cs(cs: &str, ops: &mut Vec<CigarOp>) -> Option<()> {
+    let bytes = cs.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        let kind = bytes[index];
+        let start = index + 1;
+        let mut end = start;
+        let op = match kind {
+            b':' => {
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+                CigarOp::Equal(cs[start..end].parse().ok()?)
+            }
+            b'*' => {
+                end = start + 2;
+                if end > bytes.len() {
+                    return None;
+                }
+                CigarOp::Diff(1)
+            }
+            b'=' | b'+' | b'-' => {
+                while end < bytes.len() && bytes[end].is_ascii_alphabetic() {
+                    end += 1;
+                }
+                let len = (end - start) as u32;
+                match kind {
+                    b'=' => CigarOp::Equal(len),
+                    b'+' => CigarOp::Ins(len),
+                    _ => CigarOp::Del(len),
+                }
+            }
+            // Introns and unknown operations are left to the CIGAR
+            _ => return None,
+        };
+        match (ops.last_mut(), op) {
+            (Some(CigarOp::Diff(len)), CigarOp::Diff(1)) => *len += 1,
+            _ => ops.push(op),
+        }
+        index = end;
+    }
+    Some(())
+}
+
+/// Normalize alt counts by the mean depth of the amplicon covering each position,
+/// falling back to the region-wide mean outside of amplicons
+fn normalize_per_amplicon(
+    alts: &[u32],
+    covs: &[u32],
+    region: Region,
+    amplicons: &Amplicons,
+    depth: f64,
+) -> Vec<f64> {
+    let mut norms = vec![depth; alts.len()];
+    for (start, end) in amplicons.overlapping(region.0, region.1, region.2) {
+        let start = (start.max(region.1) - region.1) as usize;
+        let end = (end.min(region.2) - region.1) as usize;
+        if start >= end {
+            continue;
+        }
+        let amplicon_depth = get_mean(&covs[start..end]);
+        norms[start..end].fill(amplicon_depth);
+    }
+    alts.iter()
+        .zip(norms)
+        .map(|(alt, norm)| *alt as f64 / norm.max(1.0))
+        .collect()
 }
 
 fn get_mean(vals: &[u32]) -> f64 {
     vals.iter().sum::<u32>() as f64 / vals.len() as f64
 }
 
-fn get_ref_len(op: &CigarOp) -> i64 {
+fn get_query_len(op: &CigarOp) -> i64 {
+    match op {
+        CigarOp::Match(len)
+        | CigarOp::Ins(len)
+        | CigarOp::SoftClip(len)
+        | CigarOp::Equal(len)
+        | CigarOp::Diff(len) => *len as i64,
+        CigarOp::Del(_) | CigarOp::RefSkip(_) | CigarOp::HardClip(_) | CigarOp::Pad(_) => 0,
+    }
+}
+
+pub(crate) fn get_ref_len(op: &CigarOp) -> i64 {
     match op {
         CigarOp::Match(len)
         | CigarOp::RefSkip(len)
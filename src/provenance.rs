@@ -0,0 +1,166 @@
+use crate::atomic::AtomicFile;
+use crate::context::{ContextBuilder, Params};
+use crate::error::Result;
+use crate::models::Models;
+use crate::selection::ModelRule;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Audit trail of a run: what went in, how it was processed, and what came out
+#[derive(Debug, Serialize)]
+pub struct RunManifest {
+    pub version: String,
+    pub command_line: Vec<String>,
+    pub host: String,
+    pub started_at: String,
+    pub duration_secs: f64,
+    pub params: Params,
+    /// SHA-256 of the model probabilities and priors, the ONNX classifier,
+    /// and the model rules
+    pub model_sha256: String,
+    pub inputs: Vec<FileRecord>,
+    pub outputs: Vec<FileRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileRecord {
+    pub role: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl FileRecord {
+    pub fn from_path(role: &str, path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut reader, &mut hasher)?;
+        Ok(FileRecord {
+            role: role.to_string(),
+            path: path.to_path_buf(),
+            size,
+            sha256: hex(&hasher.finalize()),
+        })
+    }
+}
+
+impl RunManifest {
+    /// Record the run inputs, given as (role, path) pairs in addition to the
+    /// reference and BAMs; duration and outputs are filled in once the run ends
+    pub fn new(
+        builder: &ContextBuilder,
+        input_paths: &[(&str, &Path)],
+        started_at: String,
+    ) -> Result<Self> {
+        let mut inputs = vec![FileRecord::from_path("genome", &builder.genome_path)?];
+        for (role, path) in input_paths {
+            inputs.push(FileRecord::from_path(role, path)?);
+        }
+        for entry in &builder.reads {
+            inputs.push(FileRecord::from_path(
+                &format!("bam:{}", entry.sample),
+                &entry.path,
+            )?);
+        }
+        Ok(RunManifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            command_line: std::env::args().collect(),
+            host: hostname(),
+            started_at,
+            duration_secs: 0.0,
+            params: (*builder.params).clone(),
+            model_sha256: model_checksum(&builder.models, &builder.params.model_rules),
+            inputs,
+            outputs: Vec::new(),
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = AtomicFile::create(path)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.commit()
+    }
+}
+
+/// Writer that keeps a running SHA-256 and size of everything written through it
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub fn record(&self, role: &str, path: &Path) -> FileRecord {
+        FileRecord {
+            role: role.to_string(),
+            path: path.to_path_buf(),
+            size: self.size,
+            sha256: hex(&self.hasher.clone().finalize()),
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.size += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) fn model_checksum(models: &Models, rules: &[ModelRule]) -> String {
+    let mut hasher = Sha256::new();
+    for model in [&models.reference, &models.cluster] {
+        for prob in model.probs.iter().chain([&model.prior]) {
+            hasher.update(prob.to_le_bytes());
+        }
+    }
+    let clip_bound = models.alphabet.clip_min_fraction;
+    for bound in models
+        .alphabet
+        .lower_bounds
+        .iter()
+        .chain(clip_bound.as_ref())
+    {
+        hasher.update(bound.to_le_bytes());
+    }
+    if let Some(classifier) = &models.classifier {
+        hasher.update(classifier.sha256.as_bytes());
+    }
+    for rule in rules {
+        hasher.update(format!("{rule:?}").as_bytes());
+    }
+    hex(&hasher.finalize())
+}
+
+fn hostname() -> String {
+    let mut name = String::new();
+    match File::open("/proc/sys/kernel/hostname").and_then(|mut f| f.read_to_string(&mut name)) {
+        Ok(_) => name.trim().to_string(),
+        Err(_) => std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string()),
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,165 @@
+use crate::error::{Result, VclustError};
+use crate::workflow::LocusResult;
+use itertools::Itertools;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Version of the tabular output layout; bump whenever `COLUMNS` changes
+pub const SCHEMA_VERSION: u32 = 6;
+
+pub const COLUMNS: &[&str] = &[
+    "name",
+    "region",
+    "lf_offset",
+    "rf_offset",
+    "extended_region",
+    "ns",
+    "spanning_reads",
+    "partial_reads",
+    "chimeric_reads",
+    "coverage_gaps",
+    "ladder_offsets",
+    "read_end_bias",
+];
+
+/// Columns of headerless output produced before schema versioning was introduced
+const V0_COLUMNS: &[&str] = &[
+    "name",
+    "region",
+    "lf_offset",
+    "rf_offset",
+    "extended_region",
+    "ns",
+];
+
+pub const VERSION_PREFIX: &str = "#vclust_schema_version=";
+
+pub fn header() -> String {
+    header_with(&[])
+}
+
+/// Header listing additional columns produced by custom scorers
+pub fn header_with(score_columns: &[String]) -> String {
+    let columns = COLUMNS
+        .iter()
+        .copied()
+        .chain(score_columns.iter().map(String::as_str))
+        .join("\t");
+    format!("{VERSION_PREFIX}{SCHEMA_VERSION}\n#{columns}")
+}
+
+pub fn format_row(result: &LocusResult) -> String {
+    let row = format_standard_row(result);
+    if result.scores.is_empty() {
+        row
+    } else {
+        format!("{row}\t{}", result.scores.join("\t"))
+    }
+}
+
+fn format_standard_row(result: &LocusResult) -> String {
+    let locus = &result.locus;
+    let in_region = format!("{}:{}-{}", locus.chrom, locus.start, locus.end);
+    match &result.extension {
+        Some(ext) => {
+            let (lf, rf) = (ext.lf_offset, ext.rf_offset);
+            let out_region = format!("{}:{}-{}", locus.chrom, locus.start - lf, locus.end + rf);
+            let gaps = if ext.coverage_gaps.is_empty() {
+                ".".to_string()
+            } else {
+                ext.coverage_gaps
+                    .iter()
+                    .map(|(start, end)| format!("{start}-{end}"))
+                    .join(",")
+            };
+            let ladder = format_ladder_offsets(&ext.ladder_offsets);
+            let bias = ext
+                .read_end_bias
+                .map_or(".".to_string(), |bias| format!("{bias:.2}"));
+            format!(
+                "{}\t{in_region}\t{lf}\t{rf}\t{out_region}\t{}\t{}\t{}\t{}\t{gaps}\t{ladder}\t{bias}",
+                locus.name, ext.ns, ext.spanning_reads, ext.partial_reads, ext.chimeric_reads
+            )
+        }
+        None => {
+            let missing = vec!["NA"; COLUMNS.len() - 2].join("\t");
+            format!("{}\t{in_region}\t{missing}", locus.name)
+        }
+    }
+}
+
+/// Offsets at each window length written as `window:lf_offset:rf_offset`
+pub fn format_ladder_offsets(offsets: &[(i64, i64, i64)]) -> String {
+    if offsets.is_empty() {
+        return ".".to_string();
+    }
+    offsets
+        .iter()
+        .map(|(window, lf, rf)| format!("{window}:{lf}:{rf}"))
+        .join(",")
+}
+
+/// Rewrite a results file produced by any earlier vclust release in the current schema
+pub fn migrate(path: &Path, writer: &mut impl Write) -> Result<()> {
+    let file = File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+
+    let mut pending = None;
+    let (version, columns) = match lines.next() {
+        None => (0, to_strings(V0_COLUMNS)),
+        Some(line) => {
+            let line = line?;
+            if let Some(version) = line.strip_prefix(VERSION_PREFIX) {
+                let version = version
+                    .parse::<u32>()
+                    .map_err(|_| VclustError::Parse(format!("Bad schema version line {line}")))?;
+                let columns = match lines.next() {
+                    Some(line) => line?,
+                    None => return Err(VclustError::Parse("Missing column header".to_string())),
+                };
+                let columns = columns
+                    .strip_prefix('#')
+                    .ok_or(VclustError::Parse(format!("Bad column header {columns}")))?;
+                (version, columns.split('\t').map(String::from).collect())
+            } else {
+                pending = Some(line);
+                (0, to_strings(V0_COLUMNS))
+            }
+        }
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(VclustError::Parse(format!(
+            "Schema version {version} is newer than supported version {SCHEMA_VERSION}"
+        )));
+    }
+    log::info!("Migrating schema version {version} to {SCHEMA_VERSION}");
+
+    writeln!(writer, "{}", header())?;
+    let rows = pending.into_iter().map(Ok).chain(lines);
+    for line in rows {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let row = COLUMNS
+            .iter()
+            .map(|column| {
+                columns
+                    .iter()
+                    .position(|c| c == column)
+                    .and_then(|index| fields.get(index).copied())
+                    .unwrap_or("NA")
+            })
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", row.join("\t"))?;
+    }
+
+    Ok(())
+}
+
+fn to_strings(columns: &[&str]) -> Vec<String> {
+    columns.iter().map(|c| c.to_string()).collect()
+}
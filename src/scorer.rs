@@ -0,0 +1,16 @@
+use crate::locus::Locus;
+use crate::profile::Prof;
+use rust_htslib::bam::Record;
+
+/// Custom per-locus score added to the output as extra columns
+///
+/// Scorers are registered on the `ContextBuilder` and invoked for every locus
+/// that passes the depth checks, after the profiles of all samples are pooled.
+pub trait LocusScorer: Send + Sync {
+    /// Names of the output columns; appended after the standard columns
+    fn columns(&self) -> Vec<String>;
+
+    /// One value per column computed from the pooled profile and the reads of
+    /// all samples used to build it
+    fn score(&self, locus: &Locus, prof: &Prof, reads: &[Record]) -> Vec<String>;
+}
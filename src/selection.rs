@@ -0,0 +1,156 @@
+use crate::error::{Result, VclustError};
+use crate::expression::Expression;
+use crate::models::Models;
+use itertools::Itertools;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub const MODEL_COLUMN: &str = "model";
+/// Name reported for loci where no rule applies
+pub const DEFAULT_MODEL: &str = "default";
+/// Names of the locus context available to rule conditions
+pub const SELECTION_VARIABLES: &[&str] = &["depth", "gc", "homopolymer"];
+/// Shortest run of a single base counted towards the homopolymer content
+pub const MIN_HOMOPOLYMER_LEN: usize = 6;
+
+/// Variant of the models to use at loci whose context satisfies a condition
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelRule {
+    pub name: String,
+    pub condition: Expression,
+    /// Prior of the reference model, if different from the base models
+    pub prior_ref: Option<f64>,
+    /// Laplace smoothing added to the emissions of the base models, if any
+    pub pseudocount: Option<f64>,
+}
+
+/// Properties of the search window of a locus used to select its models
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocusContext {
+    /// Mean pooled depth
+    pub depth: f64,
+    /// Fraction of G and C among the called reference bases
+    pub gc: f64,
+    /// Fraction of the reference bases in homopolymer runs
+    pub homopolymer: f64,
+}
+
+/// Load model rules from a file with columns name, condition, reference prior,
+/// and pseudocount, where `.` keeps the value of the base models
+pub fn load_model_rules(path: &Path) -> Result<Vec<ModelRule>> {
+    let file = File::open(path)?;
+    let mut rules: Vec<ModelRule> = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split('\t').collect_vec();
+        let [name, condition, prior_ref, pseudocount] = fields[..] else {
+            return Err(VclustError::Parse(format!("Bad model rule line {line}")));
+        };
+        let value = |field: &str| match field {
+            "." => Ok(None),
+            _ => field.parse::<f64>().map(Some).map_err(|_| {
+                VclustError::Parse(format!("Bad number {field} in model rule {name}"))
+            }),
+        };
+        let rule = ModelRule {
+            name: name.to_string(),
+            condition: condition.parse()?,
+            prior_ref: value(prior_ref)?,
+            pseudocount: value(pseudocount)?,
+        };
+        let known = SELECTION_VARIABLES
+            .iter()
+            .map(|v| v.to_string())
+            .collect_vec();
+        rule.condition.check_variables(&known)?;
+        if rule
+            .prior_ref
+            .is_some_and(|prior| prior <= 0.0 || prior >= 1.0)
+        {
+            return Err(VclustError::Parse(format!(
+                "Reference prior of model rule {name} must be in (0, 1)"
+            )));
+        }
+        if rule
+            .pseudocount
+            .is_some_and(|pseudocount| pseudocount <= 0.0)
+        {
+            return Err(VclustError::Parse(format!(
+                "Pseudocount of model rule {name} must be positive"
+            )));
+        }
+        if rule.name == DEFAULT_MODEL {
+            return Err(VclustError::Parse(format!(
+                "Model rule name {DEFAULT_MODEL} is reserved for loci matching no rule"
+            )));
+        }
+        if rules.iter().any(|other| other.name == rule.name) {
+            return Err(VclustError::Parse(format!(
+                "Model rule name {name} is used more than once"
+            )));
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+impl ModelRule {
+    /// Base models adjusted by the rule
+    pub fn apply(&self, models: &Models) -> Models {
+        let mut models = models.clone();
+        if let Some(prior_ref) = self.prior_ref {
+            models.reference.prior = prior_ref;
+            models.cluster.prior = 1.0 - prior_ref;
+        }
+        match self.pseudocount {
+            Some(pseudocount) => models.with_pseudocount(pseudocount),
+            None => models,
+        }
+    }
+}
+
+/// Index of the first rule whose condition holds at the locus; conditions
+/// that cannot be evaluated do not hold
+pub fn select_rule(rules: &[ModelRule], context: &LocusContext) -> Option<usize> {
+    let lookup = |name: &str| match name {
+        "depth" => Some(context.depth),
+        "gc" => Some(context.gc),
+        "homopolymer" => Some(context.homopolymer),
+        _ => None,
+    };
+    rules.iter().position(|rule| {
+        rule.condition
+            .eval(&lookup)
+            .is_some_and(|value| value != 0.0)
+    })
+}
+
+impl LocusContext {
+    pub fn new(depth: f64, seq: &[u8]) -> Self {
+        let called = seq
+            .iter()
+            .filter(|base| !matches!(base, b'N' | b'n'))
+            .count();
+        let gc = seq
+            .iter()
+            .filter(|base| matches!(base, b'G' | b'g' | b'C' | b'c'))
+            .count();
+        let homopolymer = seq
+            .iter()
+            .map(|base| base.to_ascii_uppercase())
+            .dedup_with_count()
+            .filter(|(len, base)| *len >= MIN_HOMOPOLYMER_LEN && *base != b'N')
+            .map(|(len, _)| len)
+            .sum::<usize>();
+        LocusContext {
+            depth,
+            gc: gc as f64 / called.max(1) as f64,
+            homopolymer: homopolymer as f64 / seq.len().max(1) as f64,
+        }
+    }
+}
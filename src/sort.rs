@@ -0,0 +1,157 @@
+use crate::error::Result;
+use crate::workflow::LocusResult;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Results held in memory before a sorted run of them is spilled to disk
+pub const SORT_CHUNK_RECORDS: usize = 100_000;
+
+/// Sorters created so far, to name the spilled runs of each uniquely
+static SORTERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Contig order in the reference, followed by the contig name for contigs
+/// missing from it, then the extended region, or the region itself if it could
+/// not be extended, and the name
+type SortKey = (usize, String, i64, i64, String);
+
+/// External merge sort of results by coordinate: sorted runs of at most
+/// `chunk_records` results are spilled to temporary files as JSON lines, and
+/// merged once all results are in
+pub struct ResultSorter {
+    contig_order: HashMap<String, usize>,
+    chunk_records: usize,
+    buffer: Vec<LocusResult>,
+    tmp_dir: PathBuf,
+    prefix: String,
+    runs: Vec<PathBuf>,
+}
+
+impl ResultSorter {
+    /// `contigs` are the names of the reference contigs in order
+    pub fn new(contigs: &[String], chunk_records: usize, tmp_dir: &Path) -> Self {
+        let sorter = SORTERS.fetch_add(1, Ordering::Relaxed);
+        ResultSorter {
+            contig_order: contigs
+                .iter()
+                .enumerate()
+                .map(|(index, name)| (name.clone(), index))
+                .collect(),
+            chunk_records: chunk_records.max(1),
+            buffer: Vec::new(),
+            tmp_dir: tmp_dir.to_path_buf(),
+            prefix: format!("vclust-sort-{}-{sorter}", std::process::id()),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, result: LocusResult) -> Result<()> {
+        self.buffer.push(result);
+        if self.buffer.len() >= self.chunk_records {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of runs spilled to disk so far
+    pub fn n_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn sort_key(&self, result: &LocusResult) -> SortKey {
+        let locus = &result.locus;
+        let (start, end) = match &result.extension {
+            Some(ext) => (locus.start - ext.lf_offset, locus.end + ext.rf_offset),
+            None => (locus.start, locus.end),
+        };
+        match self.contig_order.get(&locus.chrom) {
+            Some(order) => (*order, String::new(), start, end, locus.name.clone()),
+            None => (
+                usize::MAX,
+                locus.chrom.clone(),
+                start,
+                end,
+                locus.name.clone(),
+            ),
+        }
+    }
+
+    fn sort_buffer(&mut self) {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_by_cached_key(|result| self.sort_key(result));
+        self.buffer = buffer;
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.sort_buffer();
+        let path = self
+            .tmp_dir
+            .join(format!("{}-{}.jsonl", self.prefix, self.runs.len()));
+        self.runs.push(path.clone());
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for result in self.buffer.drain(..) {
+            serde_json::to_writer(&mut writer, &result)?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Pass all results to `write` in coordinate order
+    pub fn finish(mut self, mut write: impl FnMut(LocusResult) -> Result<()>) -> Result<()> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+            for result in std::mem::take(&mut self.buffer) {
+                write(result)?;
+            }
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let mut readers = self
+            .runs
+            .iter()
+            .map(|path| Ok(BufReader::new(File::open(path)?).lines()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut heads = Vec::with_capacity(readers.len());
+        let mut heap = BinaryHeap::new();
+        for (run, reader) in readers.iter_mut().enumerate() {
+            let head = next_result(reader)?;
+            if let Some(result) = &head {
+                heap.push(Reverse((self.sort_key(result), run)));
+            }
+            heads.push(head);
+        }
+        while let Some(Reverse((_, run))) = heap.pop() {
+            let result = heads[run]
+                .take()
+                .expect("Merged run without a pending result");
+            heads[run] = next_result(&mut readers[run])?;
+            if let Some(next) = &heads[run] {
+                heap.push(Reverse((self.sort_key(next), run)));
+            }
+            write(result)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ResultSorter {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn next_result(reader: &mut Lines<BufReader<File>>) -> Result<Option<LocusResult>> {
+    match reader.next().transpose()? {
+        Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+        None => Ok(None),
+    }
+}
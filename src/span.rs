@@ -0,0 +1,48 @@
+use crate::error::Result;
+use crate::provenance::hex;
+use clap::ValueEnum;
+use rust_htslib::faidx;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Reference of the extended span reported with each cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanReferenceMode {
+    /// The sequence of the span and its SHA-256 checksum
+    Sequence,
+    /// Only the SHA-256 checksum of the sequence
+    Sha256,
+}
+
+/// Reference sequence of the extended span of a cluster, uppercased so that
+/// soft-masked and unmasked references agree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanReference {
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<String>,
+}
+
+/// Reference of the half-open interval `start..end` of a contig
+pub fn span_reference(
+    reference: &faidx::Reader,
+    chrom: &str,
+    (start, end): (i64, i64),
+    mode: SpanReferenceMode,
+) -> Result<SpanReference> {
+    let seq = match end > start {
+        // Fetched intervals are closed
+        true => reference
+            .fetch_seq(chrom, start as usize, end as usize - 1)?
+            .to_ascii_uppercase(),
+        false => Vec::new(),
+    };
+    Ok(SpanReference {
+        sha256: hex(&Sha256::digest(&seq)),
+        sequence: match mode {
+            SpanReferenceMode::Sequence => Some(String::from_utf8_lossy(&seq).into_owned()),
+            SpanReferenceMode::Sha256 => None,
+        },
+    })
+}
@@ -0,0 +1,53 @@
+use crate::context::WorkflowContext;
+use crate::extend::get_extension_offsets;
+use crate::locus::Locus;
+use std::sync::Arc;
+
+/// Output columns giving the spread of each boundary over the subsampled runs
+pub const STABILITY_COLUMNS: &[&str] = &["lf_offset_sd", "rf_offset_sd"];
+
+/// Fraction of the reads profiled in each subsampled run
+pub const STABILITY_FRACTION: f64 = 0.5;
+
+/// Standard deviations of the left and right offsets of the locus when it is
+/// extended again on `n_runs` random subsets of the reads; runs that fail to
+/// extend the locus are left out, and at least two runs must succeed
+pub fn boundary_stability(
+    ctx: &mut WorkflowContext,
+    locus: &Locus,
+    n_runs: usize,
+) -> Option<(f64, f64)> {
+    let params = ctx.params.clone();
+    let mut offsets = Vec::with_capacity(n_runs);
+    for run in 0..n_runs {
+        let mut run_params = (*params).clone();
+        run_params.read_fraction = Some(STABILITY_FRACTION);
+        run_params.seed = params.seed.wrapping_add(run as u64 + 1);
+        ctx.params = Arc::new(run_params);
+        if let Ok(ext) = get_extension_offsets(ctx, locus) {
+            offsets.push((ext.lf_offset as f64, ext.rf_offset as f64));
+        }
+    }
+    ctx.params = params;
+
+    if offsets.len() < 2 {
+        return None;
+    }
+    let sd = |values: Vec<f64>| {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let var = values.iter().map(|val| (val - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        var.sqrt()
+    };
+    Some((
+        sd(offsets.iter().map(|(lf, _)| *lf).collect()),
+        sd(offsets.iter().map(|(_, rf)| *rf).collect()),
+    ))
+}
+
+/// Values of the stability columns
+pub fn stability_columns(stability: Option<(f64, f64)>) -> Vec<String> {
+    match stability {
+        Some((lf_sd, rf_sd)) => vec![format!("{lf_sd:.1}"), format!("{rf_sd:.1}")],
+        None => vec!["NA".to_string(); STABILITY_COLUMNS.len()],
+    }
+}
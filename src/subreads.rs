@@ -0,0 +1,54 @@
+use crate::error::Result;
+use rust_htslib::bam::{self, Read};
+use std::path::Path;
+
+/// Number of leading records whose names are checked
+const PROBE_RECORDS: usize = 100;
+
+/// Whether a read name has the `movie/zmw/start_end` form of PacBio subreads;
+/// HiFi reads are named `movie/zmw/ccs`
+pub fn is_subread_name(name: &[u8]) -> bool {
+    let mut fields = name.split(|byte| *byte == b'/');
+    let (Some(movie), Some(zmw), Some(span), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return false;
+    };
+    let is_number = |field: &[u8]| !field.is_empty() && field.iter().all(u8::is_ascii_digit);
+    let mut bounds = span.split(|byte| *byte == b'_');
+    !movie.is_empty()
+        && is_number(zmw)
+        && matches!(
+            (bounds.next(), bounds.next(), bounds.next()),
+            (Some(start), Some(end), None) if is_number(start) && is_number(end)
+        )
+}
+
+/// Whether a BAM holds PacBio subreads rather than HiFi reads, going by the
+/// read type of its read groups or else by the names of its first reads;
+/// PAF files are never subreads
+pub fn is_subread_bam(path: &Path) -> Result<bool> {
+    if path.extension().is_some_and(|ext| ext == "paf") {
+        return Ok(false);
+    }
+    let mut reader = bam::Reader::from_path(path)?;
+    let header = bam::Header::from_template(reader.header()).to_hashmap();
+    let read_types = header
+        .get("RG")
+        .into_iter()
+        .flatten()
+        .filter_map(|rg| rg.get("DS"))
+        .flat_map(|ds| ds.split(';'))
+        .filter_map(|field| field.strip_prefix("READTYPE="))
+        .collect::<Vec<_>>();
+    if !read_types.is_empty() {
+        return Ok(read_types.contains(&"SUBREAD"));
+    }
+
+    let (mut n_reads, mut n_subreads) = (0, 0);
+    for rec in reader.records().take(PROBE_RECORDS) {
+        n_reads += 1;
+        n_subreads += is_subread_name(rec?.qname()) as usize;
+    }
+    Ok(n_reads > 0 && 2 * n_subreads > n_reads)
+}
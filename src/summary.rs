@@ -0,0 +1,136 @@
+use crate::aligner::Aligner;
+use crate::atomic::AtomicFile;
+use crate::contamination::ContaminationEstimate;
+use crate::density::DenseRegion;
+use crate::error::{Result, VclustError};
+use crate::memory::RssSample;
+use crate::profile::SkipCounts;
+use crate::throughput::WorkerTimes;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub loci_total: usize,
+    pub loci_processed: usize,
+    pub loci_failed: usize,
+    /// Failed loci broken down by error kind
+    pub failures: BTreeMap<String, usize>,
+    pub interrupted: bool,
+    /// Coordinates of the submitted loci, used to check shards for overlaps
+    #[serde(default)]
+    pub loci: Vec<String>,
+    /// Records excluded by the read filters in each sample, over all loci
+    #[serde(default)]
+    pub skipped_reads: BTreeMap<String, SkipCounts>,
+    /// Aligner of each sample, as recorded in the program chain of its BAM
+    #[serde(default)]
+    pub aligners: BTreeMap<String, Aligner>,
+    /// RSS of the process sampled with --rss-interval or --rss-limit
+    #[serde(default)]
+    pub rss_samples: Vec<RssSample>,
+    #[serde(default)]
+    pub peak_rss_bytes: Option<u64>,
+    /// Whether the run was stopped early by --rss-limit
+    #[serde(default)]
+    pub rss_limit_exceeded: bool,
+    /// Background alt support of each sample at loci without support
+    #[serde(default)]
+    pub contamination: BTreeMap<String, ContaminationEstimate>,
+    /// Time the workers spent in each stage, summed over the workers
+    #[serde(default)]
+    pub worker_times: Option<WorkerTimes>,
+    /// Advice on --threads, if another count would likely do better
+    #[serde(default)]
+    pub thread_recommendation: Option<String>,
+    /// Megabases over --max-cluster-density, for manual review
+    #[serde(default)]
+    pub dense_regions: Vec<DenseRegion>,
+}
+
+impl RunSummary {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut file = AtomicFile::create(path)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.commit()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| VclustError::Parse(format!("Bad summary {}: {e}", path.display())))
+    }
+}
+
+/// Cohort totals over the summaries of several runs
+#[derive(Debug, Default, Serialize)]
+pub struct AggregateSummary {
+    pub runs: usize,
+    pub loci_total: usize,
+    pub loci_processed: usize,
+    pub loci_failed: usize,
+    pub failures: BTreeMap<String, usize>,
+    pub skipped_reads: BTreeMap<String, SkipCounts>,
+    pub contamination: BTreeMap<String, ContaminationEstimate>,
+    /// Runs that were stopped before processing all of their loci
+    pub interrupted_runs: Vec<PathBuf>,
+    /// Runs whose processed and failed loci do not add up to their total
+    pub incomplete_runs: Vec<PathBuf>,
+    /// Loci submitted to more than one run
+    pub duplicated_loci: Vec<String>,
+}
+
+impl AggregateSummary {
+    pub fn is_consistent(&self) -> bool {
+        self.interrupted_runs.is_empty()
+            && self.incomplete_runs.is_empty()
+            && self.duplicated_loci.is_empty()
+    }
+}
+
+pub fn aggregate_summaries(paths: &[PathBuf]) -> Result<AggregateSummary> {
+    let mut aggregate = AggregateSummary::default();
+    let mut runs_per_locus: BTreeMap<String, usize> = BTreeMap::new();
+    for path in paths {
+        let summary = RunSummary::load(path)?;
+        aggregate.runs += 1;
+        aggregate.loci_total += summary.loci_total;
+        aggregate.loci_processed += summary.loci_processed;
+        aggregate.loci_failed += summary.loci_failed;
+        for (kind, count) in summary.failures {
+            *aggregate.failures.entry(kind).or_default() += count;
+        }
+        for (sample, counts) in &summary.skipped_reads {
+            aggregate
+                .skipped_reads
+                .entry(sample.clone())
+                .or_default()
+                .merge(counts);
+        }
+        for (sample, estimate) in &summary.contamination {
+            aggregate
+                .contamination
+                .entry(sample.clone())
+                .or_default()
+                .merge(estimate);
+        }
+        if summary.interrupted {
+            aggregate.interrupted_runs.push(path.clone());
+        } else if summary.loci_processed + summary.loci_failed != summary.loci_total {
+            aggregate.incomplete_runs.push(path.clone());
+        }
+        // Loci repeated within a run are the catalog's business, not a sharding error
+        for locus in summary.loci.into_iter().collect::<BTreeSet<_>>() {
+            *runs_per_locus.entry(locus).or_default() += 1;
+        }
+    }
+    aggregate.duplicated_loci = runs_per_locus
+        .into_iter()
+        .filter(|(_, runs)| *runs > 1)
+        .map(|(locus, _)| locus)
+        .collect();
+    Ok(aggregate)
+}
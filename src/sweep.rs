@@ -0,0 +1,176 @@
+use crate::context::{Params, WorkflowContext};
+use crate::error::{Result, VclustError};
+use crate::extend::{get_extension_offsets, Extension};
+use crate::locus::Locus;
+use crate::models::LogModels;
+use itertools::Itertools;
+use std::sync::Arc;
+
+pub const SWEEP_COLUMNS: &[&str] = &[
+    "min_prob_ref",
+    "prior_ref",
+    "window_ladder",
+    "n_loci",
+    "n_extended",
+    "n_supported",
+    "mean_support",
+    "n_stable",
+    "mean_boundary_shift",
+];
+
+/// One point of the parameter grid
+#[derive(Debug, Clone)]
+pub struct SweepSetting {
+    pub min_prob_ref: f64,
+    pub prior_ref: f64,
+    pub window_ladder: Vec<i64>,
+}
+
+/// Extension outcomes of all loci under one setting, summarized against the
+/// baseline run with default parameters
+#[derive(Debug)]
+pub struct SweepRow {
+    pub setting: SweepSetting,
+    pub n_loci: usize,
+    pub n_extended: usize,
+    /// Loci supported by at least one sample
+    pub n_supported: usize,
+    pub mean_support: f64,
+    /// Loci with the same outcome and boundaries as in the baseline
+    pub n_stable: usize,
+    /// Mean total shift of both boundaries over loci extended in both runs
+    pub mean_boundary_shift: Option<f64>,
+}
+
+impl SweepRow {
+    pub fn to_row(&self) -> String {
+        let ladder = self.setting.window_ladder.iter().join(":");
+        let shift = self
+            .mean_boundary_shift
+            .map_or("NA".to_string(), |shift| format!("{shift:.2}"));
+        format!(
+            "{}\t{}\t{ladder}\t{}\t{}\t{}\t{:.2}\t{}\t{shift}",
+            self.setting.min_prob_ref,
+            self.setting.prior_ref,
+            self.n_loci,
+            self.n_extended,
+            self.n_supported,
+            self.mean_support,
+            self.n_stable,
+        )
+    }
+}
+
+pub fn sweep_header() -> String {
+    format!("#{}", SWEEP_COLUMNS.join("\t"))
+}
+
+/// Parse a window ladder written as colon-separated window lengths
+pub fn parse_window_ladder(value: &str) -> Result<Vec<i64>> {
+    let ladder = value
+        .split(':')
+        .map(|len| len.parse::<i64>().ok().filter(|len| *len > 0))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| VclustError::Parse(format!("Bad window ladder {value}")))?;
+    if ladder.is_empty() {
+        return Err(VclustError::Parse(format!("Bad window ladder {value}")));
+    }
+    Ok(ladder)
+}
+
+/// Every combination of the given thresholds, reference priors in (0, 1), and
+/// ladders
+pub fn make_grid(
+    min_prob_refs: &[f64],
+    prior_refs: &[f64],
+    window_ladders: &[Vec<i64>],
+) -> Vec<SweepSetting> {
+    itertools::iproduct!(min_prob_refs, prior_refs, window_ladders)
+        .map(|(min_prob_ref, prior_ref, window_ladder)| SweepSetting {
+            min_prob_ref: *min_prob_ref,
+            prior_ref: *prior_ref,
+            window_ladder: window_ladder.clone(),
+        })
+        .collect()
+}
+
+pub fn run_sweep(
+    ctx: &mut WorkflowContext,
+    loci: &[Locus],
+    grid: &[SweepSetting],
+) -> Result<Vec<SweepRow>> {
+    let base_params = ctx.params.clone();
+    let base_models = ctx.models.clone();
+    let baseline = extend_all(ctx, loci);
+
+    let mut rows = Vec::new();
+    for setting in grid {
+        ctx.params = Arc::new(Params {
+            min_prob_ref: setting.min_prob_ref,
+            window_ladder: setting.window_ladder.clone(),
+            ..(*base_params).clone()
+        });
+        let mut models = (*base_models).clone();
+        models.reference.prior = setting.prior_ref;
+        models.cluster.prior = 1.0 - setting.prior_ref;
+        ctx.log_models = LogModels::from(&models);
+        ctx.models = Arc::new(models);
+
+        let exts = extend_all(ctx, loci);
+        rows.push(summarize(setting, &baseline, &exts));
+    }
+
+    ctx.params = base_params;
+    ctx.log_models = LogModels::from(&*base_models);
+    ctx.models = base_models;
+    Ok(rows)
+}
+
+fn extend_all(ctx: &mut WorkflowContext, loci: &[Locus]) -> Vec<Option<Extension>> {
+    loci.iter()
+        .map(|locus| get_extension_offsets(ctx, locus).ok())
+        .collect()
+}
+
+fn summarize(
+    setting: &SweepSetting,
+    baseline: &[Option<Extension>],
+    exts: &[Option<Extension>],
+) -> SweepRow {
+    let extended = exts.iter().flatten().collect_vec();
+    let n_supported = extended.iter().filter(|ext| ext.ns > 0).count();
+    let mean_support = if extended.is_empty() {
+        0.0
+    } else {
+        extended.iter().map(|ext| ext.ns as f64).sum::<f64>() / extended.len() as f64
+    };
+
+    let mut n_stable = 0;
+    let mut shifts = Vec::new();
+    for (base, ext) in baseline.iter().zip(exts) {
+        match (base, ext) {
+            (Some(base), Some(ext)) => {
+                let shift =
+                    (base.lf_offset - ext.lf_offset).abs() + (base.rf_offset - ext.rf_offset).abs();
+                if shift == 0 {
+                    n_stable += 1;
+                }
+                shifts.push(shift as f64);
+            }
+            (None, None) => n_stable += 1,
+            _ => {}
+        }
+    }
+    let mean_boundary_shift =
+        (!shifts.is_empty()).then(|| shifts.iter().sum::<f64>() / shifts.len() as f64);
+
+    SweepRow {
+        setting: setting.clone(),
+        n_loci: exts.len(),
+        n_extended: extended.len(),
+        n_supported,
+        mean_support,
+        n_stable,
+        mean_boundary_shift,
+    }
+}
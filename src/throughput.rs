@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// Fraction of the worker time spent fetching reads above which loci are
+/// considered bound by read I/O
+pub const READ_BOUND_FRACTION: f64 = 0.5;
+
+/// Fraction of the worker time spent blocked on the writer above which the
+/// output is considered the bottleneck
+pub const WRITER_BOUND_FRACTION: f64 = 0.25;
+
+/// Time workers spent in each stage of processing loci, in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkerTimes {
+    /// Fetching and decoding the reads of the loci
+    pub read_secs: f64,
+    /// Processing the loci besides fetching reads
+    pub compute_secs: f64,
+    /// Waiting for the next locus
+    pub wait_secs: f64,
+    /// Blocked handing results to the writer
+    pub send_secs: f64,
+}
+
+impl WorkerTimes {
+    pub fn merge(&mut self, other: &WorkerTimes) {
+        self.read_secs += other.read_secs;
+        self.compute_secs += other.compute_secs;
+        self.wait_secs += other.wait_secs;
+        self.send_secs += other.send_secs;
+    }
+
+    pub fn total_secs(&self) -> f64 {
+        self.read_secs + self.compute_secs + self.wait_secs + self.send_secs
+    }
+
+    /// Share of the total time of each stage, in percent
+    pub fn report(&self) -> String {
+        let total = self.total_secs().max(f64::MIN_POSITIVE);
+        format!(
+            "Worker time: {:.0}% reading, {:.0}% computing, {:.0}% waiting for loci, \
+             {:.0}% waiting for the writer",
+            100.0 * self.read_secs / total,
+            100.0 * self.compute_secs / total,
+            100.0 * self.wait_secs / total,
+            100.0 * self.send_secs / total,
+        )
+    }
+}
+
+/// Advice on `--threads` given the time workers spent in each stage of a run
+/// with `threads` workers on `cores` cores, if another count would likely do
+/// better
+pub fn thread_recommendation(times: &WorkerTimes, threads: usize, cores: usize) -> Option<String> {
+    let total = times.total_secs();
+    if total <= 0.0 {
+        return None;
+    }
+    let read_fraction = times.read_secs / total;
+    let send_fraction = times.send_secs / total;
+    let percent = |fraction: f64| format!("{:.0}%", 100.0 * fraction);
+    if send_fraction > WRITER_BOUND_FRACTION {
+        Some(format!(
+            "Workers spent {} of their time waiting for the writer; the output \
+             storage limits the run, and more threads will not help",
+            percent(send_fraction)
+        ))
+    } else if read_fraction > READ_BOUND_FRACTION && threads <= cores {
+        Some(format!(
+            "Workers spent {} of their time reading; on network storage, \
+             --threads {} would overlap more read latency with computation",
+            percent(read_fraction),
+            2 * cores
+        ))
+    } else if read_fraction <= READ_BOUND_FRACTION && threads > cores {
+        Some(format!(
+            "Workers spent {} of their time computing; --threads {cores} would be \
+             as fast with fewer competing threads",
+            percent(times.compute_secs / total)
+        ))
+    } else if read_fraction <= READ_BOUND_FRACTION && threads < cores {
+        Some(format!(
+            "Workers spent {} of their time computing; --threads {cores} would use \
+             all cores",
+            percent(times.compute_secs / total)
+        ))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,241 @@
+use crate::atomic::AtomicFile;
+use crate::error::{Result, VclustError};
+use crate::schema::{SCHEMA_VERSION, VERSION_PREFIX};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Files written to the hub directory
+pub const HUB_FILES: [&str; 6] = [
+    "clusters.bed",
+    "support.bedGraph",
+    "chrom.sizes",
+    "hub.txt",
+    "convert.sh",
+    "igv_session.xml",
+];
+
+/// Extended region of a results record and its support
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterTrack {
+    pub name: String,
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub ns: i64,
+}
+
+/// Extended regions of a results file in the current schema; regions that
+/// could not be extended are skipped
+pub fn load_clusters(path: &Path) -> Result<Vec<ClusterTrack>> {
+    let file = File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+
+    let version_line = lines.next().transpose()?.unwrap_or_default();
+    let version = version_line
+        .strip_prefix(VERSION_PREFIX)
+        .and_then(|version| version.parse::<u32>().ok());
+    if version != Some(SCHEMA_VERSION) {
+        return Err(VclustError::Parse(format!(
+            "Results must be in schema version {SCHEMA_VERSION}; upgrade them with vclust migrate"
+        )));
+    }
+    let header = lines
+        .next()
+        .transpose()?
+        .ok_or(VclustError::Parse("Missing column header".to_string()))?;
+    let columns = header.trim_start_matches('#').split('\t').collect_vec();
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|c| *c == name)
+            .ok_or_else(|| VclustError::Parse(format!("Missing column {name}")))
+    };
+    let (name_col, extended_col, ns_col) =
+        (column("name")?, column("extended_region")?, column("ns")?);
+
+    let mut clusters = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split('\t').collect_vec();
+        let bad_line = || VclustError::Parse(format!("Bad results line {line}"));
+        let extended = *fields.get(extended_col).ok_or_else(bad_line)?;
+        if extended == "NA" {
+            continue;
+        }
+        let (chrom, span) = extended.rsplit_once(':').ok_or_else(bad_line)?;
+        let (start, end) = span.split_once('-').ok_or_else(bad_line)?;
+        let parse = |val: &str| val.parse::<i64>().map_err(|_| bad_line());
+        clusters.push(ClusterTrack {
+            name: fields.get(name_col).ok_or_else(bad_line)?.to_string(),
+            chrom: chrom.to_string(),
+            start: parse(start)?,
+            end: parse(end)?,
+            ns: parse(fields.get(ns_col).ok_or_else(bad_line)?)?,
+        });
+    }
+    clusters.sort_by(|a, b| (&a.chrom, a.start, a.end).cmp(&(&b.chrom, b.start, b.end)));
+    Ok(clusters)
+}
+
+/// Non-overlapping intervals giving the largest support of the clusters
+/// covering each position, as required by bedGraph
+pub fn support_intervals(clusters: &[ClusterTrack]) -> Vec<(String, i64, i64, i64)> {
+    let mut intervals = Vec::new();
+    for (chrom, chrom_clusters) in &clusters.iter().chunk_by(|cluster| &cluster.chrom) {
+        let chrom_clusters = chrom_clusters
+            .filter(|cluster| cluster.start < cluster.end)
+            .collect_vec();
+        let bounds = chrom_clusters
+            .iter()
+            .flat_map(|cluster| [cluster.start, cluster.end])
+            .sorted()
+            .dedup()
+            .collect_vec();
+        // Support values of the clusters covering the current segment
+        let mut active: BTreeMap<i64, usize> = BTreeMap::new();
+        let mut starts = chrom_clusters.iter().peekable();
+        let mut ends = chrom_clusters
+            .iter()
+            .sorted_by_key(|cluster| cluster.end)
+            .peekable();
+        for (&seg_start, &seg_end) in bounds.iter().tuple_windows() {
+            while let Some(cluster) = ends.next_if(|cluster| cluster.end <= seg_start) {
+                if let Some(count) = active.get_mut(&cluster.ns) {
+                    *count -= 1;
+                    if *count == 0 {
+                        active.remove(&cluster.ns);
+                    }
+                }
+            }
+            while let Some(cluster) = starts.next_if(|cluster| cluster.start <= seg_start) {
+                *active.entry(cluster.ns).or_default() += 1;
+            }
+            let Some((&ns, _)) = active.last_key_value() else {
+                continue;
+            };
+            match intervals.last_mut() {
+                Some((last_chrom, _, last_end, last_ns))
+                    if last_chrom == chrom && *last_end == seg_start && *last_ns == ns =>
+                {
+                    *last_end = seg_end
+                }
+                _ => intervals.push((chrom.clone(), seg_start, seg_end, ns)),
+            }
+        }
+    }
+    intervals
+}
+
+/// Write a track hub with the clusters and their support to `out_dir`
+///
+/// The BED and bedGraph files load directly in IGV through the session file;
+/// UCSC hubs need the indexed versions created by the `convert.sh` script.
+pub fn write_track_hub(
+    clusters: &[ClusterTrack],
+    out_dir: &Path,
+    genome: &str,
+    chrom_sizes: &[(String, u64)],
+    email: &str,
+) -> Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let [clusters_path, support_path, sizes_path, hub_path, convert_path, session_path] =
+        hub_paths(out_dir);
+    // Files are moved into place together once all of them are written
+    let mut files = Vec::new();
+
+    let mut writer = AtomicFile::create(&clusters_path)?;
+    let max_ns = clusters.iter().map(|cluster| cluster.ns).max().unwrap_or(0);
+    for cluster in clusters {
+        // BED scores range from 0 to 1000 and set the shading of the features
+        let score = if max_ns > 0 {
+            cluster.ns * 1000 / max_ns
+        } else {
+            0
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{score}\t.",
+            cluster.chrom, cluster.start, cluster.end, cluster.name
+        )?;
+    }
+    files.push(writer);
+
+    let mut writer = AtomicFile::create(&support_path)?;
+    for (chrom, start, end, ns) in support_intervals(clusters) {
+        writeln!(writer, "{chrom}\t{start}\t{end}\t{ns}")?;
+    }
+    files.push(writer);
+
+    let mut writer = AtomicFile::create(&sizes_path)?;
+    for (chrom, len) in chrom_sizes {
+        writeln!(writer, "{chrom}\t{len}")?;
+    }
+    files.push(writer);
+
+    let hub = [
+        "hub vclust".to_string(),
+        "shortLabel vclust clusters".to_string(),
+        "longLabel Variation clusters identified by vclust".to_string(),
+        "useOneFile on".to_string(),
+        format!("email {email}"),
+        String::new(),
+        format!("genome {genome}"),
+        String::new(),
+        "track clusters".to_string(),
+        "bigDataUrl clusters.bb".to_string(),
+        "shortLabel Clusters".to_string(),
+        "longLabel Extended regions shaded by the number of supporting samples".to_string(),
+        "type bigBed 6".to_string(),
+        "useScore 1".to_string(),
+        "visibility pack".to_string(),
+        String::new(),
+        "track support".to_string(),
+        "bigDataUrl support.bw".to_string(),
+        "shortLabel Support".to_string(),
+        "longLabel Number of samples supporting each cluster".to_string(),
+        "type bigWig".to_string(),
+        "visibility full".to_string(),
+    ];
+    let mut writer = AtomicFile::create(&hub_path)?;
+    writeln!(writer, "{}", hub.join("\n"))?;
+    files.push(writer);
+
+    let convert = [
+        "#!/bin/sh",
+        "# Create the indexed files referenced by hub.txt (requires the UCSC",
+        "# bedToBigBed and bedGraphToBigWig utilities)",
+        "set -e",
+        "cd \"$(dirname \"$0\")\"",
+        "bedToBigBed -type=bed6 clusters.bed chrom.sizes clusters.bb",
+        "bedGraphToBigWig support.bedGraph chrom.sizes support.bw",
+    ];
+    let mut writer = AtomicFile::create(&convert_path)?;
+    writeln!(writer, "{}", convert.join("\n"))?;
+    files.push(writer);
+
+    let session = [
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>".to_string(),
+        format!("<Session genome=\"{genome}\" version=\"8\">"),
+        "    <Resources>".to_string(),
+        "        <Resource path=\"clusters.bed\"/>".to_string(),
+        "        <Resource path=\"support.bedGraph\"/>".to_string(),
+        "    </Resources>".to_string(),
+        "</Session>".to_string(),
+    ];
+    let mut writer = AtomicFile::create(&session_path)?;
+    writeln!(writer, "{}", session.join("\n"))?;
+    files.push(writer);
+
+    files.into_iter().try_for_each(AtomicFile::commit)
+}
+
+/// Paths of the files written to a hub directory
+pub fn hub_paths(out_dir: &Path) -> [PathBuf; 6] {
+    HUB_FILES.map(|name| out_dir.join(name))
+}
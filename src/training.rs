@@ -0,0 +1,162 @@
+use crate::atomic::AtomicFile;
+use crate::error::Result;
+use crate::locus::{load_loci, Locus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Length of the exported windows, that of the first window of the default
+/// ladder
+pub const TRAINING_WINDOW_LEN: usize = 150;
+
+pub const EXAMPLE_COLUMNS: &[&str] = &["index", "name", "chrom", "start", "end", "label"];
+
+/// Files written to the training directory
+pub const TRAINING_FILES: [&str; 3] = ["windows.npy", "labels.npy", "examples.tsv"];
+
+/// Bytes reserved for the header of each array file, which is rewritten with
+/// the final shape once all examples are in
+const NPY_HEADER_LEN: usize = 128;
+
+/// Discretized pooled profile of the search region of a locus, as emitted to
+/// the models
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileStates {
+    /// Start of the search region
+    pub start: i64,
+    pub states: Vec<u8>,
+}
+
+/// Truth regions of each contig, sorted by start
+#[derive(Debug, Clone, Default)]
+pub struct TruthRegions {
+    regions: BTreeMap<String, Vec<(i64, i64)>>,
+}
+
+impl TruthRegions {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut regions: BTreeMap<String, Vec<(i64, i64)>> = BTreeMap::new();
+        for locus in load_loci(path.to_path_buf(), None)? {
+            regions
+                .entry(locus.chrom)
+                .or_default()
+                .push((locus.start, locus.end));
+        }
+        regions.values_mut().for_each(|spans| spans.sort());
+        Ok(TruthRegions { regions })
+    }
+
+    /// Whether [start, end) overlaps a truth region of the contig
+    pub fn overlaps(&self, chrom: &str, start: i64, end: i64) -> bool {
+        self.regions.get(chrom).is_some_and(|spans| {
+            spans[..spans.partition_point(|span| span.0 < end)]
+                .iter()
+                .any(|span| span.1 > start)
+        })
+    }
+}
+
+/// Writes the windows tiling the search region of each locus to a directory as
+/// NumPy arrays: `windows.npy` with the states of each window (uint8, one row
+/// per example), `labels.npy` with 1 for windows overlapping a truth region
+/// and 0 otherwise (uint8), and `examples.tsv` with the locus and coordinates
+/// of each example
+pub struct TrainingWriter {
+    truth: TruthRegions,
+    dir: PathBuf,
+    windows: AtomicFile,
+    labels: AtomicFile,
+    examples: AtomicFile,
+    n_examples: usize,
+}
+
+impl TrainingWriter {
+    pub fn create(dir: &Path, truth: TruthRegions) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let [windows, labels, examples] = training_paths(dir);
+        let mut windows = AtomicFile::create(&windows)?;
+        let mut labels = AtomicFile::create(&labels)?;
+        let mut examples = AtomicFile::create(&examples)?;
+        windows.write_all(&[b' '; NPY_HEADER_LEN])?;
+        labels.write_all(&[b' '; NPY_HEADER_LEN])?;
+        writeln!(examples, "#{}", EXAMPLE_COLUMNS.join("\t"))?;
+        Ok(TrainingWriter {
+            truth,
+            dir: dir.to_path_buf(),
+            windows,
+            labels,
+            examples,
+            n_examples: 0,
+        })
+    }
+
+    /// Add the whole windows of the search region of a locus
+    pub fn add(&mut self, locus: &Locus, profile: &ProfileStates) -> Result<()> {
+        for (index, window) in profile.states.chunks_exact(TRAINING_WINDOW_LEN).enumerate() {
+            let start = profile.start + (index * TRAINING_WINDOW_LEN) as i64;
+            let end = start + TRAINING_WINDOW_LEN as i64;
+            let label = self.truth.overlaps(&locus.chrom, start, end) as u8;
+            self.windows.write_all(window)?;
+            self.labels.write_all(&[label])?;
+            writeln!(
+                self.examples,
+                "{}\t{}\t{}\t{start}\t{end}\t{label}",
+                self.n_examples, locus.name, locus.chrom
+            )?;
+            self.n_examples += 1;
+        }
+        Ok(())
+    }
+
+    pub fn n_examples(&self) -> usize {
+        self.n_examples
+    }
+
+    /// Write the final shapes into the array headers
+    pub fn finish(self) -> Result<()> {
+        let shapes = [
+            (
+                self.windows,
+                format!("({}, {TRAINING_WINDOW_LEN})", self.n_examples),
+            ),
+            (self.labels, format!("({},)", self.n_examples)),
+        ];
+        for (mut file, shape) in shapes {
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&npy_header(&shape))?;
+            file.commit()?;
+        }
+        self.examples.commit()?;
+        log::info!(
+            "Exported {} training examples to {}",
+            self.n_examples,
+            self.dir.display()
+        );
+        Ok(())
+    }
+}
+
+/// Paths of the files written to a training directory
+pub fn training_paths(dir: &Path) -> [PathBuf; 3] {
+    TRAINING_FILES.map(|name| dir.join(name))
+}
+
+/// Header of a version 1.0 NPY file of unsigned bytes with the given shape,
+/// padded to `NPY_HEADER_LEN` bytes
+fn npy_header(shape: &str) -> Vec<u8> {
+    let dict = format!("{{'descr': '|u1', 'fortran_order': False, 'shape': {shape}, }}");
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    let len = NPY_HEADER_LEN - header.len() - 2;
+    header.extend((len as u16).to_le_bytes());
+    header.extend(dict.bytes());
+    // Shapes are at most a few tens of characters
+    assert!(
+        header.len() < NPY_HEADER_LEN,
+        "NPY header too long for {shape}"
+    );
+    header.resize(NPY_HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
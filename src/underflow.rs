@@ -0,0 +1,57 @@
+use crate::models::LogModels;
+use itertools::Itertools;
+
+pub const MODEL_UNCERTAIN_COLUMN: &str = "model_uncertain";
+/// Windows whose likelihood under both models is below this per position, as a
+/// geometric mean, are out of the distribution the models were trained on
+pub const UNDERFLOW_LIKELIHOOD: f64 = 0.01;
+/// Fewer adjacent underflowing windows than this are ordinary noise
+pub const MIN_UNDERFLOW_WINDOWS: usize = 10;
+
+/// Intervals of a discretized profile, relative to its start, covered by runs
+/// of at least `MIN_UNDERFLOW_WINDOWS` adjacent windows that neither model
+/// explains; the posterior comparison of such windows is close to arbitrary
+pub fn get_underflow_runs(alts: &[u8], window_len: usize, models: &LogModels) -> Vec<(i64, i64)> {
+    if window_len == 0 || alts.len() < window_len {
+        return Vec::new();
+    }
+    let n_states = models.n_states;
+    let min_loglik = window_len as f64 * UNDERFLOW_LIKELIHOOD.ln();
+    let loglik = |window: &[u8], log_probs: &[f64]| {
+        window
+            .iter()
+            .enumerate()
+            .map(|(pos, val)| log_probs[pos * n_states + *val as usize])
+            .sum::<f64>()
+    };
+    let underflows = alts.windows(window_len).map(|window| {
+        let ll_ref = loglik(window, &models.reference.log_probs);
+        let ll_vc = loglik(window, &models.cluster.log_probs);
+        ll_ref.max(ll_vc) < min_loglik
+    });
+
+    let mut runs: Vec<(i64, i64)> = Vec::new();
+    for (underflow, group) in &underflows.enumerate().chunk_by(|(_, underflow)| *underflow) {
+        let starts = group.map(|(start, _)| start as i64).collect_vec();
+        if !underflow || starts.len() < MIN_UNDERFLOW_WINDOWS {
+            continue;
+        }
+        let run = (starts[0], starts[starts.len() - 1] + window_len as i64);
+        match runs.last_mut() {
+            Some(last) if last.1 >= run.0 => last.1 = run.1,
+            _ => runs.push(run),
+        }
+    }
+    runs
+}
+
+/// Underflow runs as comma-separated intervals, or `.` if there are none
+pub fn format_underflow_runs(runs: &[(i64, i64)]) -> String {
+    match runs.is_empty() {
+        true => ".".to_string(),
+        false => runs
+            .iter()
+            .map(|(start, end)| format!("{start}-{end}"))
+            .join(","),
+    }
+}
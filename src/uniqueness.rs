@@ -0,0 +1,204 @@
+use crate::error::{Result, VclustError};
+use crate::profile::Region;
+use itertools::Itertools;
+use rust_htslib::faidx;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+pub const UNIQUENESS_COLUMN: &str = "uniqueness";
+/// Length of the reference k-mers, short enough to be packed into a u64
+pub const KMER_LEN: usize = 31;
+/// Without an index, k-mers are looked up within this distance of the search
+/// window, where most recent segmental duplications have their copies
+pub const LOCAL_FLANK: i64 = 50_000;
+/// Alt signal of search windows less unique than this is scaled by their
+/// uniqueness
+pub const MIN_UNIQUENESS: f64 = 0.5;
+
+const INDEX_MAGIC: &[u8; 8] = b"VCKMER01";
+
+/// Canonical reference k-mers occurring more than once in the genome, with
+/// their number of occurrences
+pub struct KmerIndex {
+    /// Sorted canonical k-mers
+    kmers: Vec<u64>,
+    counts: Vec<u32>,
+    /// Checksum of the index as saved, identifying it across runs
+    pub sha256: String,
+}
+
+impl std::fmt::Debug for KmerIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("KmerIndex")
+            .field("kmers", &self.kmers.len())
+            .field("sha256", &self.sha256)
+            .finish()
+    }
+}
+
+impl KmerIndex {
+    /// Count the k-mers of every contig of the reference
+    pub fn build(reference: &faidx::Reader) -> Result<Self> {
+        let mut kmers = Vec::new();
+        for index in 0..reference.n_seqs() {
+            let name = reference.seq_name(index as i32)?;
+            let len = reference.fetch_seq_len(&name) as usize;
+            if len == 0 {
+                continue;
+            }
+            // Fetched interval is closed
+            let seq = reference.fetch_seq(&name, 0, len - 1)?;
+            kmers.extend(canonical_kmers(seq));
+        }
+        Ok(Self::from_kmers(kmers))
+    }
+
+    fn from_kmers(mut kmers: Vec<u64>) -> Self {
+        kmers.sort_unstable();
+        let (kmers, counts) = kmers
+            .into_iter()
+            .dedup_with_count()
+            .filter(|(count, _)| *count > 1)
+            .map(|(count, kmer)| (kmer, count.min(u32::MAX as usize) as u32))
+            .unzip();
+        Self::with_checksum(kmers, counts)
+    }
+
+    fn with_checksum(kmers: Vec<u64>, counts: Vec<u32>) -> Self {
+        // Hashed in the layout of the saved file, so that built and loaded
+        // copies of an index agree
+        let mut hasher = Sha256::new();
+        hasher.update(INDEX_MAGIC);
+        hasher.update((kmers.len() as u64).to_le_bytes());
+        for (kmer, count) in kmers.iter().zip(&counts) {
+            hasher.update(kmer.to_le_bytes());
+            hasher.update(count.to_le_bytes());
+        }
+        KmerIndex {
+            kmers,
+            counts,
+            sha256: format!("{:x}", hasher.finalize()),
+        }
+    }
+
+    /// Occurrences of a k-mer of the reference in the genome
+    pub fn count(&self, kmer: u64) -> u32 {
+        match self.kmers.binary_search(&kmer) {
+            Ok(index) => self.counts[index],
+            Err(_) => 1,
+        }
+    }
+
+    pub fn save(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&(self.kmers.len() as u64).to_le_bytes())?;
+        for (kmer, count) in self.kmers.iter().zip(&self.counts) {
+            writer.write_all(&kmer.to_le_bytes())?;
+            writer.write_all(&count.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(VclustError::Parse(format!(
+                "{} is not a vclust k-mer index",
+                path.display()
+            )));
+        }
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf)?;
+        let len = u64::from_le_bytes(buf) as usize;
+        let (mut kmers, mut counts) = (Vec::with_capacity(len), Vec::with_capacity(len));
+        let mut count = [0; 4];
+        for _ in 0..len {
+            reader.read_exact(&mut buf)?;
+            reader.read_exact(&mut count)?;
+            kmers.push(u64::from_le_bytes(buf));
+            counts.push(u32::from_le_bytes(count));
+        }
+        Ok(Self::with_checksum(kmers, counts))
+    }
+}
+
+/// Fraction of the k-mers of a window that occur nowhere else, given the
+/// number of occurrences of each k-mer in the genome or a neighbourhood of the
+/// window; repeats within the window, such as tandem repeats, stay unique
+pub fn uniqueness(window: &[u8], occurrences: &dyn Fn(u64) -> u32) -> f64 {
+    let counts = count_kmers(window);
+    let total = counts.values().sum::<u32>();
+    if total == 0 {
+        return 1.0;
+    }
+    let unique = counts
+        .iter()
+        .filter(|(kmer, count)| occurrences(**kmer) <= **count)
+        .map(|(_, count)| count)
+        .sum::<u32>();
+    unique as f64 / total as f64
+}
+
+/// Uniqueness of the reference sequence of a search window, from the index if
+/// there is one and from the neighbourhood of the window otherwise
+pub fn window_uniqueness(
+    reference: &faidx::Reader,
+    index: Option<&KmerIndex>,
+    region: Region,
+) -> Result<f64> {
+    // Fetched intervals are closed
+    let seq = reference
+        .fetch_seq(region.0, region.1 as usize, region.2 as usize - 1)?
+        .to_vec();
+    match index {
+        Some(index) => Ok(uniqueness(&seq, &|kmer| index.count(kmer))),
+        None => {
+            let contig_len = reference.fetch_seq_len(region.0) as i64;
+            let start = (region.1 - LOCAL_FLANK).max(0);
+            let end = (region.2 + LOCAL_FLANK).min(contig_len);
+            let flanked = reference.fetch_seq(region.0, start as usize, end as usize - 1)?;
+            let counts = count_kmers(flanked);
+            Ok(uniqueness(&seq, &|kmer| {
+                counts.get(&kmer).copied().unwrap_or(0)
+            }))
+        }
+    }
+}
+
+/// Occurrences of each k-mer in a sequence
+pub fn count_kmers(seq: &[u8]) -> HashMap<u64, u32> {
+    let mut counts = HashMap::new();
+    for kmer in canonical_kmers(seq) {
+        *counts.entry(kmer).or_default() += 1;
+    }
+    counts
+}
+
+/// Two-bit encoded k-mers of a sequence, each the smaller of itself and its
+/// reverse complement; k-mers with bases other than ACGT are skipped
+pub fn canonical_kmers(seq: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    let mask = (1u64 << (2 * KMER_LEN)) - 1;
+    let shift = 2 * (KMER_LEN as u64 - 1);
+    let (mut forward, mut reverse, mut len) = (0u64, 0u64, 0);
+    seq.iter().filter_map(move |base| {
+        let code = match base {
+            b'A' | b'a' => 0,
+            b'C' | b'c' => 1,
+            b'G' | b'g' => 2,
+            b'T' | b't' => 3,
+            _ => {
+                len = 0;
+                return None;
+            }
+        };
+        forward = ((forward << 2) | code) & mask;
+        reverse = (reverse >> 2) | ((3 - code) << shift);
+        len += 1;
+        (len >= KMER_LEN).then(|| forward.min(reverse))
+    })
+}
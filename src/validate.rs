@@ -0,0 +1,270 @@
+use crate::error::{Result, VclustError};
+use crate::schema::{COLUMNS, SCHEMA_VERSION, VERSION_PREFIX};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Problem found in a results file, at a 1-based line number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Contig names and lengths of a FAI index, in the order of the reference
+pub fn load_fai(path: &Path) -> Result<Vec<(String, i64)>> {
+    let file = File::open(path)?;
+    let mut contigs = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let bad_line = || VclustError::Parse(format!("Bad index line {line}"));
+        let mut fields = line.split('\t');
+        let name = fields.next().ok_or_else(bad_line)?;
+        let len = fields
+            .next()
+            .and_then(|len| len.parse::<i64>().ok())
+            .ok_or_else(bad_line)?;
+        contigs.push((name.to_string(), len));
+    }
+    Ok(contigs)
+}
+
+/// Check a results file for conformity with the current schema, consistency
+/// of the offsets with the extended regions, and, given the contigs of the
+/// reference, coordinates within the contig bounds; if `sorted`, records must
+/// also be grouped by contig, in reference order if known, and ordered by the
+/// start of the extended region, or of the region if not extended, within each
+/// contig
+pub fn validate_results(
+    path: &Path,
+    contigs: Option<&[(String, i64)]>,
+    sorted: bool,
+) -> Result<Vec<ValidationIssue>> {
+    let file = File::open(path)?;
+    let mut lines = BufReader::new(file).lines();
+    let mut issues = Vec::new();
+    let mut issue = |line: usize, message: String| issues.push(ValidationIssue { line, message });
+
+    let version_line = lines.next().transpose()?.unwrap_or_default();
+    let version = version_line
+        .strip_prefix(VERSION_PREFIX)
+        .and_then(|version| version.parse::<u32>().ok());
+    if version != Some(SCHEMA_VERSION) {
+        issue(1, format!("Expected schema version {SCHEMA_VERSION}"));
+    }
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let columns = header.trim_start_matches('#').split('\t').collect_vec();
+    if !header.starts_with('#') || !columns.starts_with(COLUMNS) {
+        issue(
+            2,
+            "Column header does not start with the standard columns".to_string(),
+        );
+        return Ok(issues);
+    }
+
+    let contig_index = contigs.map(|contigs| {
+        contigs
+            .iter()
+            .enumerate()
+            .map(|(index, (name, len))| (name.as_str(), (index, *len)))
+            .collect::<HashMap<_, _>>()
+    });
+    // Reference order of the contigs seen so far, and the last record start on
+    // the current one
+    let mut seen_contigs: HashMap<String, usize> = HashMap::new();
+    let mut last: Option<(String, i64)> = None;
+    for (index, line) in lines.enumerate() {
+        let line_number = index + 3;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = line.split('\t').collect_vec();
+        if fields.len() != columns.len() {
+            issue(
+                line_number,
+                format!("Expected {} fields, found {}", columns.len(), fields.len()),
+            );
+            continue;
+        }
+        let Some((chrom, start, end)) = parse_region(fields[1]) else {
+            issue(line_number, format!("Bad region {}", fields[1]));
+            continue;
+        };
+
+        // Extended records are ordered by their extended region
+        let order_start = parse_region(fields[4]).map_or(start, |(_, start, _)| start);
+        let contig = contig_index
+            .as_ref()
+            .map(|contig_index| contig_index.get(chrom.as_str()).copied());
+        match &last {
+            _ if !sorted => {}
+            Some((last_chrom, last_start)) if *last_chrom == chrom => {
+                if order_start < *last_start {
+                    issue(line_number, format!("Region {} is out of order", fields[1]));
+                }
+            }
+            _ => {
+                if seen_contigs.contains_key(&chrom) {
+                    issue(
+                        line_number,
+                        format!("Records of {chrom} are not contiguous"),
+                    );
+                }
+                if let Some(Some((contig_order, _))) = contig {
+                    if seen_contigs.values().any(|order| *order > contig_order) {
+                        issue(
+                            line_number,
+                            format!("Records of {chrom} are not in reference order"),
+                        );
+                    }
+                }
+                seen_contigs
+                    .entry(chrom.clone())
+                    .or_insert(contig.flatten().map_or(0, |(order, _)| order));
+            }
+        }
+        last = Some((chrom.clone(), order_start));
+
+        let contig_len = match contig {
+            Some(Some((_, len))) => Some(len),
+            Some(None) => {
+                issue(
+                    line_number,
+                    format!("Contig {chrom} is not in the reference"),
+                );
+                None
+            }
+            None => None,
+        };
+        if contig_len.is_some_and(|len| end > len) {
+            issue(
+                line_number,
+                format!("Region {} ends past the contig", fields[1]),
+            );
+        }
+
+        for message in check_record(&fields, &chrom, start, end, contig_len) {
+            issue(line_number, message);
+        }
+    }
+    Ok(issues)
+}
+
+/// Parse a region written as `chrom:start-end`
+fn parse_region(region: &str) -> Option<(String, i64, i64)> {
+    let (chrom, span) = region.rsplit_once(':')?;
+    let (start, end) = span.split_once('-')?;
+    let (start, end) = (start.parse::<i64>().ok()?, end.parse::<i64>().ok()?);
+    (!chrom.is_empty() && 0 <= start && start < end).then(|| (chrom.to_string(), start, end))
+}
+
+/// Problems with the standard columns of a record
+fn check_record(
+    fields: &[&str],
+    chrom: &str,
+    start: i64,
+    end: i64,
+    contig_len: Option<i64>,
+) -> Vec<String> {
+    let mut messages = Vec::new();
+    let column = |name: &str| fields[COLUMNS.iter().position(|c| *c == name).unwrap()];
+    if column("name").is_empty() {
+        messages.push("Empty name".to_string());
+    }
+    let standard = &fields[2..COLUMNS.len()];
+    if column("lf_offset") == "NA" {
+        if standard.iter().any(|field| *field != "NA") {
+            messages.push("Unextended record has values besides NA".to_string());
+        }
+        return messages;
+    }
+
+    let mut count = |name: &str| match column(name).parse::<i64>() {
+        Ok(value) if value >= 0 => Some(value),
+        _ => {
+            messages.push(format!("Bad {name} {}", column(name)));
+            None
+        }
+    };
+    let (lf_offset, rf_offset) = (count("lf_offset"), count("rf_offset"));
+    for name in ["ns", "spanning_reads", "partial_reads", "chimeric_reads"] {
+        count(name);
+    }
+    let (Some(lf_offset), Some(rf_offset)) = (lf_offset, rf_offset) else {
+        return messages;
+    };
+
+    let (ext_start, ext_end) = (start - lf_offset, end + rf_offset);
+    let expected = format!("{chrom}:{ext_start}-{ext_end}");
+    if column("extended_region") != expected {
+        messages.push(format!(
+            "Extended region {} does not match the offsets ({expected})",
+            column("extended_region")
+        ));
+    }
+    if ext_start < 0 || contig_len.is_some_and(|len| ext_end > len) {
+        messages.push(format!(
+            "Extended region {expected} is outside of the contig"
+        ));
+    }
+
+    let gaps = column("coverage_gaps");
+    if gaps != "." {
+        for gap in gaps.split(',') {
+            let bounds = gap.split_once('-').and_then(|(start, end)| {
+                Some((start.parse::<i64>().ok()?, end.parse::<i64>().ok()?))
+            });
+            match bounds {
+                Some((gap_start, gap_end))
+                    if ext_start <= gap_start && gap_start < gap_end && gap_end <= ext_end => {}
+                _ => messages.push(format!("Coverage gap {gap} is not within {expected}")),
+            }
+        }
+    }
+
+    let ladder = column("ladder_offsets");
+    if ladder != "." {
+        let offsets = ladder
+            .split(',')
+            .map(|step| {
+                let values = step
+                    .split(':')
+                    .map(|value| value.parse::<i64>().ok())
+                    .collect::<Option<Vec<_>>>()?;
+                match values[..] {
+                    [window, lf, rf] => Some((window, lf, rf)),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>();
+        match offsets.as_deref().and_then(|offsets| offsets.last()) {
+            Some(&(_, lf, rf)) if (lf, rf) == (lf_offset, rf_offset) => {}
+            Some(_) => messages.push(format!(
+                "Final ladder offsets of {ladder} do not match the offsets"
+            )),
+            None => messages.push(format!("Bad ladder_offsets {ladder}")),
+        }
+    }
+
+    let bias = column("read_end_bias");
+    if bias != "."
+        && !bias
+            .parse::<f64>()
+            .is_ok_and(|bias| (0.0..=1.0).contains(&bias))
+    {
+        messages.push(format!("Bad read_end_bias {bias}"));
+    }
+    messages
+}
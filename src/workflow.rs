@@ -1,18 +1,236 @@
-use crate::extend::get_extension_offsets;
+use crate::annotation::ANNOTATION_COLUMNS;
+use crate::concordance::{get_concordance, Concordance};
+use crate::context::{Params, WorkflowContext};
+use crate::coverage::{coverage_columns, COVERAGE_COLUMNS};
+use crate::error::{Result, VclustError};
+use crate::extend::{get_extension_offsets, Extension};
+use crate::haplotype::{haplotype_columns, HAPLOTYPE_COLUMNS};
 use crate::locus::Locus;
-use rust_htslib::bam::IndexedReader;
-
-pub fn run_workflow(bams: &mut Vec<IndexedReader>, locus: &Locus) -> Result<String, String> {
-    let in_region = format!("{}:{}-{}", locus.chrom, locus.start, locus.end);
-    let offsets = get_extension_offsets(locus, bams);
-
-    if let Some((lf, rf, ns)) = offsets {
-        let out_region = format!("{}:{}-{}", locus.chrom, locus.start - lf, locus.end + rf);
-        Ok(format!(
-            "{}\t{in_region}\t{lf}\t{rf}\t{out_region}\t{ns}",
-            locus.name
-        ))
+use crate::offtarget::{off_target_columns, OFF_TARGET_COLUMNS};
+use crate::population::POPULATION_COLUMNS;
+use crate::priority::{format_priority, priority, PRIORITY_COLUMN};
+use crate::selection::MODEL_COLUMN;
+use crate::span::span_reference;
+use crate::stability::{boundary_stability, stability_columns, STABILITY_COLUMNS};
+use crate::underflow::{format_underflow_runs, MODEL_UNCERTAIN_COLUMN};
+use crate::uniqueness::UNIQUENESS_COLUMN;
+use serde::{Deserialize, Serialize};
+
+/// Groups of built-in output columns, each enabled by an option
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreColumn {
+    Annotation,
+    Priority,
+    Haplotype,
+    Model,
+    ModelUncertain,
+    Uniqueness,
+    Stability,
+    Population,
+    OffTarget,
+    Coverage,
+}
+
+/// Output order of the built-in columns; the columns of the registered
+/// scorers follow them
+pub const SCORE_COLUMNS: [ScoreColumn; 10] = [
+    ScoreColumn::Annotation,
+    ScoreColumn::Priority,
+    ScoreColumn::Haplotype,
+    ScoreColumn::Model,
+    ScoreColumn::ModelUncertain,
+    ScoreColumn::Uniqueness,
+    ScoreColumn::Stability,
+    ScoreColumn::Population,
+    ScoreColumn::OffTarget,
+    ScoreColumn::Coverage,
+];
+
+impl ScoreColumn {
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            ScoreColumn::Annotation => ANNOTATION_COLUMNS,
+            ScoreColumn::Priority => &[PRIORITY_COLUMN],
+            ScoreColumn::Haplotype => HAPLOTYPE_COLUMNS,
+            ScoreColumn::Model => &[MODEL_COLUMN],
+            ScoreColumn::ModelUncertain => &[MODEL_UNCERTAIN_COLUMN],
+            ScoreColumn::Uniqueness => &[UNIQUENESS_COLUMN],
+            ScoreColumn::Stability => STABILITY_COLUMNS,
+            ScoreColumn::Population => POPULATION_COLUMNS,
+            ScoreColumn::OffTarget => OFF_TARGET_COLUMNS,
+            ScoreColumn::Coverage => COVERAGE_COLUMNS,
+        }
+    }
+
+    /// Whether the columns are written with these parameters, known element
+    /// annotations, and population catalog
+    pub fn is_enabled(self, params: &Params, annotations: bool, population: bool) -> bool {
+        match self {
+            ScoreColumn::Annotation => annotations,
+            ScoreColumn::Priority => params.priority_score,
+            ScoreColumn::Haplotype => params.haplotype_balance,
+            ScoreColumn::Model => !params.model_rules.is_empty(),
+            ScoreColumn::ModelUncertain => params.flag_model_uncertain,
+            ScoreColumn::Uniqueness => params.uniqueness,
+            ScoreColumn::Stability => params.stability_check.is_some(),
+            ScoreColumn::Population => population,
+            ScoreColumn::OffTarget => params.off_target_depth.is_some(),
+            ScoreColumn::Coverage => params.base_coverage,
+        }
+    }
+}
+
+/// Built-in columns written with these inputs, in output order
+pub fn enabled_columns(
+    params: &Params,
+    annotations: bool,
+    population: bool,
+) -> impl Iterator<Item = ScoreColumn> + '_ {
+    SCORE_COLUMNS
+        .into_iter()
+        .filter(move |column| column.is_enabled(params, annotations, population))
+}
+
+/// Values of a group of columns other than the priority for a locus
+fn column_values(
+    ctx: &WorkflowContext,
+    column: ScoreColumn,
+    locus: &Locus,
+    ext: Option<&Extension>,
+    stability: Option<(f64, f64)>,
+) -> Vec<String> {
+    match column {
+        ScoreColumn::Annotation => match (&ctx.annotations, ext) {
+            (Some(annotations), Some(ext)) => annotations.columns_for(
+                &locus.chrom,
+                locus.start - ext.lf_offset,
+                locus.end + ext.rf_offset,
+            ),
+            _ => vec!["NA".to_string(); ANNOTATION_COLUMNS.len()],
+        },
+        ScoreColumn::Priority => unreachable!("priority is computed from the other columns"),
+        ScoreColumn::Haplotype => haplotype_columns(ext, ctx.params.imbalance_fraction),
+        ScoreColumn::Model => {
+            let model = ext.and_then(|ext| ext.model.clone());
+            vec![model.unwrap_or("NA".to_string())]
+        }
+        ScoreColumn::ModelUncertain => vec![ext.map_or("NA".to_string(), |ext| {
+            format_underflow_runs(&ext.model_underflow)
+        })],
+        ScoreColumn::Uniqueness => {
+            let uniqueness = ext.and_then(|ext| ext.uniqueness);
+            vec![uniqueness.map_or("NA".to_string(), |score| format!("{score:.3}"))]
+        }
+        ScoreColumn::Stability => stability_columns(stability),
+        ScoreColumn::Population => match &ctx.population {
+            Some(population) => population.columns_for(locus),
+            None => vec!["NA".to_string(); POPULATION_COLUMNS.len()],
+        },
+        ScoreColumn::OffTarget => {
+            off_target_columns(ext, ctx.params.off_target_depth.unwrap_or_default())
+        }
+        ScoreColumn::Coverage => coverage_columns(ext),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LocusResult {
+    pub locus: Locus,
+    /// Cluster boundaries, if the locus could be extended
+    pub extension: Option<Extension>,
+    pub concordance: Vec<Concordance>,
+    /// Values of the built-in columns that are enabled followed by those of
+    /// the registered scorers, in the order of the `score_columns`
+    pub scores: Vec<String>,
+}
+
+pub fn run_workflow(ctx: &mut WorkflowContext, locus: &Locus) -> Result<LocusResult> {
+    let mut extension = match get_extension_offsets(ctx, locus) {
+        Ok(ext) => Some(ext),
+        // Audit and scorer failures are reported with the failed loci
+        Err(VclustError::Numerics(msg)) => {
+            return Err(VclustError::Numerics(format!("{}: {msg}", locus.name)));
+        }
+        Err(VclustError::Scorer(msg)) => {
+            return Err(VclustError::Scorer(format!("{}: {msg}", locus.name)));
+        }
+        Err(e) => {
+            log::debug!("Unable to extend {}: {e}", locus.name);
+            None
+        }
+    };
+
+    if let (Some(mode), Some(ext)) = (ctx.params.span_reference, &mut extension) {
+        let span = (locus.start - ext.lf_offset, locus.end + ext.rf_offset);
+        ext.span_reference = Some(span_reference(&ctx.reference, &locus.chrom, span, mode)?);
+    }
+
+    // Only supported clusters have boundaries worth checking
+    let stability = match (ctx.params.stability_check, &extension) {
+        (Some(n_runs), Some(ext)) if ext.ns > 0 => boundary_stability(ctx, locus, n_runs),
+        _ => None,
+    };
+
+    let concordance = if ctx.params.replicate_concordance {
+        get_concordance(ctx, locus)
     } else {
-        Ok(format!("{}\t{in_region}\tNA", locus.name))
+        Vec::new()
+    };
+
+    let mut columns = Vec::new();
+    let mut scores = Vec::new();
+    let mut priority_at = None;
+    let enabled = enabled_columns(
+        &ctx.params,
+        ctx.annotations.is_some(),
+        ctx.population.is_some(),
+    );
+    for column in enabled {
+        // The priority is computed from the values of all the other columns
+        if column == ScoreColumn::Priority {
+            priority_at = Some(scores.len());
+            continue;
+        }
+        columns.extend(column.names().iter().map(|name| name.to_string()));
+        scores.extend(column_values(
+            ctx,
+            column,
+            locus,
+            extension.as_ref(),
+            stability,
+        ));
     }
+    match &mut extension {
+        Some(ext) => scores.append(&mut ext.scores),
+        None => {
+            let n_columns = ctx.scorers.iter().map(|s| s.columns().len()).sum();
+            scores.extend(vec!["NA".to_string(); n_columns]);
+        }
+    }
+    if let Some(at) = priority_at {
+        columns.extend(ctx.scorers.iter().flat_map(|scorer| scorer.columns()));
+        let value = extension.as_ref().and_then(|ext| {
+            let n_samples = ctx
+                .karyotypes
+                .iter()
+                .filter(|k| k.map_or(2, |k| k.ploidy(&locus.chrom, (locus.start, locus.end))) > 0)
+                .count();
+            priority(
+                ctx.params.priority_expression.as_ref(),
+                locus,
+                ext,
+                n_samples,
+                &columns,
+                &scores,
+            )
+        });
+        scores.insert(at, format_priority(value));
+    }
+
+    Ok(LocusResult {
+        locus: locus.clone(),
+        extension,
+        concordance,
+        scores,
+    })
 }
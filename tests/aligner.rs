@@ -0,0 +1,59 @@
+mod common;
+
+use common::{make_record, write_bam};
+use rust_htslib::bam::record::Cigar;
+use std::collections::HashMap;
+use vclust::aligner::{check_alignments, find_aligner, Aligner};
+
+fn program(tags: &[(&str, &str)]) -> HashMap<String, String> {
+    tags.iter()
+        .map(|(tag, value)| (tag.to_string(), value.to_string()))
+        .collect()
+}
+
+/// The aligner is found by walking back from the last program of the chain,
+/// past later processing steps
+#[test]
+fn aligner_is_found_along_the_program_chain() {
+    let programs = [
+        program(&[("ID", "minimap2"), ("PN", "minimap2"), ("VN", "2.17")]),
+        program(&[
+            ("ID", "pbmm2"),
+            ("PN", "pbmm2"),
+            ("VN", "1.13.0"),
+            ("PP", "minimap2"),
+            ("CL", "pbmm2 align --preset CCS"),
+        ]),
+        program(&[("ID", "samtools"), ("PN", "samtools"), ("PP", "pbmm2")]),
+    ];
+    assert_eq!(
+        find_aligner(&programs),
+        Some(Aligner {
+            program: "pbmm2".to_string(),
+            version: Some("1.13.0".to_string()),
+            command_line: Some("pbmm2 align --preset CCS".to_string()),
+        })
+    );
+
+    let programs = [
+        program(&[("ID", "bwa"), ("PN", "bwa"), ("VN", "0.7.17")]),
+        program(&[("ID", "samtools"), ("PN", "samtools"), ("PP", "bwa")]),
+    ];
+    assert_eq!(find_aligner(&programs), None);
+}
+
+#[test]
+fn match_operations_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let bam_path = dir.path().join("sample0.bam");
+    let records = (0..5)
+        .map(|index| make_record(&format!("read{index}"), 1000, vec![Cigar::Match(500)], 60))
+        .collect();
+    write_bam(&bam_path, "sample0", records);
+    let check = check_alignments(&bam_path).unwrap().unwrap();
+    assert!(!check.has_programs);
+    assert!(check.missing_eqx);
+    let warnings = check.warnings("sample0");
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("realign with --eqx"), "{warnings:?}");
+}
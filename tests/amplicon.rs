@@ -0,0 +1,102 @@
+mod common;
+
+use common::{make_record, write_bam, CONTIG};
+use rust_htslib::bam::record::Cigar;
+use rust_htslib::bam::Record;
+use std::fs;
+use std::path::Path;
+use vclust::alignment::open_source;
+use vclust::amplicon::Amplicons;
+use vclust::context::{Params, Scratch};
+use vclust::profile::{get_profile, Prof, ProfileOptions};
+
+const REGION: (&str, i64, i64) = (CONTIG, 7000, 9000);
+
+/// Profile of the region over the reads of one BAM, with or without the
+/// amplicons of a BED file
+fn profile(dir: &Path, records: Vec<Record>, bed: Option<&str>) -> Prof {
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let amplicons = bed.map(|bed| {
+        let bed_path = dir.join("amplicons.bed");
+        fs::write(&bed_path, bed).unwrap();
+        Amplicons::load(&bed_path).unwrap()
+    });
+    let mut bam = open_source(&bam_path).unwrap();
+    let options = ProfileOptions {
+        locus_span: (7990, 8010),
+        amplicons: amplicons.as_ref(),
+        keep_reads: false,
+        read_quota: None,
+        reference: None,
+    };
+    let (prof, _) = get_profile(
+        bam.as_mut(),
+        REGION,
+        &Params::default(),
+        &mut Scratch::default(),
+        options,
+    )
+    .unwrap();
+    prof
+}
+
+fn at(pos: i64) -> usize {
+    (pos - REGION.1) as usize
+}
+
+/// Reads clipped within the tolerance of an amplicon start carry primer
+/// clips, while clips in the middle of the amplicon are alt signal
+#[test]
+fn primer_clips_are_ignored_only_at_amplicon_boundaries() {
+    let dir = tempfile::tempdir().unwrap();
+    let records = (0..20)
+        .map(|index| {
+            let pos = match index % 2 == 0 {
+                true => 7805,
+                false => 7850,
+            };
+            let ops = vec![Cigar::SoftClip(30), Cigar::Equal(1000)];
+            make_record(&format!("read{index}"), pos, ops, 60)
+        })
+        .collect::<Vec<_>>();
+
+    let prof = profile(dir.path(), records.clone(), Some("chr1\t7800\t8900\n"));
+    assert_eq!(prof.clips[at(7805)], 0.0);
+    assert!(prof.clips[at(7850)] > 0.0);
+    assert_eq!(prof.alts[at(7805)], 0.0);
+    assert!(prof.alts[at(7850)] > 0.0);
+
+    let prof = profile(dir.path(), records, None);
+    assert!(prof.clips[at(7805)] > 0.0);
+    assert!(prof.alts[at(7805)] > 0.0);
+}
+
+/// Half of the reads of a deep and a shallow amplicon carry a mismatch run;
+/// the alt fraction of both is one half only when normalized per amplicon
+#[test]
+fn alts_are_normalized_by_the_depth_of_their_amplicon() {
+    let dir = tempfile::tempdir().unwrap();
+    let amplicon_reads = |start: i64, n_reads: usize| {
+        (0..n_reads).map(move |index| {
+            let ops = match index % 2 == 0 {
+                true => vec![Cigar::Equal(500), Cigar::Diff(20), Cigar::Equal(480)],
+                false => vec![Cigar::Equal(1000)],
+            };
+            make_record(&format!("{start}_{index}"), start, ops, 60)
+        })
+    };
+    let records = amplicon_reads(7000, 40)
+        .chain(amplicon_reads(8000, 10))
+        .collect::<Vec<_>>();
+
+    let bed = "chr1\t7000\t8000\nchr1\t8000\t9000\n";
+    let prof = profile(dir.path(), records.clone(), Some(bed));
+    assert_eq!(prof.alts[at(7510)], 0.5);
+    assert_eq!(prof.alts[at(8510)], 0.5);
+
+    // The region-wide mean depth is 25
+    let prof = profile(dir.path(), records, None);
+    assert_eq!(prof.alts[at(7510)], 0.8);
+    assert_eq!(prof.alts[at(8510)], 0.2);
+}
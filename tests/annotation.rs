@@ -0,0 +1,24 @@
+use std::fs;
+use vclust::annotation::Annotations;
+
+#[test]
+fn overlapping_elements_are_counted_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("known.bed");
+    fs::write(
+        &path,
+        "chr1\t9030\t9050\tSTR1\nchr1\t9045\t9100\tVNTR1\nchr1\t1000\t2000\nchr2\t9000\t9060\tSD1\n",
+    )
+    .unwrap();
+    let annotations = Annotations::load(&path).unwrap();
+
+    let (names, fraction) = annotations.annotate("chr1", 9000, 9060);
+    assert_eq!(names, ["STR1", "VNTR1"]);
+    assert_eq!(fraction, 0.5);
+
+    assert_eq!(
+        annotations.columns_for("chr1", 900, 1100),
+        ["chr1:1000-2000", "0.50"]
+    );
+    assert_eq!(annotations.columns_for("chr3", 0, 100), [".", "0.00"]);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::CONTIG;
+use std::fs;
+use vclust::anonymize::pseudonym;
+
+/// Evidence files written with --anonymize keep their rows but name the
+/// samples only by pseudonym
+#[test]
+fn anonymized_evidence_hides_sample_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions_path = dir.path().join("cluster.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+
+    let run = |evidence_name: &str, extra_args: &[&str]| {
+        let evidence_path = dir.path().join(evidence_name);
+        let status = common::vclust(&dataset.with_regions(&regions_path))
+            .arg("--support-evidence")
+            .arg(&evidence_path)
+            .args(extra_args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+        fs::read_to_string(evidence_path).unwrap()
+    };
+
+    let plain = run("evidence.txt", &[]);
+    let anonymized = run("anonymized.txt", &["--anonymize"]);
+    assert!(plain.contains("\tsample0\t"));
+    assert!(!anonymized.contains("sample0"));
+    let expected = plain
+        .replace("\tsample0\t", &format!("\t{}\t", pseudonym("sample0")))
+        .replace("\tsample1\t", &format!("\t{}\t", pseudonym("sample1")));
+    assert_eq!(anonymized, expected);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::File;
+use std::io::Write;
+
+/// Two haplotype contigs spanning the whole reference, one carrying a cluster
+/// of indels next to the region
+#[test]
+fn assembly_mode_extends_over_haplotype_divergence() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let divergent = vec![
+        Cigar::Equal(8000),
+        Cigar::Del(30),
+        Cigar::Equal(20),
+        Cigar::Ins(40),
+        Cigar::Diff(10),
+        Cigar::Equal(CONTIG_LEN as u32 - 8060),
+    ];
+    let haplotypes = [divergent, vec![Cigar::Equal(CONTIG_LEN as u32)]];
+    let reads_path = dir.path().join("reads.txt");
+    let mut reads = File::create(&reads_path).unwrap();
+    for (index, ops) in haplotypes.into_iter().enumerate() {
+        let path = dir.path().join(format!("hap{index}.bam"));
+        write_bam(
+            &path,
+            "asm",
+            vec![common::make_record("contig", 0, ops, 60)],
+        );
+        writeln!(reads, "{}\tasm", path.display()).unwrap();
+    }
+
+    let regions_path = dir.path().join("regions.bed");
+    let mut regions = File::create(&regions_path).unwrap();
+    writeln!(regions, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let run = |extra_args: &[&str]| {
+        let output = common::vclust(&dataset).args(extra_args).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let row = stdout.lines().last().unwrap().to_string();
+        row.split('\t').map(str::to_string).collect::<Vec<_>>()
+    };
+
+    // Single-alignment depth is below the read-based thresholds
+    assert_eq!(run(&[])[2], "NA");
+
+    let row = run(&["--assembly"]);
+    assert_eq!(row[2], "40");
+    assert_eq!(row[5], "1");
+}
+
+#[test]
+fn paf_alignments_match_bam_alignments() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let tail = CONTIG_LEN - 8060;
+    let haplotypes = [
+        format!("8000=30D20=40I10X{tail}="),
+        format!("{CONTIG_LEN}="),
+    ];
+    let reads_path = dir.path().join("reads.txt");
+    let mut reads = File::create(&reads_path).unwrap();
+    for (index, cigar) in haplotypes.iter().enumerate() {
+        let path = dir.path().join(format!("hap{index}.paf"));
+        let mut paf = File::create(&path).unwrap();
+        writeln!(
+            paf,
+            "contig{index}\t{CONTIG_LEN}\t0\t{CONTIG_LEN}\t+\t{CONTIG}\t{CONTIG_LEN}\t0\t{CONTIG_LEN}\t{CONTIG_LEN}\t{CONTIG_LEN}\t60\ttp:A:P\tcg:Z:{cigar}"
+        )
+        .unwrap();
+        writeln!(reads, "{}\tasm", path.display()).unwrap();
+    }
+
+    let regions_path = dir.path().join("regions.bed");
+    let mut regions = File::create(&regions_path).unwrap();
+    writeln!(regions, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let output = common::vclust(&dataset).arg("--assembly").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let row = stdout
+        .lines()
+        .last()
+        .unwrap()
+        .split('\t')
+        .collect::<Vec<_>>();
+    assert_eq!(row[2], "40");
+    assert_eq!(row[5], "1");
+}
@@ -0,0 +1,25 @@
+use std::fs;
+use std::io::Write;
+use vclust::atomic::{check_overwrite, AtomicFile};
+
+#[test]
+fn files_appear_only_on_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.txt");
+
+    let mut file = AtomicFile::create(&path).unwrap();
+    writeln!(file, "partial").unwrap();
+    drop(file);
+    assert!(!path.exists());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+    let mut file = AtomicFile::create(&path).unwrap();
+    writeln!(file, "complete").unwrap();
+    assert!(!path.exists());
+    file.commit().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "complete\n");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    assert!(check_overwrite(&[path.as_path()], false).is_err());
+    assert!(check_overwrite(&[path.as_path()], true).is_ok());
+}
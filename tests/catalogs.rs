@@ -0,0 +1,53 @@
+mod common;
+
+use std::fs;
+use vclust::locus::{tag_catalogs, Catalog};
+
+#[test]
+fn catalogs_are_labelled() {
+    let catalog = Catalog::parse("/data/repeats.bed");
+    assert_eq!(catalog.label, "repeats");
+    assert!(!tag_catalogs(std::slice::from_ref(&catalog)).unwrap());
+
+    let labelled = Catalog::parse("trs=/data/repeats.bed");
+    assert_eq!(labelled.label, "trs");
+    assert_eq!(labelled.path, catalog.path);
+    assert!(tag_catalogs(&[labelled]).unwrap());
+    assert!(tag_catalogs(&[catalog.clone(), Catalog::parse("/other/repeats.bed")]).is_err());
+}
+
+/// Regions in both catalogs are processed once for each
+#[test]
+fn records_are_tagged_with_their_catalog() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let other_path = dir.path().join("other.bed");
+    fs::write(&other_path, "chr1\t8040\t8060\tcluster\n").unwrap();
+    let main = dataset.with_regions(format!("main={}", dataset.regions_path.display()));
+    let output = common::vclust(&main)
+        .arg("--regions")
+        .arg(&other_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines.next().unwrap().ends_with("\tcatalog"));
+    let tagged = lines
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            (fields[0], fields[5], fields[fields.len() - 1])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tagged,
+        [
+            ("cluster", "2", "main"),
+            ("reference", "0", "main"),
+            ("contig_start", "NA", "main"),
+            ("no_coverage", "NA", "main"),
+            ("cluster", "2", "other"),
+        ]
+    );
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::{Aux, Cigar};
+use std::fs::File;
+use std::io::Write;
+
+/// Reads carrying the cluster also have supplementary alignments on another
+/// contig, so the cluster is only supported by chimeras
+#[test]
+fn chimeric_reads_are_excluded_from_support() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let mut records = Vec::new();
+    for index in 0..20 {
+        let name = format!("read{index}");
+        if index % 2 == 0 {
+            let ops = vec![
+                Cigar::Equal(3000),
+                Cigar::Del(30),
+                Cigar::Equal(20),
+                Cigar::Ins(40),
+                Cigar::Diff(10),
+                Cigar::Equal(940),
+            ];
+            let mut rec = common::make_record(&name, 5000, ops, 60);
+            rec.push_aux(b"SA", Aux::String("chr5,1000,+,2000S2000M,60,0;"))
+                .unwrap();
+            records.push(rec);
+        } else {
+            records.push(common::make_record(
+                &name,
+                5000,
+                vec![Cigar::Equal(4000)],
+                60,
+            ));
+        }
+    }
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let reads_path = dir.path().join("reads.txt");
+    writeln!(File::create(&reads_path).unwrap(), "{}", bam_path.display()).unwrap();
+
+    let regions_path = dir.path().join("regions.bed");
+    let mut regions = File::create(&regions_path).unwrap();
+    writeln!(regions, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let run = |extra_args: &[&str]| {
+        let output = common::vclust(&dataset).args(extra_args).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let row = stdout.lines().last().unwrap().to_string();
+        row.split('\t').map(str::to_string).collect::<Vec<_>>()
+    };
+
+    let row = run(&["--exclude-chimeras"]);
+    assert_eq!(row[2], "0");
+    assert_eq!(row[5], "0");
+    assert_eq!(row[8], "10");
+
+    // Chimeras are only excluded on request
+    let row = run(&[]);
+    assert_eq!(row[2], "40");
+    assert_eq!(row[5], "1");
+    assert_eq!(row[8], "0");
+}
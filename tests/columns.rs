@@ -0,0 +1,54 @@
+mod common;
+
+#[test]
+fn selected_columns_are_written_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let run = |args: &[&str]| common::vclust(&dataset).args(args).output().unwrap();
+
+    let output = run(&["--columns", "name,chrom,start,end,ns"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "#name\tchrom\tstart\tend\tns",
+            "cluster\tchr1\t8000\t8060\t2",
+            "reference\tchr1\t6000\t6020\t0",
+            "contig_start\tchr1\tNA\tNA\tNA",
+            "no_coverage\tchr1\tNA\tNA\tNA",
+        ]
+    );
+
+    // BED output keeps its rule of writing extended regions only
+    let output = run(&[
+        "--output-format",
+        "bed",
+        "--columns",
+        "chrom,start,end,spanning_reads",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.starts_with("chr1\t8000\t8060\t"));
+
+    // BED records start with the extended region whatever the selection
+    let output = run(&["--output-format", "bed", "--columns", "ns,name,start"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "chr1\t8000\t8060\t2\tcluster",
+            "chr1\t6000\t6020\t0\treference"
+        ]
+    );
+
+    let output = run(&["--columns", "name,depth"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Unknown output column depth"));
+    assert!(!run(&["--output-format", "jsonl", "--columns", "name"])
+        .status
+        .success());
+}
@@ -0,0 +1,174 @@
+//! Helpers for building synthetic alignments and datasets in tests
+
+#![allow(dead_code)]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_htslib::bam::header::HeaderRecord;
+use rust_htslib::bam::record::{Cigar, CigarString};
+use rust_htslib::bam::{self, Header, Record};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const CONTIG: &str = "chr1";
+pub const CONTIG_LEN: usize = 20_000;
+
+/// Build a mapped record with a dummy sequence matching the query length of its CIGAR
+pub fn make_record(name: &str, pos: i64, ops: Vec<Cigar>, mapq: u8) -> Record {
+    let query_len = ops
+        .iter()
+        .map(|op| match op {
+            Cigar::Match(len)
+            | Cigar::Equal(len)
+            | Cigar::Diff(len)
+            | Cigar::Ins(len)
+            | Cigar::SoftClip(len) => *len as usize,
+            _ => 0,
+        })
+        .sum::<usize>();
+    let seq = vec![b'A'; query_len];
+    let qual = vec![30; query_len];
+
+    let mut rec = Record::new();
+    rec.set(name.as_bytes(), Some(&CigarString(ops)), &seq, &qual);
+    rec.set_tid(0);
+    rec.set_pos(pos);
+    rec.set_mapq(mapq);
+    rec.unset_flags();
+    rec
+}
+
+pub fn write_fasta(path: &Path, seq: &[u8]) {
+    let mut file = File::create(path).unwrap();
+    writeln!(file, ">{CONTIG}").unwrap();
+    for line in seq.chunks(60) {
+        file.write_all(line).unwrap();
+        writeln!(file).unwrap();
+    }
+}
+
+/// Write a coordinate-sorted, indexed BAM with a single contig
+/// An unmapped record placed at a position and a mapped record without a
+/// CIGAR, both at `pos`; htslib itself rejects records whose CIGAR and
+/// sequence lengths differ when reading a BAM
+pub fn malformed_records(pos: i64) -> Vec<Record> {
+    let mut unmapped = make_record("unmapped", pos, Vec::new(), 0);
+    unmapped.set_flags(0x4);
+    let empty = make_record("empty_cigar", pos, Vec::new(), 60);
+    vec![unmapped, empty]
+}
+
+pub fn write_bam(path: &Path, sample: &str, mut records: Vec<Record>) {
+    let mut header = Header::new();
+    let mut sq = HeaderRecord::new(b"SQ");
+    sq.push_tag(b"SN", CONTIG);
+    sq.push_tag(b"LN", CONTIG_LEN);
+    header.push_record(&sq);
+    let mut rg = HeaderRecord::new(b"RG");
+    rg.push_tag(b"ID", sample);
+    rg.push_tag(b"SM", sample);
+    header.push_record(&rg);
+
+    records.sort_by_key(|rec| rec.pos());
+    {
+        let mut writer = bam::Writer::from_path(path, &header, bam::Format::Bam).unwrap();
+        for rec in &records {
+            writer.write(rec).unwrap();
+        }
+    }
+    bam::index::build(path, None, bam::index::Type::Bai, 1).unwrap();
+}
+
+#[derive(Clone)]
+pub struct Dataset {
+    pub genome_path: PathBuf,
+    pub reads_path: PathBuf,
+    pub regions_path: PathBuf,
+}
+
+impl Dataset {
+    /// The same genome and reads with other regions
+    pub fn with_regions(&self, regions_path: impl Into<PathBuf>) -> Dataset {
+        Dataset {
+            regions_path: regions_path.into(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Command running vclust on the genome, reads, and regions of a dataset
+pub fn vclust(dataset: &Dataset) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vclust"));
+    command
+        .arg("--genome")
+        .arg(&dataset.genome_path)
+        .arg("--reads")
+        .arg(&dataset.reads_path)
+        .arg("--regions")
+        .arg(&dataset.regions_path);
+    command
+}
+
+/// Deterministically generate the small three-sample dataset used by golden tests
+///
+/// The first two samples carry a variation cluster at 8000-8100 on half of their
+/// reads; the third sample is reference-like throughout.
+pub fn build_dataset(dir: &Path) -> Dataset {
+    let mut rng = StdRng::seed_from_u64(7);
+    let genome = (0..CONTIG_LEN)
+        .map(|_| b"ACGT"[rng.gen_range(0..4)])
+        .collect::<Vec<_>>();
+    let genome_path = dir.join("genome.fa");
+    write_fasta(&genome_path, &genome);
+
+    let read_len: i64 = 4000;
+    let mut bam_paths = Vec::new();
+    for sample in 0..3 {
+        let mut records = Vec::new();
+        for index in 0..40 {
+            let pos = rng.gen_range(3000..12000 - read_len);
+            let offset = 8000 - pos;
+            let ops = if sample < 2 && index % 2 == 0 && (200..read_len - 300).contains(&offset) {
+                let offset = offset as u32;
+                let tail = read_len as u32 - offset - 100;
+                vec![
+                    Cigar::Equal(offset),
+                    Cigar::Del(30),
+                    Cigar::Equal(20),
+                    Cigar::Ins(40),
+                    Cigar::Diff(10),
+                    Cigar::Equal(40),
+                    Cigar::Equal(tail),
+                ]
+            } else {
+                vec![Cigar::Equal(read_len as u32)]
+            };
+            let name = format!("m0/{sample}/{index}/ccs");
+            records.push(make_record(&name, pos, ops, 60));
+        }
+        let path = dir.join(format!("sample{sample}.bam"));
+        write_bam(&path, &format!("sample{sample}"), records);
+        bam_paths.push(path);
+    }
+
+    let reads_path = dir.join("reads.txt");
+    let mut file = File::create(&reads_path).unwrap();
+    for path in &bam_paths {
+        writeln!(file, "{}", path.display()).unwrap();
+    }
+
+    let regions_path = dir.join("regions.bed");
+    let mut file = File::create(&regions_path).unwrap();
+    writeln!(file, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+    writeln!(file, "{CONTIG}\t6000\t6020\treference").unwrap();
+    writeln!(file, "{CONTIG}\t100\t120\tcontig_start").unwrap();
+    writeln!(file, "{CONTIG}\t16000\t16020\tno_coverage").unwrap();
+
+    Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    }
+}
@@ -0,0 +1,76 @@
+use vclust::compare::{compare_clusters, write_comparison, ClusterStatus};
+use vclust::liftover::parse_chains;
+use vclust::tracks::ClusterTrack;
+
+/// chr1:0-5000 maps to chrA:1000-6000 and chr1:5000-10000 to chrA:7000-12000
+const CHAINS: &[u8] = b"chain 1000 chr1 20000 + 0 10000 chrA 21000 + 1000 11000 1\n\
+    5000 0 1000\n\
+    5000\n";
+
+fn cluster(name: &str, chrom: &str, start: i64, end: i64) -> ClusterTrack {
+    ClusterTrack {
+        name: name.to_string(),
+        chrom: chrom.to_string(),
+        start,
+        end,
+        ns: 2,
+    }
+}
+
+#[test]
+fn clusters_are_matched_across_references() {
+    let chains = parse_chains(CHAINS).unwrap();
+    let first = [
+        cluster("shared", "chr1", 100, 200),
+        cluster("first_only", "chr1", 6000, 6100),
+        cluster("unlifted", "chr1", 15000, 15100),
+    ];
+    let second = [
+        cluster("shared", "chrA", 1150, 1250),
+        cluster("second_only", "chrA", 3000, 3100),
+    ];
+    let compared = compare_clusters(&first, &second, &chains);
+    let statuses = compared
+        .iter()
+        .map(|rec| (rec.reference, rec.cluster.name.as_str(), rec.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            (0, "shared", ClusterStatus::Shared),
+            (0, "first_only", ClusterStatus::Specific),
+            (0, "unlifted", ClusterStatus::Unlifted),
+            (1, "shared", ClusterStatus::Shared),
+            (1, "second_only", ClusterStatus::Specific),
+        ]
+    );
+
+    let mut report = Vec::new();
+    write_comparison(&compared, ["GRCh38", "CHM13"], &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let lines = report.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        "#reference\tname\textended_region\tns\tstatus\tother_region"
+    );
+    assert_eq!(
+        lines[1],
+        "GRCh38\tshared\tchr1:100-200\t2\tshared\tchrA:1150-1250"
+    );
+    assert_eq!(
+        lines[2],
+        "GRCh38\tfirst_only\tchr1:6000-6100\t2\tspecific\tchrA:8000-8100"
+    );
+    assert_eq!(
+        lines[3],
+        "GRCh38\tunlifted\tchr1:15000-15100\t2\tunlifted\tNA"
+    );
+    assert_eq!(
+        lines[4],
+        "CHM13\tshared\tchrA:1150-1250\t2\tshared\tchr1:100-200"
+    );
+    assert_eq!(
+        lines[5],
+        "CHM13\tsecond_only\tchrA:3000-3100\t2\tspecific\tNA"
+    );
+}
@@ -0,0 +1,36 @@
+use std::process::Command;
+
+fn vclust(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn completion_scripts_list_subcommands_and_options() {
+    let bash = vclust(&["completions", "bash"]);
+    assert!(bash.contains("complete -o default -F _vclust vclust"));
+    assert!(bash.contains("--output-format) opts=\"tsv bed vcf jsonl\""));
+    assert!(bash.contains("--chain --unmapped"));
+
+    let zsh = vclust(&["completions", "zsh"]);
+    assert!(zsh.starts_with("#compdef vclust"));
+    assert!(zsh.contains("'tracks:Package results as a track hub for UCSC and IGV'"));
+
+    let fish = vclust(&["completions", "fish"]);
+    assert!(fish.contains("-n '__fish_seen_subcommand_from liftover' -l chain"));
+}
+
+#[test]
+fn man_page_documents_every_option() {
+    let page = vclust(&["man"]);
+    assert!(page.starts_with(&format!(
+        ".TH VCLUST 1 \"\" \"vclust {}\"",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(page.contains("\\fB\\-\\-output\\-format\\fR \\fIFORMAT\\fR"));
+    assert!(page.contains(".SS liftover"));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::collections::BTreeMap;
+use vclust::contamination::{contamination_warnings, ContaminationEstimate};
+use vclust::summary::RunSummary;
+
+#[test]
+fn estimates_pool_deep_enough_loci() {
+    let mut estimate = ContaminationEstimate::default();
+    estimate.add(1, 40);
+    estimate.add(3, 60);
+    // Too shallow to count
+    estimate.add(5, 5);
+    assert_eq!((estimate.hom_ref_loci, estimate.reads), (2, 100));
+    assert_eq!(estimate.alt_fraction, 0.04);
+
+    let mut clean = ContaminationEstimate::default();
+    clean.add(0, 50);
+    let estimates = BTreeMap::from([
+        ("clean".to_string(), clean),
+        ("mixed".to_string(), estimate.clone()),
+    ]);
+    let warnings = contamination_warnings(&estimates);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("4.0% of the reads of mixed"));
+
+    let mut merged = estimate.clone();
+    merged.merge(&estimate);
+    assert_eq!((merged.hom_ref_loci, merged.alt_reads), (4, 8));
+    assert_eq!(merged.alt_fraction, 0.04);
+}
+
+/// Samples of the reference-like locus carry no alt reads
+#[test]
+fn summary_reports_background_alt_fractions() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let summary_path = dir.path().join("summary.json");
+    let output = common::vclust(&dataset)
+        .arg("--summary")
+        .arg(&summary_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let summary = RunSummary::load(&summary_path).unwrap();
+    let samples = summary.contamination.keys().collect::<Vec<_>>();
+    assert_eq!(samples, ["sample0", "sample1", "sample2"]);
+    for estimate in summary.contamination.values() {
+        assert_eq!(estimate.hom_ref_loci, 1);
+        assert!(estimate.reads >= 10);
+        assert_eq!(estimate.alt_fraction, 0.0);
+    }
+}
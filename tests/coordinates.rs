@@ -0,0 +1,128 @@
+mod common;
+
+use proptest::prelude::*;
+use rust_htslib::bam::record::Cigar;
+use vclust::context::Params;
+use vclust::error::VclustError;
+use vclust::extend::{extend_region, extend_to_ref_flanks};
+use vclust::locus::Locus;
+use vclust::models::{LogModels, Models};
+use vclust::profile::{region_len, update_profs};
+
+/// Operations including those that hold no aligned bases
+fn cigar_op() -> impl Strategy<Value = Cigar> {
+    prop_oneof![
+        (0..200u32).prop_map(Cigar::Equal),
+        (0..20u32).prop_map(Cigar::Diff),
+        (0..50u32).prop_map(Cigar::Ins),
+        (0..50u32).prop_map(Cigar::Del),
+        (0..500u32).prop_map(Cigar::RefSkip),
+        (0..30u32).prop_map(Cigar::SoftClip),
+        (0..30u32).prop_map(Cigar::HardClip),
+        (0..5u32).prop_map(Cigar::Pad),
+    ]
+}
+
+fn position() -> impl Strategy<Value = i64> {
+    prop_oneof![-1000..3000i64, (1i64 << 40)..(1i64 << 41)]
+}
+
+proptest! {
+    #[test]
+    fn search_regions_lie_within_the_contig(
+        start in -100..2000i64,
+        len in -50..200i64,
+        radius in 0..1000i64,
+        contig_len in -1..3000i64,
+    ) {
+        let locus = Locus {
+            chrom: "chr1".to_string(),
+            start,
+            end: start + len,
+            name: "locus".to_string(),
+            group: None,
+        };
+        let valid = contig_len >= 0
+            && start >= radius
+            && len > 0
+            && start + len + radius <= contig_len;
+        match extend_region(&locus, radius, contig_len) {
+            Ok((_, region_start, region_end)) => {
+                prop_assert!(valid);
+                prop_assert_eq!((region_start, region_end), (start - radius, start + len + radius));
+                prop_assert!(0 <= region_start && region_end <= contig_len);
+            }
+            Err(e) => {
+                prop_assert!(!valid);
+                prop_assert!(matches!(e, VclustError::Region(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn profiles_reject_bad_regions_without_panicking(
+        pos in position(),
+        ops in prop::collection::vec(cigar_op(), 0..15),
+        region_start in -500..2000i64,
+        len in -100..1000i64,
+        len_delta in -1..=1i64,
+    ) {
+        let region = ("chr1", region_start, region_start + len);
+        let counts_len = (len + len_delta).max(0) as usize;
+        let mut covs = vec![0; counts_len];
+        let mut alts = vec![0; counts_len];
+        let mut clips = vec![0; counts_len];
+        let rec = common::make_record("read", pos, ops.clone(), 60);
+        let result = update_profs(&rec, &mut covs, &mut alts, &mut clips, region, 5);
+
+        let valid = region_len(region).is_ok_and(|len| len == counts_len);
+        prop_assert_eq!(result.is_ok(), valid);
+        if valid {
+            let aligned = ops
+                .iter()
+                .map(|op| match op {
+                    Cigar::Equal(len) | Cigar::Diff(len) | Cigar::Del(len) => *len,
+                    _ => 0,
+                })
+                .sum::<u32>();
+            prop_assert!(covs.iter().sum::<u32>() <= aligned);
+            prop_assert!(covs.iter().all(|cov| *cov <= 1));
+        }
+    }
+
+    /// A core of alt states between reference-like flanks stops extension at
+    /// the flanks if they hold a whole window, including one at the very
+    /// start or end of the profile
+    #[test]
+    fn flanks_are_found_at_the_profile_ends(
+        window_len in 5..20i64,
+        left in 0..40usize,
+        core in 1..30usize,
+        right in 0..40usize,
+    ) {
+        let models = LogModels::from(&Models::default());
+        let alt_state = models.n_states as u8 - 1;
+        let alts = [vec![0; left], vec![alt_state; core], vec![0; right]].concat();
+        let span = (left as i64, (left + core) as i64);
+        let extended = extend_to_ref_flanks(
+            &alts,
+            span,
+            window_len,
+            &models,
+            Params::default().min_prob_ref,
+            None,
+            false,
+        )
+        .unwrap();
+        let has_flanks = left as i64 >= window_len && right as i64 >= window_len;
+        prop_assert_eq!(extended, has_flanks.then_some(span));
+    }
+}
+
+#[test]
+fn regions_must_start_before_their_end() {
+    assert_eq!(region_len(("chr1", 0, 10)).unwrap(), 10);
+    for region in [("chr1", 10, 10), ("chr1", 10, 5), ("chr1", -1, 5)] {
+        assert!(matches!(region_len(region), Err(VclustError::Region(_))));
+    }
+}
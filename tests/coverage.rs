@@ -0,0 +1,67 @@
+mod common;
+
+use common::make_record;
+use rust_htslib::bam::record::Cigar;
+use vclust::coverage::{get_base_coverage, BaseCoverage};
+
+#[test]
+fn deletions_cover_physically_but_not_base_level() {
+    // Four reads over 1000-1400 deleting 1100-1300
+    let mut reads = (0..4)
+        .map(|index| {
+            let ops = vec![Cigar::Equal(100), Cigar::Del(200), Cigar::Equal(100)];
+            make_record(&format!("deleted{index}"), 1000, ops, 60)
+        })
+        .collect::<Vec<_>>();
+    let within = get_base_coverage(&reads, (1100, 1300), 1);
+    assert_eq!(
+        within,
+        BaseCoverage {
+            physical_depth: 4.0,
+            base_depth: 0.0
+        }
+    );
+    let across = get_base_coverage(&reads, (1000, 1400), 2);
+    assert_eq!(
+        across,
+        BaseCoverage {
+            physical_depth: 2.0,
+            base_depth: 1.0
+        }
+    );
+
+    // Spliced-out intervals are covered by neither
+    let ops = vec![Cigar::Equal(100), Cigar::RefSkip(200), Cigar::Equal(100)];
+    reads.push(make_record("spliced", 1000, ops, 60));
+    assert_eq!(
+        get_base_coverage(&reads, (1100, 1300), 1).physical_depth,
+        4.0
+    );
+}
+
+#[test]
+fn adds_coverage_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let output = common::vclust(&dataset)
+        .arg("--base-coverage")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines
+        .next()
+        .unwrap()
+        .ends_with("\tphysical_depth\tbase_depth"));
+    let rows = lines
+        .map(|line| line.split('\t').rev().take(2).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // Regions that could not be extended have no depth to report
+    assert_eq!(rows[2], ["NA", "NA"]);
+    for row in &rows[..2] {
+        let [base, physical] = [row[0], row[1]].map(|value| value.parse::<f64>().unwrap());
+        assert!(physical > 0.0);
+        assert!(base <= physical);
+    }
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::{Aux, Cigar};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::deletion::LongDeletion;
+use vclust::extend::{get_extension_offsets, Extension};
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+
+/// Extend a locus within a 2.5 kb deletion of a single sample, whose 20 reads
+/// are split across the deletion with their primary alignment on the left
+/// flank and a supplementary alignment on the right flank
+fn extend_deletion(dir: &Path, long_deletions: bool) -> vclust::error::Result<Extension> {
+    write_fasta(&dir.join("genome.fa"), &vec![b'A'; CONTIG_LEN]);
+    let mut records = Vec::new();
+    for index in 0..20 {
+        let name = format!("read{index}");
+        let mut primary = make_record(
+            &name,
+            7000,
+            vec![Cigar::Equal(2000), Cigar::SoftClip(1000)],
+            60,
+        );
+        primary
+            .push_aux(b"SA", Aux::String("chr1,11501,+,2000S1000M,60,0;"))
+            .unwrap();
+        let mut supplementary = make_record(
+            &name,
+            11500,
+            vec![Cigar::SoftClip(2000), Cigar::Equal(1000)],
+            60,
+        );
+        supplementary.set_flags(0x800);
+        supplementary
+            .push_aux(b"SA", Aux::String("chr1,7001,+,2000M1000S,60,0;"))
+            .unwrap();
+        records.extend([primary, supplementary]);
+    }
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let mut reads = File::create(dir.join("reads.txt")).unwrap();
+    writeln!(reads, "{}", bam_path.display()).unwrap();
+
+    let params = Params {
+        long_deletions,
+        min_depth: 5.0,
+        ..Default::default()
+    };
+    let builder = ContextBuilder {
+        params: Arc::new(params),
+        ..ContextBuilder::new(
+            dir.join("genome.fa"),
+            load_manifest(&dir.join("reads.txt")).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 10000,
+        end: 10100,
+        name: "cluster".to_string(),
+        group: None,
+    };
+    get_extension_offsets(&mut ctx, &locus)
+}
+
+#[test]
+fn split_reads_call_a_deletion_longer_than_the_window() {
+    let dir = tempfile::tempdir().unwrap();
+    let ext = extend_deletion(dir.path(), true).unwrap();
+    assert_eq!(
+        ext.long_deletion,
+        Some(LongDeletion {
+            start: 9000,
+            end: 11500,
+            split_reads: 20,
+        })
+    );
+    assert_eq!((ext.lf_offset, ext.rf_offset), (1000, 1400));
+    assert_eq!(ext.ns, 1);
+}
+
+#[test]
+fn long_deletions_fail_for_low_depth_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(extend_deletion(dir.path(), false).is_err());
+}
@@ -0,0 +1,24 @@
+use std::process::Command;
+
+/// The demo writes its inputs, runs the workflow, and explains the results;
+/// running it again in the same directory replaces them
+#[test]
+fn demo_extends_the_bundled_cluster() {
+    let dir = tempfile::tempdir().unwrap();
+    for _ in 0..2 {
+        let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+            .arg("demo")
+            .arg("--out-dir")
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("cluster (chr1:6040-6060)"));
+        let lf_offset = lines.next().unwrap().split_whitespace().collect::<Vec<_>>();
+        assert_eq!(lf_offset[..2], ["lf_offset", "40"]);
+        assert!(stdout.contains("reference (chr1:4000-4020)"));
+    }
+    assert!(dir.path().join("results.txt").exists());
+}
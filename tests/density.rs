@@ -0,0 +1,145 @@
+mod common;
+
+use common::CONTIG;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use vclust::density::DensityGuard;
+use vclust::locus::Locus;
+use vclust::summary::RunSummary;
+use vclust::workflow::LocusResult;
+
+/// Run over the dataset with a second locus in its cluster, so that its
+/// megabase holds two supported clusters
+fn run_dense(dir: &Path, extra_args: &[&str]) -> (String, RunSummary) {
+    let dataset = common::build_dataset(dir);
+    let mut regions = File::options()
+        .append(true)
+        .open(&dataset.regions_path)
+        .unwrap();
+    writeln!(regions, "{CONTIG}\t8050\t8070\tcluster_copy").unwrap();
+    let summary_path = dir.join("summary.json");
+    let output = common::vclust(&dataset)
+        .arg("--summary")
+        .arg(&summary_path)
+        .args(extra_args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (stdout, RunSummary::load(&summary_path).unwrap())
+}
+
+#[test]
+fn flags_clusters_of_dense_megabases() {
+    let dir = tempfile::tempdir().unwrap();
+    let (stdout, summary) = run_dense(dir.path(), &["--max-cluster-density", "1"]);
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines.next().unwrap().ends_with("\tdense_region"));
+    let flags = lines
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            (fields[0], *fields.last().unwrap())
+        })
+        .collect::<Vec<_>>();
+    let dense = format!("{CONTIG}:0-1000000");
+    assert_eq!(
+        flags,
+        [
+            ("cluster", dense.as_str()),
+            ("reference", "."),
+            ("contig_start", "."),
+            ("no_coverage", "."),
+            ("cluster_copy", dense.as_str()),
+        ]
+    );
+    assert_eq!(summary.dense_regions.len(), 1);
+    assert_eq!(summary.dense_regions[0].region, dense);
+    assert_eq!(summary.dense_regions[0].clusters, 2);
+    assert!(!summary.dense_regions[0].suppressed);
+    assert_eq!(summary.loci_processed, 5);
+
+    // Megabases within the limit are left alone
+    let dir = tempfile::tempdir().unwrap();
+    let (stdout, summary) = run_dense(dir.path(), &["--max-cluster-density", "2"]);
+    assert!(summary.dense_regions.is_empty());
+    assert!(stdout.lines().skip(2).all(|line| line.ends_with("\t.")));
+}
+
+#[test]
+fn suppresses_clusters_of_dense_megabases() {
+    let dir = tempfile::tempdir().unwrap();
+    let args = ["--max-cluster-density", "1", "--suppress-dense-regions"];
+    let (stdout, summary) = run_dense(dir.path(), &args);
+    let mut lines = stdout.lines().skip(1);
+    assert!(!lines.next().unwrap().ends_with("\tdense_region"));
+    let names = lines
+        .map(|line| line.split('\t').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["reference", "contig_start", "no_coverage"]);
+    assert!(summary.dense_regions[0].suppressed);
+}
+
+#[test]
+fn spilled_results_keep_their_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut guard = DensityGuard::new(1, false, 2, dir.path());
+    for index in 0..5 {
+        let locus = Locus {
+            chrom: CONTIG.to_string(),
+            start: index * 100,
+            end: index * 100 + 20,
+            name: format!("locus{index}"),
+            group: None,
+        };
+        let result = LocusResult {
+            locus,
+            extension: None,
+            concordance: Vec::new(),
+            scores: Vec::new(),
+        };
+        guard.push(result).unwrap();
+    }
+    assert!(guard.has_spilled());
+    let mut names = Vec::new();
+    let dense_regions = guard
+        .finish(|result| {
+            assert_eq!(result.scores, ["."]);
+            names.push(result.locus.name);
+            Ok(())
+        })
+        .unwrap();
+    assert!(dense_regions.is_empty());
+    assert_eq!(names, ["locus0", "locus1", "locus2", "locus3", "locus4"]);
+    // The spill file is removed once the results are out
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn low_memory_runs_flag_the_same_clusters() {
+    let dir = tempfile::tempdir().unwrap();
+    let (in_memory, _) = run_dense(dir.path(), &["--max-cluster-density", "1"]);
+    let dir = tempfile::tempdir().unwrap();
+    let args = ["--max-cluster-density", "1", "--low-memory"];
+    let (spilled, summary) = run_dense(dir.path(), &args);
+    assert_eq!(in_memory, spilled);
+    assert_eq!(summary.dense_regions.len(), 1);
+}
+
+#[test]
+fn refuses_to_stream_to_sockets() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let output = common::vclust(&dataset)
+        .args([
+            "--max-cluster-density",
+            "1",
+            "--output",
+            "tcp://127.0.0.1:9",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot stream them to a socket"));
+}
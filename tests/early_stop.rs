@@ -0,0 +1,155 @@
+mod common;
+
+use common::{write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::{self, File};
+use std::io::Write;
+use vclust::alignment::open_source;
+use vclust::context::{Params, Scratch};
+use vclust::early_stop::EarlyStop;
+use vclust::profile::{get_profile, ProfileOptions};
+
+#[test]
+fn variable_samples_need_more_reads() {
+    let decided_after = |alt_every: usize| {
+        let mut early_stop = EarlyStop::new(0.95, 3);
+        (1..=1000)
+            .find(|index| {
+                early_stop.add(index % alt_every == 0);
+                early_stop.is_decided()
+            })
+            .unwrap()
+    };
+    assert_eq!(decided_after(usize::MAX), 20);
+    let heterozygous = decided_after(2);
+    assert!((35..=45).contains(&heterozygous), "{heterozygous}");
+
+    let mut strict = EarlyStop::new(0.999, 3);
+    (0..heterozygous).for_each(|index| strict.add(index % 2 == 0));
+    assert!(!strict.is_decided());
+}
+
+/// A sample with a single alt read could still gain the three needed to
+/// support a cluster, however narrow its interval
+#[test]
+fn unsaturated_support_keeps_reading() {
+    let mut early_stop = EarlyStop::new(0.95, 3);
+    early_stop.add(true);
+    (0..200).for_each(|_| early_stop.add(false));
+    assert!(!early_stop.is_decided());
+    (0..2).for_each(|_| early_stop.add(true));
+    assert!(early_stop.is_decided());
+}
+
+/// Of 60 reads ending within the region, 60 spanning it, and 60 starting
+/// within it, the last come after every spanning read; stopping must thin
+/// them as much as the reads covering the left flank
+#[test]
+fn stopped_samples_keep_both_flanks_alike() {
+    let dir = tempfile::tempdir().unwrap();
+    let records = (0..60)
+        .flat_map(|index| {
+            [
+                (format!("left{index}"), 5000, 2500),
+                (format!("spanning{index}"), 6000, 4000),
+                (format!("right{index}"), 8500, 2500),
+            ]
+        })
+        .map(|(name, pos, len)| common::make_record(&name, pos, vec![Cigar::Equal(len)], 60))
+        .collect();
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let depths = |early_stop_confidence: Option<f64>| {
+        let params = Params {
+            early_stop_confidence,
+            ..Default::default()
+        };
+        let mut bam = open_source(&bam_path).unwrap();
+        let options = ProfileOptions {
+            locus_span: (7990, 8010),
+            amplicons: None,
+            keep_reads: false,
+            read_quota: None,
+            reference: None,
+        };
+        let (prof, _) = get_profile(
+            bam.as_mut(),
+            (CONTIG, 7000, 9000),
+            &params,
+            &mut Scratch::default(),
+            options,
+        )
+        .unwrap();
+        (prof.spanning_reads, prof.covs[200], prof.covs[1800])
+    };
+
+    let (spanning, left, right) = depths(None);
+    assert_eq!((spanning, left), (60, right));
+    let (spanning, left, right) = depths(Some(0.95));
+    assert_eq!(spanning, 20);
+    assert!((0.7..1.4).contains(&(left / right)), "{left} and {right}");
+}
+
+/// A deep sample, below the maximal depth without stopping, with a reference-like locus and a heterozygous deletion
+#[test]
+fn deep_samples_stop_reading_early() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let mut records = Vec::new();
+    for index in 0..140 {
+        let name = format!("ref{index}");
+        records.push(common::make_record(
+            &name,
+            5000,
+            vec![Cigar::Equal(4000)],
+            60,
+        ));
+        let ops = match index % 2 {
+            0 => vec![Cigar::Equal(2000), Cigar::Del(30), Cigar::Equal(1970)],
+            _ => vec![Cigar::Equal(4000)],
+        };
+        records.push(common::make_record(&format!("het{index}"), 13000, ops, 60));
+    }
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let reads_path = dir.path().join("reads.txt");
+    writeln!(File::create(&reads_path).unwrap(), "{}", bam_path.display()).unwrap();
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(
+        &regions_path,
+        format!("{CONTIG}\t7000\t7020\tref\n{CONTIG}\t15000\t15020\thet\n"),
+    )
+    .unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let run = |extra_args: &[&str]| {
+        let output = common::vclust(&dataset).args(extra_args).output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout
+            .lines()
+            .skip(2)
+            .map(|line| {
+                let fields = line.split('\t').collect::<Vec<_>>();
+                (fields[5].to_string(), fields[6].parse::<usize>().unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let full = run(&[]);
+    assert_eq!(full, [("0".to_string(), 140), ("1".to_string(), 140)]);
+    let stopped = run(&["--early-stop", "0.95"]);
+    assert_eq!(stopped[0], ("0".to_string(), 20));
+    assert_eq!(stopped[1].0, "1");
+    assert!((35..=45).contains(&stopped[1].1), "{stopped:?}");
+}
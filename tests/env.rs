@@ -0,0 +1,45 @@
+mod common;
+
+use std::process::Command;
+
+#[test]
+fn options_can_be_set_through_the_environment() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+
+    let from_args = common::vclust(&dataset)
+        .args(["--output-format", "bed"])
+        .output()
+        .unwrap();
+    let from_env = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .env("VCLUST_GENOME", &dataset.genome_path)
+        .env("VCLUST_READS", &dataset.reads_path)
+        .env("VCLUST_REGIONS", &dataset.regions_path)
+        .env("VCLUST_OUTPUT_FORMAT", "bed")
+        .output()
+        .unwrap();
+    assert!(from_env.status.success());
+    assert!(!from_env.stdout.is_empty());
+    assert_eq!(from_env.stdout, from_args.stdout);
+
+    // Command line options take precedence over the environment
+    let overridden = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .env("VCLUST_GENOME", &dataset.genome_path)
+        .env("VCLUST_READS", &dataset.reads_path)
+        .env("VCLUST_REGIONS", &dataset.regions_path)
+        .env("VCLUST_OUTPUT_FORMAT", "vcf")
+        .args(["--output-format", "bed"])
+        .output()
+        .unwrap();
+    assert_eq!(overridden.stdout, from_args.stdout);
+}
+
+#[test]
+fn run_variables_do_not_affect_subcommands() {
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .env("VCLUST_GENOME", "genome.fa")
+        .args(["completions", "bash"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
@@ -0,0 +1,25 @@
+use vclust::error::VclustError;
+
+#[test]
+fn malformed_json_is_a_parse_error() {
+    for input in ["{\"a\":", "{\"a\" 1}", "[1, 2]"] {
+        let err =
+            serde_json::from_str::<std::collections::HashMap<String, u32>>(input).unwrap_err();
+        assert_eq!(VclustError::from(err).kind(), "parse", "{input}");
+    }
+}
+
+#[test]
+fn failed_json_writes_are_io_errors() {
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::StorageFull.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let err = serde_json::to_writer(Full, &[1, 2]).unwrap_err();
+    assert_eq!(VclustError::from(err).kind(), "io");
+}
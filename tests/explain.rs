@@ -0,0 +1,118 @@
+mod common;
+
+use std::process::Command;
+use vclust::error::VclustError;
+use vclust::explain::parse_locus;
+
+fn explain(dataset: &common::Dataset, region: &str) -> (bool, String) {
+    explain_with(dataset, region, &[])
+}
+
+fn explain_with(dataset: &common::Dataset, region: &str, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("explain")
+        .arg("--genome")
+        .arg(&dataset.genome_path)
+        .arg("--reads")
+        .arg(&dataset.reads_path)
+        .arg(region)
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn narrates_the_window_ladder() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let (success, stdout) = explain(&dataset, "chr1:8040-8060");
+    assert!(success);
+    assert!(stdout.starts_with("Locus chr1:8040-8060"));
+    for step in 1..=4 {
+        assert!(stdout.contains(&format!("   Step {step} (")));
+    }
+    // The cluster starts at 8000, so only the left boundary moves
+    assert!(stdout.contains("left boundary 8000: window 7990-8000"));
+    assert!(stdout.contains("5. States at the boundaries: 8000 0000000000|"));
+    assert!(stdout.contains("Outcome: extended to chr1:8000-8060; 2 samples"));
+}
+
+#[test]
+fn gives_the_reason_regions_are_not_extended() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let (success, stdout) = explain(&dataset, "chr1:100-120");
+    assert!(success);
+    assert!(stdout.contains("Outcome: not extended (Region error"));
+    let (_, stdout) = explain(&dataset, "chr1:16000-16020");
+    assert!(stdout.contains("Outcome: not extended (Depth error"));
+    assert!(stdout.contains("between 5 and 150"));
+}
+
+#[test]
+fn compares_two_settings_step_by_step() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let (success, stdout) = explain_with(
+        &dataset,
+        "chr1:8040-8060",
+        &["--compare-min-prob-ref", "0.9"],
+    );
+    assert!(success);
+    let rows = stdout.lines().collect::<Vec<_>>();
+    assert!(rows[2].starts_with("min_prob_ref") && rows[2].ends_with(" *"));
+    assert!(!rows[3].ends_with(" *"));
+    // The stricter threshold stops the first step one position closer
+    assert!(
+        stdout.contains("first diverge at step 1 on the left flank: 8004 under A, 8003 under B")
+    );
+
+    let (_, stdout) = explain_with(
+        &dataset,
+        "chr1:8040-8060",
+        &["--compare-window-ladder", "150:50:25:10"],
+    );
+    assert!(!stdout.contains(" *\n"));
+    assert!(stdout.ends_with("Boundaries agree at every step\n"));
+
+    // The search fails when no window is reference-like enough
+    let (_, stdout) = explain_with(
+        &dataset,
+        "chr1:8040-8060",
+        &[
+            "--compare-min-prob-ref",
+            "0.99",
+            "--compare-window-ladder",
+            "50:10",
+        ],
+    );
+    assert!(stdout.contains("not extended (Model error) *"));
+}
+
+/// Degenerate priors are rejected as by the other subcommands
+#[test]
+fn rejects_degenerate_priors() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    for args in [["--prior-ref", "1"], ["--compare-prior-ref", "0"]] {
+        let (success, _) = explain_with(&dataset, "chr1:8040-8060", &args);
+        assert!(!success, "{args:?}");
+    }
+}
+
+#[test]
+fn parses_regions() {
+    let locus = parse_locus("chr1:1,000-2000").unwrap();
+    assert_eq!(
+        (locus.chrom.as_str(), locus.start, locus.end),
+        ("chr1", 1000, 2000)
+    );
+    assert_eq!(locus.name, "chr1:1,000-2000");
+    for region in ["chr1", "chr1:10", "chr1:20-10", ":10-20", "chr1:a-b"] {
+        assert!(matches!(parse_locus(region), Err(VclustError::Parse(_))));
+    }
+}
@@ -0,0 +1,70 @@
+use vclust::expression::Expression;
+
+fn eval(source: &str) -> Option<f64> {
+    let expression = source.parse::<Expression>().unwrap();
+    let lookup = |name: &str| match name {
+        "ns" => Some(3.0),
+        "alt" => Some(0.5),
+        _ => None,
+    };
+    expression.eval(&lookup)
+}
+
+#[test]
+fn operators_follow_the_usual_precedence() {
+    assert_eq!(eval("1 + 2 * 3"), Some(7.0));
+    assert_eq!(eval("(1 + 2) * 3"), Some(9.0));
+    assert_eq!(eval("2 ^ 3 ^ 2"), Some(512.0));
+    assert_eq!(eval("-2 ^ 2"), Some(-4.0));
+    assert_eq!(eval("10 - 4 - 3"), Some(3.0));
+    assert_eq!(eval("1.5e1 / 3"), Some(5.0));
+    assert_eq!(eval("ns * 10 + alt"), Some(30.5));
+}
+
+#[test]
+fn comparisons_and_functions() {
+    assert_eq!(eval("(ns >= 2) * 100"), Some(100.0));
+    assert_eq!(eval("ns < 2"), Some(0.0));
+    assert_eq!(eval("ns != 3"), Some(0.0));
+    assert_eq!(eval("max(ns, 4, alt)"), Some(4.0));
+    assert_eq!(eval("min(ns, alt)"), Some(0.5));
+    assert_eq!(eval("abs(-ns) + sqrt(16)"), Some(7.0));
+    assert_eq!(eval("log(1)"), Some(0.0));
+}
+
+#[test]
+fn missing_values_and_non_finite_results_give_none() {
+    assert_eq!(eval("ns + read_end_bias"), None);
+    assert_eq!(eval("ns / 0"), None);
+    assert_eq!(eval("log(-1)"), None);
+}
+
+#[test]
+fn malformed_expressions_are_rejected() {
+    for source in [
+        "",
+        "1 +",
+        "(ns",
+        "ns )",
+        "2 $ 3",
+        "foo(1)",
+        "abs(1, 2)",
+        "1..2",
+    ] {
+        assert!(source.parse::<Expression>().is_err(), "{source}");
+    }
+}
+
+#[test]
+fn unknown_names_are_reported() {
+    let expression = "ns * breadh".parse::<Expression>().unwrap();
+    assert_eq!(expression.variables(), vec!["ns", "breadh"]);
+    let known = ["ns".to_string(), "breadth".to_string()];
+    let err = expression.check_variables(&known).unwrap_err();
+    assert!(err.to_string().contains("Unknown name breadh"));
+    assert!("ns * breadth"
+        .parse::<Expression>()
+        .unwrap()
+        .check_variables(&known)
+        .is_ok());
+}
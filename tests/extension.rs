@@ -0,0 +1,270 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params, WorkflowContext};
+use vclust::evidence::SupportEvidence;
+use vclust::extend::get_extension_offsets;
+use vclust::locus::{load_loci, Locus};
+use vclust::manifest::load_manifest;
+use vclust::profile::AltKind;
+
+#[test]
+fn parallel_flank_scans_match_sequential_scans() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let extend = |parallel_scan_len: Option<i64>| {
+        let builder = ContextBuilder {
+            params: Arc::new(Params {
+                parallel_scan_len,
+                ..Default::default()
+            }),
+            ..ContextBuilder::new(
+                dataset.genome_path.clone(),
+                load_manifest(&dataset.reads_path).unwrap(),
+            )
+        };
+        let mut ctx = builder.build().unwrap();
+        load_loci(dataset.regions_path.clone(), None)
+            .unwrap()
+            .iter()
+            .map(|locus| {
+                get_extension_offsets(&mut ctx, locus)
+                    .ok()
+                    .map(|ext| (ext.lf_offset, ext.rf_offset, ext.ns, ext.ladder_offsets))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let sequential = extend(None);
+    assert!(sequential.iter().any(|ext| ext.is_some()));
+    assert_eq!(extend(Some(0)), sequential);
+}
+
+#[test]
+fn pooled_extension_does_not_depend_on_sample_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let extend = |reverse: bool| {
+        let mut reads = load_manifest(&dataset.reads_path).unwrap();
+        if reverse {
+            reads.reverse();
+        }
+        let builder = ContextBuilder::new(dataset.genome_path.clone(), reads);
+        let mut ctx = builder.build().unwrap();
+        load_loci(dataset.regions_path.clone(), None)
+            .unwrap()
+            .iter()
+            .map(|locus| {
+                get_extension_offsets(&mut ctx, locus)
+                    .ok()
+                    .map(|ext| (ext.lf_offset, ext.rf_offset, ext.ns, ext.read_end_bias))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(extend(true), extend(false));
+}
+
+/// Two samples with alt reads over 8040-8100 in the first and over 8400-8410
+/// in the second
+fn build_cluster_context(dir: &Path, params: Params) -> WorkflowContext {
+    let genome_path = dir.join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let mut reads = File::create(dir.join("reads.txt")).unwrap();
+    for sample in 0..2 {
+        let records = (0..20)
+            .map(|index| {
+                let ops = match (sample, index % 2) {
+                    (0, 0) => vec![
+                        Cigar::Equal(3040),
+                        Cigar::Del(30),
+                        Cigar::Equal(20),
+                        Cigar::Ins(40),
+                        Cigar::Diff(10),
+                        Cigar::Equal(900),
+                    ],
+                    (1, 0) if index < 6 => {
+                        vec![Cigar::Equal(3400), Cigar::Diff(10), Cigar::Equal(590)]
+                    }
+                    _ => vec![Cigar::Equal(4000)],
+                };
+                make_record(&format!("read{sample}_{index}"), 5000, ops, 60)
+            })
+            .collect();
+        let bam_path = dir.join(format!("sample{sample}.bam"));
+        write_bam(&bam_path, &format!("sample{sample}"), records);
+        writeln!(reads, "{}", bam_path.display()).unwrap();
+    }
+
+    let builder = ContextBuilder {
+        params: Arc::new(params),
+        ..ContextBuilder::new(genome_path, load_manifest(&dir.join("reads.txt")).unwrap())
+    };
+    builder.build().unwrap()
+}
+
+fn cluster_locus() -> Locus {
+    Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "cluster".to_string(),
+        group: None,
+    }
+}
+
+/// The second sample carries variation in the search window but away from the
+/// cluster, so it does not support it
+#[test]
+fn support_is_counted_within_the_cluster() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut ctx = build_cluster_context(dir.path(), Params::default());
+    let ext = get_extension_offsets(&mut ctx, &cluster_locus()).unwrap();
+    assert!(8040 + ext.rf_offset + 20 < 8400);
+    assert_eq!(ext.ns, 1);
+    assert!(ext.support_evidence.is_empty());
+}
+
+/// Every alt read of the first sample carries a deletion over 8040-8070, an
+/// insertion at 8090 and mismatches over 8090-8100; the three-way tie goes to
+/// the leftmost event, the deletion
+#[test]
+fn support_evidence_reports_the_strongest_event() {
+    let dir = tempfile::tempdir().unwrap();
+    let params = Params {
+        support_evidence: true,
+        ..Default::default()
+    };
+    let mut ctx = build_cluster_context(dir.path(), params);
+    let ext = get_extension_offsets(&mut ctx, &cluster_locus()).unwrap();
+    assert_eq!(
+        ext.support_evidence,
+        vec![SupportEvidence {
+            sample: "sample0".to_string(),
+            start: 8040,
+            end: 8070,
+            kind: AltKind::Deletion,
+            reads: 10,
+        }]
+    );
+}
+
+/// Half of the reads of a single sample carry only a 40 bp insertion at 8050,
+/// as reads of a repeat expansion do
+#[test]
+fn insertion_only_reads_support_the_cluster() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let records = (0..20)
+        .map(|index| {
+            let ops = match index % 2 {
+                0 => vec![Cigar::Equal(3050), Cigar::Ins(40), Cigar::Equal(950)],
+                _ => vec![Cigar::Equal(4000)],
+            };
+            make_record(&format!("read{index}"), 5000, ops, 60)
+        })
+        .collect();
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let reads_path = dir.path().join("reads.txt");
+    std::fs::write(&reads_path, format!("{}\n", bam_path.display())).unwrap();
+
+    let builder = ContextBuilder {
+        params: Arc::new(Params {
+            support_evidence: true,
+            ..Default::default()
+        }),
+        ..ContextBuilder::new(genome_path, load_manifest(&reads_path).unwrap())
+    };
+    let mut ctx = builder.build().unwrap();
+    let ext = get_extension_offsets(&mut ctx, &cluster_locus()).unwrap();
+    assert_eq!(ext.ns, 1);
+    assert_eq!(
+        ext.support_evidence,
+        vec![SupportEvidence {
+            sample: "sample".to_string(),
+            start: 8050,
+            end: 8051,
+            kind: AltKind::Insertion,
+            reads: 10,
+        }]
+    );
+}
+
+/// Every read of a single sample deletes 8000-8100; the reads still span the
+/// deletion but sequence none of its bases, so it is a coverage gap
+#[test]
+fn deletions_are_reported_as_coverage_gaps() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let records = (0..20)
+        .map(|index| {
+            let ops = vec![Cigar::Equal(3000), Cigar::Del(100), Cigar::Equal(1000)];
+            make_record(&format!("read{index}"), 5000, ops, 60)
+        })
+        .collect();
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let reads_path = dir.path().join("reads.txt");
+    std::fs::write(&reads_path, format!("{}\n", bam_path.display())).unwrap();
+
+    let builder = ContextBuilder::new(genome_path, load_manifest(&reads_path).unwrap());
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "deletion".to_string(),
+        group: None,
+    };
+    let ext = get_extension_offsets(&mut ctx, &locus).unwrap();
+    assert_eq!(ext.coverage_gaps, vec![(8000, 8100)]);
+}
+
+/// Sample A is sequenced in two replicates of 40 and 20 reads carrying
+/// mismatches over 8040-8060 in every other read, and sample B in a single
+/// BAM of 20 reads without variation
+#[test]
+fn replicates_count_and_average_as_one_sample() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let mut reads = File::create(dir.path().join("reads.txt")).unwrap();
+    for (bam, sample, n_reads, alt) in [
+        ("rep1", "sampleA", 40, true),
+        ("rep2", "sampleA", 20, true),
+        ("sampleB", "sampleB", 20, false),
+    ] {
+        let records = (0..n_reads)
+            .map(|index| {
+                let ops = match alt && index % 2 == 0 {
+                    true => vec![Cigar::Equal(3040), Cigar::Diff(20), Cigar::Equal(940)],
+                    false => vec![Cigar::Equal(4000)],
+                };
+                make_record(&format!("{bam}_{index}"), 5000, ops, 60)
+            })
+            .collect();
+        let bam_path = dir.path().join(format!("{bam}.bam"));
+        write_bam(&bam_path, sample, records);
+        writeln!(reads, "{}\t{sample}", bam_path.display()).unwrap();
+    }
+
+    let builder = ContextBuilder::new(
+        genome_path,
+        load_manifest(&dir.path().join("reads.txt")).unwrap(),
+    );
+    let mut ctx = builder.build().unwrap();
+    let ext = get_extension_offsets(&mut ctx, &cluster_locus()).unwrap();
+    assert_eq!(ext.ns, 1);
+    // (40 + 20) / 2 reads for sample A and 20 for sample B
+    assert!((ext.locus_depth - 25.0).abs() < 1e-9, "{}", ext.locus_depth);
+}
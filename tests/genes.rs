@@ -0,0 +1,112 @@
+mod common;
+
+use common::CONTIG;
+use std::fs;
+use std::process::Command;
+use vclust::genes::{load_genes, rollup_genes, GeneRollup};
+use vclust::tracks::ClusterTrack;
+
+fn cluster(start: i64, end: i64) -> ClusterTrack {
+    ClusterTrack {
+        name: format!("{CONTIG}:{start}-{end}"),
+        chrom: CONTIG.to_string(),
+        start,
+        end,
+        ns: 1,
+    }
+}
+
+fn gtf_line(feature: &str, start: i64, end: i64, gene: &str) -> String {
+    format!(
+        "{CONTIG}\ttest\t{feature}\t{start}\t{end}\t.\t+\t.\tgene_id \"{gene}.1\"; gene_name \"{gene}\";\n"
+    )
+}
+
+/// GENE1 has exons at 1000-1200 and 1500-1700 (0-based) across two
+/// transcripts, with a 5' UTR at 1000-1050
+#[test]
+fn clusters_are_tallied_per_gene_part() {
+    let dir = tempfile::tempdir().unwrap();
+    let gtf_path = dir.path().join("genes.gtf");
+    let gtf = [
+        gtf_line("gene", 1001, 1700, "GENE1"),
+        gtf_line("transcript", 1001, 1700, "GENE1"),
+        gtf_line("exon", 1001, 1200, "GENE1"),
+        gtf_line("exon", 1501, 1700, "GENE1"),
+        gtf_line("exon", 1101, 1200, "GENE1"),
+        gtf_line("five_prime_utr", 1001, 1050, "GENE1"),
+        gtf_line("exon", 5001, 5100, "GENE2"),
+    ]
+    .concat();
+    fs::write(&gtf_path, gtf).unwrap();
+    let genes = load_genes(&gtf_path).unwrap();
+    assert_eq!(genes.len(), 2);
+    assert_eq!(genes[0].name, "GENE1");
+    assert_eq!(genes[0].exons, [(1050, 1200), (1500, 1700)]);
+    assert_eq!(genes[0].introns, [(1200, 1500)]);
+    assert_eq!(genes[0].utrs, [(1000, 1050)]);
+    assert_eq!((genes[1].start, genes[1].end), (5000, 5100));
+
+    let clusters = [cluster(990, 1060), cluster(1300, 1350), cluster(1650, 1800)];
+    let rollups = rollup_genes(&genes, &clusters);
+    assert_eq!(
+        rollups[0],
+        GeneRollup {
+            exon_clusters: 2,
+            exon_bp: 60,
+            intron_clusters: 1,
+            intron_bp: 50,
+            utr_clusters: 1,
+            utr_bp: 50,
+        }
+    );
+    assert_eq!(rollups[1], GeneRollup::default());
+}
+
+/// The rollup of a panel lists its genes only, including those without
+/// clusters
+#[test]
+fn panel_rollup_lists_panel_genes() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let results_path = dir.path().join("results.txt");
+    let status = common::vclust(&dataset)
+        .arg("--output")
+        .arg(&results_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let gtf_path = dir.path().join("genes.gtf");
+    let gtf = [
+        gtf_line("exon", 7001, 8050, "CLUSTERED"),
+        gtf_line("exon", 11001, 12000, "CLUSTERED"),
+        gtf_line("exon", 14001, 15000, "QUIET"),
+        gtf_line("exon", 2001, 3000, "OFF_PANEL"),
+    ]
+    .concat();
+    fs::write(&gtf_path, gtf).unwrap();
+    let panel_path = dir.path().join("panel.txt");
+    fs::write(&panel_path, "CLUSTERED\nQUIET\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("gene-rollup")
+        .arg(&results_path)
+        .arg("--genes")
+        .arg(&gtf_path)
+        .arg("--panel")
+        .arg(&panel_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("#gene\tchrom\tstart\tend\texon_clusters"));
+    let clustered = lines[1].split('\t').collect::<Vec<_>>();
+    assert_eq!(clustered[..4], ["CLUSTERED", CONTIG, "7000", "12000"]);
+    assert_eq!(clustered[4], "1");
+    assert_eq!(clustered[6], "1");
+    assert!(lines[2].starts_with("QUIET\t"));
+    assert!(lines[2].ends_with("\t0\t0\t0\t0\t0\t0"));
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::path::Path;
+
+fn run_vclust(dataset: &common::Dataset, extra_args: &[&std::ffi::OsStr]) -> String {
+    let output = common::vclust(dataset).args(extra_args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn read_golden() -> String {
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden.tsv");
+    std::fs::read_to_string(golden_path).unwrap()
+}
+
+/// Compare the output on the synthetic dataset to the checked-in golden file;
+/// run with UPDATE_GOLDEN=1 to regenerate it after intended changes.
+#[test]
+fn golden_output_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+
+    let observed = run_vclust(&dataset, &[]);
+
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden.tsv");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden_path, &observed).unwrap();
+    }
+    assert_eq!(observed, read_golden());
+}
+
+/// Results served from the cache must be identical to freshly computed ones
+#[test]
+fn cached_output_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let cache_dir = dir.path().join("cache");
+    let args = ["--cache".as_ref(), cache_dir.as_os_str()];
+
+    let first = run_vclust(&dataset, &args);
+    assert!(std::fs::read_dir(&cache_dir).unwrap().next().is_some());
+    let second = run_vclust(&dataset, &args);
+    assert_eq!(first, read_golden());
+    assert_eq!(second, read_golden());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use vclust::locus::{load_loci, parse_loci};
+
+#[test]
+fn writes_a_joint_record_per_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions = std::fs::read_to_string(&dataset.regions_path).unwrap();
+    let regions = regions
+        .lines()
+        .map(|line| match line.split('\t').nth(3).unwrap() {
+            "cluster" | "reference" => format!("{line}\tgroup=pair\n"),
+            "contig_start" => format!("{line}\tgroup=edge\n"),
+            _ => format!("{line}\n"),
+        })
+        .collect::<String>();
+    // A region within another region of its group is a member of its own
+    let regions = format!("{regions}chr1\t8045\t8055\tinner\tgroup=pair\n");
+    let regions_path = dir.path().join("groups.bed");
+    std::fs::write(&regions_path, regions).unwrap();
+    let report_path = dir.path().join("groups.txt");
+
+    let output = common::vclust(&dataset.with_regions(&regions_path))
+        .arg("--group-report")
+        .arg(&report_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // The group column leaves the regions and their results unchanged
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 7);
+    let report = std::fs::read_to_string(&report_path).unwrap();
+    assert_eq!(
+        report.lines().collect::<Vec<_>>(),
+        [
+            "#group\tn_loci\tn_extended\tn_supported\tmax_ns\tjoint_region\tmembers",
+            "edge\t1\t0\t0\tNA\t.\tcontig_start=NA",
+            "pair\t3\t3\t2\t2\tchr1:6000-8060\t\
+             cluster=chr1:8000-8060:2,reference=chr1:6000-6020:0,inner=chr1:8000-8060:2",
+        ]
+    );
+}
+
+#[test]
+fn gff_features_declare_groups_as_attributes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("regions.gff3");
+    std::fs::write(
+        &path,
+        "##gff-version 3\n\
+         chr1\t.\trepeat\t1001\t1100\t.\t+\t.\tID=str;group=compound\n\
+         chr1\t.\trepeat\t1101\t1500\t.\t+\t.\tID=vntr;group=compound\n\
+         chr1\t.\trepeat\t5001\t5100\t.\t+\t.\tID=alone\n",
+    )
+    .unwrap();
+    let loci = load_loci(path, None).unwrap();
+    let grouped = loci
+        .iter()
+        .map(|locus| locus.group.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(grouped, [Some("compound"), Some("compound"), None]);
+}
+
+#[test]
+fn bed_lines_declare_groups_after_the_name() {
+    let input = b"chr1\t100\t200\tvntr\tgroup=compound\n\
+        chr1\t150\t160\tstr\tscore=3\tgroup=compound\n\
+        chr1\t500\t600\talone\n";
+    let loci = parse_loci(&input[..]).unwrap();
+    let grouped = loci
+        .iter()
+        .map(|locus| locus.group.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(grouped, [Some("compound"), Some("compound"), None]);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::{Aux, Cigar};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::extend::get_extension_offsets;
+use vclust::haplotype::haplotype_columns;
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+
+/// Haplotype columns of a cluster at 8040 in a single sample whose 40 reads
+/// are phased in two halves; the deletion is carried by `hp1_alts` reads of
+/// the first haplotype and `hp2_alts` of the second
+fn cluster_columns(dir: &Path, hp1_alts: usize, hp2_alts: usize) -> Vec<String> {
+    write_fasta(&dir.join("genome.fa"), &vec![b'A'; CONTIG_LEN]);
+    let records = (0..40)
+        .map(|index| {
+            let (haplotype, rank) = (1 + index / 20, index % 20);
+            let carries = rank < [hp1_alts, hp2_alts][haplotype - 1];
+            let ops = match carries {
+                true => vec![Cigar::Equal(3040), Cigar::Del(30), Cigar::Equal(960)],
+                false => vec![Cigar::Equal(4000)],
+            };
+            let mut rec = make_record(&format!("read{index}"), 5000, ops, 60);
+            rec.push_aux(b"HP", Aux::I32(haplotype as i32)).unwrap();
+            rec
+        })
+        .collect();
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let mut reads = File::create(dir.join("reads.txt")).unwrap();
+    writeln!(reads, "{}", bam_path.display()).unwrap();
+
+    let params = Params {
+        haplotype_balance: true,
+        ..Default::default()
+    };
+    let builder = ContextBuilder {
+        params: Arc::new(params.clone()),
+        ..ContextBuilder::new(
+            dir.join("genome.fa"),
+            load_manifest(&dir.join("reads.txt")).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "cluster".to_string(),
+        group: None,
+    };
+    let ext = get_extension_offsets(&mut ctx, &locus).unwrap();
+    haplotype_columns(Some(&ext), params.imbalance_fraction)
+}
+
+#[test]
+fn support_on_one_haplotype_is_not_imbalance() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(cluster_columns(dir.path(), 12, 0), ["0.00", "false"]);
+}
+
+#[test]
+fn skewed_support_on_both_haplotypes_is_imbalance() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(cluster_columns(dir.path(), 16, 2), ["0.11", "true"]);
+}
+
+#[test]
+fn balanced_support_is_not_imbalance() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(cluster_columns(dir.path(), 10, 10), ["0.50", "false"]);
+}
+
+#[test]
+fn too_few_phased_reads_are_not_assessed() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(cluster_columns(dir.path(), 4, 1), ["0.20", "NA"]);
+    assert_eq!(
+        haplotype_columns(None, 0.2),
+        ["NA".to_string(), "NA".to_string()]
+    );
+}
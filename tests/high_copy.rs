@@ -0,0 +1,110 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, Dataset, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::error::{Result, VclustError};
+use vclust::extend::{get_extension_offsets, Extension};
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+
+/// One sample with 300 reads over the locus, above the default cap of 200; the
+/// last 150 reads carry a deletion at 8045
+fn write_deep_sample(dir: &Path) -> Dataset {
+    let genome_path = dir.join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let records = (0..300)
+        .map(|index| {
+            let ops = match index < 150 {
+                true => vec![Cigar::Equal(4000)],
+                false => vec![Cigar::Equal(2045), Cigar::Del(30), Cigar::Equal(1955)],
+            };
+            make_record(&format!("read{index}"), 6000, ops, 60)
+        })
+        .collect();
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let reads_path = dir.join("reads.txt");
+    fs::write(&reads_path, format!("{}\n", bam_path.display())).unwrap();
+    let regions_path = dir.join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+    Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    }
+}
+
+fn extend(dataset: &Dataset, high_copy_contigs: &[&str]) -> Result<Extension> {
+    let builder = ContextBuilder {
+        params: Arc::new(Params {
+            high_copy_contigs: high_copy_contigs.iter().map(|c| c.to_string()).collect(),
+            support_evidence: true,
+            ..Default::default()
+        }),
+        ..ContextBuilder::new(
+            dataset.genome_path.clone(),
+            load_manifest(&dataset.reads_path).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "cluster".to_string(),
+        group: None,
+    };
+    get_extension_offsets(&mut ctx, &locus)
+}
+
+#[test]
+fn regions_over_the_read_cap_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_deep_sample(dir.path());
+    assert!(matches!(extend(&dataset, &[]), Err(VclustError::Depth(_))));
+    // Only the listed contigs are subsampled
+    assert!(matches!(
+        extend(&dataset, &["chrM"]),
+        Err(VclustError::Depth(_))
+    ));
+}
+
+/// The reservoir keeps 200 reads drawn from all 300 rather than the first 200,
+/// so about half of the kept reads carry the deletion
+#[test]
+fn high_copy_contigs_are_subsampled_uniformly() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_deep_sample(dir.path());
+    let ext = extend(&dataset, &[CONTIG]).unwrap();
+    assert_eq!(ext.spanning_reads + ext.partial_reads, 200);
+    let alt_reads = ext
+        .support_evidence
+        .iter()
+        .map(|evidence| evidence.reads)
+        .max()
+        .unwrap();
+    assert!((80..=120).contains(&alt_reads), "{alt_reads} alt reads");
+
+    // Subsampling is seeded, so reruns keep the same reads
+    let rerun = extend(&dataset, &[CONTIG]).unwrap();
+    assert_eq!(rerun.support_evidence, ext.support_evidence);
+}
+
+#[test]
+fn high_copy_contigs_are_extended_from_the_command_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_deep_sample(dir.path());
+    let run = |args: &[&str]| {
+        let output = common::vclust(&dataset).args(args).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let row = stdout.lines().last().unwrap().to_string();
+        row.split('\t').nth(2).unwrap().to_string()
+    };
+    assert_eq!(run(&[]), "NA");
+    assert_ne!(run(&["--high-copy-contigs", CONTIG]), "NA");
+}
@@ -0,0 +1,17 @@
+use std::process::Command;
+
+#[test]
+fn info_reports_the_build_as_json() {
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("info")
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["features"]["cram"], true);
+    assert_eq!(info["model_sha256"].as_str().unwrap().len(), 64);
+    let formats = info["output_formats"].as_array().unwrap();
+    assert!(formats.contains(&serde_json::json!("tsv")));
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use vclust::manifest::{load_manifest, Karyotype};
+
+#[test]
+fn sex_chromosomes_follow_the_karyotype() {
+    let locus = (8_000_000, 8_000_100);
+    assert_eq!(Karyotype::Xx.ploidy("chrX", locus), 2);
+    assert_eq!(Karyotype::Xx.ploidy("chrY", locus), 0);
+    assert_eq!(Karyotype::Xy.ploidy("chrX", locus), 1);
+    assert_eq!(Karyotype::Xy.ploidy("Y", locus), 1);
+    assert_eq!(Karyotype::Xx.ploidy("chr1", locus), 2);
+    assert_eq!(Karyotype::Xy.ploidy("chr1", locus), 2);
+}
+
+/// Loci overlapping PAR1 or PAR2 are diploid in XY samples, up to the last
+/// base of each region
+#[test]
+fn pseudoautosomal_regions_are_diploid() {
+    assert_eq!(Karyotype::Xy.ploidy("chrX", (60_000, 60_100)), 2);
+    assert_eq!(Karyotype::Xy.ploidy("chrX", (2_781_400, 2_781_500)), 2);
+    assert_eq!(Karyotype::Xy.ploidy("chrX", (2_781_479, 2_781_500)), 1);
+    assert_eq!(Karyotype::Xy.ploidy("chrX", (155_800_000, 155_800_100)), 2);
+    assert_eq!(Karyotype::Xy.ploidy("chrY", (56_900_000, 56_900_100)), 2);
+    assert_eq!(Karyotype::Xy.ploidy("chrY", (9_000, 9_100)), 1);
+    // XX samples carry no chrY, pseudoautosomal or not
+    assert_eq!(Karyotype::Xx.ploidy("chrY", (60_000, 60_100)), 0);
+}
+
+#[test]
+fn karyotypes_are_read_from_the_reads_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reads.txt");
+    fs::write(
+        &path,
+        "a.bam\tmother\tXX\nb.bam\tfather\txy\nc.bam\tchild\n",
+    )
+    .unwrap();
+    let karyotypes = load_manifest(&path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.karyotype)
+        .collect::<Vec<_>>();
+    assert_eq!(karyotypes, [Some(Karyotype::Xx), Some(Karyotype::Xy), None]);
+
+    fs::write(&path, "a.bam\tmother\tXXY\n").unwrap();
+    assert!(load_manifest(&path).is_err());
+}
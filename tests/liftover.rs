@@ -0,0 +1,101 @@
+use std::fs;
+use vclust::liftover::{liftover_results, parse_chains, LiftoverCounts};
+use vclust::schema::header;
+
+const CHAINS: &[u8] = b"chain 1000 chr1 20000 + 0 10000 chrA 21000 + 1000 11000 1\n\
+    5000 0 1000\n\
+    5000\n\n\
+    chain 500 chr2 5000 + 0 5000 chrB 5000 - 0 5000 2\n\
+    5000\n";
+
+#[test]
+fn intervals_are_lifted_through_blocks() {
+    let chains = parse_chains(CHAINS).unwrap();
+    assert_eq!(
+        chains.lift("chr1", 100, 200),
+        Some(("chrA".to_string(), 1100, 1200))
+    );
+    // The second block is shifted by the insertion in the query
+    assert_eq!(
+        chains.lift("chr1", 4900, 5100),
+        Some(("chrA".to_string(), 5900, 7100))
+    );
+    assert_eq!(chains.lift("chr1", 12000, 12100), None);
+    // Reverse strand intervals are reported on the forward strand
+    assert_eq!(
+        chains.lift("chr2", 100, 200),
+        Some(("chrB".to_string(), 4800, 4900))
+    );
+}
+
+#[test]
+fn unmapped_records_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.txt");
+    let na = ["NA"; 10].join("\t");
+    fs::write(
+        &path,
+        format!(
+            "{}\n\
+            vc1\tchr1:8040-8060\t40\t0\tchr1:8000-8060\t2\t50\t0\t0\t8010-8020\t150:40:0\t0.00\n\
+            vc2\tchr2:1000-1020\t10\t0\tchr2:990-1020\t1\t50\t0\t0\t.\t150:10:0\t0.00\n\
+            na\tchr1:9000-9020\t{na}\n\
+            lost\tchr1:15000-15020\t{na}\n",
+            header()
+        ),
+    )
+    .unwrap();
+    let chains = parse_chains(CHAINS).unwrap();
+    let (mut lifted, mut unmapped) = (Vec::new(), Vec::new());
+    let counts = liftover_results(&path, &chains, &mut lifted, &mut unmapped).unwrap();
+    assert_eq!(
+        counts,
+        LiftoverCounts {
+            lifted: 3,
+            unmapped: 1
+        }
+    );
+
+    let lifted = String::from_utf8(lifted).unwrap();
+    let rows = lifted.lines().skip(2).collect::<Vec<_>>();
+    assert!(rows[0]
+        .starts_with("vc1\tchrA:10040-10060\t40\t0\tchrA:10000-10060\t2\t50\t0\t0\t10010-10020\t"));
+    assert!(rows[1].starts_with("vc2\tchrB:3980-4000\t0\t10\tchrB:3980-4010\t"));
+    assert!(rows[2].starts_with("na\tchrA:11000-11020\tNA"));
+    let unmapped = String::from_utf8(unmapped).unwrap();
+    assert!(unmapped.contains("#Region not in chain\nlost\t"));
+}
+
+#[test]
+fn unmapped_file_is_not_overwritten_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.txt");
+    fs::write(&path, format!("{}\n", header())).unwrap();
+    let chain_path = dir.path().join("chains.chain");
+    fs::write(&chain_path, CHAINS).unwrap();
+    let unmapped_path = dir.path().join("unmapped.txt");
+    fs::write(&unmapped_path, "previous").unwrap();
+    let liftover = |force: bool| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_vclust"));
+        command
+            .arg("liftover")
+            .arg(&path)
+            .arg("--chain")
+            .arg(&chain_path)
+            .arg("--unmapped")
+            .arg(&unmapped_path);
+        if force {
+            command.arg("--force");
+        }
+        command.output().unwrap()
+    };
+
+    let output = liftover(false);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unmapped.txt"));
+    assert_eq!(fs::read_to_string(&unmapped_path).unwrap(), "previous");
+
+    assert!(liftover(true).status.success());
+    assert_ne!(fs::read_to_string(&unmapped_path).unwrap(), "previous");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+}
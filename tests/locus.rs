@@ -0,0 +1,86 @@
+use vclust::locus::{dedup_loci, parse_gff, parse_interval_list, parse_loci};
+
+#[test]
+fn interval_list_is_converted_to_half_open() {
+    let input = b"@HD\tVN:1.6\n@SQ\tSN:chr1\tLN:1000\nchr1\t101\t120\t+\ttarget1\n";
+    let loci = parse_interval_list(&input[..]).unwrap();
+    assert_eq!(loci.len(), 1);
+    assert_eq!((loci[0].start, loci[0].end), (100, 120));
+    assert_eq!(loci[0].name, "target1");
+}
+
+#[test]
+fn gff_features_are_selected_by_type() {
+    let input = b"##gff-version 3\n\
+        chr1\tsrc\tgene\t1\t500\t.\t+\t.\tID=gene1\n\
+        chr1\tsrc\texon\t101\t200\t.\t+\t.\tID=exon1;Parent=gene1\n\
+        chr1\tsrc\texon\t301\t400\t.\t+\t.\tParent=gene1\n\
+        ##FASTA\n>chr1\n";
+    let exons = parse_gff(&input[..], Some("exon")).unwrap();
+    let names = exons.iter().map(|l| l.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["exon1", "chr1:300-400"]);
+    assert_eq!((exons[0].start, exons[0].end), (100, 200));
+    assert_eq!(parse_gff(&input[..], None).unwrap().len(), 3);
+}
+
+#[test]
+fn duplicate_and_contained_loci_are_collapsed() {
+    let input = b"chr1\t100\t200\tlocus1\n\
+        chr1\t150\t160\tinner\n\
+        chr2\t100\t200\tother_contig\n\
+        chr1\t100\t200\tdup\n\
+        chr1\t190\t250\toverlapping\n";
+    let (kept, collapsed) = dedup_loci(parse_loci(&input[..]).unwrap(), true);
+    let names = kept.iter().map(|l| l.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["locus1", "other_contig", "overlapping"]);
+    let reasons = collapsed
+        .iter()
+        .map(|c| (c.name.as_str(), c.kept_name.as_str(), c.reason))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reasons,
+        [
+            ("dup", "locus1", "duplicate"),
+            ("inner", "locus1", "contained")
+        ]
+    );
+}
+
+#[test]
+fn contained_loci_are_kept_by_default() {
+    // An STR within its flanking VNTR is a distinct locus
+    let input = b"chr1\t100\t200\tvntr\n\
+        chr1\t150\t160\tstr\n\
+        chr1\t150\t160\tstr_dup\n\
+        chr1\t100\t200\tvntr_dup\n";
+    let (kept, collapsed) = dedup_loci(parse_loci(&input[..]).unwrap(), false);
+    let names = kept.iter().map(|l| l.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["vntr", "str"]);
+    let reasons = collapsed
+        .iter()
+        .map(|c| (c.name.as_str(), c.kept_name.as_str(), c.reason))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reasons,
+        [
+            ("vntr_dup", "vntr", "duplicate"),
+            ("str_dup", "str", "duplicate")
+        ]
+    );
+}
+
+#[test]
+fn loci_are_contained_by_an_earlier_locus_reaching_past_them() {
+    // inner lies within first but starts after the overlapping second
+    let input = b"chr1\t0\t100\tfirst\n\
+        chr1\t10\t200\tsecond\n\
+        chr1\t20\t90\tinner\n";
+    let (kept, collapsed) = dedup_loci(parse_loci(&input[..]).unwrap(), true);
+    let names = kept.iter().map(|l| l.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["first", "second"]);
+    assert_eq!(collapsed.len(), 1);
+    assert_eq!(
+        (collapsed[0].name.as_str(), collapsed[0].kept_name.as_str()),
+        ("inner", "second")
+    );
+}
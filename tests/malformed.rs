@@ -0,0 +1,75 @@
+mod common;
+
+use common::make_record;
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use std::collections::BTreeMap;
+use vclust::profile::{malformation, malformed_record_warnings, Malformation, SkipCounts};
+
+#[test]
+fn well_formed_records_pass() {
+    let rec = make_record("read", 100, vec![Cigar::SoftClip(5), Cigar::Equal(50)], 60);
+    assert_eq!(malformation(&rec), None);
+
+    // Secondary alignments often omit the sequence
+    let mut rec = Record::new();
+    let cigar = CigarString(vec![Cigar::Equal(50)]);
+    rec.set(b"read", Some(&cigar), &[], &[]);
+    rec.set_pos(100);
+    rec.unset_flags();
+    assert_eq!(malformation(&rec), None);
+}
+
+#[test]
+fn unmapped_records_placed_at_a_position_are_malformed() {
+    let mut rec = make_record("read", 100, vec![Cigar::Equal(50)], 0);
+    rec.set_flags(0x4);
+    assert_eq!(malformation(&rec), Some(Malformation::Unmapped));
+}
+
+#[test]
+fn empty_cigars_are_malformed() {
+    let rec = make_record("read", 100, Vec::new(), 60);
+    assert_eq!(malformation(&rec), Some(Malformation::EmptyCigar));
+}
+
+#[test]
+fn cigars_inconsistent_with_the_sequence_are_malformed() {
+    for ops in [
+        vec![Cigar::Equal(40)],
+        vec![Cigar::Equal(40), Cigar::Ins(20)],
+    ] {
+        let mut rec = Record::new();
+        rec.set(b"read", Some(&CigarString(ops)), &[b'A'; 50], &[30; 50]);
+        rec.set_pos(100);
+        rec.unset_flags();
+        assert_eq!(malformation(&rec), Some(Malformation::CigarLength));
+    }
+}
+
+#[test]
+fn warnings_list_malformed_records_by_kind() {
+    let skipped = BTreeMap::from([
+        (
+            "clean".to_string(),
+            SkipCounts {
+                mapq: 3,
+                ..Default::default()
+            },
+        ),
+        (
+            "broken".to_string(),
+            SkipCounts {
+                unmapped: 2,
+                cigar_length: 1,
+                ..Default::default()
+            },
+        ),
+    ]);
+    assert_eq!(
+        malformed_record_warnings(&skipped),
+        [
+            "Skipped malformed records of broken: 2 unmapped but placed at a position, \
+             1 with a CIGAR inconsistent with the read length"
+        ]
+    );
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use vclust::memory::parse_size;
+use vclust::summary::RunSummary;
+
+#[test]
+fn sizes_use_binary_units() {
+    assert_eq!(parse_size("512").unwrap(), 512);
+    assert_eq!(parse_size("4K").unwrap(), 4096);
+    assert_eq!(parse_size("1.5m").unwrap(), 3 << 19);
+    assert_eq!(parse_size("16G").unwrap(), 16 << 30);
+    assert_eq!(parse_size("2T").unwrap(), 2 << 40);
+    assert!(parse_size("").is_err());
+    assert!(parse_size("G").is_err());
+    assert!(parse_size("-1G").is_err());
+}
+
+/// Sampled runs record their RSS in the summary, and runs over the limit stop
+/// with their partial results and a distinct exit code
+#[cfg(target_os = "linux")]
+#[test]
+fn rss_is_sampled_and_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let run = |extra_args: &[&str]| {
+        let summary_path = dir.path().join("summary.json");
+        let output_path = dir.path().join("results.txt");
+        let status = common::vclust(&dataset)
+            .arg("--summary")
+            .arg(&summary_path)
+            .arg("--output")
+            .arg(&output_path)
+            .arg("--force")
+            .args(extra_args)
+            .status()
+            .unwrap();
+        assert!(output_path.exists());
+        (status.code(), RunSummary::load(&summary_path).unwrap())
+    };
+
+    let (code, summary) = run(&["--rss-interval", "0.01"]);
+    assert_eq!(code, Some(0));
+    assert!(!summary.rss_samples.is_empty());
+    let peak = summary.peak_rss_bytes.unwrap();
+    assert!(summary.rss_samples.iter().all(|s| s.rss_bytes <= peak));
+    assert!(!summary.rss_limit_exceeded);
+
+    let (code, summary) = run(&["--rss-limit", "1K", "--low-memory"]);
+    assert_eq!(code, Some(3));
+    assert!(summary.rss_limit_exceeded);
+    assert!(summary.interrupted);
+}
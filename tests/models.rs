@@ -0,0 +1,81 @@
+use vclust::models::{Alphabet, LogModels, Models};
+
+#[test]
+fn default_alphabet_has_six_states() {
+    let alphabet = Alphabet::default();
+    assert_eq!(alphabet.n_states(), 6);
+    let states = [0.0, 0.10, 0.11, 0.25, 0.74, 0.75, 1.5, 4.99, 5.0, 12.0]
+        .iter()
+        .map(|val| alphabet.state(*val, 0.0))
+        .collect::<Vec<_>>();
+    assert_eq!(states, [0, 0, 1, 2, 2, 3, 4, 4, 5, 5]);
+}
+
+#[test]
+fn models_must_match_the_alphabet() {
+    let mut models = Models::default();
+    assert!(models.validate().is_ok());
+    assert_eq!(models.max_window_len(), 250);
+
+    models.alphabet.lower_bounds.push(20.0);
+    assert!(models.validate().is_err());
+
+    // A seven-state alphabet with tables of matching width is accepted
+    for model in [&mut models.reference, &mut models.cluster] {
+        model.probs = model
+            .probs
+            .chunks(6)
+            .flat_map(|row| row.iter().copied().chain([1e-6]))
+            .collect();
+    }
+    assert!(models.validate().is_ok());
+    assert_eq!(models.max_window_len(), 250);
+}
+
+#[test]
+fn clip_state_is_added_after_alt_states() {
+    let models = Models::default().with_clip_state(0.3);
+    assert!(models.validate().is_ok());
+    assert_eq!(models.alphabet.n_states(), 7);
+    assert_eq!(models.max_window_len(), 250);
+    assert_eq!(models.alphabet.state(7.0, 0.1), 5);
+    assert_eq!(models.alphabet.state(7.0, 0.3), 6);
+    for row in models.reference.probs.chunks(7) {
+        assert!((row.iter().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert_eq!(row[5], row[6]);
+    }
+}
+
+#[test]
+fn models_must_be_distributions() {
+    let mut models = Models::default();
+    models.cluster.probs[7] = 0.0;
+    let err = models.validate().unwrap_err().to_string();
+    assert!(err.contains("1 zero probabilities"), "{err}");
+
+    // Smoothing removes the zero, leaving every position normalized
+    let models = models.with_pseudocount(1e-3);
+    assert!(models.validate().is_ok());
+    assert!(models.cluster.probs[7] > 0.0);
+
+    let mut models = Models::default();
+    models.reference.probs[12] += 0.5;
+    let err = models.validate().unwrap_err().to_string();
+    assert!(err.contains("window position 2"), "{err}");
+}
+
+#[test]
+fn log_models_match_the_emissions() {
+    let models = Models::default().with_clip_state(0.3);
+    let log_models = LogModels::from(&models);
+    assert_eq!(log_models.n_states, 7);
+    assert_eq!(log_models.cluster.log_prior, models.cluster.prior.ln());
+    for (log_prob, prob) in log_models
+        .reference
+        .log_probs
+        .iter()
+        .zip(&models.reference.probs)
+    {
+        assert_eq!(*log_prob, prob.ln());
+    }
+}
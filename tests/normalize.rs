@@ -0,0 +1,82 @@
+mod common;
+
+use rust_htslib::bam::record::{Cigar, CigarString};
+use vclust::normalize::{left_align_indels, normalize_ops, RefWindow};
+use vclust::profile::update_profs;
+
+fn ops(cigar: &str) -> Vec<Cigar> {
+    CigarString::try_from(cigar).unwrap().0
+}
+
+/// pbmm2 writes `=`/`X` operations with deletions ahead of insertions
+const PBMM2: &str = "10S100=1X50=2D1I30=10S";
+
+#[test]
+fn pbmm2_alignments_are_already_canonical() {
+    assert_eq!(normalize_ops(1000, &ops(PBMM2)), (1000, ops(PBMM2)));
+}
+
+/// minimap2 with --eqx may split a homopolymer indel around an insertion,
+/// report the insertion first, and hard clip supplementary alignments
+#[test]
+fn minimap2_indel_runs_are_merged() {
+    let minimap2 = ops("7H10S100=1X50=1I1D1D30=10S");
+    assert_eq!(normalize_ops(1000, &minimap2), (1000, ops(PBMM2)));
+    let minimap2 = ops("10S100M2D1I30M10S");
+    assert_eq!(
+        normalize_ops(1000, &minimap2),
+        (1000, ops("10S100=2D1I30=10S"))
+    );
+}
+
+/// winnowmap may start or end an alignment with an indel instead of clipping
+#[test]
+fn winnowmap_flanking_indels_are_clipped() {
+    let winnowmap = ops("5S5I3D100=1X40=10=2D1I30=4I6S");
+    assert_eq!(normalize_ops(997, &winnowmap), (1000, ops(PBMM2)));
+}
+
+/// The same alignment reported by different aligners yields the same profile
+#[test]
+fn profiles_are_comparable_across_aligners() {
+    let region = ("chr1", 900, 1300);
+    let profile = |pos: i64, cigar: &str| {
+        let mut covs = vec![0; 400];
+        let mut alts = vec![0; 400];
+        let mut clips = vec![0; 400];
+        let rec = common::make_record("read", pos, ops(cigar), 60);
+        update_profs(&rec, &mut covs, &mut alts, &mut clips, region, 2).unwrap();
+        (covs, alts, clips)
+    };
+    let pbmm2 = profile(1000, PBMM2);
+    assert_eq!(profile(1000, "10S100=1X50=1I1D1D30=10S"), pbmm2);
+    assert_eq!(profile(997, "5S5I3D100=1X40=10=2D1I30=4I6S"), pbmm2);
+}
+
+/// Indels in repeats move to their leftmost position, deletions by the
+/// reference and insertions by the read sequence, but not past the window
+#[test]
+fn indels_are_left_aligned() {
+    let reference = RefWindow {
+        start: 100,
+        seq: b"GGGCACACACATTT",
+    };
+    let read = b"GGGCACACATTT";
+    assert_eq!(
+        left_align_indels(100, &ops("7=2D5="), read, reference),
+        (100, ops("3=2D9="))
+    );
+    assert_eq!(
+        left_align_indels(100, &ops("5=1I1="), b"AAGTTTC", reference),
+        (100, ops("3=1I3="))
+    );
+
+    let clipped = RefWindow {
+        start: 105,
+        seq: &reference.seq[5..],
+    };
+    assert_eq!(
+        left_align_indels(100, &ops("7=2D5="), read, clipped),
+        (100, ops("5=2D7="))
+    );
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::CONTIG;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::error::{Result, VclustError};
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+use vclust::models::Models;
+use vclust::workflow::{run_workflow, LocusResult};
+
+/// Run the cluster locus of the dataset with the given model priors, which
+/// are not checked when models are built as a library
+fn run_cluster(audit_numerics: bool, prior_ref: f64, prior_vc: f64) -> Result<LocusResult> {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let mut models = Models::default();
+    models.reference.prior = prior_ref;
+    models.cluster.prior = prior_vc;
+    let builder = ContextBuilder {
+        params: Arc::new(Params {
+            audit_numerics,
+            ..Default::default()
+        }),
+        models: Arc::new(models),
+        ..ContextBuilder::new(
+            dataset.genome_path.clone(),
+            load_manifest(&dataset.reads_path).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "cluster".to_string(),
+        group: None,
+    };
+    run_workflow(&mut ctx, &locus)
+}
+
+/// A zero reference prior sends the reference log-likelihood of every window
+/// to -inf, while the posterior stays finite
+#[test]
+fn infinite_log_likelihoods_fail_the_locus() {
+    let err = run_cluster(true, 0.0, 1.0).err().unwrap();
+    assert_eq!(err.kind(), "numerics");
+    let VclustError::Numerics(msg) = err else {
+        panic!("{err}")
+    };
+    assert!(msg.starts_with("cluster: Window of "), "{msg}");
+    assert!(msg.contains("log-likelihoods -inf (reference)"), "{msg}");
+    assert!(msg.contains("posterior 0;"), "{msg}");
+    assert!(msg.contains("reference log emissions"), "{msg}");
+}
+
+/// With both priors zero the posterior is NaN
+#[test]
+fn undefined_posteriors_fail_the_locus() {
+    let err = run_cluster(true, 0.0, 0.0).err().unwrap();
+    let VclustError::Numerics(msg) = err else {
+        panic!("{err}")
+    };
+    assert!(msg.contains("(cluster) and posterior NaN"), "{msg}");
+}
+
+/// Without the audit, the same models do not fail the locus, and finite
+/// models pass the audit
+#[test]
+fn only_audited_loci_fail() {
+    assert!(run_cluster(false, 0.0, 1.0).is_ok());
+    assert!(run_cluster(true, 0.5, 0.5).unwrap().extension.is_some());
+}
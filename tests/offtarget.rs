@@ -0,0 +1,87 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use vclust::context::{ContextBuilder, WorkflowContext};
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+use vclust::offtarget::estimate_off_target_depth;
+
+/// Panel capturing 9000-10100 deeply, with two reads of background over the
+/// whole contig
+fn build_panel(dir: &Path) -> WorkflowContext {
+    write_fasta(&dir.join("genome.fa"), &vec![b'A'; CONTIG_LEN]);
+    let mut records = (0..10)
+        .map(|index| make_record(&format!("target{index}"), 9200, vec![Cigar::Equal(700)], 60))
+        .collect::<Vec<_>>();
+    for index in 0..2 {
+        let ops = vec![Cigar::Equal(CONTIG_LEN as u32)];
+        records.push(make_record(&format!("background{index}"), 0, ops, 60));
+    }
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let mut reads = File::create(dir.join("reads.txt")).unwrap();
+    writeln!(reads, "{}", bam_path.display()).unwrap();
+
+    let builder = ContextBuilder::new(
+        dir.join("genome.fa"),
+        load_manifest(&dir.join("reads.txt")).unwrap(),
+    );
+    builder.build().unwrap()
+}
+
+fn locus(start: i64, end: i64) -> Locus {
+    Locus {
+        chrom: CONTIG.to_string(),
+        start,
+        end,
+        name: "target".to_string(),
+        group: None,
+    }
+}
+
+#[test]
+fn samples_positions_outside_the_search_regions() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut ctx = build_panel(dir.path());
+    let estimate = estimate_off_target_depth(&mut ctx, &[locus(9500, 9600)], 100, 42).unwrap();
+    assert_eq!(estimate.positions, 100);
+    assert_eq!(estimate.depth, 2.0);
+
+    // Regions covering the whole contig leave nothing to sample
+    let everything = locus(0, CONTIG_LEN as i64);
+    assert!(estimate_off_target_depth(&mut ctx, &[everything], 100, 42).is_err());
+}
+
+#[test]
+fn adds_capture_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let output = common::vclust(&dataset)
+        .arg("--off-target-sample")
+        .arg("100")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines
+        .next()
+        .unwrap()
+        .ends_with("\ton_target_depth\toff_target_depth\tcapture_enrichment"));
+    let rows = lines
+        .map(|line| line.split('\t').rev().take(3).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // Regions that could not be extended have no depth to compare
+    assert_eq!(rows[2], ["NA", "NA", "NA"]);
+    assert_eq!(rows[0][1], rows[1][1]);
+    for row in &rows[..2] {
+        let [enrichment, off_target, on_target] =
+            [row[0], row[1], row[2]].map(|value| value.parse::<f64>().unwrap());
+        assert!(off_target > 0.0);
+        assert!((enrichment - on_target / off_target).abs() < 0.01);
+    }
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::fs;
+use vclust::onnx::OnnxClassifier;
+use vclust::training::TRAINING_WINDOW_LEN;
+
+fn varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn field(number: u64, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    varint(number << 3 | 2, &mut bytes);
+    varint(payload.len() as u64, &mut bytes);
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn int_field(number: u64, value: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    varint(number << 3, &mut bytes);
+    varint(value, &mut bytes);
+    bytes
+}
+
+/// Float tensor initializer, with its values as raw data
+fn initializer(name: &str, dims: &[u64], value: f32) -> Vec<u8> {
+    let len = dims.iter().product::<u64>() as usize;
+    let raw = std::iter::repeat_n(value.to_le_bytes(), len)
+        .flatten()
+        .collect::<Vec<_>>();
+    let mut tensor = dims
+        .iter()
+        .flat_map(|dim| int_field(1, *dim))
+        .collect::<Vec<_>>();
+    tensor.extend(int_field(2, 1));
+    tensor.extend(field(8, name.as_bytes()));
+    tensor.extend(field(9, &raw));
+    field(5, &tensor)
+}
+
+fn value_info(number: u64, name: &str, dims: &[u64]) -> Vec<u8> {
+    let shape = dims
+        .iter()
+        .flat_map(|dim| field(1, &int_field(1, *dim)))
+        .collect::<Vec<_>>();
+    let mut tensor_type = int_field(1, 1);
+    tensor_type.extend(field(2, &shape));
+    let mut info = field(1, name.as_bytes());
+    info.extend(field(2, &field(1, &tensor_type)));
+    field(number, &info)
+}
+
+fn node(op_type: &str, inputs: &[&str], output: &str) -> Vec<u8> {
+    let mut node = inputs
+        .iter()
+        .flat_map(|input| field(1, input.as_bytes()))
+        .collect::<Vec<_>>();
+    node.extend(field(2, output.as_bytes()));
+    node.extend(field(4, op_type.as_bytes()));
+    field(1, &node)
+}
+
+/// Logistic regression on the sum of the states of a window, as exported by
+/// common training tools
+fn logistic_model(weight: f32, bias: f32, last_op: &str) -> Vec<u8> {
+    let len = TRAINING_WINDOW_LEN as u64;
+    let mut graph = node("Gemm", &["x", "W", "B"], "z");
+    graph.extend(node(last_op, &["z"], "y"));
+    graph.extend(initializer("W", &[len, 1], weight));
+    graph.extend(initializer("B", &[1], bias));
+    graph.extend(value_info(11, "x", &[1, len]));
+    graph.extend(value_info(12, "y", &[1, 1]));
+    let mut model = int_field(1, 8);
+    model.extend(field(7, &graph));
+    model
+}
+
+#[test]
+fn classifier_runs_supported_operators() {
+    let classifier = OnnxClassifier::from_bytes(&logistic_model(1.0, -10.0, "Sigmoid")).unwrap();
+    assert_eq!(classifier.window_len, TRAINING_WINDOW_LEN);
+    assert_eq!(classifier.sha256.len(), 64);
+
+    let mut states = vec![0; TRAINING_WINDOW_LEN];
+    let prob = classifier.cluster_probability(&states).unwrap();
+    assert!((prob - 1.0 / (1.0 + 10f64.exp())).abs() < 1e-6);
+    states[..5].fill(2);
+    let prob = classifier.cluster_probability(&states).unwrap();
+    assert!((prob - 0.5).abs() < 1e-6);
+
+    let error = OnnxClassifier::from_bytes(&logistic_model(1.0, 0.0, "Conv")).unwrap_err();
+    assert!(error.to_string().contains("Unsupported operator Conv"));
+}
+
+/// A classifier that never calls a cluster keeps covered regions as they are,
+/// and one that always does never reaches reference-like flanks
+#[test]
+fn onnx_model_assesses_windows() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let run = |weight: f32, bias: f32| {
+        let model_path = dir.path().join("model.onnx");
+        fs::write(&model_path, logistic_model(weight, bias, "Sigmoid")).unwrap();
+        let output = common::vclust(&dataset)
+            .arg("--model-type")
+            .arg("onnx")
+            .arg("--model")
+            .arg(&model_path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| {
+                let fields = line.split('\t').collect::<Vec<_>>();
+                format!("{} {}", fields[0], fields[4])
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        run(0.0, -20.0),
+        [
+            "cluster chr1:8040-8060",
+            "reference chr1:6000-6020",
+            "contig_start NA",
+            "no_coverage NA"
+        ]
+    );
+    assert!(run(0.0, 20.0).iter().all(|result| result.ends_with(" NA")));
+
+    let output = common::vclust(&dataset)
+        .arg("--model-type")
+        .arg("onnx")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("requires --model"));
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use rust_htslib::faidx;
+use serde_json::Value;
+
+fn run(dataset: &common::Dataset, args: &[&str]) -> String {
+    let output = common::vclust(dataset).args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Only the extended cluster and reference loci are written, in the order of
+/// the regions file and each with the reference base at the start of its extended region
+#[test]
+fn vcf_records_carry_the_reference_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let vcf = run(&dataset, &["--output-format", "vcf"]);
+
+    let meta = vcf
+        .lines()
+        .filter(|line| line.starts_with("##"))
+        .collect::<Vec<_>>();
+    assert_eq!(meta[0], "##fileformat=VCFv4.2");
+    assert!(meta.contains(&"##contig=<ID=chr1,length=20000>"));
+    let header = vcf.lines().find(|line| line.starts_with("#CHROM")).unwrap();
+    assert_eq!(header, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO");
+
+    let reference = faidx::Reader::from_path(&dataset.genome_path).unwrap();
+    let records = vcf
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split('\t').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        records.iter().map(|fields| fields[2]).collect::<Vec<_>>(),
+        ["cluster", "reference"]
+    );
+    for fields in &records {
+        assert_eq!(fields.len(), 8);
+        let pos = fields[1].parse::<usize>().unwrap();
+        let base = reference
+            .fetch_seq_string(fields[0], pos - 1, pos - 1)
+            .unwrap();
+        assert_eq!(fields[3], base);
+        assert_eq!(fields[4], "<VC>");
+    }
+    let cluster = &records[0];
+    assert_eq!(cluster[1], "8001");
+    assert!(cluster[7].starts_with("END=8060;LF=40;RF=0;NS=2;"));
+}
+
+/// Score columns of regions that could not be extended are `null` rather than
+/// the `NA` of the TSV output
+#[test]
+fn jsonl_scores_are_null_when_missing() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let jsonl = run(&dataset, &["--output-format", "jsonl", "--base-coverage"]);
+
+    let records = jsonl
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 4);
+    let record = |name: &str| {
+        records
+            .iter()
+            .find(|record| record["name"] == name)
+            .unwrap()
+    };
+    for name in ["contig_start", "no_coverage"] {
+        assert_eq!(record(name)["lf_offset"], Value::Null);
+        assert_eq!(record(name)["physical_depth"], Value::Null);
+        assert_eq!(record(name)["base_depth"], Value::Null);
+    }
+    let depth = record("cluster")["base_depth"].as_str().unwrap();
+    assert!(depth.parse::<f64>().unwrap() > 0.0);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, Dataset, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use vclust::pedigree::load_pedigree;
+
+/// A trio whose child alone carries a deletion at 8040
+fn write_trio(dir: &Path) -> Dataset {
+    let genome_path = dir.join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let reads_path = dir.join("reads.txt");
+    let mut reads = File::create(&reads_path).unwrap();
+    for sample in ["child", "father", "mother"] {
+        let records = (0..20)
+            .map(|index| {
+                let ops = match sample == "child" && index % 2 == 0 {
+                    true => vec![Cigar::Equal(3040), Cigar::Del(30), Cigar::Equal(960)],
+                    false => vec![Cigar::Equal(4000)],
+                };
+                make_record(&format!("{sample}{index}"), 5000, ops, 60)
+            })
+            .collect();
+        let bam_path = dir.join(format!("{sample}.bam"));
+        write_bam(&bam_path, sample, records);
+        writeln!(reads, "{}", bam_path.display()).unwrap();
+    }
+    let regions_path = dir.join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+    Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    }
+}
+
+#[test]
+fn pedigree_lists_families_in_file_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("families.ped");
+    fs::write(
+        &path,
+        "# family sample father mother sex phenotype\n\
+         fam1 father 0 0 1 0\n\
+         fam2 other 0 0 1 0\n\
+         fam1 child father mother 2 0\n\
+         fam1 mother 0 0 2 0\n",
+    )
+    .unwrap();
+    let pedigree = load_pedigree(&path).unwrap();
+    let ids = pedigree.families.iter().map(|f| &f.id).collect::<Vec<_>>();
+    assert_eq!(ids, ["fam1", "fam2"]);
+    let child = &pedigree.families[0].members[1];
+    assert_eq!(child.father.as_deref(), Some("father"));
+    assert_eq!(child.mother.as_deref(), Some("mother"));
+    assert_eq!(pedigree.families[0].members[0].father, None);
+
+    fs::write(&path, "fam1 child 0 0\nfam2 child 0 0\n").unwrap();
+    assert!(load_pedigree(&path).is_err());
+    fs::write(&path, "fam1 child\n").unwrap();
+    assert!(load_pedigree(&path).is_err());
+}
+
+/// Support in the child but in neither assessed parent is flagged; families
+/// without any sequenced member are not reported
+#[test]
+fn carrier_child_of_non_carriers_is_inconsistent() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_trio(dir.path());
+    let ped_path = dir.path().join("families.ped");
+    fs::write(
+        &ped_path,
+        "fam1 father 0 0 1 0\n\
+         fam1 mother 0 0 2 0\n\
+         fam1 child father mother 2 0\n\
+         fam1 sibling father mother 1 0\n\
+         fam2 unrelated 0 0 1 0\n",
+    )
+    .unwrap();
+    let segregation_path = dir.path().join("segregation.txt");
+    let status = common::vclust(&dataset)
+        .arg("--pedigree")
+        .arg(&ped_path)
+        .arg("--segregation")
+        .arg(&segregation_path)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+    assert_eq!(
+        fs::read_to_string(&segregation_path).unwrap(),
+        "#name\tfamily\tcarriers\tnon_carriers\tunassessed\tinconsistent\n\
+         cluster\tfam1\tchild\tfather,mother\tsibling\tchild\n"
+    );
+}
+
+#[test]
+fn segregation_requires_a_pedigree() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_trio(dir.path());
+    let output = common::vclust(&dataset)
+        .arg("--segregation")
+        .arg(dir.path().join("segregation.txt"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use vclust::population::{LocusFrequency, PopulationCatalog};
+
+fn vclust(args: &[&std::ffi::OsStr]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn aggregates_per_sample_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let manifest = std::fs::read_to_string(&dataset.reads_path).unwrap();
+    let mut results_paths = Vec::new();
+    for (index, bam) in manifest.lines().enumerate() {
+        let reads_path = dir.path().join(format!("reads{index}.txt"));
+        std::fs::write(&reads_path, format!("{bam}\n")).unwrap();
+        let results = vclust(&[
+            "--genome".as_ref(),
+            dataset.genome_path.as_os_str(),
+            "--reads".as_ref(),
+            reads_path.as_os_str(),
+            "--regions".as_ref(),
+            dataset.regions_path.as_os_str(),
+        ]);
+        let results_path = dir.path().join(format!("results{index}.txt"));
+        std::fs::write(&results_path, results).unwrap();
+        results_paths.push(results_path);
+    }
+
+    let mut args = vec!["population-catalog".as_ref()];
+    args.extend(results_paths.iter().map(|path| path.as_os_str()));
+    let catalog = vclust(&args);
+    let lines = catalog.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "#vclust_population_catalog_version=1");
+    // Only the first two samples carry the cluster; loci extended in no
+    // sample are left out
+    assert_eq!(
+        &lines[2..],
+        [
+            "reference\tchr1:6000-6020\t3\t0\t0.0000\t.\t.",
+            "cluster\tchr1:8040-8060\t3\t2\t0.6667\t40\t40:2",
+        ]
+    );
+}
+
+#[test]
+fn medians_come_from_supporting_samples() {
+    let freq = LocusFrequency {
+        n_samples: 6,
+        n_supporting: 4,
+        extension_sizes: BTreeMap::from([(10, 1), (40, 2), (100, 1)]),
+    };
+    assert_eq!(freq.median_extension(), Some(40));
+    assert!((freq.support_frequency() - 4.0 / 6.0).abs() < 1e-12);
+    assert_eq!(LocusFrequency::default().median_extension(), None);
+    assert_eq!(LocusFrequency::default().support_frequency(), 0.0);
+}
+
+/// Write a catalog of the dataset in which the cluster is supported in
+/// `n_supporting` of 50 samples
+fn write_cohort_catalog(path: &std::path::Path, n_supporting: usize) {
+    std::fs::write(
+        path,
+        format!(
+            "#vclust_population_catalog_version=1\n\
+             #name\tregion\tn_samples\tn_supporting\tsupport_frequency\tmedian_extension\textension_sizes\n\
+             reference\tchr1:6000-6020\t50\t0\t0.0000\t.\t.\n\
+             cluster\tchr1:8040-8060\t50\t{n_supporting}\t{:.4}\t38\t38:{n_supporting}\n",
+            n_supporting as f64 / 50.0
+        ),
+    )
+    .unwrap();
+}
+
+fn population_columns(stdout: &str) -> Vec<Vec<&str>> {
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines
+        .next()
+        .unwrap()
+        .ends_with("\tpopulation_frequency\tpopulation_extension"));
+    lines
+        .map(|line| line.split('\t').rev().take(2).collect::<Vec<_>>())
+        .collect()
+}
+
+#[test]
+fn annotates_records_with_population_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let catalog_path = dir.path().join("cohort.catalog.tsv");
+    write_cohort_catalog(&catalog_path, 45);
+    let stdout = vclust(&[
+        "--genome".as_ref(),
+        dataset.genome_path.as_os_str(),
+        "--reads".as_ref(),
+        dataset.reads_path.as_os_str(),
+        "--regions".as_ref(),
+        dataset.regions_path.as_os_str(),
+        "--population-catalog".as_ref(),
+        catalog_path.as_os_str(),
+    ]);
+    // Regions missing from the catalog were never extended in the cohort
+    assert_eq!(
+        population_columns(&stdout),
+        [["38", "0.9000"], [".", "0.0000"], [".", "."], [".", "."]]
+    );
+}
+
+#[test]
+fn cached_results_follow_the_catalog() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let cache_dir = dir.path().join("cache");
+    let run = |catalog_path: &std::path::Path| {
+        vclust(&[
+            "--genome".as_ref(),
+            dataset.genome_path.as_os_str(),
+            "--reads".as_ref(),
+            dataset.reads_path.as_os_str(),
+            "--regions".as_ref(),
+            dataset.regions_path.as_os_str(),
+            "--population-catalog".as_ref(),
+            catalog_path.as_os_str(),
+            "--cache".as_ref(),
+            cache_dir.as_os_str(),
+        ])
+    };
+    let first_path = dir.path().join("first.catalog.tsv");
+    write_cohort_catalog(&first_path, 45);
+    assert_eq!(population_columns(&run(&first_path))[0], ["38", "0.9000"]);
+    let second_path = dir.path().join("second.catalog.tsv");
+    write_cohort_catalog(&second_path, 5);
+    assert_eq!(population_columns(&run(&second_path))[0], ["38", "0.1000"]);
+    // Rerunning with the first catalog is still served its own frequencies
+    assert_eq!(population_columns(&run(&first_path))[0], ["38", "0.9000"]);
+}
+
+#[test]
+fn catalogs_must_be_versioned() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("catalog.tsv");
+    std::fs::write(&path, "#name\tregion\n").unwrap();
+    assert!(PopulationCatalog::load(&path).is_err());
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::extend::get_extension_offsets;
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+
+fn run(dataset: &common::Dataset, args: &[&str]) -> Vec<String> {
+    let output = common::vclust(dataset).args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// The cluster carries enough alt signal to pass the screen and is extended as
+/// without it; the reference locus is reported unextended with its full ladder
+#[test]
+fn screened_loci_are_reported_unextended() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let screened = run(&dataset, &["--prescreen", "1"]);
+    let unscreened = run(&dataset, &[]);
+    let row = |rows: &[String], name: &str| {
+        rows.iter()
+            .find(|row| row.starts_with(&format!("{name}\t")))
+            .unwrap()
+            .split('\t')
+            .map(|field| field.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(row(&screened, "cluster"), row(&unscreened, "cluster"));
+    let reference = row(&screened, "reference");
+    assert_eq!(reference[2..4], ["0", "0"]);
+    assert_eq!(reference[4], "chr1:6000-6020");
+    assert_eq!(reference[5], "0");
+    assert_eq!(reference[10], row(&unscreened, "reference")[10]);
+    for name in ["contig_start", "no_coverage"] {
+        assert_eq!(row(&screened, name)[2], "NA");
+    }
+}
+
+/// Low-quality reads in the flank of a locus without variation are only seen,
+/// and counted as skipped, when the search region is fetched
+#[test]
+fn screen_does_not_fetch_the_flanks() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let mut records = (0..20)
+        .map(|index| make_record(&format!("read{index}"), 4000, vec![Cigar::Equal(4000)], 60))
+        .collect::<Vec<_>>();
+    records.extend(
+        (0..5).map(|index| make_record(&format!("flank{index}"), 5600, vec![Cigar::Equal(200)], 0)),
+    );
+    let bam_path = dir.path().join("sample.bam");
+    write_bam(&bam_path, "sample", records);
+    let reads_path = dir.path().join("reads.txt");
+    std::fs::write(&reads_path, format!("{}\n", bam_path.display())).unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 6000,
+        end: 6020,
+        name: "reference".to_string(),
+        group: None,
+    };
+    let skipped_mapq = |prescreen_alt_mass: Option<f64>| {
+        let builder = ContextBuilder {
+            params: Arc::new(Params {
+                prescreen_alt_mass,
+                ..Default::default()
+            }),
+            ..ContextBuilder::new(genome_path.clone(), load_manifest(&reads_path).unwrap())
+        };
+        let mut ctx = builder.build().unwrap();
+        let ext = get_extension_offsets(&mut ctx, &locus).unwrap();
+        assert_eq!((ext.lf_offset, ext.rf_offset, ext.ns), (0, 0, 0));
+        ext.skipped_reads["sample"].mapq
+    };
+
+    assert_eq!(skipped_mapq(None), 5);
+    assert_eq!(skipped_mapq(Some(1.0)), 0);
+}
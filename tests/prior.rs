@@ -0,0 +1,100 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use vclust::locus::Locus;
+use vclust::prior::{PriorSpans, PRIOR_MARGIN};
+use vclust::schema;
+
+fn run(dataset: &common::Dataset, output_path: &Path, extra_args: &[&str]) -> Vec<String> {
+    let status = common::vclust(dataset)
+        .arg("--output")
+        .arg(output_path)
+        .args(extra_args)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let extended_col = schema::COLUMNS
+        .iter()
+        .position(|column| *column == "extended_region")
+        .unwrap();
+    fs::read_to_string(output_path)
+        .unwrap()
+        .lines()
+        .skip(2)
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            format!("{} {}", fields[0], fields[extended_col])
+        })
+        .collect()
+}
+
+/// Searching around the extended regions of a previous run finds the same
+/// clusters, also when a cluster grew past its previous span
+#[test]
+fn warm_started_runs_find_the_same_clusters() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let cold_path = dir.path().join("cold.txt");
+    let cold = run(&dataset, &cold_path, &[]);
+    assert!(cold.iter().any(|line| line.starts_with("cluster chr1:")));
+
+    let warm_path = dir.path().join("warm.txt");
+    let prior_arg = cold_path.to_str().unwrap();
+    assert_eq!(
+        run(&dataset, &warm_path, &["--prior-results", prior_arg]),
+        cold
+    );
+
+    // Clusters that grew a little since the previous run stay within the margin
+    let shrunk_path = dir.path().join("shrunk.txt");
+    let shrunk = fs::read_to_string(&cold_path)
+        .unwrap()
+        .lines()
+        .map(|line| match line.starts_with("cluster\t") {
+            true => line
+                .split('\t')
+                .enumerate()
+                .map(|(index, field)| match schema::COLUMNS[index] {
+                    "extended_region" => "chr1:8040-8060",
+                    _ => field,
+                })
+                .collect::<Vec<_>>()
+                .join("\t"),
+            false => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    fs::write(&shrunk_path, shrunk + "\n").unwrap();
+    let shrunk_arg = shrunk_path.to_str().unwrap();
+    let rerun_path = dir.path().join("rerun.txt");
+    assert_eq!(
+        run(&dataset, &rerun_path, &["--prior-results", shrunk_arg]),
+        cold
+    );
+}
+
+#[test]
+fn radius_covers_the_prior_span() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let results_path = dir.path().join("results.txt");
+    run(&dataset, &results_path, &[]);
+    let prior = PriorSpans::load(&results_path).unwrap();
+    let locus = |name: &str, start: i64| Locus {
+        chrom: "chr1".to_string(),
+        start,
+        end: start + 20,
+        name: name.to_string(),
+        group: None,
+    };
+    let radius = prior.radius_for(&locus("cluster", 8040), 5000).unwrap();
+    assert!(radius > PRIOR_MARGIN && radius < 5000);
+    assert_eq!(
+        prior.radius_for(&locus("cluster", 8040), PRIOR_MARGIN),
+        None
+    );
+    // Loci that moved or were not extended keep the full radius
+    assert_eq!(prior.radius_for(&locus("cluster", 12000), 5000), None);
+    assert_eq!(prior.radius_for(&locus("missing", 8040), 5000), None);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::CONTIG;
+use std::fs;
+
+/// The variation cluster carried by two of the three samples is scored above
+/// a reference-like region, and regions that cannot be extended are NA
+#[test]
+fn priority_ranks_the_cluster_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(
+        &regions_path,
+        format!("{CONTIG}\t8040\t8060\tcluster\n{CONTIG}\t5000\t5020\treference\n{CONTIG}\t100\t120\tedge\n"),
+    )
+    .unwrap();
+
+    let output = common::vclust(&dataset.with_regions(&regions_path))
+        .arg("--priority")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    let columns = lines.next().unwrap().split('\t').collect::<Vec<_>>();
+    assert_eq!(columns.last(), Some(&"priority"));
+    let priorities = lines
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            (fields[0].to_string(), fields.last().unwrap().to_string())
+        })
+        .collect::<Vec<_>>();
+    let score = |name: &str| {
+        let (_, value) = priorities.iter().find(|(n, _)| n == name).unwrap();
+        value.parse::<f64>().ok()
+    };
+    let (cluster, reference) = (score("cluster").unwrap(), score("reference").unwrap());
+    assert!(cluster > reference, "{cluster} <= {reference}");
+    assert!((0.0..=100.0).contains(&cluster) && (0.0..=100.0).contains(&reference));
+    assert_eq!(score("edge"), None);
+}
+
+/// A user expression replaces the default score
+#[test]
+fn priority_expression_replaces_the_default_score() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+
+    let run = |expression: &str| {
+        common::vclust(&dataset.with_regions(&regions_path))
+            .args(["--priority-expr", expression])
+            .output()
+            .unwrap()
+    };
+    let output = run("ns * 10 + (spanning_reads > 0)");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let row = stdout.lines().nth(2).unwrap();
+    assert_eq!(row.split('\t').next_back(), Some("21"));
+
+    let output = run("ns * spanning");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Unknown name spanning"));
+}
+
+/// The priority sits right after the annotation columns and is computed from
+/// the values of the columns added after it
+#[test]
+fn priority_reads_the_columns_that_follow_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+
+    let output = common::vclust(&dataset.with_regions(&regions_path))
+        .args(["--priority-expr", "base_depth * 10"])
+        .args(["--haplotype-balance", "--base-coverage"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    let columns = lines.next().unwrap().split('\t').collect::<Vec<_>>();
+    let fields = lines.next().unwrap().split('\t').collect::<Vec<_>>();
+    assert_eq!(fields.len(), columns.len());
+    let n = columns.len();
+    assert_eq!(
+        columns[n - 5..],
+        [
+            "priority",
+            "haplotype_balance",
+            "allelic_imbalance",
+            "physical_depth",
+            "base_depth"
+        ]
+    );
+    let base_depth = fields[n - 1].parse::<f64>().unwrap();
+    let priority = fields[n - 5].parse::<f64>().unwrap();
+    assert!((priority - base_depth * 10.0).abs() < 1e-9);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use proptest::prelude::*;
+use rust_htslib::bam::record::{Aux, Cigar};
+use vclust::profile::{count_alt_events, update_profs};
+
+fn cigar_op() -> impl Strategy<Value = Cigar> {
+    prop_oneof![
+        (1..200u32).prop_map(Cigar::Match),
+        (1..200u32).prop_map(Cigar::Equal),
+        (1..20u32).prop_map(Cigar::Diff),
+        (1..50u32).prop_map(Cigar::Ins),
+        (1..50u32).prop_map(Cigar::Del),
+    ]
+}
+
+fn cigar() -> impl Strategy<Value = Vec<Cigar>> {
+    (0..30u32, prop::collection::vec(cigar_op(), 1..20), 0..30u32).prop_map(
+        |(left_clip, mut ops, right_clip)| {
+            if left_clip > 0 {
+                ops.insert(0, Cigar::SoftClip(left_clip));
+            }
+            if right_clip > 0 {
+                ops.push(Cigar::SoftClip(right_clip));
+            }
+            ops
+        },
+    )
+}
+
+fn ref_len(ops: &[Cigar]) -> i64 {
+    ops.iter()
+        .map(|op| match op {
+            Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) | Cigar::Del(len) => {
+                *len as i64
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Reference span of the aligned bases, which excludes deletions before the
+/// first and after the last of them; alignments without aligned bases are left
+/// as they are
+fn aligned_span(pos: i64, ops: &[Cigar]) -> (i64, i64) {
+    let is_aligned = |op: &Cigar| matches!(op, Cigar::Match(_) | Cigar::Equal(_) | Cigar::Diff(_));
+    let (Some(first), Some(last)) = (
+        ops.iter().position(is_aligned),
+        ops.iter().rposition(is_aligned).map(|last| last + 1),
+    ) else {
+        return (pos, pos + ref_len(ops));
+    };
+    let start = pos + ref_len(&ops[..first]);
+    (start, start + ref_len(&ops[first..last]))
+}
+
+proptest! {
+    #[test]
+    fn coverage_matches_reference_overlap(
+        pos in 0..2000i64,
+        ops in cigar(),
+        region_start in 0..2000i64,
+        region_len in 1..1000i64,
+    ) {
+        let region = ("chr1", region_start, region_start + region_len);
+        let mut covs = vec![0; region_len as usize];
+        let mut alts = vec![0; region_len as usize];
+        let mut clips = vec![0; region_len as usize];
+        let rec = common::make_record("read", pos, ops.clone(), 60);
+        update_profs(&rec, &mut covs, &mut alts, &mut clips, region, 5).unwrap();
+
+        let (read_start, read_end) = aligned_span(pos, &ops);
+        let overlap = (read_end.min(region.2) - read_start.max(region.1)).max(0);
+        prop_assert_eq!(covs.iter().sum::<u32>() as i64, overlap);
+        prop_assert!(covs.iter().all(|cov| *cov <= 1));
+    }
+
+    #[test]
+    fn mismatches_are_counted_as_alts(
+        pos in 0..500i64,
+        lead in 1..500u32,
+        diff in 1..20u32,
+    ) {
+        let region = ("chr1", 0, 1500);
+        let mut covs = vec![0; 1500];
+        let mut alts = vec![0; 1500];
+        let mut clips = vec![0; 1500];
+        let ops = vec![Cigar::Equal(lead), Cigar::Diff(diff), Cigar::Equal(100)];
+        let rec = common::make_record("read", pos, ops, 60);
+        let any_alt = update_profs(&rec, &mut covs, &mut alts, &mut clips, region, 5).unwrap();
+
+        let diff_start = (pos + lead as i64) as usize;
+        prop_assert_eq!(alts.iter().sum::<u32>(), diff);
+        prop_assert!(alts[diff_start..diff_start + diff as usize].iter().all(|alt| *alt == 1));
+        prop_assert_eq!(any_alt, diff >= 5);
+    }
+
+    #[test]
+    fn cs_tag_resolves_match_only_cigars(
+        pos in 0..500i64,
+        lead in 1..500u32,
+        diff in 1..20u32,
+        del in 1..20u32,
+    ) {
+        let region = ("chr1", 0, 1500);
+        let profile = |rec| {
+            let mut covs = vec![0; 1500];
+            let mut alts = vec![0; 1500];
+            let mut clips = vec![0; 1500];
+            let any_alt = update_profs(&rec, &mut covs, &mut alts, &mut clips, region, 5).unwrap();
+            (covs, alts, any_alt)
+        };
+
+        let ops = vec![
+            Cigar::Equal(lead),
+            Cigar::Diff(diff),
+            Cigar::Del(del),
+            Cigar::Equal(100),
+        ];
+        let expected = profile(common::make_record("read", pos, ops, 60));
+
+        let ops = vec![Cigar::Match(lead + diff), Cigar::Del(del), Cigar::Match(100)];
+        let mut rec = common::make_record("read", pos, ops, 60);
+        let cs = format!(
+            ":{lead}{}-{}:100",
+            "*ag".repeat(diff as usize),
+            "t".repeat(del as usize)
+        );
+        rec.push_aux(b"cs", Aux::String(&cs)).unwrap();
+        prop_assert_eq!(profile(rec), expected);
+    }
+
+    #[test]
+    fn read_end_events_lie_within_read_end_len(
+        clip in 0..30u32,
+        lead in 0..400u32,
+        diff in 1..20u32,
+        tail in 0..400u32,
+    ) {
+        let region = ("chr1", 0, 1500);
+        let mut events = vec![0; 1500];
+        let mut read_end_events = vec![0; 1500];
+        let ops = vec![
+            Cigar::SoftClip(clip),
+            Cigar::Equal(lead),
+            Cigar::Diff(diff),
+            Cigar::Equal(tail),
+        ];
+        let rec = common::make_record("read", 100, ops, 60);
+        count_alt_events(&rec, &mut events, &mut read_end_events, region, 100);
+
+        prop_assert_eq!(events.iter().sum::<u32>(), 1);
+        prop_assert_eq!(events[100 + lead as usize], 1);
+        let near_end = lead < 100 || diff + tail < 100;
+        prop_assert_eq!(read_end_events.iter().sum::<u32>(), near_end as u32);
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+fn run_manifest(dataset: &common::Dataset, path: &Path, extra_args: &[&Path]) -> serde_json::Value {
+    let output = common::vclust(dataset)
+        .arg("--run-manifest")
+        .arg(path)
+        .arg("--force")
+        .args(extra_args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn roles(manifest: &serde_json::Value, files: &str) -> Vec<String> {
+    manifest[files]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["role"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Every report written by the run, and every input it read, is recorded
+#[test]
+fn manifest_records_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let truth_path = dir.path().join("truth.bed");
+    fs::write(&truth_path, "chr1\t8000\t8100\ttruth\n").unwrap();
+    let at = |name: &str| dir.path().join(name);
+    let args = [
+        Path::new("--preflight"),
+        &at("preflight.txt"),
+        Path::new("--dedup-report"),
+        &at("collapsed.txt"),
+        Path::new("--group-report"),
+        &at("groups.txt"),
+        Path::new("--export-training"),
+        &at("training"),
+        Path::new("--truth-regions"),
+        &truth_path,
+    ];
+    let manifest = run_manifest(&dataset, &at("manifest.json"), &args);
+
+    let outputs = roles(&manifest, "outputs");
+    for role in ["results", "preflight", "dedup_report", "group_report"] {
+        assert!(outputs.contains(&role.to_string()), "{outputs:?}");
+    }
+    let training = outputs.iter().filter(|role| *role == "export_training");
+    assert_eq!(training.count(), 3);
+    assert!(roles(&manifest, "inputs").contains(&"truth_regions".to_string()));
+}
+
+/// Model rules change the models of the run, and so its model checksum
+#[test]
+fn model_checksum_covers_the_model_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let rules_path = dir.path().join("rules.tsv");
+    fs::write(&rules_path, "shallow\tdepth < 15\t0.7\t.\n").unwrap();
+    let manifest_path = dir.path().join("manifest.json");
+
+    let checksum = |extra_args: &[&Path]| {
+        let manifest = run_manifest(&dataset, &manifest_path, extra_args);
+        manifest["model_sha256"].as_str().unwrap().to_string()
+    };
+    let default = checksum(&[]);
+    assert_eq!(checksum(&[]), default);
+    let with_rules = checksum(&[Path::new("--model-rules"), &rules_path]);
+    assert_ne!(with_rules, default);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::extend::get_extension_offsets;
+use vclust::locus::load_loci;
+use vclust::manifest::load_manifest;
+
+/// The 20 bp cluster is searched within the smallest radius, which still fits
+/// the longest window of the ladder
+#[test]
+fn smallest_radius_keeps_the_whole_ladder() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let builder = ContextBuilder {
+        params: Arc::new(Params {
+            radius_scale: Some(1.0),
+            ..Default::default()
+        }),
+        ..ContextBuilder::new(
+            dataset.genome_path.clone(),
+            load_manifest(&dataset.reads_path).unwrap(),
+        )
+    };
+    assert_eq!(builder.params.radius_for(20), 300);
+    assert!(builder.params.dropped_windows().is_empty());
+    let mut ctx = builder.build().unwrap();
+    let loci = load_loci(dataset.regions_path.clone(), None).unwrap();
+    let cluster = loci.iter().find(|locus| locus.name == "cluster").unwrap();
+    let ext = get_extension_offsets(&mut ctx, cluster).unwrap();
+    let windows = ext
+        .ladder_offsets
+        .iter()
+        .map(|(len, _, _)| *len)
+        .collect::<Vec<_>>();
+    assert_eq!(windows, Params::default().window_ladder);
+    assert_eq!((ext.lf_offset, ext.rf_offset), (40, 0));
+}
+
+/// A window as long as the search radius has no room to slide over a flank
+#[test]
+fn windows_longer_than_the_smallest_radius_are_dropped() {
+    let scaled = Params {
+        radius_scale: Some(2.0),
+        min_radius: 150,
+        ..Default::default()
+    };
+    assert_eq!(scaled.dropped_windows(), [150]);
+    let fixed = Params {
+        radius: 40,
+        ..Default::default()
+    };
+    assert_eq!(fixed.dropped_windows(), [150, 50]);
+}
+
+#[test]
+fn radius_bounds_must_be_positive() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    for bound in ["--min-radius", "--max-radius"] {
+        let output = common::vclust(&dataset)
+            .args(["--radius-scale", "2", bound, "0"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(bound));
+    }
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use rust_htslib::bam::Record;
+use std::sync::Arc;
+use vclust::context::ContextBuilder;
+use vclust::error::VclustError;
+use vclust::locus::{load_loci, Locus};
+use vclust::manifest::load_manifest;
+use vclust::profile::Prof;
+use vclust::schema::{format_row, header_with, COLUMNS};
+use vclust::scorer::LocusScorer;
+use vclust::workflow::run_workflow;
+
+struct ReadCounter;
+
+impl LocusScorer for ReadCounter {
+    fn columns(&self) -> Vec<String> {
+        vec!["n_reads".to_string()]
+    }
+
+    fn score(&self, _locus: &Locus, _prof: &Prof, reads: &[Record]) -> Vec<String> {
+        vec![reads.len().to_string()]
+    }
+}
+
+/// Declares one column but scores two
+struct MisalignedScorer;
+
+impl LocusScorer for MisalignedScorer {
+    fn columns(&self) -> Vec<String> {
+        vec!["score".to_string()]
+    }
+
+    fn score(&self, _locus: &Locus, _prof: &Prof, _reads: &[Record]) -> Vec<String> {
+        vec!["1".to_string(), "2".to_string()]
+    }
+}
+
+#[test]
+fn scorer_columns_are_appended() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let builder = ContextBuilder {
+        scorers: vec![Arc::new(ReadCounter)],
+        ..ContextBuilder::new(
+            dataset.genome_path.clone(),
+            load_manifest(&dataset.reads_path).unwrap(),
+        )
+    };
+    let last_column = COLUMNS.last().unwrap();
+    assert!(header_with(&builder.score_columns()).ends_with(&format!("\t{last_column}\tn_reads")));
+
+    let mut ctx = builder.build().unwrap();
+    for locus in load_loci(dataset.regions_path.clone(), None).unwrap() {
+        let result = run_workflow(&mut ctx, &locus).unwrap();
+        let row = format_row(&result);
+        let score = row.rsplit('\t').next().unwrap();
+        match result.extension {
+            Some(ext) => {
+                let n_reads = score.parse::<usize>().unwrap();
+                assert!(n_reads >= ext.spanning_reads + ext.partial_reads);
+            }
+            None => assert_eq!(score, "NA"),
+        }
+        assert_eq!(row.split('\t').count(), COLUMNS.len() + 1);
+    }
+}
+
+/// Loci too shallow to score are still reported as not extended
+#[test]
+fn misaligned_scores_fail_the_locus() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let builder = ContextBuilder {
+        scorers: vec![Arc::new(MisalignedScorer)],
+        ..ContextBuilder::new(
+            dataset.genome_path.clone(),
+            load_manifest(&dataset.reads_path).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    for locus in load_loci(dataset.regions_path.clone(), None).unwrap() {
+        match (locus.name.as_str(), run_workflow(&mut ctx, &locus)) {
+            ("cluster" | "reference", Err(VclustError::Scorer(msg))) => {
+                assert!(msg.starts_with(&format!("{}: ", locus.name)));
+                assert!(msg.contains("returned 2 values"));
+            }
+            ("contig_start" | "no_coverage", Ok(result)) => assert!(result.extension.is_none()),
+            (name, result) => panic!("Unexpected result for {name}: {:?}", result.err()),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::fs;
+use vclust::selection::{load_model_rules, select_rule, LocusContext};
+
+const RULES: &str = "#name\tcondition\tprior_ref\tpseudocount\n\
+                     deep\tdepth > 1000\t0.9\t.\n\
+                     balanced\t(gc > 0.4) * (gc < 0.6)\t0.6\t0.001\n\
+                     fallback\t1\t.\t.\n";
+
+/// The first rule whose condition holds selects the models and is reported
+#[test]
+fn first_matching_rule_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let rules_path = dir.path().join("rules.tsv");
+    fs::write(&rules_path, RULES).unwrap();
+
+    let output = common::vclust(&dataset)
+        .arg("--model-rules")
+        .arg(&rules_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    let columns = lines.next().unwrap().split('\t').collect::<Vec<_>>();
+    assert_eq!(columns.last(), Some(&"model"));
+    let models = lines
+        .map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            (fields[0], fields.last().copied().unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert!(models.contains(&("cluster", "balanced")));
+    assert!(models.contains(&("reference", "balanced")));
+    assert!(models.contains(&("no_coverage", "NA")));
+}
+
+#[test]
+fn locus_context_counts_called_bases() {
+    let context = LocusContext::new(30.0, b"GGCCNNAAAAAAAT");
+    assert_eq!(context.gc, 4.0 / 12.0);
+    assert_eq!(context.homopolymer, 7.0 / 14.0);
+
+    let dir = tempfile::tempdir().unwrap();
+    let rules_path = dir.path().join("rules.tsv");
+    fs::write(&rules_path, RULES).unwrap();
+    let rules = load_model_rules(&rules_path).unwrap();
+    assert_eq!(select_rule(&rules, &context), Some(2));
+}
+
+#[test]
+fn rules_with_unknown_names_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let rules_path = dir.path().join("rules.tsv");
+    fs::write(&rules_path, "repeat\tmappability < 0.5\t.\t.\n").unwrap();
+    let err = load_model_rules(&rules_path).unwrap_err().to_string();
+    assert!(err.contains("Unknown name mappability"), "{err}");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs::File;
+use std::io::Write;
+
+/// 150 bp reads tiling the region every 10 bp with mapping quality 30, those
+/// overlapping the middle of the region carrying a deletion
+#[test]
+fn short_read_mode_profiles_lower_mapping_qualities() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+
+    let records = (7300..8800)
+        .step_by(10)
+        .map(|pos| {
+            let offset = 8040 - pos;
+            let ops = match (30..=120).contains(&offset) {
+                true => vec![
+                    Cigar::Equal(offset as u32),
+                    Cigar::Del(30),
+                    Cigar::Equal(150 - offset as u32),
+                ],
+                false => vec![Cigar::Equal(150)],
+            };
+            make_record(&format!("read{pos}"), pos, ops, 30)
+        })
+        .collect();
+    let bam_path = dir.path().join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let reads_path = dir.path().join("reads.txt");
+    let mut reads = File::create(&reads_path).unwrap();
+    writeln!(reads, "{}", bam_path.display()).unwrap();
+
+    let regions_path = dir.path().join("regions.bed");
+    let mut regions = File::create(&regions_path).unwrap();
+    writeln!(regions, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let run = |extra_args: &[&str]| {
+        let output = common::vclust(&dataset).args(extra_args).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let row = stdout.lines().last().unwrap().to_string();
+        row.split('\t').map(str::to_string).collect::<Vec<_>>()
+    };
+
+    // All reads fail the long-read mapping quality filter
+    assert_eq!(run(&[])[2], "NA");
+
+    let row = run(&["--short-reads"]);
+    assert_eq!(row[4], "chr1:8040-8070");
+    assert_eq!(row[5], "1");
+    // Alt events 30 to 120 bp into 150 bp reads are not counted as read ends
+    assert_eq!(row[11], "0.00");
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::{Aux, Cigar};
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::Arc;
+use vclust::context::{ContextBuilder, Params};
+use vclust::extend::{get_extension_offsets, Extension};
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+use vclust::profile::{OntReadClass, SkipCounts};
+use vclust::summary::RunSummary;
+
+const SECONDARY: u16 = 0x100;
+const SUPPLEMENTARY: u16 = 0x800;
+
+/// One sample with 20 passing reads plus 2 secondary, 3 supplementary, and 4
+/// low-MAPQ records, and two malformed ones; 12 of the passing reads are ONT
+/// duplex reads, 4 simplex, and 4 carry no read class
+fn write_sample(dir: &std::path::Path) -> common::Dataset {
+    let genome_path = dir.join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let mut records = Vec::new();
+    for index in 0..29 {
+        let (flags, mapq) = match index {
+            20..=21 => (SECONDARY, 60),
+            22..=24 => (SUPPLEMENTARY, 60),
+            25.. => (0, 5),
+            _ => (0, 60),
+        };
+        let mut rec = make_record(
+            &format!("read{index}"),
+            5000,
+            vec![Cigar::Equal(4000)],
+            mapq,
+        );
+        rec.set_flags(flags);
+        match index {
+            0..=11 => rec.push_aux(b"dx", Aux::I32(1)).unwrap(),
+            12..=15 => rec.push_aux(b"dx", Aux::I32(0)).unwrap(),
+            _ => {}
+        }
+        records.push(rec);
+    }
+    records.extend(common::malformed_records(8000));
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    let reads_path = dir.join("reads.txt");
+    let mut reads = File::create(&reads_path).unwrap();
+    writeln!(reads, "{}", bam_path.display()).unwrap();
+    let regions_path = dir.join("regions.bed");
+    fs::write(
+        &regions_path,
+        format!("{CONTIG}\t8040\t8060\tlocus1\n{CONTIG}\t8400\t8420\tlocus2\n"),
+    )
+    .unwrap();
+    common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    }
+}
+
+fn sample_extension(dir: &std::path::Path, params: Params) -> Extension {
+    let builder = ContextBuilder {
+        params: Arc::new(params),
+        ..ContextBuilder::new(
+            dir.join("genome.fa"),
+            load_manifest(&dir.join("reads.txt")).unwrap(),
+        )
+    };
+    let mut ctx = builder.build().unwrap();
+    let locus = Locus {
+        chrom: CONTIG.to_string(),
+        start: 8040,
+        end: 8060,
+        name: "locus1".to_string(),
+        group: None,
+    };
+    get_extension_offsets(&mut ctx, &locus).unwrap()
+}
+
+#[test]
+fn skipped_records_are_counted_by_reason() {
+    let dir = tempfile::tempdir().unwrap();
+    write_sample(dir.path());
+    let ext = sample_extension(dir.path(), Params::default());
+    let expected = SkipCounts {
+        secondary: 2,
+        supplementary: 3,
+        mapq: 4,
+        read_class: 0,
+        chimeric: 0,
+        unmapped: 1,
+        empty_cigar: 1,
+        cigar_length: 0,
+    };
+    assert_eq!(ext.skipped_reads["sample0"], expected);
+}
+
+#[test]
+fn duplex_mode_skips_simplex_and_untagged_reads() {
+    let dir = tempfile::tempdir().unwrap();
+    write_sample(dir.path());
+    let params = Params {
+        ont_read_class: OntReadClass::Duplex,
+        ..Default::default()
+    };
+    let ext = sample_extension(dir.path(), params);
+    assert_eq!(ext.skipped_reads["sample0"].read_class, 8);
+}
+
+/// The summary tallies the skipped records over all regions and the detailed
+/// file lists them per region
+#[test]
+fn skipped_records_are_reported_per_run_and_region() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = write_sample(dir.path());
+    let (summary_path, skipped_path) = (
+        dir.path().join("summary.json"),
+        dir.path().join("skipped.txt"),
+    );
+    let status = common::vclust(&dataset)
+        .arg("--summary")
+        .arg(&summary_path)
+        .arg("--skipped-reads")
+        .arg(&skipped_path)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+
+    let summary = RunSummary::load(&summary_path).unwrap();
+    let expected = SkipCounts {
+        secondary: 4,
+        supplementary: 6,
+        mapq: 8,
+        read_class: 0,
+        chimeric: 0,
+        unmapped: 2,
+        empty_cigar: 2,
+        cigar_length: 0,
+    };
+    assert_eq!(summary.skipped_reads["sample0"], expected);
+    assert_eq!(
+        fs::read_to_string(&skipped_path).unwrap(),
+        "#name\tsample\tsecondary\tsupplementary\tmapq\tread_class\tchimeric\t\
+         unmapped\tempty_cigar\tcigar_length\n\
+         locus1\tsample0\t2\t3\t4\t0\t0\t1\t1\t0\n\
+         locus2\tsample0\t2\t3\t4\t0\t0\t1\t1\t0\n"
+    );
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use vclust::locus::Locus;
+use vclust::sort::ResultSorter;
+use vclust::validate::validate_results;
+use vclust::workflow::LocusResult;
+
+fn result(chrom: &str, start: i64, name: &str) -> LocusResult {
+    LocusResult {
+        locus: Locus {
+            chrom: chrom.to_string(),
+            start,
+            end: start + 20,
+            name: name.to_string(),
+            group: None,
+        },
+        extension: None,
+        concordance: Vec::new(),
+        scores: vec![name.to_uppercase()],
+    }
+}
+
+/// Spilled runs are merged in reference contig order, with unknown contigs
+/// last, and removed afterwards
+#[test]
+fn spilled_runs_are_merged_by_coordinate() {
+    let dir = tempfile::tempdir().unwrap();
+    let contigs = ["chr2".to_string(), "chr1".to_string()];
+    let mut sorter = ResultSorter::new(&contigs, 2, dir.path());
+    for (chrom, start, name) in [
+        ("chr1", 500, "e"),
+        ("chrUn", 10, "f"),
+        ("chr1", 100, "d"),
+        ("chr2", 300, "b"),
+        ("chr2", 300, "c"),
+        ("chr2", 50, "a"),
+    ] {
+        sorter.push(result(chrom, start, name)).unwrap();
+    }
+    assert_eq!(sorter.n_runs(), 3);
+
+    let mut names = Vec::new();
+    sorter
+        .finish(|result| {
+            assert_eq!(result.scores, [result.locus.name.to_uppercase()]);
+            names.push(result.locus.name);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(names, ["a", "b", "c", "d", "e", "f"]);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+/// The test regions are out of order, and BED output is ordered by the
+/// extended regions
+#[test]
+fn results_are_sorted_by_coordinate() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let run = |args: &[&str]| {
+        let output = common::vclust(&dataset)
+            .arg("--sort-output")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let results_path = dir.path().join("results.txt");
+    run(&["--output", results_path.to_str().unwrap()]);
+    let issues = validate_results(&results_path, None, true).unwrap();
+    assert!(issues.is_empty(), "{issues:?}");
+
+    let stdout = run(&[]);
+    let names = stdout
+        .lines()
+        .skip(2)
+        .map(|line| line.split('\t').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["contig_start", "reference", "cluster", "no_coverage"]
+    );
+
+    let stdout = run(&["--output-format", "bed"]);
+    let starts = stdout
+        .lines()
+        .map(|line| line.split('\t').nth(1).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(starts, ["6000", "8000"]);
+}
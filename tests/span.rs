@@ -0,0 +1,43 @@
+mod common;
+
+use sha2::{Digest, Sha256};
+use std::fs;
+
+/// JSONL records of extended regions carry the reference of the extended span,
+/// with the sequence only if requested
+#[test]
+fn extended_spans_report_their_reference() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let genome = fs::read_to_string(&dataset.genome_path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .collect::<String>();
+
+    let run = |mode: &str| {
+        let output = common::vclust(&dataset)
+            .args(["--output-format", "jsonl", "--span-reference", mode])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let line = stdout.lines().next().unwrap();
+        serde_json::from_str::<serde_json::Value>(line).unwrap()
+    };
+
+    let record = run("sequence");
+    assert_eq!(record["name"], "cluster");
+    let (lf_offset, rf_offset) = (record["lf_offset"].as_u64(), record["rf_offset"].as_u64());
+    let start = 8040 - lf_offset.unwrap() as usize;
+    let end = 8060 + rf_offset.unwrap() as usize;
+    let expected = &genome[start..end];
+    let span_reference = &record["span_reference"];
+    assert_eq!(span_reference["sequence"], expected);
+    let sha256 = format!("{:x}", Sha256::digest(expected.as_bytes()));
+    assert_eq!(span_reference["sha256"], sha256);
+
+    let span_reference = &run("sha256")["span_reference"];
+    assert_eq!(span_reference["sha256"], sha256);
+    assert!(span_reference.get("sequence").is_none());
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use vclust::alignment::open_source;
+use vclust::context::{ContextBuilder, Params, Scratch};
+use vclust::extend::get_extension_offsets;
+use vclust::locus::Locus;
+use vclust::manifest::load_manifest;
+use vclust::profile::{get_profile, ProfileOptions};
+
+/// One sample with 10 reference reads and 2 alt reads spanning the locus at
+/// 8040-8060, and an alt read starting within the locus
+fn write_sample(dir: &Path) {
+    write_fasta(&dir.join("genome.fa"), &vec![b'A'; CONTIG_LEN]);
+    let mut records = (0..12)
+        .map(|index| {
+            let ops = match index < 2 {
+                true => vec![Cigar::Equal(2045), Cigar::Diff(10), Cigar::Equal(1945)],
+                false => vec![Cigar::Equal(4000)],
+            };
+            make_record(&format!("spanning{index}"), 6000, ops, 60)
+        })
+        .collect::<Vec<_>>();
+    let ops = vec![Cigar::Diff(10), Cigar::Equal(3990)];
+    records.push(make_record("partial", 8050, ops, 60));
+    let bam_path = dir.join("sample0.bam");
+    write_bam(&bam_path, "sample0", records);
+    fs::write(dir.join("reads.txt"), format!("{}\n", bam_path.display())).unwrap();
+}
+
+fn params(require_spanning: bool) -> Params {
+    Params {
+        require_spanning,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn non_spanning_alt_reads_are_dropped_from_support() {
+    let dir = tempfile::tempdir().unwrap();
+    write_sample(dir.path());
+    let alt_reads = |require_spanning: bool| {
+        let mut bam = open_source(&dir.path().join("sample0.bam")).unwrap();
+        let options = ProfileOptions {
+            locus_span: (8040, 8060),
+            amplicons: None,
+            keep_reads: false,
+            read_quota: None,
+            reference: None,
+        };
+        let (prof, support) = get_profile(
+            bam.as_mut(),
+            (CONTIG, 7000, 9000),
+            &params(require_spanning),
+            &mut Scratch::default(),
+            options,
+        )
+        .unwrap();
+        // The partial read still counts towards depth
+        assert_eq!((prof.spanning_reads, prof.partial_reads), (12, 1));
+        support.count_reads(8000, 8100)
+    };
+    assert_eq!(alt_reads(false), 3);
+    assert_eq!(alt_reads(true), 2);
+}
+
+/// Without the partial read, the sample falls short of the three alt reads
+/// needed to support the cluster
+#[test]
+fn spanning_requirement_can_remove_sample_support() {
+    let dir = tempfile::tempdir().unwrap();
+    write_sample(dir.path());
+    let ns = |require_spanning: bool| {
+        let builder = ContextBuilder {
+            params: Arc::new(params(require_spanning)),
+            ..ContextBuilder::new(
+                dir.path().join("genome.fa"),
+                load_manifest(&dir.path().join("reads.txt")).unwrap(),
+            )
+        };
+        let mut ctx = builder.build().unwrap();
+        let locus = Locus {
+            chrom: CONTIG.to_string(),
+            start: 8040,
+            end: 8060,
+            name: "cluster".to_string(),
+            group: None,
+        };
+        get_extension_offsets(&mut ctx, &locus).unwrap().ns
+    };
+    assert_eq!(ns(false), 1);
+    assert_eq!(ns(true), 0);
+}
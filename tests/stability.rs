@@ -0,0 +1,37 @@
+mod common;
+
+/// Supported clusters report the spread of their boundaries over subsampled
+/// runs, and other loci report NA
+#[test]
+fn supported_clusters_report_boundary_spread() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let run = |n_runs: &str| {
+        common::vclust(&dataset)
+            .args(["--stability-check", n_runs])
+            .output()
+            .unwrap()
+    };
+
+    let output = run("4");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    let header = lines.next().unwrap();
+    assert!(header.ends_with("\tlf_offset_sd\trf_offset_sd"));
+    for line in lines {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let spread = &fields[fields.len() - 2..];
+        match fields[0] {
+            "cluster" => {
+                for sd in spread {
+                    assert!(sd.parse::<f64>().unwrap() >= 0.0, "{line}");
+                }
+            }
+            _ => assert_eq!(spread, ["NA", "NA"], "{line}"),
+        }
+    }
+
+    // A single run has no spread
+    assert!(!run("1").status.success());
+}
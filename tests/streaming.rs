@@ -0,0 +1,64 @@
+mod common;
+
+use std::io::Read;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::thread;
+
+fn run_with_output(dir: &Path, output: &str) {
+    let dataset = common::build_dataset(dir);
+    let status = common::vclust(&dataset)
+        .arg("--output")
+        .arg(output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Every region arrives as one JSON object per line, whatever the output format
+fn check_records(received: &str) {
+    let names = received
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["name"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["cluster", "reference", "contig_start", "no_coverage"]
+    );
+}
+
+#[test]
+fn results_are_streamed_to_a_tcp_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let reader = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        received
+    });
+    run_with_output(dir.path(), &format!("tcp://{address}"));
+    check_records(&reader.join().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn results_are_streamed_to_a_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("results.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let reader = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        received
+    });
+    run_with_output(dir.path(), &format!("unix://{}", socket_path.display()));
+    check_records(&reader.join().unwrap());
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{make_record, write_bam, write_fasta, CONTIG, CONTIG_LEN};
+use rust_htslib::bam::record::Cigar;
+use std::fs;
+use vclust::subreads::is_subread_name;
+
+#[test]
+fn subread_names_are_recognized() {
+    assert!(is_subread_name(b"m64011_190830_220126/1/0_1234"));
+    assert!(!is_subread_name(b"m64011_190830_220126/1/ccs"));
+    assert!(!is_subread_name(b"m64011_190830_220126/1/0_1234/extra"));
+    assert!(!is_subread_name(b"read1"));
+}
+
+/// Subread BAMs are rejected unless they are explicitly allowed
+#[test]
+fn subread_bams_require_allow_subreads() {
+    let dir = tempfile::tempdir().unwrap();
+    let genome_path = dir.path().join("genome.fa");
+    write_fasta(&genome_path, &vec![b'A'; CONTIG_LEN]);
+    let records = (0..20)
+        .map(|index| {
+            let name = format!("m64011_190830_220126/{index}/0_3000");
+            make_record(&name, 7000 + index, vec![Cigar::Equal(3000)], 60)
+        })
+        .collect();
+    let bam_path = dir.path().join("subreads.bam");
+    write_bam(&bam_path, "sample0", records);
+    let reads_path = dir.path().join("reads.txt");
+    fs::write(&reads_path, format!("{}\n", bam_path.display())).unwrap();
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+
+    let dataset = common::Dataset {
+        genome_path,
+        reads_path,
+        regions_path,
+    };
+    let run = |extra_args: &[&str]| common::vclust(&dataset).args(extra_args).output().unwrap();
+    let output = run(&[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("looks like a PacBio subread BAM"),
+        "{stderr}"
+    );
+
+    let output = run(&["--allow-subreads"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+}
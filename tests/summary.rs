@@ -0,0 +1,34 @@
+use std::fs;
+use vclust::summary::aggregate_summaries;
+
+#[test]
+fn shards_are_summed_and_checked_for_overlaps() {
+    let dir = tempfile::tempdir().unwrap();
+    let shards = [
+        r#"{"loci_total": 2, "loci_processed": 1, "loci_failed": 1, "failures": {"depth": 1},
+            "interrupted": false, "loci": ["chr1:100-120", "chr1:500-520"]}"#,
+        r#"{"loci_total": 2, "loci_processed": 2, "loci_failed": 0, "failures": {},
+            "interrupted": false, "loci": ["chr1:500-520", "chr2:100-120"]}"#,
+        r#"{"loci_total": 3, "loci_processed": 1, "loci_failed": 1, "failures": {"depth": 1},
+            "interrupted": true, "loci": ["chr3:1-2", "chr3:5-6", "chr3:5-6"]}"#,
+    ];
+    let paths = shards
+        .iter()
+        .enumerate()
+        .map(|(index, shard)| {
+            let path = dir.path().join(format!("shard{index}.json"));
+            fs::write(&path, shard).unwrap();
+            path
+        })
+        .collect::<Vec<_>>();
+
+    let aggregate = aggregate_summaries(&paths).unwrap();
+    assert_eq!(aggregate.runs, 3);
+    assert_eq!(aggregate.loci_total, 7);
+    assert_eq!(aggregate.loci_processed, 4);
+    assert_eq!(aggregate.failures["depth"], 2);
+    assert_eq!(aggregate.interrupted_runs, [paths[2].clone()]);
+    assert!(aggregate.incomplete_runs.is_empty());
+    assert_eq!(aggregate.duplicated_loci, ["chr1:500-520"]);
+    assert!(!aggregate.is_consistent());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::process::{Command, Output};
+use vclust::sweep::{make_grid, sweep_header};
+
+fn sweep(dataset: &common::Dataset, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("sweep")
+        .arg("--genome")
+        .arg(&dataset.genome_path)
+        .arg("--reads")
+        .arg(&dataset.reads_path)
+        .arg("--regions")
+        .arg(&dataset.regions_path)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn rows(output: &Output) -> Vec<Vec<String>> {
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next().unwrap(), sweep_header());
+    lines
+        .map(|line| line.split('\t').map(|field| field.to_string()).collect())
+        .collect()
+}
+
+#[test]
+fn grid_covers_every_combination() {
+    let grid = make_grid(&[0.5, 0.9], &[0.3, 0.6], &[vec![150, 50]]);
+    let settings = grid
+        .iter()
+        .map(|setting| (setting.min_prob_ref, setting.prior_ref))
+        .collect::<Vec<_>>();
+    assert_eq!(settings, [(0.5, 0.3), (0.5, 0.6), (0.9, 0.3), (0.9, 0.6)]);
+    assert!(grid
+        .iter()
+        .all(|setting| setting.window_ladder == [150, 50]));
+}
+
+/// The default setting reproduces the baseline: the cluster and reference loci
+/// are extended, the cluster is supported, and every locus is stable
+#[test]
+fn rows_summarize_each_setting() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let rows = rows(&sweep(&dataset, &["--min-prob-ref", "0.5,0.9"]));
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0][..2], ["0.5", "0.5779905"]);
+    assert_eq!(rows[1][0], "0.9");
+    assert_eq!(rows[0][2], "150:50:25:10");
+    // Loci, extended, supported, and stable loci
+    assert_eq!(
+        [&rows[0][3], &rows[0][4], &rows[0][5], &rows[0][7]],
+        ["4", "2", "1", "4"]
+    );
+}
+
+#[test]
+fn priors_are_checked_when_parsing() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let output = sweep(&dataset, &["--prior-ref", "0.5,1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Prior must be in (0, 1): 1"));
+}
+
+/// Reads of the dataset have a mapping quality of 60, so none are profiled
+/// with a higher threshold
+#[test]
+fn search_options_apply_to_every_setting() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let rows = rows(&sweep(&dataset, &["--min-mapq", "61", "--radius", "300"]));
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0][4], "0");
+}
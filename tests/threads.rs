@@ -0,0 +1,39 @@
+mod common;
+
+use common::CONTIG;
+use std::fs::File;
+use std::io::Write;
+
+/// Results, including those of subsampled regions, are identical and in input
+/// order whatever the number of threads, and with the queues capped in
+/// low-memory mode
+#[test]
+fn results_do_not_depend_on_thread_count() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let regions_path = dir.path().join("many_regions.bed");
+    let mut regions = File::create(&regions_path).unwrap();
+    for index in 0..40 {
+        let start = 4000 + index * 211;
+        writeln!(regions, "{CONTIG}\t{start}\t{}\tregion{index}", start + 20).unwrap();
+    }
+    writeln!(regions, "{CONTIG}\t8040\t8060\tcluster").unwrap();
+
+    let run = |threads: &str, extra_args: &[&str]| {
+        let output = common::vclust(&dataset.with_regions(&regions_path))
+            .args(["--threads", threads])
+            .args(extra_args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    for extra_args in [&[][..], &["--max-pooled-reads", "30"], &["--low-memory"]] {
+        let single = run("1", extra_args);
+        assert_eq!(single.lines().count(), 43);
+        for threads in ["2", "8"] {
+            assert_eq!(run(threads, extra_args), single);
+        }
+    }
+}
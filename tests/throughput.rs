@@ -0,0 +1,55 @@
+mod common;
+
+use vclust::summary::RunSummary;
+use vclust::throughput::{thread_recommendation, WorkerTimes};
+
+#[test]
+fn recommendations_follow_the_bottleneck() {
+    let times = |read_secs: f64, compute_secs: f64, send_secs: f64| WorkerTimes {
+        read_secs,
+        compute_secs,
+        wait_secs: 0.0,
+        send_secs,
+    };
+    let read_bound = times(80.0, 20.0, 0.0);
+    let advice = thread_recommendation(&read_bound, 8, 8).unwrap();
+    assert!(advice.contains("80% of their time reading"), "{advice}");
+    assert!(advice.contains("--threads 16"), "{advice}");
+    // Already oversubscribed
+    assert_eq!(thread_recommendation(&read_bound, 16, 8), None);
+
+    let compute_bound = times(10.0, 90.0, 0.0);
+    let advice = thread_recommendation(&compute_bound, 32, 8).unwrap();
+    assert!(advice.contains("--threads 8 would be as fast"), "{advice}");
+    let advice = thread_recommendation(&compute_bound, 2, 8).unwrap();
+    assert!(
+        advice.contains("--threads 8 would use all cores"),
+        "{advice}"
+    );
+    assert_eq!(thread_recommendation(&compute_bound, 8, 8), None);
+
+    let writer_bound = times(10.0, 50.0, 40.0);
+    let advice = thread_recommendation(&writer_bound, 8, 8).unwrap();
+    assert!(advice.contains("waiting for the writer"), "{advice}");
+    assert_eq!(thread_recommendation(&WorkerTimes::default(), 8, 8), None);
+}
+
+#[test]
+fn summary_reports_worker_times() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let summary_path = dir.path().join("summary.json");
+    let output = common::vclust(&dataset)
+        .args(["--threads", "2"])
+        .arg("--summary")
+        .arg(&summary_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let summary = RunSummary::load(&summary_path).unwrap();
+    let times = summary.worker_times.unwrap();
+    assert!(times.read_secs > 0.0);
+    assert!(times.compute_secs > 0.0);
+    assert!(times.total_secs() >= times.read_secs + times.compute_secs);
+}
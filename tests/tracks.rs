@@ -0,0 +1,96 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+use vclust::tracks::{support_intervals, ClusterTrack, HUB_FILES};
+
+fn cluster(chrom: &str, start: i64, end: i64, ns: i64) -> ClusterTrack {
+    ClusterTrack {
+        name: format!("{chrom}:{start}-{end}"),
+        chrom: chrom.to_string(),
+        start,
+        end,
+        ns,
+    }
+}
+
+#[test]
+fn overlapping_clusters_report_largest_support() {
+    let clusters = [
+        cluster("chr1", 100, 200, 2),
+        cluster("chr1", 150, 300, 5),
+        cluster("chr1", 160, 180, 1),
+        cluster("chr1", 300, 400, 5),
+        cluster("chr1", 500, 600, 3),
+        cluster("chr2", 100, 200, 4),
+    ];
+    let intervals = support_intervals(&clusters)
+        .into_iter()
+        .map(|(chrom, start, end, ns)| format!("{chrom}:{start}-{end}={ns}"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        intervals,
+        [
+            "chr1:100-150=2",
+            "chr1:150-400=5",
+            "chr1:500-600=3",
+            "chr2:100-200=4"
+        ]
+    );
+}
+
+#[test]
+fn hub_files_are_not_overwritten_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let results_path = dir.path().join("results.txt");
+    let status = common::vclust(&dataset)
+        .arg("--output")
+        .arg(&results_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let hub_dir = dir.path().join("hub");
+    fs::create_dir(&hub_dir).unwrap();
+    fs::write(hub_dir.join("hub.txt"), "previous").unwrap();
+    let tracks = |force: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_vclust"));
+        command
+            .arg("tracks")
+            .arg(&results_path)
+            .arg("--genome")
+            .arg(&dataset.genome_path)
+            .arg("--genome-name")
+            .arg("hg38")
+            .arg("--email")
+            .arg("lab@example.org")
+            .arg("--out-dir")
+            .arg(&hub_dir);
+        if force {
+            command.arg("--force");
+        }
+        command.output().unwrap()
+    };
+
+    let output = tracks(false);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("hub.txt"));
+    assert_eq!(
+        fs::read_to_string(hub_dir.join("hub.txt")).unwrap(),
+        "previous"
+    );
+    assert!(!hub_dir.join("clusters.bed").exists());
+
+    assert!(tracks(true).status.success());
+    let mut files = fs::read_dir(&hub_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    let mut expected = HUB_FILES.to_vec();
+    expected.sort();
+    assert_eq!(files, expected);
+    assert!(fs::read_to_string(hub_dir.join("hub.txt"))
+        .unwrap()
+        .starts_with("hub vclust\n"));
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::fs;
+use vclust::training::TRAINING_WINDOW_LEN;
+
+/// Extended loci contribute the whole windows of their search region of 500
+/// bps on either side, and loci that could not be extended none
+#[test]
+fn windows_are_exported_with_truth_labels() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let truth_path = dir.path().join("truth.bed");
+    fs::write(&truth_path, "chr1\t8000\t8100\ttruth\n").unwrap();
+    let export_dir = dir.path().join("training");
+    let output = common::vclust(&dataset)
+        .arg("--export-training")
+        .arg(&export_dir)
+        .arg("--truth-regions")
+        .arg(&truth_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let examples = fs::read_to_string(export_dir.join("examples.tsv")).unwrap();
+    let examples = examples
+        .lines()
+        .skip(1)
+        .map(|line| line.split('\t').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(examples.len(), 12);
+    let positives = examples
+        .iter()
+        .filter(|fields| fields[5] == "1")
+        .map(|fields| (fields[1], fields[3]))
+        .collect::<Vec<_>>();
+    assert_eq!(positives, [("cluster", "7990")]);
+
+    let windows = fs::read(export_dir.join("windows.npy")).unwrap();
+    let header_len = 10 + u16::from_le_bytes([windows[8], windows[9]]) as usize;
+    let header = String::from_utf8_lossy(&windows[..header_len]);
+    assert!(header.starts_with("\u{fffd}NUMPY"));
+    assert!(header.contains("'descr': '|u1'"));
+    assert!(header.contains(&format!("'shape': (12, {TRAINING_WINDOW_LEN})")));
+    assert_eq!(windows.len(), header_len + 12 * TRAINING_WINDOW_LEN);
+
+    let labels = fs::read(export_dir.join("labels.npy")).unwrap();
+    let header_len = 10 + u16::from_le_bytes([labels[8], labels[9]]) as usize;
+    let labels = &labels[header_len..];
+    assert_eq!(labels.len(), 12);
+    assert_eq!(labels.iter().map(|label| *label as usize).sum::<usize>(), 1);
+}
+
+/// Exported arrays are outputs like any other: they are not replaced without
+/// `--force`, and no partial files are left behind
+#[test]
+fn exports_are_not_overwritten_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let truth_path = dir.path().join("truth.bed");
+    fs::write(&truth_path, "chr1\t8000\t8100\ttruth\n").unwrap();
+    let export_dir = dir.path().join("training");
+    fs::create_dir(&export_dir).unwrap();
+    fs::write(export_dir.join("labels.npy"), "previous").unwrap();
+    let export = |force: bool| {
+        let mut command = common::vclust(&dataset);
+        command
+            .arg("--export-training")
+            .arg(&export_dir)
+            .arg("--truth-regions")
+            .arg(&truth_path);
+        if force {
+            command.arg("--force");
+        }
+        command.output().unwrap()
+    };
+
+    let output = export(false);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("labels.npy"));
+    assert_eq!(
+        fs::read_to_string(export_dir.join("labels.npy")).unwrap(),
+        "previous"
+    );
+    assert!(!export_dir.join("windows.npy").exists());
+
+    assert!(export(true).status.success());
+    let mut files = fs::read_dir(&export_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, ["examples.tsv", "labels.npy", "windows.npy"]);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use vclust::models::{LogModels, Models};
+use vclust::underflow::{format_underflow_runs, get_underflow_runs, MIN_UNDERFLOW_WINDOWS};
+
+#[test]
+fn coalesces_adjacent_underflowing_windows() {
+    let models = LogModels::from(&Models::default());
+    let mut alts = vec![0; 1000];
+    assert!(get_underflow_runs(&alts, 150, &models).is_empty());
+
+    // Pileups deeper in alt operations than in reads, as at coverage cliffs
+    alts[400..600].fill(5);
+    let runs = get_underflow_runs(&alts, 150, &models);
+    assert_eq!(runs.len(), 1);
+    let (start, end) = runs[0];
+    assert!(start < 400 && 600 < end);
+    assert!(end - start - 150 >= MIN_UNDERFLOW_WINDOWS as i64);
+    assert_eq!(format_underflow_runs(&runs), format!("{start}-{end}"));
+
+    // Too few windows hold enough of a short stretch to underflow
+    let mut alts = vec![0; 1000];
+    alts[400..410].fill(5);
+    assert!(get_underflow_runs(&alts, 150, &models).is_empty());
+    assert_eq!(format_underflow_runs(&[]), ".");
+}
+
+/// The profiles of the test dataset are explained by the models
+#[test]
+fn flagged_column_is_added() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let output = common::vclust(&dataset)
+        .arg("--flag-model-uncertain")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines().skip(1);
+    assert!(lines.next().unwrap().ends_with("\tmodel_uncertain"));
+    let flags = lines
+        .map(|line| line.rsplit('\t').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(flags, [".", ".", "NA", "NA"]);
+}
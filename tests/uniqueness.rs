@@ -0,0 +1,167 @@
+mod common;
+
+use common::{write_fasta, CONTIG};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::process::Command;
+use vclust::uniqueness::{count_kmers, uniqueness};
+
+fn random_seq(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+#[test]
+fn repeats_within_the_window_stay_unique() {
+    let unit = random_seq(50, 1);
+    let tandem = unit.repeat(10);
+    let counts = count_kmers(&tandem);
+    assert_eq!(uniqueness(&tandem, &|kmer| counts[&kmer]), 1.0);
+
+    // Half of the window is copied, in reverse complement, outside of it
+    let window = random_seq(400, 2);
+    let copy = window[200..]
+        .iter()
+        .rev()
+        .map(|base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'A',
+        })
+        .collect::<Vec<_>>();
+    let background = count_kmers(&[window.clone(), random_seq(100, 3), copy].concat());
+    let score = uniqueness(&window, &|kmer| background[&kmer]);
+    assert!((0.45..0.55).contains(&score), "{score}");
+}
+
+/// The search window of the cluster is duplicated elsewhere on the contig, so
+/// its alt signal is dampened and it is not extended; the index and the
+/// neighbourhood of the window give the same score
+#[test]
+fn duplicated_windows_are_dampened() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let mut genome = fs::read_to_string(&dataset.genome_path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .collect::<String>()
+        .into_bytes();
+    genome.copy_within(7400..8700, 14000);
+    write_fasta(&dataset.genome_path, &genome);
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(
+        &regions_path,
+        format!("{CONTIG}\t8040\t8060\tcluster\n{CONTIG}\t5000\t5020\treference\n"),
+    )
+    .unwrap();
+
+    let index_path = dir.path().join("genome.kmers");
+    let status = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("kmer-index")
+        .arg("--genome")
+        .arg(&dataset.genome_path)
+        .arg("--output")
+        .arg(&index_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // An existing index is only replaced with --force
+    let rebuild = Command::new(env!("CARGO_BIN_EXE_vclust"))
+        .arg("kmer-index")
+        .arg("--genome")
+        .arg(&dataset.genome_path)
+        .arg("--output")
+        .arg(&index_path)
+        .output()
+        .unwrap();
+    assert!(!rebuild.status.success());
+
+    let run = |extra_args: &[&str]| {
+        let output = common::vclust(&dataset.with_regions(&regions_path))
+            .args(extra_args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout
+            .lines()
+            .skip(2)
+            .map(|line| {
+                let fields = line.split('\t').collect::<Vec<_>>();
+                let name = fields[0].to_string();
+                let (lf_offset, score) = (fields[2], fields.last().unwrap());
+                (name, lf_offset.to_string(), score.to_string())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let expected = [
+        ("cluster".to_string(), "0".to_string(), "0.000".to_string()),
+        (
+            "reference".to_string(),
+            "0".to_string(),
+            "1.000".to_string(),
+        ),
+    ];
+    assert_eq!(run(&["--uniqueness"]), expected);
+    let index_arg = index_path.to_str().unwrap();
+    assert_eq!(run(&["--kmer-index", index_arg]), expected);
+    // Without the dampening the cluster is extended
+    assert_ne!(run(&[])[0].1, "0");
+}
+
+/// Two indexes of the same size, only the first duplicating the search window
+/// of the cluster, must not share cached results
+#[test]
+fn cached_results_follow_the_index_contents() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let genome = fs::read_to_string(&dataset.genome_path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .collect::<String>()
+        .into_bytes();
+    let index_path = dir.path().join("genome.kmers");
+    let build_index = |copied: std::ops::Range<usize>| {
+        let mut duplicated = genome.clone();
+        duplicated.copy_within(copied, 14000);
+        let genome_path = dir.path().join("duplicated.fa");
+        write_fasta(&genome_path, &duplicated);
+        let status = Command::new(env!("CARGO_BIN_EXE_vclust"))
+            .arg("kmer-index")
+            .arg("--genome")
+            .arg(&genome_path)
+            .arg("--output")
+            .arg(&index_path)
+            .arg("--force")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::metadata(&index_path).unwrap().len()
+    };
+    let regions_path = dir.path().join("regions.bed");
+    fs::write(&regions_path, format!("{CONTIG}\t8040\t8060\tcluster\n")).unwrap();
+    let cache_dir = dir.path().join("cache");
+    let run = || {
+        let output = common::vclust(&dataset.with_regions(&regions_path))
+            .arg("--kmer-index")
+            .arg(&index_path)
+            .arg("--cache")
+            .arg(&cache_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let row = stdout.lines().last().unwrap().to_string();
+        row.split('\t').next_back().unwrap().to_string()
+    };
+
+    let size = build_index(7400..8700);
+    assert_eq!(run(), "0.000");
+    assert_eq!(build_index(4400..5700), size);
+    assert_eq!(run(), "1.000");
+}
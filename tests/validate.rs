@@ -0,0 +1,90 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+use vclust::schema;
+use vclust::validate::validate_results;
+
+/// Freshly written results pass in catalog order, which is unsorted in the
+/// test dataset
+#[test]
+fn results_of_a_run_are_valid() {
+    let dir = tempfile::tempdir().unwrap();
+    let dataset = common::build_dataset(dir.path());
+    let results_path = dir.path().join("results.txt");
+    let status = common::vclust(&dataset)
+        .arg("--output")
+        .arg(&results_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let validate = |extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_vclust"))
+            .arg("validate-output")
+            .arg(&results_path)
+            .arg("--fai")
+            .arg(dataset.genome_path.with_extension("fa.fai"))
+            .args(extra_args)
+            .output()
+            .unwrap()
+    };
+    let output = validate(&["--allow-unsorted"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = validate(&[]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "line 4: Region chr1:6000-6020 is out of order\n\
+         line 5: Region chr1:100-120 is out of order\n"
+    );
+}
+
+#[test]
+fn inconsistent_records_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.txt");
+    let rows = [
+        "ok\tchr1:100-120\t10\t5\tchr1:90-125\t1\t20\t0\t0\t.\t150:10:5\t0.10",
+        "offsets\tchr1:200-220\t10\t5\tchr1:190-230\t1\t20\t0\t0\t.\t150:10:5\t.",
+        "gap\tchr1:300-320\t0\t0\tchr1:300-320\t0\t20\t0\t0\t250-310\t150:0:0\t.",
+        "ladder\tchr1:400-420\t3\t0\tchr1:397-420\t1\t-2\t0\t0\t.\t150:2:0\t1.50",
+        "na\tchr1:500-520\tNA\tNA\tNA\tNA\tNA\tNA\tNA\t.\tNA\tNA",
+        "past\tchr1:900-1020\t0\t0\tchr1:900-1020\t0\t20\t0\t0\t.\t.\t.",
+        "short\tchr1:600-620\t0",
+        "other\tchr2:100-120\tNA\tNA\tNA\tNA\tNA\tNA\tNA\tNA\tNA\tNA",
+    ];
+    fs::write(
+        &path,
+        format!("{}\n{}\n", schema::header(), rows.join("\n")),
+    )
+    .unwrap();
+    let contigs = [("chr1".to_string(), 1000)];
+    let issues = validate_results(&path, Some(&contigs), true)
+        .unwrap()
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        [
+            "line 4: Extended region chr1:190-230 does not match the offsets (chr1:190-225)",
+            "line 5: Coverage gap 250-310 is not within chr1:300-320",
+            "line 6: Bad spanning_reads -2",
+            "line 6: Final ladder offsets of 150:2:0 do not match the offsets",
+            "line 6: Bad read_end_bias 1.50",
+            "line 7: Unextended record has values besides NA",
+            "line 8: Region chr1:900-1020 ends past the contig",
+            "line 8: Extended region chr1:900-1020 is outside of the contig",
+            "line 9: Expected 12 fields, found 3",
+            "line 10: Contig chr2 is not in the reference",
+        ]
+    );
+
+    fs::write(&path, "#name\tregion\n").unwrap();
+    let issues = validate_results(&path, None, true).unwrap();
+    assert_eq!(issues.len(), 2);
+}